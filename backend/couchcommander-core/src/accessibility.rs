@@ -0,0 +1,364 @@
+//! System accessibility features a remote can switch: inverted colors, high
+//! contrast, the screen reader (VoiceOver, Narrator or Orca) and dark mode.
+//! Screen zoom has its own module, `zoom`.
+//!
+//! Announcements are handed to the screen reader of whoever sits at the host:
+//! AppKit's accessibility announcements for VoiceOver, UI Automation
+//! notifications for Narrator, NVDA or JAWS, and speech-dispatcher, which
+//! Orca speaks through, on Linux.
+//!
+//! A feature is set on or off where its state can be read. Where it can't,
+//! e.g. inverted colors on macOS, which only has a shortcut, it can only be
+//! toggled.
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::CommandResponse;
+
+const MAX_ANNOUNCEMENT_CHARS: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    InvertColors,
+    IncreaseContrast,
+    ScreenReader,
+    DarkMode,
+}
+
+impl Feature {
+    fn label(self) -> &'static str {
+        match self {
+            Feature::InvertColors => "Inverted colors",
+            Feature::IncreaseContrast => "High contrast",
+            Feature::ScreenReader => "Screen reader",
+            Feature::DarkMode => "Dark mode",
+        }
+    }
+}
+
+/// Whether a process with this exact name is running, e.g. "Narrator.exe".
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn process_running(name: &str) -> bool {
+    let mut system = sysinfo::System::new();
+    system.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+    system
+        .processes()
+        .values()
+        .any(|process| process.name() == name)
+}
+
+/// Run `gsettings` and return what it printed, e.g. `true` for a `get`.
+#[cfg(target_os = "linux")]
+pub(crate) fn gsettings(args: &[&str]) -> Result<String, String> {
+    let output = std::process::Command::new("gsettings")
+        .args(args)
+        .output()
+        .map_err(|e| format!("This needs GNOME's settings (gsettings): {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{process_running, Feature};
+    use crate::applescript::{self, Script};
+
+    pub fn set(feature: Feature, enabled: Option<bool>) -> Result<Option<bool>, String> {
+        match feature {
+            Feature::DarkMode => {
+                let enabled = match enabled {
+                    Some(enabled) => enabled,
+                    None => applescript::run(&Script::GetDarkMode)? != "true",
+                };
+                applescript::run(&Script::SetDarkMode { enabled })?;
+                Ok(Some(enabled))
+            }
+            Feature::ScreenReader => {
+                let running = process_running("VoiceOver");
+                if enabled != Some(running) {
+                    applescript::run(&Script::ToggleVoiceOver)?;
+                }
+                Ok(Some(enabled.unwrap_or(!running)))
+            }
+            Feature::InvertColors if enabled.is_none() => {
+                applescript::run(&Script::ToggleInvertColors)?;
+                Ok(None)
+            }
+            Feature::InvertColors => Err("macOS can only toggle inverted colors".to_string()),
+            Feature::IncreaseContrast => {
+                Err("macOS doesn't let other apps change Increase Contrast".to_string())
+            }
+        }
+    }
+
+    pub fn announce(text: &str) -> Result<(), String> {
+        crate::macos_ax::announce(text)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use enigo::Key;
+    use std::ffi::c_void;
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    use super::{process_running, Feature};
+    use crate::input_backend::SystemBackend;
+
+    // Keeps reg from flashing a console window
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    const PERSONALIZE_KEY: &str =
+        r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize";
+    const COLOR_FILTERING_KEY: &str = r"HKCU\Software\Microsoft\ColorFiltering";
+    // The "Inverted" color filter
+    const INVERTED_FILTER: u32 = 2;
+
+    const SPI_GETHIGHCONTRAST: u32 = 0x0042;
+    const SPI_SETHIGHCONTRAST: u32 = 0x0043;
+    const SPIF_UPDATEINIFILE: u32 = 0x1;
+    const SPIF_SENDCHANGE: u32 = 0x2;
+    const HCF_HIGHCONTRASTON: u32 = 0x1;
+
+    #[repr(C)]
+    struct HighContrast {
+        size: u32,
+        flags: u32,
+        default_scheme: *mut u16,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn SystemParametersInfoW(action: u32, param: u32, data: *mut c_void, ini: u32) -> i32;
+    }
+
+    fn reg(args: &[&str]) -> Result<String, String> {
+        let output = Command::new("reg")
+            .args(args)
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .map_err(|e| format!("Failed to run reg: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    // `    AppsUseLightTheme    REG_DWORD    0x1`, None when it isn't set
+    fn read_dword(key: &str, name: &str) -> Option<u32> {
+        let output = reg(&["query", key, "/v", name]).ok()?;
+        let value = output.split_whitespace().last()?;
+        u32::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+    }
+
+    fn write_dword(key: &str, name: &str, value: u32) -> Result<(), String> {
+        let value = value.to_string();
+        let args = [
+            "add",
+            key,
+            "/v",
+            name,
+            "/t",
+            "REG_DWORD",
+            "/d",
+            &value,
+            "/f",
+        ];
+        reg(&args).map(|_| ())
+    }
+
+    fn high_contrast() -> Result<HighContrast, String> {
+        let mut settings = HighContrast {
+            size: std::mem::size_of::<HighContrast>() as u32,
+            flags: 0,
+            default_scheme: std::ptr::null_mut(),
+        };
+        let ok = unsafe {
+            SystemParametersInfoW(
+                SPI_GETHIGHCONTRAST,
+                settings.size,
+                &mut settings as *mut HighContrast as *mut c_void,
+                0,
+            )
+        };
+        if ok == 0 {
+            return Err("Failed to read the high contrast setting".to_string());
+        }
+        Ok(settings)
+    }
+
+    fn set_high_contrast(enabled: Option<bool>) -> Result<bool, String> {
+        let mut settings = high_contrast()?;
+        let enabled = enabled.unwrap_or(settings.flags & HCF_HIGHCONTRASTON == 0);
+        if enabled {
+            settings.flags |= HCF_HIGHCONTRASTON;
+        } else {
+            settings.flags &= !HCF_HIGHCONTRASTON;
+        }
+        let ok = unsafe {
+            SystemParametersInfoW(
+                SPI_SETHIGHCONTRAST,
+                settings.size,
+                &mut settings as *mut HighContrast as *mut c_void,
+                SPIF_UPDATEINIFILE | SPIF_SENDCHANGE,
+            )
+        };
+        if ok == 0 {
+            return Err("Failed to change the high contrast setting".to_string());
+        }
+        Ok(enabled)
+    }
+
+    pub fn set(feature: Feature, enabled: Option<bool>) -> Result<Option<bool>, String> {
+        let chord = |modifiers: &'static [Key], key: Key| {
+            crate::input_worker::run_blocking::<SystemBackend, _, _>(move |input| {
+                crate::shortcuts::press_chord(input, modifiers, key)
+                    .map_err(|e| format!("Failed to send the shortcut: {:?}", e))
+            })
+        };
+        match feature {
+            Feature::IncreaseContrast => set_high_contrast(enabled).map(Some),
+            Feature::DarkMode => {
+                let light = read_dword(PERSONALIZE_KEY, "AppsUseLightTheme").unwrap_or(1) != 0;
+                let enabled = enabled.unwrap_or(light);
+                let light = u32::from(!enabled);
+                write_dword(PERSONALIZE_KEY, "AppsUseLightTheme", light)?;
+                write_dword(PERSONALIZE_KEY, "SystemUsesLightTheme", light)?;
+                Ok(Some(enabled))
+            }
+            // Win+Ctrl+Enter starts and stops Narrator
+            Feature::ScreenReader => {
+                let running = process_running("Narrator.exe");
+                if enabled != Some(running) {
+                    chord(&[Key::Meta, Key::Control], Key::Return)?;
+                }
+                Ok(Some(enabled.unwrap_or(!running)))
+            }
+            // Win+Ctrl+C toggles the color filter, when the shortcut is on in
+            // the color filter settings
+            Feature::InvertColors => {
+                let active = read_dword(COLOR_FILTERING_KEY, "Active").unwrap_or(0) != 0;
+                let enabled = enabled.unwrap_or(!active);
+                if enabled != active {
+                    if enabled {
+                        write_dword(COLOR_FILTERING_KEY, "FilterType", INVERTED_FILTER)?;
+                    }
+                    chord(&[Key::Meta, Key::Control], Key::Unicode('c'))?;
+                }
+                Ok(Some(enabled))
+            }
+        }
+    }
+
+    pub fn announce(text: &str) -> Result<(), String> {
+        crate::windows_uia::announce(text)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{gsettings, Feature};
+
+    // Flip or set a boolean GNOME setting
+    fn switch(schema: &str, key: &str, enabled: Option<bool>) -> Result<bool, String> {
+        let enabled = match enabled {
+            Some(enabled) => enabled,
+            None => gsettings(&["get", schema, key])? != "true",
+        };
+        gsettings(&["set", schema, key, if enabled { "true" } else { "false" }])?;
+        Ok(enabled)
+    }
+
+    pub fn set(feature: Feature, enabled: Option<bool>) -> Result<Option<bool>, String> {
+        match feature {
+            Feature::IncreaseContrast => {
+                switch("org.gnome.desktop.a11y.interface", "high-contrast", enabled).map(Some)
+            }
+            Feature::ScreenReader => switch(
+                "org.gnome.desktop.a11y.applications",
+                "screen-reader-enabled",
+                enabled,
+            )
+            .map(Some),
+            Feature::DarkMode => {
+                const SCHEMA: &str = "org.gnome.desktop.interface";
+                let enabled = match enabled {
+                    Some(enabled) => enabled,
+                    None => gsettings(&["get", SCHEMA, "color-scheme"])? != "'prefer-dark'",
+                };
+                let scheme = if enabled { "prefer-dark" } else { "default" };
+                gsettings(&["set", SCHEMA, "color-scheme", scheme])?;
+                Ok(Some(enabled))
+            }
+            Feature::InvertColors => Err("Inverting colors isn't supported on Linux".to_string()),
+        }
+    }
+
+    // `--` keeps text starting with a dash from being read as an option
+    pub fn announce(text: &str) -> Result<(), String> {
+        let output = std::process::Command::new("spd-say")
+            .args(["--priority", "message", "--", text])
+            .output()
+            .map_err(|e| format!("Announcements need speech-dispatcher (spd-say): {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod platform {
+    use super::Feature;
+
+    pub fn set(_feature: Feature, _enabled: Option<bool>) -> Result<Option<bool>, String> {
+        Err("Accessibility features can't be changed on this platform".to_string())
+    }
+
+    pub fn announce(_text: &str) -> Result<(), String> {
+        Err("Announcements aren't supported on this platform".to_string())
+    }
+}
+
+/// Turn a feature on or off, or flip it when `enabled` is None.
+pub async fn set(feature: Feature, enabled: Option<bool>) -> Result<CommandResponse, String> {
+    let state = tokio::task::spawn_blocking(move || platform::set(feature, enabled))
+        .await
+        .map_err(|e| format!("Accessibility task panicked: {:?}", e))??;
+    let message = match state {
+        Some(true) => format!("{} on", feature.label()),
+        Some(false) => format!("{} off", feature.label()),
+        None => format!("{} toggled", feature.label()),
+    };
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message,
+    })
+}
+
+/// Have the host's screen reader read `text` out, e.g. to voice what a
+/// remote just did for someone who can't see the screen.
+pub async fn announce(text: &str) -> Result<CommandResponse, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Nothing to announce".to_string());
+    }
+    if text.chars().count() > MAX_ANNOUNCEMENT_CHARS {
+        return Err(format!(
+            "Announcements are limited to {} characters",
+            MAX_ANNOUNCEMENT_CHARS
+        ));
+    }
+    let text = text.to_string();
+    tokio::task::spawn_blocking(move || platform::announce(&text))
+        .await
+        .map_err(|e| format!("Announcement task panicked: {:?}", e))??;
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: "Announced".to_string(),
+    })
+}
@@ -0,0 +1,91 @@
+//! Command aliases from `settings.toml`, so older remotes keep working when
+//! a command is renamed and users can name their own shortcuts:
+//!
+//! ```toml
+//! [aliases]
+//! "vol+" = "volume_up"
+//! rewind = { command = "send_key", data = { key = "j" } }
+//! movie_night = [
+//!     { command = "set_display_mode", data = { mode = "external" } },
+//!     { command = "open_website", data = { url = "https://www.netflix.com" } },
+//! ]
+//! ```
+//!
+//! Targets are always real commands, aliases aren't expanded again.
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::config;
+use crate::protocol::WebSocketCommand;
+
+const MAX_MACRO_STEPS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AliasStep {
+    pub command: String,
+    // Sent instead of the data the remote sent, if given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CommandAlias {
+    Command(String),
+    Step(AliasStep),
+    // Run in order, stopping at the first one that fails
+    Macro(Vec<AliasStep>),
+}
+
+impl CommandAlias {
+    fn steps(&self) -> Vec<AliasStep> {
+        match self {
+            CommandAlias::Command(command) => vec![AliasStep {
+                command: command.clone(),
+                data: None,
+            }],
+            CommandAlias::Step(step) => vec![step.clone()],
+            CommandAlias::Macro(steps) => steps.clone(),
+        }
+    }
+}
+
+pub fn validate(name: &str, alias: &CommandAlias) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Command aliases must have a name".to_string());
+    }
+    let steps = alias.steps();
+    if steps.is_empty() || steps.len() > MAX_MACRO_STEPS {
+        return Err(format!(
+            "Alias '{}' needs between 1 and {} commands",
+            name, MAX_MACRO_STEPS
+        ));
+    }
+    if steps.iter().any(|step| step.command.trim().is_empty()) {
+        return Err(format!("Alias '{}' has a step without a command", name));
+    }
+    Ok(())
+}
+
+/// The commands to run for `command`: itself unless the settings alias it.
+pub fn expand(command: WebSocketCommand) -> Vec<WebSocketCommand> {
+    let settings = config::current();
+    let Some(alias) = settings.aliases.get(&command.command) else {
+        return vec![command];
+    };
+    let steps = alias.steps();
+    debug!(
+        "Alias '{}' expands to {} commands",
+        command.command,
+        steps.len()
+    );
+    steps
+        .into_iter()
+        .map(|step| WebSocketCommand {
+            id: command.id.clone(),
+            command: step.command,
+            data: step.data.or_else(|| command.data.clone()),
+        })
+        .collect()
+}
@@ -0,0 +1,201 @@
+//! HTTP API for Stream Decks, Shortcuts, curl scripts and headless
+//! administration. Every endpoint needs `Authorization: Bearer <api_token>`.
+//!
+//! - `POST /api/command` runs a command in the WebSocket JSON format
+//! - `GET /api/status` and `GET /api/clients` report on the server
+//! - `GET /api/settings` reads the settings, `PUT /api/settings` replaces
+//!   them with a full settings object as returned by GET
+//! - `DELETE /api/secrets/<name>` clears one of the secrets GET leaves blank
+//! - `GET /api/macros` reads the command aliases and macros, `PUT /api/macros`
+//!   replaces them
+//! - `GET /api/activity` lists the last commands handled, oldest first
+//!
+//! ```text
+//! curl -H "Authorization: Bearer $TOKEN" -d '{"command":"play_pause"}' http://host:8080/api/command
+//! ```
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use hyper::body::Incoming;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Instant;
+use tracing::{debug_span, info, warn, Instrument};
+
+use crate::aliases::CommandAlias;
+use crate::input_backend::SystemBackend;
+use crate::protocol::{WebSocketCommand, WebSocketResponse};
+use crate::{config, metrics, server, signing, websocket};
+
+pub const API_PREFIX: &str = "/api/";
+// Commands and settings are small, anything bigger is a mistake or abuse
+const MAX_BODY_BYTES: usize = 64 * 1024;
+// Client id API commands run as
+const API_CLIENT: &str = "api";
+
+pub fn is_api_request<B>(req: &Request<B>) -> bool {
+    req.uri().path().starts_with(API_PREFIX)
+}
+
+fn json<T: Serialize>(status: StatusCode, body: &T) -> Response {
+    let body = serde_json::to_string(body)
+        .unwrap_or_else(|_| r#"{"status":"error","message":"Serialization error"}"#.to_string());
+    (status, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    let body = WebSocketResponse {
+        id: None,
+        status: "error".to_string(),
+        message: message.to_string(),
+        data: None,
+        duration_us: None,
+    };
+    json(status, &body)
+}
+
+fn authorize<B>(req: &Request<B>) -> Result<(), Box<Response>> {
+    let expected = config::current().api_token;
+    if expected.is_empty() {
+        return Err(Box::new(error(
+            StatusCode::FORBIDDEN,
+            "The HTTP API is disabled in settings",
+        )));
+    }
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !signing::same(presented.trim().as_bytes(), expected.as_bytes()) {
+        warn!("Rejected HTTP API request with a bad token");
+        return Err(Box::new(error(
+            StatusCode::UNAUTHORIZED,
+            "Invalid API token",
+        )));
+    }
+    Ok(())
+}
+
+async fn read_json<T: DeserializeOwned>(req: Request<Incoming>) -> Result<T, Response> {
+    let body = axum::body::to_bytes(Body::new(req.into_body()), MAX_BODY_BYTES)
+        .await
+        .map_err(|e| error(StatusCode::PAYLOAD_TOO_LARGE, &e.to_string()))?;
+    serde_json::from_slice(&body)
+        .map_err(|e| error(StatusCode::BAD_REQUEST, &format!("Invalid JSON: {}", e)))
+}
+
+async fn run_command(req: Request<Incoming>) -> Response {
+    let command: WebSocketCommand = match read_json(req).await {
+        Ok(command) => command,
+        Err(response) => return response,
+    };
+
+    let span = debug_span!("api_command", name = %command.command, id = ?command.id);
+    let command_name = command.command.clone();
+    let started = Instant::now();
+    let response = websocket::dispatch::<SystemBackend>(API_CLIENT, command)
+        .instrument(span)
+        .await;
+    let elapsed = started.elapsed();
+    metrics::record_command(&command_name, elapsed, response.status != "error");
+    metrics::publish_activity(
+        API_CLIENT,
+        "HTTP API",
+        &command_name,
+        &response.status,
+        &response.message,
+        elapsed,
+    );
+
+    let timed_out = response
+        .data
+        .as_ref()
+        .and_then(|data| data.get("code"))
+        .is_some_and(|code| code == websocket::TIMEOUT_CODE);
+    let status = if timed_out {
+        StatusCode::GATEWAY_TIMEOUT
+    } else if response.status == "error" {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::OK
+    };
+    json(status, &response)
+}
+
+async fn put_settings(req: Request<Incoming>) -> Response {
+    let mut settings: config::Settings = match read_json(req).await {
+        Ok(settings) => settings,
+        Err(response) => return response,
+    };
+    // Secrets are never sent out, blank ones stay as they are
+    config::keep_secrets(&mut settings, &config::current());
+    match config::update(settings) {
+        Ok(_) => {
+            info!("Settings updated through the HTTP API");
+            json(StatusCode::OK, &config::redacted(config::current()))
+        }
+        Err(e) => error(StatusCode::BAD_REQUEST, &e),
+    }
+}
+
+async fn put_macros(req: Request<Incoming>) -> Response {
+    let aliases: BTreeMap<String, CommandAlias> = match read_json(req).await {
+        Ok(aliases) => aliases,
+        Err(response) => return response,
+    };
+    let mut settings = config::current();
+    settings.aliases = aliases;
+    match config::update(settings) {
+        Ok(_) => {
+            info!("Macros updated through the HTTP API");
+            json(StatusCode::OK, &config::current().aliases)
+        }
+        Err(e) => error(StatusCode::BAD_REQUEST, &e),
+    }
+}
+
+fn delete_secret(name: &str) -> Response {
+    match config::clear_secret(name) {
+        Ok(()) => {
+            info!("Secret {} cleared through the HTTP API", name);
+            json(StatusCode::OK, &config::redacted(config::current()))
+        }
+        Err(e) => error(StatusCode::BAD_REQUEST, &e),
+    }
+}
+
+/// Answer a request under `/api/`.
+pub async fn handle(req: Request<Incoming>) -> Response {
+    if let Err(response) = authorize(&req) {
+        return *response;
+    }
+
+    let path = req.uri().path().to_string();
+    match (req.method().clone(), path.trim_start_matches(API_PREFIX)) {
+        (Method::POST, "command") => run_command(req).await,
+        (Method::GET, "status") => match server::get_server_status().await {
+            Ok(status) => json(StatusCode::OK, &status),
+            Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+        },
+        (Method::GET, "clients") => match server::list_clients().await {
+            Ok(clients) => json(StatusCode::OK, &clients),
+            Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+        },
+        (Method::GET, "settings") => json(StatusCode::OK, &config::redacted(config::current())),
+        (Method::PUT, "settings") => put_settings(req).await,
+        (Method::GET, "macros") => json(StatusCode::OK, &config::current().aliases),
+        (Method::PUT, "macros") => put_macros(req).await,
+        (Method::GET, "activity") => json(StatusCode::OK, &metrics::recent_activity()),
+        (Method::DELETE, path) if path.starts_with("secrets/") => {
+            delete_secret(path.trim_start_matches("secrets/"))
+        }
+        (_, "command" | "status" | "clients" | "settings" | "macros" | "activity") => {
+            error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed")
+        }
+        _ => error(StatusCode::NOT_FOUND, &format!("No API endpoint {}", path)),
+    }
+}
@@ -0,0 +1,144 @@
+//! A fixed library of AppleScripts run through `osascript`. Remotes pick a
+//! script by name and pass typed arguments, they can never send script
+//! source of their own.
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Apps with a scriptable player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaApp {
+    Music,
+    Spotify,
+}
+
+impl MediaApp {
+    fn name(self) -> &'static str {
+        match self {
+            MediaApp::Music => "Music",
+            MediaApp::Spotify => "Spotify",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerAction {
+    PlayPause,
+    Next,
+    Previous,
+}
+
+/// A script from the library, e.g. `{"script": "set_volume", "value": 40}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "script", rename_all = "snake_case")]
+pub enum Script {
+    GetVolume,
+    SetVolume { value: u8 },
+    SetMuted { muted: bool },
+    GetInputVolume,
+    SetInputVolume { value: u8 },
+    Player { app: MediaApp, action: PlayerAction },
+    CurrentTrack { app: MediaApp },
+    PlayerState { app: MediaApp },
+    SleepDisplay,
+    ToggleMirroring,
+    GetDarkMode,
+    SetDarkMode { enabled: bool },
+    ToggleInvertColors,
+    ToggleVoiceOver,
+    SetLowPowerMode { enabled: bool },
+}
+
+impl Script {
+    fn source(&self) -> String {
+        match self {
+            Script::GetVolume => "output volume of (get volume settings)".to_string(),
+            Script::SetVolume { value } => {
+                format!("set volume output volume {}", (*value).min(100))
+            }
+            Script::SetMuted { muted } => format!("set volume output muted {}", muted),
+            Script::GetInputVolume => "input volume of (get volume settings)".to_string(),
+            Script::SetInputVolume { value } => {
+                format!("set volume input volume {}", (*value).min(100))
+            }
+            // Only talk to players that are already open, `tell` would launch them
+            Script::Player { app, action } => {
+                let command = match action {
+                    PlayerAction::PlayPause => "playpause",
+                    PlayerAction::Next => "next track",
+                    PlayerAction::Previous => "previous track",
+                };
+                format!(
+                    "if application \"{0}\" is running then tell application \"{0}\" to {1}",
+                    app.name(),
+                    command
+                )
+            }
+            Script::CurrentTrack { app } => format!(
+                "if application \"{0}\" is running then tell application \"{0}\" \
+                 to (artist of current track) & \" - \" & (name of current track)",
+                app.name()
+            ),
+            // "playing", "paused" or "stopped", empty when the app isn't open
+            Script::PlayerState { app } => format!(
+                "if application \"{0}\" is running then tell application \"{0}\" \
+                 to player state as text",
+                app.name()
+            ),
+            Script::SleepDisplay => "do shell script \"pmset displaysleepnow\"".to_string(),
+            // Cmd+brightness up toggles display mirroring
+            Script::ToggleMirroring => {
+                "tell application \"System Events\" to key code 144 using command down".to_string()
+            }
+            Script::GetDarkMode => {
+                "tell application \"System Events\" to tell appearance preferences to get dark mode"
+                    .to_string()
+            }
+            Script::SetDarkMode { enabled } => format!(
+                "tell application \"System Events\" to tell appearance preferences \
+                 to set dark mode to {}",
+                enabled
+            ),
+            // Ctrl+Option+Cmd+8, when the shortcut is on in the keyboard settings
+            Script::ToggleInvertColors => "tell application \"System Events\" to key code 28 \
+                 using {control down, option down, command down}"
+                .to_string(),
+            // Cmd+F5
+            Script::ToggleVoiceOver => {
+                "tell application \"System Events\" to key code 96 using command down".to_string()
+            }
+            // pmset needs root, so the host user is asked for their password
+            Script::SetLowPowerMode { enabled } => format!(
+                "do shell script \"pmset -a lowpowermode {}\" with administrator privileges",
+                u8::from(*enabled)
+            ),
+        }
+    }
+}
+
+/// Run a library script and return what it printed.
+#[cfg(target_os = "macos")]
+pub fn run(script: &Script) -> Result<String, String> {
+    let source = script.source();
+    debug!("Running AppleScript {:?}", script);
+    let output = std::process::Command::new("osascript")
+        .arg("-e")
+        .arg(&source)
+        .output()
+        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "AppleScript failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn run(script: &Script) -> Result<String, String> {
+    debug!("Ignoring AppleScript outside macOS: {}", script.source());
+    Err("AppleScript is only available on macOS".to_string())
+}
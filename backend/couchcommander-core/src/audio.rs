@@ -0,0 +1,178 @@
+//! System volume on Linux. PipeWire and PulseAudio desktops are driven with
+//! `pactl` (pipewire-pulse provides it too), bare ALSA systems fall back to
+//! `amixer`, which can't see PipeWire-only outputs.
+
+use serde::Serialize;
+use std::process::Command;
+use std::sync::OnceLock;
+use tracing::{debug, info};
+
+use crate::input_backend::command_available;
+
+const DEFAULT_SINK: &str = "@DEFAULT_SINK@";
+const DEFAULT_SOURCE: &str = "@DEFAULT_SOURCE@";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mixer {
+    Pactl,
+    Amixer,
+}
+
+/// An audio output the host can play through.
+#[derive(Debug, Clone, Serialize)]
+pub struct Sink {
+    pub name: String,
+    pub description: String,
+    pub default: bool,
+}
+
+fn mixer() -> Result<Mixer, String> {
+    static MIXER: OnceLock<Option<Mixer>> = OnceLock::new();
+    let mixer = *MIXER.get_or_init(|| {
+        let mixer = if command_available("pactl") {
+            Some(Mixer::Pactl)
+        } else if command_available("amixer") {
+            Some(Mixer::Amixer)
+        } else {
+            None
+        };
+        info!("Using {:?} for volume control", mixer);
+        mixer
+    });
+    mixer.ok_or_else(|| "Neither pactl nor amixer is installed".to_string())
+}
+
+/// The tool volume changes go through, for diagnostics.
+pub fn mixer_name() -> Option<&'static str> {
+    match mixer().ok()? {
+        Mixer::Pactl => Some("pactl"),
+        Mixer::Amixer => Some("amixer"),
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    debug!("Running {} {:?}", program, args);
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// First "NN%" in the output, e.g. "front-left: 32768 /  50% / -18.06 dB"
+// from pactl or "[50%] [on]" from amixer
+fn parse_percent(output: &str) -> Option<u8> {
+    output
+        .split(|c: char| c.is_whitespace() || c == '[' || c == ']')
+        .find_map(|token| token.strip_suffix('%')?.parse::<u32>().ok())
+        .map(|percent| percent.min(100) as u8)
+}
+
+/// Volume of the default output in percent.
+pub fn volume() -> Result<u8, String> {
+    let output = match mixer()? {
+        Mixer::Pactl => run("pactl", &["get-sink-volume", DEFAULT_SINK])?,
+        Mixer::Amixer => run("amixer", &["get", "Master"])?,
+    };
+    parse_percent(&output).ok_or_else(|| "Could not read the current volume".to_string())
+}
+
+pub fn set_volume(value: u8) -> Result<(), String> {
+    let percent = format!("{}%", value.min(100));
+    match mixer()? {
+        Mixer::Pactl => run("pactl", &["set-sink-volume", DEFAULT_SINK, &percent])?,
+        Mixer::Amixer => run("amixer", &["set", "Master", &percent])?,
+    };
+    Ok(())
+}
+
+pub fn is_muted() -> Result<bool, String> {
+    match mixer()? {
+        // "Mute: yes"
+        Mixer::Pactl => Ok(run("pactl", &["get-sink-mute", DEFAULT_SINK])?.contains("yes")),
+        // "[50%] [off]" when muted
+        Mixer::Amixer => Ok(run("amixer", &["get", "Master"])?.contains("[off]")),
+    }
+}
+
+pub fn set_muted(muted: bool) -> Result<(), String> {
+    match mixer()? {
+        Mixer::Pactl => run(
+            "pactl",
+            &["set-sink-mute", DEFAULT_SINK, if muted { "1" } else { "0" }],
+        )?,
+        Mixer::Amixer => run(
+            "amixer",
+            &["set", "Master", if muted { "mute" } else { "unmute" }],
+        )?,
+    };
+    Ok(())
+}
+
+/// Whether the default microphone is muted.
+pub fn is_mic_muted() -> Result<bool, String> {
+    match mixer()? {
+        Mixer::Pactl => Ok(run("pactl", &["get-source-mute", DEFAULT_SOURCE])?.contains("yes")),
+        Mixer::Amixer => Ok(run("amixer", &["get", "Capture"])?.contains("[off]")),
+    }
+}
+
+pub fn set_mic_muted(muted: bool) -> Result<(), String> {
+    match mixer()? {
+        Mixer::Pactl => run(
+            "pactl",
+            &[
+                "set-source-mute",
+                DEFAULT_SOURCE,
+                if muted { "1" } else { "0" },
+            ],
+        )?,
+        // ALSA's capture switch is on when recording, so muting turns it off
+        Mixer::Amixer => run(
+            "amixer",
+            &["set", "Capture", if muted { "nocap" } else { "cap" }],
+        )?,
+    };
+    Ok(())
+}
+
+/// Every output PulseAudio or PipeWire knows about.
+pub fn sinks() -> Result<Vec<Sink>, String> {
+    if mixer()? != Mixer::Pactl {
+        return Err("Listing audio outputs needs pactl".to_string());
+    }
+
+    let default = run("pactl", &["get-default-sink"])?.trim().to_string();
+    let mut sinks: Vec<Sink> = Vec::new();
+    for line in run("pactl", &["list", "sinks"])?.lines() {
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix("Name:") {
+            let name = name.trim().to_string();
+            sinks.push(Sink {
+                default: name == default,
+                description: name.clone(),
+                name,
+            });
+        } else if let Some(description) = line.strip_prefix("Description:") {
+            if let Some(sink) = sinks.last_mut() {
+                sink.description = description.trim().to_string();
+            }
+        }
+    }
+    Ok(sinks)
+}
+
+/// Route new and playing streams to `name`.
+pub fn set_default_sink(name: &str) -> Result<(), String> {
+    if mixer()? != Mixer::Pactl {
+        return Err("Switching audio outputs needs pactl".to_string());
+    }
+    run("pactl", &["set-default-sink", name]).map(|_| ())
+}
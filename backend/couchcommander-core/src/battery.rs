@@ -0,0 +1,133 @@
+//! Host battery level, and a warning to every remote when a laptop driving
+//! the TV runs low while unplugged.
+
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::protocol::Topic;
+use crate::{config, events};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatteryStatus {
+    pub percent: u8,
+    pub charging: bool,
+}
+
+#[cfg(target_os = "linux")]
+fn read() -> Option<BatteryStatus> {
+    let read_file = |path: std::path::PathBuf| std::fs::read_to_string(path).ok();
+    std::fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| read_file(path.join("type")).is_some_and(|kind| kind.trim() == "Battery"))
+        .and_then(|path| {
+            let percent = read_file(path.join("capacity"))?.trim().parse().ok()?;
+            let status = read_file(path.join("status")).unwrap_or_default();
+            Some(BatteryStatus {
+                percent,
+                charging: matches!(status.trim(), "Charging" | "Full"),
+            })
+        })
+}
+
+// "-InternalBattery-0 (id=1234)	85%; charging; 0:42 remaining present: true"
+#[cfg(target_os = "macos")]
+fn read() -> Option<BatteryStatus> {
+    let output = std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let line = output
+        .lines()
+        .find(|line| line.contains("InternalBattery"))?;
+    let mut fields = line.split('\t').nth(1)?.split(';').map(str::trim);
+    let percent = fields.next()?.trim_end_matches('%').parse().ok()?;
+    let state = fields.next().unwrap_or_default();
+    Some(BatteryStatus {
+        percent,
+        charging: state == "charging" || state == "charged",
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn read() -> Option<BatteryStatus> {
+    #[repr(C)]
+    #[derive(Default)]
+    struct SystemPowerStatus {
+        ac_line_status: u8,
+        battery_flag: u8,
+        battery_life_percent: u8,
+        _system_status_flag: u8,
+        _battery_life_time: u32,
+        _battery_full_life_time: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+    }
+
+    const NO_SYSTEM_BATTERY: u8 = 128;
+    const UNKNOWN: u8 = 255;
+
+    let mut status = SystemPowerStatus::default();
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return None;
+    }
+    if status.battery_flag & NO_SYSTEM_BATTERY != 0 || status.battery_life_percent == UNKNOWN {
+        return None;
+    }
+    Some(BatteryStatus {
+        percent: status.battery_life_percent,
+        charging: status.ac_line_status == 1,
+    })
+}
+
+/// The battery's charge, None on machines without one.
+pub fn status() -> Option<BatteryStatus> {
+    read()
+}
+
+/// Poll the battery and publish a `battery` event once each time it drops to
+/// `low_battery_percent` while unplugged.
+pub fn watch_low_battery() {
+    std::thread::spawn(|| {
+        let mut warned = false;
+        loop {
+            let threshold = config::current().low_battery_percent;
+            match status() {
+                Some(battery) if threshold > 0 && !battery.charging => {
+                    if battery.percent <= threshold && !warned {
+                        warn!("Host battery low: {}%", battery.percent);
+                        events::publish(
+                            Topic::Battery,
+                            serde_json::json!({
+                                "event": "low",
+                                "message": format!(
+                                    "Host battery at {}%, plug in the computer",
+                                    battery.percent
+                                ),
+                                "battery": battery,
+                            }),
+                        );
+                        warned = true;
+                    } else if battery.percent > threshold && warned {
+                        warned = false;
+                    }
+                }
+                Some(_) => warned = false,
+                // Desktops have no battery, nothing to watch
+                None => {
+                    info!("No battery found, not watching for low battery");
+                    return;
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
@@ -0,0 +1,279 @@
+//! Paired Bluetooth devices, so a remote can bring back the living-room
+//! speaker or a game controller that connected to something else.
+//!
+//! Linux talks to BlueZ over D-Bus and macOS runs `blueutil` (`brew install
+//! blueutil`). Windows lists paired devices through PowerShell but has no
+//! public way to connect one; it reconnects them on its own when they're in
+//! range.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BluetoothDevice {
+    // e.g. "00:1A:7D:DA:71:13"
+    pub address: String,
+    pub name: String,
+    pub connected: bool,
+    // A hint like "audio-headphones" or "input-gaming", when the host knows
+    pub kind: Option<String>,
+}
+
+#[cfg(target_os = "linux")]
+mod bluez {
+    use std::collections::HashMap;
+    use tokio::sync::OnceCell;
+    use zbus::zvariant::OwnedValue;
+    use zbus::{Connection, Proxy};
+
+    use super::BluetoothDevice;
+
+    const DESTINATION: &str = "org.bluez";
+    const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+
+    static SYSTEM_BUS: OnceCell<Connection> = OnceCell::const_new();
+
+    async fn system_bus() -> Result<&'static Connection, String> {
+        SYSTEM_BUS
+            .get_or_try_init(Connection::system)
+            .await
+            .map_err(|e| format!("Failed to connect to the system bus: {}", e))
+    }
+
+    fn string(properties: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+        String::try_from(properties.get(key)?.try_clone().ok()?).ok()
+    }
+
+    fn flag(properties: &HashMap<String, OwnedValue>, key: &str) -> bool {
+        properties
+            .get(key)
+            .and_then(|value| bool::try_from(value).ok())
+            .unwrap_or(false)
+    }
+
+    // Paired devices with their D-Bus object paths
+    async fn paired() -> Result<Vec<(String, BluetoothDevice)>, String> {
+        let manager = zbus::fdo::ObjectManagerProxy::builder(system_bus().await?)
+            .destination(DESTINATION)
+            .and_then(|b| b.path("/"))
+            .map_err(|e| format!("Invalid BlueZ proxy: {}", e))?
+            .build()
+            .await
+            .map_err(|e| format!("Failed to reach BlueZ: {}", e))?;
+        let objects = manager
+            .get_managed_objects()
+            .await
+            .map_err(|e| format!("Bluetooth isn't available: {}", e))?;
+        let mut devices: Vec<(String, BluetoothDevice)> = objects
+            .into_iter()
+            .filter_map(|(path, interfaces)| {
+                let properties = interfaces.get(DEVICE_INTERFACE)?;
+                if !flag(properties, "Paired") {
+                    return None;
+                }
+                let address = string(properties, "Address")?;
+                Some((
+                    path.to_string(),
+                    BluetoothDevice {
+                        name: string(properties, "Alias").unwrap_or_else(|| address.clone()),
+                        address,
+                        connected: flag(properties, "Connected"),
+                        kind: string(properties, "Icon"),
+                    },
+                ))
+            })
+            .collect();
+        devices.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+        Ok(devices)
+    }
+
+    pub async fn list() -> Result<Vec<BluetoothDevice>, String> {
+        Ok(paired()
+            .await?
+            .into_iter()
+            .map(|(_, device)| device)
+            .collect())
+    }
+
+    pub async fn connect(address: &str) -> Result<BluetoothDevice, String> {
+        let (path, device) = paired()
+            .await?
+            .into_iter()
+            .find(|(_, device)| device.address.eq_ignore_ascii_case(address))
+            .ok_or_else(|| format!("No paired device {}", address))?;
+        let proxy = Proxy::new(system_bus().await?, DESTINATION, path, DEVICE_INTERFACE)
+            .await
+            .map_err(|e| format!("Failed to reach {}: {}", device.name, e))?;
+        proxy
+            .call_method("Connect", &())
+            .await
+            .map_err(|e| format!("Failed to connect {}: {}", device.name, e))?;
+        Ok(BluetoothDevice {
+            connected: true,
+            ..device
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod blueutil {
+    use std::process::Command;
+
+    use super::BluetoothDevice;
+
+    fn run(args: &[&str]) -> Result<String, String> {
+        let output = Command::new("blueutil").args(args).output().map_err(|_| {
+            "blueutil is not available, install it with: brew install blueutil".to_string()
+        })?;
+        if !output.status.success() {
+            return Err(format!(
+                "blueutil failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    // `[{"address": "00-1a-7d-da-71-13", "name": "Speaker", "connected": true, ...}]`
+    pub fn list() -> Result<Vec<BluetoothDevice>, String> {
+        let paired: Vec<serde_json::Value> =
+            serde_json::from_str(&run(&["--paired", "--format", "json"])?)
+                .map_err(|e| format!("Unexpected blueutil output: {}", e))?;
+        Ok(paired
+            .iter()
+            .filter_map(|device| {
+                let address = device.get("address")?.as_str()?.replace('-', ":");
+                Some(BluetoothDevice {
+                    name: device
+                        .get("name")
+                        .and_then(|name| name.as_str())
+                        .map(str::to_string)
+                        .unwrap_or_else(|| address.to_uppercase()),
+                    address: address.to_uppercase(),
+                    connected: device
+                        .get("connected")
+                        .and_then(|connected| connected.as_bool())
+                        .unwrap_or(false),
+                    kind: None,
+                })
+            })
+            .collect())
+    }
+
+    pub fn connect(device: &BluetoothDevice) -> Result<(), String> {
+        run(&["--connect", &device.address]).map(|_| ())
+    }
+}
+
+// Bluetooth devices that are paired, with whether Windows sees them connected
+#[cfg(target_os = "windows")]
+fn windows_list() -> Result<Vec<BluetoothDevice>, String> {
+    // Device nodes are `BTHENUM\DEV_001A7DDA7113\...`, one per device, and the
+    // property is the device's connected flag
+    const SCRIPT: &str = "Get-PnpDevice -Class Bluetooth | \
+        Where-Object { $_.InstanceId -like 'BTHENUM\\DEV_*' } | ForEach-Object { \
+        $connected = ($_ | Get-PnpDeviceProperty -KeyName '{83DA6326-97A6-4088-9453-A1923F573B29} 15').Data; \
+        [PSCustomObject]@{ name = $_.FriendlyName; address = $_.InstanceId.Split('\\')[1].Substring(4); \
+        connected = [bool]$connected } } | ConvertTo-Json -Compress";
+
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .output()
+        .map_err(|e| format!("Failed to run PowerShell: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to list Bluetooth devices: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    // A single device comes back as an object rather than an array
+    let devices = match serde_json::from_str(text.trim()) {
+        Ok(serde_json::Value::Array(devices)) => devices,
+        Ok(device) => vec![device],
+        Err(e) => return Err(format!("Unexpected PowerShell output: {}", e)),
+    };
+    Ok(devices
+        .iter()
+        .filter_map(|device| {
+            // A 12 digit hex string, e.g. "001A7DDA7113"
+            let raw = device.get("address")?.as_str()?;
+            let address = raw
+                .as_bytes()
+                .chunks(2)
+                .map(|pair| String::from_utf8_lossy(pair).to_uppercase())
+                .collect::<Vec<_>>()
+                .join(":");
+            Some(BluetoothDevice {
+                name: device.get("name")?.as_str()?.to_string(),
+                address,
+                connected: device
+                    .get("connected")
+                    .and_then(|connected| connected.as_bool())
+                    .unwrap_or(false),
+                kind: None,
+            })
+        })
+        .collect())
+}
+
+/// Bluetooth devices paired with the host.
+pub async fn list() -> Result<Vec<BluetoothDevice>, String> {
+    #[cfg(target_os = "linux")]
+    {
+        bluez::list().await
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        tokio::task::spawn_blocking(blueutil::list)
+            .await
+            .map_err(|e| format!("Bluetooth task panicked: {:?}", e))?
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        tokio::task::spawn_blocking(windows_list)
+            .await
+            .map_err(|e| format!("Bluetooth task panicked: {:?}", e))?
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        Err("Bluetooth isn't supported on this platform".to_string())
+    }
+}
+
+/// Connect the paired device with `address`, e.g. "00:1A:7D:DA:71:13".
+pub async fn connect(address: String) -> Result<BluetoothDevice, String> {
+    #[cfg(target_os = "linux")]
+    {
+        bluez::connect(&address).await
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let address = address.replace('-', ":");
+        tokio::task::spawn_blocking(move || {
+            let device = blueutil::list()?
+                .into_iter()
+                .find(|device| device.address.eq_ignore_ascii_case(&address))
+                .ok_or_else(|| format!("No paired device {}", address))?;
+            blueutil::connect(&device)?;
+            Ok(BluetoothDevice {
+                connected: true,
+                ..device
+            })
+        })
+        .await
+        .map_err(|e| format!("Bluetooth task panicked: {:?}", e))?
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        let _ = address;
+        Err("Bluetooth devices can't be connected from a remote on this platform".to_string())
+    }
+}
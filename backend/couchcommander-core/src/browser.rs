@@ -0,0 +1,130 @@
+//! Fullscreen kiosk launches for `open_website`, so streaming sites open
+//! ready to watch instead of as another tab behind whatever was focused.
+//! Chromium-based browsers and Firefox both take `--kiosk <url>`.
+
+use std::process::Command;
+use tracing::info;
+
+// Tried in order when no browser is configured
+#[cfg(target_os = "linux")]
+const KIOSK_BROWSERS: &[&str] = &[
+    "google-chrome",
+    "google-chrome-stable",
+    "chromium",
+    "chromium-browser",
+    "microsoft-edge",
+    "brave-browser",
+    "firefox",
+];
+#[cfg(target_os = "macos")]
+const KIOSK_BROWSERS: &[&str] = &[
+    "Google Chrome",
+    "Chromium",
+    "Microsoft Edge",
+    "Brave Browser",
+    "Firefox",
+];
+// Edge ships with Windows, registered under App Paths rather than on PATH
+#[cfg(target_os = "windows")]
+const KIOSK_BROWSERS: &[&str] = &["msedge"];
+
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+#[cfg(target_os = "linux")]
+fn installed(browser: &str) -> bool {
+    crate::input_backend::command_available(browser)
+}
+
+#[cfg(target_os = "macos")]
+fn installed(browser: &str) -> bool {
+    std::path::Path::new("/Applications")
+        .join(format!("{}.app", browser))
+        .exists()
+}
+
+#[cfg(target_os = "windows")]
+fn installed(_browser: &str) -> bool {
+    true
+}
+
+#[cfg(target_os = "linux")]
+fn kiosk_command(browser: &str, url: &str) -> Command {
+    let mut command = Command::new(browser);
+    command.args(["--kiosk", url]);
+    command
+}
+
+// -n starts a new instance so the flags aren't dropped by a running one
+#[cfg(target_os = "macos")]
+fn kiosk_command(browser: &str, url: &str) -> Command {
+    let mut command = Command::new("open");
+    command.args(["-na", browser, "--args", "--kiosk", url]);
+    command
+}
+
+// Where `start` would find the browser, without going through `cmd`, which
+// would run whatever follows a `&` in the URL
+#[cfg(target_os = "windows")]
+fn browser_path(browser: &str) -> String {
+    use std::os::windows::process::CommandExt;
+
+    if browser.contains(['\\', '/']) {
+        return browser.to_string();
+    }
+    let exe = if browser.to_lowercase().ends_with(".exe") {
+        browser.to_string()
+    } else {
+        format!("{}.exe", browser)
+    };
+    for root in ["HKCU", "HKLM"] {
+        let key = format!(
+            r"{}\SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths\{}",
+            root, exe
+        );
+        let Ok(output) = Command::new("reg")
+            .args(["query", &key, "/ve"])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+        else {
+            continue;
+        };
+        // `    (Default)    REG_SZ    C:\Program Files (x86)\...\msedge.exe`
+        let path = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.split_once("REG_SZ"))
+            .map(|(_, path)| path.trim().trim_matches('"').to_string());
+        if let Some(path) = path.filter(|path| output.status.success() && !path.is_empty()) {
+            return path;
+        }
+    }
+    // Maybe it's on PATH
+    exe
+}
+
+#[cfg(target_os = "windows")]
+fn kiosk_command(browser: &str, url: &str) -> Command {
+    let mut command = Command::new(browser_path(browser));
+    command.args(["--kiosk", url]);
+    command
+}
+
+/// Open `url` fullscreen in `browser`, or the first installed kiosk-capable
+/// browser when it's empty. Returns the browser that was used.
+pub fn open_kiosk(browser: &str, url: &str) -> Result<String, String> {
+    let browser = if browser.trim().is_empty() {
+        KIOSK_BROWSERS
+            .iter()
+            .find(|browser| installed(browser))
+            .map(|browser| browser.to_string())
+            .ok_or_else(|| "No browser with kiosk mode found, set kiosk_browser".to_string())?
+    } else {
+        browser.trim().to_string()
+    };
+
+    info!("Opening {} in kiosk mode with {}", url, browser);
+    kiosk_command(&browser, url)
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", browser, e))?;
+    Ok(browser)
+}
@@ -0,0 +1,237 @@
+//! Pushing a media URL from the host straight to a TV or speaker on the
+//! network, either a Chromecast or a DLNA renderer. While something is
+//! playing its state is pushed on the `cast` topic.
+
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::protocol::{CommandResponse, Topic};
+use crate::{chromecast, config, dlna, events, url_policy};
+
+const DISCOVERY_TIME: Duration = Duration::from_secs(3);
+const STATUS_INTERVAL: Duration = Duration::from_secs(2);
+// Give up on a device that stopped answering
+const MAX_STATUS_FAILURES: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CastKind {
+    Chromecast,
+    Dlna,
+}
+
+// How to reach a device, kept out of the list sent to remotes
+#[derive(Debug, Clone)]
+pub(crate) enum Endpoint {
+    Chromecast(SocketAddr),
+    Dlna {
+        control_url: String,
+        service_type: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CastDevice {
+    pub id: String,
+    pub name: String,
+    pub kind: CastKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip)]
+    pub(crate) endpoint: Endpoint,
+}
+
+/// `state` is one of playing, paused, buffering, idle or stopped.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlaybackState {
+    pub state: String,
+    pub position: Option<f64>,
+    pub duration: Option<f64>,
+}
+
+impl PlaybackState {
+    pub fn stopped() -> Self {
+        PlaybackState {
+            state: "stopped".to_string(),
+            position: None,
+            duration: None,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    // Devices from the last search, so casting doesn't search again
+    static ref DEVICES: Mutex<Vec<CastDevice>> = Mutex::new(Vec::new());
+    // The device playing what was cast last
+    static ref CASTING: Mutex<Option<CastDevice>> = Mutex::new(None);
+}
+
+// Bumped on every cast and stop so only the newest watcher keeps running
+static WATCH_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Search the network for Chromecasts and DLNA renderers.
+pub async fn list_devices() -> Result<Vec<CastDevice>, String> {
+    let chromecasts = tokio::task::spawn_blocking(|| chromecast::discover(DISCOVERY_TIME));
+    let (chromecasts, renderers) = tokio::join!(chromecasts, dlna::discover(DISCOVERY_TIME));
+    let chromecasts = chromecasts.map_err(|e| format!("Discovery task panicked: {:?}", e))?;
+
+    let mut devices = Vec::new();
+    for found in [chromecasts, renderers] {
+        match found {
+            Ok(found) => devices.extend(found),
+            Err(e) => warn!("Cast discovery failed: {}", e),
+        }
+    }
+    devices.sort_by_key(|device| device.name.to_lowercase());
+    *DEVICES.lock().unwrap() = devices.clone();
+    Ok(devices)
+}
+
+// A device by id or name, searching again if it isn't known yet
+async fn find(device: &str) -> Result<CastDevice, String> {
+    let matches =
+        |known: &CastDevice| known.id == device || known.name.eq_ignore_ascii_case(device);
+    let known = DEVICES.lock().unwrap().iter().find(|d| matches(d)).cloned();
+    if let Some(known) = known {
+        return Ok(known);
+    }
+    list_devices()
+        .await?
+        .into_iter()
+        .find(matches)
+        .ok_or_else(|| format!("No cast device named {}", device))
+}
+
+// Chromecasts need a content type, guessed from the extension
+fn content_type(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let extension = path.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("mp4") | Some("m4v") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("mkv") => "video/x-matroska",
+        Some("m3u8") => "application/x-mpegURL",
+        Some("mpd") => "application/dash+xml",
+        Some("mp3") => "audio/mpeg",
+        Some("m4a") | Some("aac") => "audio/mp4",
+        Some("ogg") | Some("oga") => "audio/ogg",
+        Some("flac") => "audio/flac",
+        Some("wav") => "audio/wav",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        _ => "video/mp4",
+    }
+}
+
+async fn status(device: &CastDevice) -> Result<PlaybackState, String> {
+    match device.kind {
+        CastKind::Chromecast => chromecast::status(device).await,
+        CastKind::Dlna => dlna::status(device).await,
+    }
+}
+
+fn publish(device: &CastDevice, state: &PlaybackState) {
+    events::publish(
+        Topic::Cast,
+        serde_json::json!({
+            "device": device.id,
+            "name": device.name,
+            "state": state,
+        }),
+    );
+}
+
+// Relay the device's state to remotes until playback ends or the device
+// goes away
+fn watch(device: CastDevice) {
+    let generation = WATCH_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    tokio::spawn(async move {
+        let mut last: Option<PlaybackState> = None;
+        let mut started = false;
+        let mut failures = 0;
+        loop {
+            tokio::time::sleep(STATUS_INTERVAL).await;
+            if WATCH_GENERATION.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            let state = match status(&device).await {
+                Ok(state) => {
+                    failures = 0;
+                    state
+                }
+                Err(e) => {
+                    failures += 1;
+                    if failures < MAX_STATUS_FAILURES {
+                        continue;
+                    }
+                    warn!("Lost track of {}: {}", device.name, e);
+                    PlaybackState::stopped()
+                }
+            };
+            if last.as_ref() != Some(&state) {
+                publish(&device, &state);
+            }
+            let playing = matches!(state.state.as_str(), "playing" | "paused" | "buffering");
+            started |= playing;
+            if (started && !playing) || failures >= MAX_STATUS_FAILURES {
+                return;
+            }
+            last = Some(state);
+        }
+    });
+}
+
+/// Play `url` on `device`, given by id or name.
+pub async fn cast_media(url: &str, device: &str) -> Result<CommandResponse, String> {
+    url_policy::check(&config::current(), url)?;
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err("Only http:// and https:// URLs can be cast".to_string());
+    }
+    let device = find(device).await?;
+    match device.kind {
+        CastKind::Chromecast => chromecast::cast(&device, url, content_type(url)).await?,
+        CastKind::Dlna => dlna::cast(&device, url).await?,
+    }
+    info!("Casting {} to {}", url, device.name);
+    *CASTING.lock().unwrap() = Some(device.clone());
+    watch(device.clone());
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Casting to {}", device.name),
+    })
+}
+
+/// Stop playback on `device`, or on the device last cast to.
+pub async fn stop(device: Option<&str>) -> Result<CommandResponse, String> {
+    let device = match device {
+        Some(device) => find(device).await?,
+        None => CASTING
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| "Nothing is being cast".to_string())?,
+    };
+    match device.kind {
+        CastKind::Chromecast => chromecast::stop(&device).await?,
+        CastKind::Dlna => dlna::stop(&device).await?,
+    }
+    {
+        let mut casting = CASTING.lock().unwrap();
+        if casting
+            .as_ref()
+            .is_some_and(|casting| casting.id == device.id)
+        {
+            *casting = None;
+            WATCH_GENERATION.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+    publish(&device, &PlaybackState::stopped());
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Stopped casting to {}", device.name),
+    })
+}
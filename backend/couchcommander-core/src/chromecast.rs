@@ -0,0 +1,428 @@
+//! Chromecast support for `cast`: mDNS discovery and just enough of the
+//! Cast v2 protocol to start the default media receiver, load a URL into
+//! it, read its playback state and stop it.
+//!
+//! Cast v2 is protobuf messages over TLS on port 8009. Chromecasts use
+//! certificates signed by Google's own device CA, so the certificate isn't
+//! checked, as every sender without that CA does.
+
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+use serde_json::{json, Value};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use tracing::debug;
+
+use crate::cast::{CastDevice, CastKind, Endpoint, PlaybackState};
+
+pub const SERVICE_TYPE: &str = "_googlecast._tcp.local.";
+
+const DEFAULT_MEDIA_RECEIVER: &str = "CC1AD845";
+const SENDER: &str = "sender-0";
+const RECEIVER: &str = "receiver-0";
+const CONNECTION_NAMESPACE: &str = "urn:x-cast:com.google.cast.tp.connection";
+const HEARTBEAT_NAMESPACE: &str = "urn:x-cast:com.google.cast.tp.heartbeat";
+const RECEIVER_NAMESPACE: &str = "urn:x-cast:com.google.cast.receiver";
+const MEDIA_NAMESPACE: &str = "urn:x-cast:com.google.cast.media";
+// Launching the receiver app can take a few seconds on older devices
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+// Accepts any certificate, see the module docs
+#[derive(Debug)]
+struct AnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], position: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*position)?;
+        *position += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+// CastMessage { protocol_version = 0, source_id, destination_id, namespace,
+// payload_type = STRING, payload_utf8 }
+fn encode(destination: &str, namespace: &str, payload: &str) -> Vec<u8> {
+    let mut message = vec![0x08, 0x00];
+    for (tag, text) in [(0x12, SENDER), (0x1a, destination), (0x22, namespace)] {
+        message.push(tag);
+        write_varint(&mut message, text.len() as u64);
+        message.extend_from_slice(text.as_bytes());
+    }
+    message.extend_from_slice(&[0x28, 0x00, 0x32]);
+    write_varint(&mut message, payload.len() as u64);
+    message.extend_from_slice(payload.as_bytes());
+    message
+}
+
+// (namespace, payload) of a CastMessage, skipping fields we don't need
+fn decode(message: &[u8]) -> Option<(String, String)> {
+    let mut position = 0;
+    let (mut namespace, mut payload) = (String::new(), String::new());
+    while position < message.len() {
+        let key = read_varint(message, &mut position)?;
+        match key & 0x07 {
+            0 => {
+                read_varint(message, &mut position)?;
+            }
+            2 => {
+                let length = read_varint(message, &mut position)? as usize;
+                let bytes = message.get(position..position.checked_add(length)?)?;
+                position += length;
+                match key >> 3 {
+                    4 => namespace = String::from_utf8_lossy(bytes).into_owned(),
+                    6 => payload = String::from_utf8_lossy(bytes).into_owned(),
+                    _ => {}
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some((namespace, payload))
+}
+
+struct Connection {
+    stream: TlsStream<TcpStream>,
+    request_id: u64,
+}
+
+impl Connection {
+    async fn open(address: SocketAddr) -> Result<Self, String> {
+        let provider = Arc::new(ring::default_provider());
+        let config = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("Failed to configure TLS: {}", e))?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AnyCertificate(provider)))
+            .with_no_client_auth();
+        let tcp = TcpStream::connect(address)
+            .await
+            .map_err(|e| format!("Failed to connect to the Chromecast: {}", e))?;
+        let stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::IpAddress(address.ip().into()), tcp)
+            .await
+            .map_err(|e| format!("Chromecast TLS handshake failed: {}", e))?;
+        let mut connection = Connection {
+            stream,
+            request_id: 0,
+        };
+        connection
+            .send(RECEIVER, CONNECTION_NAMESPACE, json!({ "type": "CONNECT" }))
+            .await?;
+        Ok(connection)
+    }
+
+    async fn send(
+        &mut self,
+        destination: &str,
+        namespace: &str,
+        payload: Value,
+    ) -> Result<(), String> {
+        let message = encode(destination, namespace, &payload.to_string());
+        let mut frame = (message.len() as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(&message);
+        self.stream
+            .write_all(&frame)
+            .await
+            .map_err(|e| format!("Failed to send to the Chromecast: {}", e))
+    }
+
+    // Next message that isn't a heartbeat, answering pings on the way
+    async fn receive(&mut self) -> Result<(String, Value), String> {
+        loop {
+            let mut length = [0u8; 4];
+            self.stream
+                .read_exact(&mut length)
+                .await
+                .map_err(|e| format!("Chromecast closed the connection: {}", e))?;
+            let length = u32::from_be_bytes(length) as usize;
+            if length > MAX_MESSAGE_BYTES {
+                return Err("Chromecast sent an oversized message".to_string());
+            }
+            let mut message = vec![0u8; length];
+            self.stream
+                .read_exact(&mut message)
+                .await
+                .map_err(|e| format!("Chromecast closed the connection: {}", e))?;
+            let (namespace, payload) =
+                decode(&message).ok_or_else(|| "Malformed Chromecast message".to_string())?;
+            let payload: Value = serde_json::from_str(&payload).unwrap_or(Value::Null);
+            if namespace == HEARTBEAT_NAMESPACE {
+                if payload["type"] == "PING" {
+                    self.send(RECEIVER, HEARTBEAT_NAMESPACE, json!({ "type": "PONG" }))
+                        .await?;
+                }
+                continue;
+            }
+            return Ok((namespace, payload));
+        }
+    }
+
+    // Send a request and wait for the reply carrying its requestId
+    async fn request(
+        &mut self,
+        destination: &str,
+        namespace: &str,
+        mut payload: Value,
+    ) -> Result<Value, String> {
+        self.request_id += 1;
+        let request_id = self.request_id;
+        payload["requestId"] = json!(request_id);
+        self.send(destination, namespace, payload).await?;
+
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        loop {
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .ok_or_else(|| "The Chromecast didn't answer".to_string())?;
+            let (reply_namespace, reply) =
+                tokio::time::timeout(remaining, self.receive())
+                    .await
+                    .map_err(|_| "The Chromecast didn't answer".to_string())??;
+            if reply_namespace == namespace && reply["requestId"] == request_id {
+                return Ok(reply);
+            }
+            debug!("Skipping Chromecast message {}", reply["type"]);
+        }
+    }
+
+    // The default media receiver's session and transport ids, if it runs
+    async fn media_receiver(&mut self) -> Result<Option<(String, String)>, String> {
+        let status = self
+            .request(
+                RECEIVER,
+                RECEIVER_NAMESPACE,
+                json!({ "type": "GET_STATUS" }),
+            )
+            .await?;
+        Ok(running_app(&status))
+    }
+
+    async fn media_status(&mut self, transport: &str) -> Result<Option<Value>, String> {
+        self.send(
+            transport,
+            CONNECTION_NAMESPACE,
+            json!({ "type": "CONNECT" }),
+        )
+        .await?;
+        let status = self
+            .request(transport, MEDIA_NAMESPACE, json!({ "type": "GET_STATUS" }))
+            .await?;
+        Ok(status["status"].as_array().and_then(|s| s.first()).cloned())
+    }
+}
+
+fn running_app(status: &Value) -> Option<(String, String)> {
+    let app = status["status"]["applications"]
+        .as_array()?
+        .iter()
+        .find(|app| app["appId"] == DEFAULT_MEDIA_RECEIVER)?;
+    Some((
+        app["sessionId"].as_str()?.to_string(),
+        app["transportId"].as_str()?.to_string(),
+    ))
+}
+
+/// Chromecasts on the network, browsing for up to `timeout`.
+pub fn discover(timeout: Duration) -> Result<Vec<CastDevice>, String> {
+    let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS daemon: {}", e))?;
+    let receiver = daemon
+        .browse(SERVICE_TYPE)
+        .map_err(|e| format!("Failed to browse mDNS services: {}", e))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut devices: Vec<CastDevice> = Vec::new();
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match receiver.recv_timeout(remaining) {
+            Ok(ServiceEvent::ServiceResolved(info)) => {
+                let Some(address) = info.get_addresses_v4().into_iter().next().copied() else {
+                    continue;
+                };
+                let id = format!(
+                    "chromecast:{}",
+                    info.get_property_val_str("id")
+                        .unwrap_or(info.get_fullname())
+                );
+                if devices.iter().any(|device| device.id == id) {
+                    continue;
+                }
+                devices.push(CastDevice {
+                    name: info
+                        .get_property_val_str("fn")
+                        .unwrap_or(info.get_hostname())
+                        .to_string(),
+                    model: info.get_property_val_str("md").map(str::to_string),
+                    kind: CastKind::Chromecast,
+                    id,
+                    endpoint: Endpoint::Chromecast(SocketAddr::new(
+                        IpAddr::V4(address),
+                        info.get_port(),
+                    )),
+                });
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+
+    let _ = daemon.stop_browse(SERVICE_TYPE);
+    let _ = daemon.shutdown();
+    Ok(devices)
+}
+
+fn socket_address(device: &CastDevice) -> Result<SocketAddr, String> {
+    match device.endpoint {
+        Endpoint::Chromecast(address) => Ok(address),
+        _ => Err(format!("{} isn't a Chromecast", device.name)),
+    }
+}
+
+/// Start the default media receiver and play `url` on it.
+pub async fn cast(device: &CastDevice, url: &str, content_type: &str) -> Result<(), String> {
+    let mut connection = Connection::open(socket_address(device)?).await?;
+    let (session, transport) = match connection.media_receiver().await? {
+        Some(running) => running,
+        None => {
+            let status = connection
+                .request(
+                    RECEIVER,
+                    RECEIVER_NAMESPACE,
+                    json!({ "type": "LAUNCH", "appId": DEFAULT_MEDIA_RECEIVER }),
+                )
+                .await?;
+            running_app(&status)
+                .ok_or_else(|| "The Chromecast didn't start its media player".to_string())?
+        }
+    };
+
+    connection
+        .send(
+            &transport,
+            CONNECTION_NAMESPACE,
+            json!({ "type": "CONNECT" }),
+        )
+        .await?;
+    let reply = connection
+        .request(
+            &transport,
+            MEDIA_NAMESPACE,
+            json!({
+                "type": "LOAD",
+                "sessionId": session,
+                "autoplay": true,
+                "media": {
+                    "contentId": url,
+                    "contentType": content_type,
+                    "streamType": "BUFFERED",
+                },
+            }),
+        )
+        .await?;
+    match reply["type"].as_str() {
+        Some("MEDIA_STATUS") => Ok(()),
+        _ => Err(format!(
+            "The Chromecast couldn't play the media ({})",
+            reply["type"].as_str().unwrap_or("no reason given")
+        )),
+    }
+}
+
+/// What the default media receiver is playing, stopped when it isn't running.
+pub async fn status(device: &CastDevice) -> Result<PlaybackState, String> {
+    let mut connection = Connection::open(socket_address(device)?).await?;
+    let Some((_, transport)) = connection.media_receiver().await? else {
+        return Ok(PlaybackState::stopped());
+    };
+    let Some(status) = connection.media_status(&transport).await? else {
+        return Ok(PlaybackState::stopped());
+    };
+    Ok(PlaybackState {
+        state: status["playerState"]
+            .as_str()
+            .unwrap_or("IDLE")
+            .to_lowercase(),
+        position: status["currentTime"].as_f64(),
+        duration: status["media"]["duration"].as_f64(),
+    })
+}
+
+/// Close the media receiver, which stops playback.
+pub async fn stop(device: &CastDevice) -> Result<(), String> {
+    let mut connection = Connection::open(socket_address(device)?).await?;
+    if let Some((session, _)) = connection.media_receiver().await? {
+        connection
+            .request(
+                RECEIVER,
+                RECEIVER_NAMESPACE,
+                json!({ "type": "STOP", "sessionId": session }),
+            )
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,168 @@
+//! Plain-text clipboard access through each platform's own tools, and paste
+//! injection for text that can't be typed key by key.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::input_backend::InputBackend;
+use enigo::{Direction, Key};
+
+// Give the focused app time to read the clipboard before it's restored
+const PASTE_SETTLE: Duration = Duration::from_millis(150);
+
+// Counts sensitive pastes starting and ending, odd while one holds the
+// clipboard. See `secret_generation`
+static SECRET_PASTES: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+#[cfg(target_os = "macos")]
+fn commands() -> (Vec<&'static str>, Vec<&'static str>) {
+    (vec!["pbpaste"], vec!["pbcopy"])
+}
+
+#[cfg(target_os = "windows")]
+fn commands() -> (Vec<&'static str>, Vec<&'static str>) {
+    (
+        vec![
+            "powershell",
+            "-NoProfile",
+            "-Command",
+            "[Console]::OutputEncoding = [Text.Encoding]::UTF8; Get-Clipboard -Raw",
+        ],
+        vec![
+            "powershell",
+            "-NoProfile",
+            "-Command",
+            "[Console]::InputEncoding = [Text.Encoding]::UTF8; \
+             Set-Clipboard -Value ([Console]::In.ReadToEnd())",
+        ],
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn commands() -> (Vec<&'static str>, Vec<&'static str>) {
+    if crate::wayland::is_wayland_session() {
+        (vec!["wl-paste", "--no-newline"], vec!["wl-copy"])
+    } else {
+        (
+            vec!["xclip", "-selection", "clipboard", "-o"],
+            vec!["xclip", "-selection", "clipboard"],
+        )
+    }
+}
+
+fn command(argv: &[&str]) -> Command {
+    let mut command = Command::new(argv[0]);
+    command.args(&argv[1..]);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+}
+
+/// The clipboard's text, empty if it holds something else.
+pub fn get() -> Result<String, String> {
+    let (argv, _) = commands();
+    let output = command(&argv)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", argv[0], e))?;
+    if !output.status.success() {
+        return Err(format!("{} failed to read the clipboard", argv[0]));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub fn set(text: &str) -> Result<(), String> {
+    let (_, argv) = commands();
+    let mut child = command(&argv)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", argv[0], e))?;
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| format!("Failed to open {} input", argv[0]))?
+        .write_all(text.as_bytes())
+        .map_err(|e| format!("Failed to write to {}: {}", argv[0], e))?;
+    let status = child
+        .wait()
+        .map_err(|e| format!("{} did not finish: {}", argv[0], e))?;
+    if !status.success() {
+        return Err(format!("{} failed to set the clipboard", argv[0]));
+    }
+    Ok(())
+}
+
+fn paste_modifier() -> Key {
+    if cfg!(target_os = "macos") {
+        Key::Meta
+    } else {
+        Key::Control
+    }
+}
+
+/// Changes while a sensitive paste is running and is odd during it. A
+/// clipboard read is only safe to share if it's even and the same before
+/// and after the read.
+pub fn secret_generation() -> u64 {
+    SECRET_PASTES.load(Ordering::SeqCst)
+}
+
+/// Put `text` on the clipboard, send Cmd/Ctrl+V and restore what the
+/// clipboard held before. Only text contents can be restored.
+pub fn paste<B: InputBackend>(input: &mut B, text: &str) -> Result<(), String> {
+    paste_with(input, text, false)
+}
+
+/// [`paste`] for a password: the clipboard isn't shared with remotes while
+/// it holds `text`, and is cleared afterwards if it can't be restored.
+pub fn paste_secret<B: InputBackend>(input: &mut B, text: &str) -> Result<(), String> {
+    SECRET_PASTES.fetch_add(1, Ordering::SeqCst);
+    let result = paste_with(input, text, true);
+    SECRET_PASTES.fetch_add(1, Ordering::SeqCst);
+    result
+}
+
+fn paste_with<B: InputBackend>(input: &mut B, text: &str, secret: bool) -> Result<(), String> {
+    let previous = get().ok();
+    set(text)?;
+    // Not even the length of a secret is logged
+    if !secret {
+        debug!("Pasting {} bytes from the clipboard", text.len());
+    }
+
+    let modifier = paste_modifier();
+    let result = input
+        .key(modifier, Direction::Press)
+        .and_then(|_| {
+            // Always let go of the modifier, even if the V didn't go through
+            let pressed = input.key(Key::Unicode('v'), Direction::Click);
+            let released = input.key(modifier, Direction::Release);
+            pressed.and(released)
+        })
+        .map_err(|e| format!("Failed to send paste shortcut: {:?}", e));
+
+    std::thread::sleep(PASTE_SETTLE);
+    let restored = match previous {
+        Some(previous) => set(&previous).map_err(|e| {
+            warn!("Failed to restore the clipboard: {}", e);
+        }),
+        None => Err(()),
+    };
+    // A secret mustn't stay behind in place of contents we can't restore
+    if restored.is_err() && secret {
+        if let Err(e) = set("") {
+            warn!("Failed to clear the clipboard: {}", e);
+        }
+    }
+    result
+}
@@ -0,0 +1,90 @@
+//! Local time of day, for rules like "blocked after 21:00". The standard
+//! library only knows UTC, so the local offset is asked from the system
+//! (`date` or PowerShell on Windows) and kept for a few minutes, long enough
+//! to not run a process per command and short enough to follow DST.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+const OFFSET_TTL: Duration = Duration::from_secs(10 * 60);
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+static OFFSET: Mutex<Option<(Instant, i64)>> = Mutex::new(None);
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    // Keeps powershell from flashing a console window
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    pub fn utc_offset_secs() -> Option<i64> {
+        let output = Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                "[int][TimeZoneInfo]::Local.GetUtcOffset([DateTime]::Now).TotalSeconds",
+            ])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod platform {
+    use std::process::Command;
+
+    // `date +%z` prints e.g. "+0530" or "-0800"
+    pub fn utc_offset_secs() -> Option<i64> {
+        let output = Command::new("date").arg("+%z").output().ok()?;
+        let offset = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let (sign, digits) = match offset.split_at_checked(1)? {
+            ("+", digits) => (1, digits),
+            ("-", digits) => (-1, digits),
+            _ => return None,
+        };
+        if digits.len() != 4 {
+            return None;
+        }
+        let hours: i64 = digits[..2].parse().ok()?;
+        let minutes: i64 = digits[2..].parse().ok()?;
+        Some(sign * (hours * 3600 + minutes * 60))
+    }
+}
+
+fn utc_offset_secs() -> i64 {
+    let mut cached = OFFSET.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((fetched_at, offset)) = *cached {
+        if fetched_at.elapsed() < OFFSET_TTL {
+            return offset;
+        }
+    }
+    let offset = platform::utc_offset_secs().unwrap_or_else(|| {
+        warn!("Couldn't read the local time zone, using UTC");
+        0
+    });
+    *cached = Some((Instant::now(), offset));
+    offset
+}
+
+/// Minutes since local midnight.
+pub fn minutes_of_day() -> u32 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs() as i64)
+        .unwrap_or_default();
+    ((now + utc_offset_secs()).rem_euclid(SECS_PER_DAY) / 60) as u32
+}
+
+/// Parse a 24 hour `"HH:MM"` time into minutes since midnight.
+pub fn parse_time(text: &str) -> Option<u32> {
+    let (hours, minutes) = text.split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60 && text.len() == 5).then_some(hours * 60 + minutes)
+}
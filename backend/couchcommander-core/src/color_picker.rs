@@ -0,0 +1,130 @@
+//! The color of one pixel on screen, under the pointer or at given
+//! coordinates. Windows reads it from GDI. macOS captures a one pixel
+//! screenshot with `screencapture`, which needs the Screen Recording
+//! permission, and Linux with `grim` on Wayland or ImageMagick's `import`
+//! on X11.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PickedColor {
+    pub x: i32,
+    pub y: i32,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    // e.g. "#1E90FF"
+    pub hex: String,
+}
+
+// Top left pixel of a PNG screenshot, a Retina capture of one point is 2x2
+#[cfg(not(target_os = "windows"))]
+fn first_pixel(png: &[u8]) -> Result<(u8, u8, u8), String> {
+    let image = image::load_from_memory_with_format(png, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to read the screenshot: {}", e))?
+        .to_rgb8();
+    let pixel = image
+        .pixels()
+        .next()
+        .ok_or_else(|| "The screenshot is empty".to_string())?;
+    Ok((pixel[0], pixel[1], pixel[2]))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn capture(program: &str, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(target_os = "windows")]
+fn sample(x: i32, y: i32) -> Result<(u8, u8, u8), String> {
+    use std::ffi::c_void;
+
+    // GetPixel's answer outside the screen
+    const CLR_INVALID: u32 = 0xFFFF_FFFF;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetDC(window: *mut c_void) -> *mut c_void;
+        fn ReleaseDC(window: *mut c_void, hdc: *mut c_void) -> i32;
+    }
+    #[link(name = "gdi32")]
+    extern "system" {
+        fn GetPixel(hdc: *mut c_void, x: i32, y: i32) -> u32;
+    }
+
+    let color = unsafe {
+        let screen = GetDC(std::ptr::null_mut());
+        if screen.is_null() {
+            return Err("Failed to open the screen".to_string());
+        }
+        let color = GetPixel(screen, x, y);
+        ReleaseDC(std::ptr::null_mut(), screen);
+        color
+    };
+    if color == CLR_INVALID {
+        return Err(format!("({}, {}) is off screen", x, y));
+    }
+    // 0x00BBGGRR
+    Ok((color as u8, (color >> 8) as u8, (color >> 16) as u8))
+}
+
+#[cfg(target_os = "macos")]
+fn sample(x: i32, y: i32) -> Result<(u8, u8, u8), String> {
+    // screencapture only writes to files
+    let path = std::env::temp_dir().join(format!("couchcommander-{}.png", uuid::Uuid::new_v4()));
+    let rect = format!("{},{},1,1", x, y);
+    let result = capture(
+        "screencapture",
+        &["-x", "-t", "png", "-R", &rect, &path.to_string_lossy()],
+    )
+    .and_then(|_| {
+        std::fs::read(&path).map_err(|e| format!("Failed to read the screenshot: {}", e))
+    });
+    let _ = std::fs::remove_file(&path);
+    first_pixel(&result?)
+}
+
+#[cfg(target_os = "linux")]
+fn sample(x: i32, y: i32) -> Result<(u8, u8, u8), String> {
+    let png = if crate::wayland::is_wayland_session() {
+        capture(
+            "grim",
+            &["-g", &format!("{},{} 1x1", x, y), "-t", "png", "-"],
+        )?
+    } else {
+        let crop = format!("1x1+{}+{}", x, y);
+        capture("import", &["-window", "root", "-crop", &crop, "png:-"])?
+    };
+    first_pixel(&png)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn sample(_x: i32, _y: i32) -> Result<(u8, u8, u8), String> {
+    Err("Colors can't be picked on this platform".to_string())
+}
+
+/// The color of the pixel at `(x, y)` in desktop coordinates.
+pub async fn pick(x: i32, y: i32) -> Result<PickedColor, String> {
+    let (r, g, b) = tokio::task::spawn_blocking(move || sample(x, y))
+        .await
+        .map_err(|e| format!("Color picker panicked: {:?}", e))??;
+    Ok(PickedColor {
+        x,
+        y,
+        r,
+        g,
+        b,
+        hex: format!("#{:02X}{:02X}{:02X}", r, g, b),
+    })
+}
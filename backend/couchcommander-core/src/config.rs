@@ -0,0 +1,884 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
+
+use crate::aliases::{self, CommandAlias};
+use crate::i18n::Locale;
+use crate::{gamepad, http_client, keymap, relay, secrets, stats};
+
+const SETTINGS_FILE: &str = "settings.toml";
+const APP_DIR_NAME: &str = "CouchCommander";
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(2);
+// Schemes that run code or read local files when opened
+const UNSAFE_URL_SCHEMES: &[&str] = &["file", "javascript", "data", "vbscript", "ms-msdt"];
+
+lazy_static::lazy_static! {
+    static ref SETTINGS: RwLock<Settings> = RwLock::new(Settings::default());
+    static ref LOADED_MTIME: RwLock<Option<SystemTime>> = RwLock::new(None);
+    // What the keychain holds for each secret as far as this run knows, so a
+    // save only runs the keychain tool for the ones that changed
+    static ref KEYCHAIN: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    None,
+    Token,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeatureToggles {
+    pub media: bool,
+    pub keyboard: bool,
+    pub text_input: bool,
+    pub mouse: bool,
+    pub system_controls: bool,
+    pub open_website: bool,
+    // Muting the host's microphone
+    pub microphone: bool,
+    // Browsing and opening media folders, and uploads
+    pub files: bool,
+    // Schedules and commands forwarded to linked hosts
+    pub automation: bool,
+}
+
+impl Default for FeatureToggles {
+    fn default() -> Self {
+        Self {
+            media: true,
+            keyboard: true,
+            text_input: true,
+            mouse: true,
+            system_controls: true,
+            open_website: true,
+            microphone: true,
+            files: true,
+            automation: true,
+        }
+    }
+}
+
+impl FeatureToggles {
+    /// Whether `command` is one of the media commands the `media` toggle covers.
+    pub fn is_media(command: &str) -> bool {
+        matches!(
+            command,
+            "play_pause"
+                | "media_previous"
+                | "media_next"
+                | "media_stop"
+                | "media_seek"
+                | "media_browse"
+                | "media_on_deck"
+                | "volume_up"
+                | "volume_down"
+                | "volume_mute"
+                | "set_mute"
+        )
+    }
+
+    /// Whether these toggles allow a protocol command to run. Commands no
+    /// toggle knows about are refused.
+    pub fn allows(&self, command: &str) -> bool {
+        match command {
+            command if Self::is_media(command) => self.media,
+            "send_key"
+            | "toggle_modifier_key"
+            | "clear_modifier_keys"
+            | "open_search"
+            | "find_in_page"
+            | "gamepad_button"
+            | "gamepad_release"
+            | "key_down"
+            | "key_up"
+            | "release_keys"
+            | "edit_action"
+            | "send_raw_key" => self.keyboard,
+            "text_input" | "text_stream" => self.text_input,
+            "mouse_move" | "mouse_click" | "scroll" | "get_mouse_position" | "pick_color" => {
+                self.mouse
+            }
+            "volume_set"
+            | "volume_fade"
+            | "brightness_set"
+            | "brightness_up"
+            | "brightness_down"
+            | "set_audio_output"
+            | "applescript"
+            | "wake_device"
+            | "switch_desktop"
+            | "move_window_to_desktop"
+            | "set_display_mode"
+            | "display_sleep"
+            | "display_wake"
+            | "set_keyboard_backlight"
+            | "set_power_profile"
+            | "connect_bluetooth_device"
+            | "display_content"
+            | "hide_content"
+            | "zoom"
+            | "toggle_accessibility"
+            | "speak"
+            | "announce"
+            | "stop_speaking"
+            | "notify_host"
+            | "test_enigo_creation"
+            | "test_space_key" => self.system_controls,
+            "open_website" | "cast_media" | "cast_stop" => self.open_website,
+            "toggle_mic_mute" | "get_mic_mute" => self.microphone,
+            "list_directory" | "open_file" | "upload_start" | "upload_cancel" => self.files,
+            "schedule_command" | "list_schedules" | "cancel_schedule" | "forward_to"
+            | "list_linked_hosts" => self.automation,
+            // Reading state, and what only touches the remote's own session
+            "get_capabilities"
+            | "get_keyboard_layout"
+            | "get_modifier_key_states"
+            | "now_playing"
+            | "get_volume"
+            | "get_brightness"
+            | "get_privacy_indicators"
+            | "list_audio_outputs"
+            | "list_cast_devices"
+            | "get_idle_time"
+            | "get_frontmost_app"
+            | "get_host_status"
+            | "get_activity"
+            | "get_battery_status"
+            | "list_displays"
+            | "list_display_modes"
+            | "get_keyboard_backlight"
+            | "get_power_profiles"
+            | "get_network_info"
+            | "list_bluetooth_devices"
+            | "subscribe"
+            | "unsubscribe"
+            | "subscribe_system_stats"
+            | "unsubscribe_system_stats"
+            | "take_control"
+            | "release_control"
+            | "set_client_name"
+            | "get_remote_layout"
+            | "set_remote_layout"
+            | "start_repeat"
+            | "stop_repeat"
+            | "ping" => true,
+            // Admin remotes are checked on their own
+            "list_clients" | "kick_client" | "set_input_paused" | "regenerate_pairing_code" => true,
+            _ => false,
+        }
+    }
+}
+
+/// An extra listener next to the main one, with its own auth and allowed
+/// commands, e.g. a LAN one limited to media keys while the main one only
+/// listens on Tailscale.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListenerProfile {
+    // Shown in the server status and the client list
+    pub name: String,
+    pub bind_address: String,
+    pub port: u16,
+    pub auth_mode: AuthMode,
+    // Applied on top of the main feature toggles, which still switch a
+    // feature off everywhere
+    pub features: FeatureToggles,
+}
+
+impl Default for ListenerProfile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            bind_address: "0.0.0.0".to_string(),
+            port: 0,
+            auth_mode: AuthMode::Token,
+            features: FeatureToggles::default(),
+        }
+    }
+}
+
+/// Another CouchCommander host this one passes commands on to with
+/// `forward_to`, through that host's HTTP API. See `forwarding`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkedHost {
+    // What remotes call it, e.g. "htpc": letters, digits, `-` and `_`
+    pub name: String,
+    // e.g. "http://192.168.1.20:8080", or "https://..." with `fingerprint`
+    pub url: String,
+    // That host's `api_token`
+    pub token: String,
+    // SHA-256 of that host's certificate, needed for an https:// URL
+    pub fingerprint: String,
+}
+
+/// A relay both the host and remotes connect out to, for control from
+/// outside the LAN without port forwarding. See `relay`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelaySettings {
+    // "relay.example.com:7000", empty to turn the relay off
+    pub address: String,
+    // Shared with remotes through pairing, anyone who knows it can reach
+    // the TLS handshake
+    pub room: String,
+}
+
+impl RelaySettings {
+    pub fn enabled(&self) -> bool {
+        !self.address.trim().is_empty()
+    }
+}
+
+// Global shortcuts in accelerator syntax, e.g. "CmdOrCtrl+Alt+P". Empty disables one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeySettings {
+    pub toggle_server: String,
+    pub toggle_pause: String,
+}
+
+impl Default for HotkeySettings {
+    fn default() -> Self {
+        Self {
+            toggle_server: "CmdOrCtrl+Alt+S".to_string(),
+            toggle_pause: "CmdOrCtrl+Alt+P".to_string(),
+        }
+    }
+}
+
+// Which remotes may push files to the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadPermission {
+    Off,
+    // Only clients that connected with a pairing or session token
+    Paired,
+    All,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadSettings {
+    pub permission: UploadPermission,
+    // Folder uploads are saved to, empty for the system downloads folder
+    pub dir: String,
+    pub max_mb: u64,
+}
+
+impl Default for UploadSettings {
+    fn default() -> Self {
+        Self {
+            permission: UploadPermission::Off,
+            dir: String::new(),
+            max_mb: 512,
+        }
+    }
+}
+
+// How input from several remotes at once is arbitrated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputPolicy {
+    // Every remote's input goes through
+    #[default]
+    Shared,
+    // One remote holds input after take_control
+    Exclusive,
+    // The remote that sent input last has it
+    LastWriter,
+}
+
+// Where gamepad presses from remotes go
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamepadDevice {
+    // Key presses from the bindings
+    #[default]
+    Keys,
+    // A virtual controller through uinput (Linux only)
+    Virtual,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadSettings {
+    pub device: GamepadDevice,
+    // Button to key name overrides, e.g. a = "j"
+    pub bindings: BTreeMap<String, String>,
+}
+
+// Media server that takes media commands over its HTTP API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaServerKind {
+    #[default]
+    None,
+    Plex,
+    Jellyfin,
+    Kodi,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MediaServerSettings {
+    pub kind: MediaServerKind,
+    // e.g. "http://127.0.0.1:32400" for Plex, port 8096 for Jellyfin and
+    // 8080 for Kodi's web server
+    pub url: String,
+    // Plex token, Jellyfin API key, or "user:password" for Kodi
+    pub token: String,
+    // Jellyfin user whose libraries are browsed, empty for the first one
+    pub user_id: String,
+}
+
+// What to do once no remote has sent input for a while and media is playing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdleSettings {
+    // 0 to never go idle
+    pub timeout_mins: u64,
+    pub pause_media: bool,
+    pub dim_brightness: bool,
+    pub dim_percent: u8,
+}
+
+impl Default for IdleSettings {
+    fn default() -> Self {
+        Self {
+            timeout_mins: 0,
+            pause_media: true,
+            dim_brightness: false,
+            dim_percent: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub port: u16,
+    pub bind_address: String,
+    pub auth_mode: AuthMode,
+    // Seconds before the QR pairing token expires, 0 to never expire
+    pub pairing_token_ttl_secs: u64,
+    // Rotate the pairing token as soon as a client has paired with it
+    pub pairing_single_use: bool,
+    pub pointer_sensitivity: f64,
+    pub scroll_sensitivity: f64,
+    // Paste text_input of at least this many characters through the
+    // clipboard instead of typing it, 0 to always type
+    pub paste_threshold: usize,
+    // Extra lowercase key names for send_key, e.g. "rewind" = "j"
+    pub keymaps: BTreeMap<String, String>,
+    // Command names remapped before they run, e.g. "vol+" = "volume_up",
+    // see `aliases`
+    pub aliases: BTreeMap<String, CommandAlias>,
+    // Remote layout to suggest while an app is in front, e.g. "vlc" =
+    // "video_player", on top of the built-in ones, see `layouts`
+    pub app_layouts: BTreeMap<String, String>,
+    // Language of reply messages: "en", "es", "fr" or "de"
+    pub locale: Locale,
+    pub autostart: bool,
+    // Start the server again when the app launches if it was running when
+    // the app last quit, on the same port
+    pub start_on_launch: bool,
+    pub mdns_advertise: bool,
+    // Ask the router to forward the server port so remotes can connect from
+    // outside the LAN. Needs token auth
+    pub port_mapping: bool,
+    pub relay: RelaySettings,
+    pub tls_enabled: bool,
+    // Without TLS, only accept remotes that sign their messages, see
+    // `signing`. Needs token auth
+    pub require_signing: bool,
+    // Bearer token for POST /api/command, empty to turn the HTTP API off
+    pub api_token: String,
+    // Remotes connecting with `?admin=<token>` may list and kick remotes,
+    // pause input and regenerate the pairing code. Empty turns that off
+    pub admin_token: String,
+    // Domains open_website may open, subdomains included. Empty allows any
+    // domain that isn't denied
+    pub url_allowlist: Vec<String>,
+    pub url_denylist: Vec<String>,
+    // App schemes open_website may open besides http(s), e.g. "spotify"
+    pub url_schemes: Vec<String>,
+    // Browser for fullscreen kiosk launches, e.g. "google-chrome" or
+    // "firefox". Empty picks the first installed one
+    pub kiosk_browser: String,
+    // Domains open_website always opens in kiosk mode, e.g. "netflix.com"
+    pub kiosk_domains: Vec<String>,
+    pub uploads: UploadSettings,
+    pub idle: IdleSettings,
+    pub input_policy: InputPolicy,
+    pub gamepad: GamepadSettings,
+    // Warn remotes when the unplugged host battery drops to this
+    // percentage, 0 to disable
+    pub low_battery_percent: u8,
+    // Let remotes subscribe to the host clipboard's text
+    pub share_clipboard: bool,
+    // Forward host notification titles to subscribed remotes (Linux only)
+    pub mirror_notifications: bool,
+    // Show which remote is sending input in a small overlay on the host
+    pub control_overlay: bool,
+    // Refuse typing from non-admin remotes while a password field has focus
+    // (macOS and Windows)
+    pub protect_password_fields: bool,
+    // How often subscribed remotes get system stats unless they ask otherwise
+    pub stats_interval_ms: u64,
+    // Folders remotes may browse and open files from, e.g. "~/Movies"
+    pub media_roots: Vec<String>,
+    pub media_server: MediaServerSettings,
+    pub features: FeatureToggles,
+    pub hotkeys: HotkeySettings,
+    pub listeners: Vec<ListenerProfile>,
+    pub linked_hosts: Vec<LinkedHost>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            bind_address: "0.0.0.0".to_string(),
+            auth_mode: AuthMode::None,
+            pairing_token_ttl_secs: 600,
+            pairing_single_use: true,
+            pointer_sensitivity: 1.0,
+            scroll_sensitivity: 1.0,
+            paste_threshold: 0,
+            keymaps: BTreeMap::new(),
+            app_layouts: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            locale: Locale::default(),
+            autostart: false,
+            start_on_launch: false,
+            mdns_advertise: true,
+            port_mapping: false,
+            relay: RelaySettings::default(),
+            tls_enabled: false,
+            require_signing: false,
+            api_token: String::new(),
+            admin_token: String::new(),
+            url_allowlist: Vec::new(),
+            url_denylist: Vec::new(),
+            url_schemes: vec!["spotify".to_string(), "vlc".to_string()],
+            kiosk_browser: String::new(),
+            kiosk_domains: Vec::new(),
+            uploads: UploadSettings::default(),
+            idle: IdleSettings::default(),
+            input_policy: InputPolicy::default(),
+            gamepad: GamepadSettings::default(),
+            low_battery_percent: 15,
+            share_clipboard: false,
+            mirror_notifications: false,
+            control_overlay: true,
+            protect_password_fields: true,
+            stats_interval_ms: 2000,
+            media_roots: Vec::new(),
+            media_server: MediaServerSettings::default(),
+            features: FeatureToggles::default(),
+            hotkeys: HotkeySettings::default(),
+            listeners: Vec::new(),
+            linked_hosts: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == 0 {
+            return Err("Port must be between 1 and 65535".to_string());
+        }
+        self.bind_address
+            .parse::<IpAddr>()
+            .map_err(|_| format!("Invalid bind address: {}", self.bind_address))?;
+        for (name, value) in [
+            ("Pointer sensitivity", self.pointer_sensitivity),
+            ("Scroll sensitivity", self.scroll_sensitivity),
+        ] {
+            if !(0.1..=10.0).contains(&value) {
+                return Err(format!("{} must be between 0.1 and 10", name));
+            }
+        }
+        if self.port_mapping && self.auth_mode != AuthMode::Token {
+            return Err("Port mapping needs token auth, so strangers can't connect".to_string());
+        }
+        if self.require_signing && self.auth_mode != AuthMode::Token {
+            return Err("Signed messages need token auth, the token is the key".to_string());
+        }
+        if self.relay.enabled() {
+            if !self.tls_enabled || self.auth_mode != AuthMode::Token {
+                return Err("The relay needs TLS and token auth, so only paired remotes can read or send input".to_string());
+            }
+            if self.relay.room.len() < relay::MIN_ROOM_LENGTH
+                || self.relay.room.contains(char::is_whitespace)
+            {
+                return Err(format!(
+                    "Relay room must be at least {} characters without spaces",
+                    relay::MIN_ROOM_LENGTH
+                ));
+            }
+        }
+        if !self.api_token.is_empty() && self.api_token.len() < 16 {
+            return Err("API token must be at least 16 characters".to_string());
+        }
+        if !self.admin_token.is_empty() && self.admin_token.len() < 16 {
+            return Err("Admin token must be at least 16 characters".to_string());
+        }
+        for domain in self.url_allowlist.iter().chain(&self.url_denylist) {
+            if domain.trim().is_empty() {
+                return Err("URL allow and deny lists can't have empty entries".to_string());
+            }
+        }
+        for domain in &self.kiosk_domains {
+            if domain.trim().is_empty() {
+                return Err("Kiosk domains can't have empty entries".to_string());
+            }
+        }
+        for scheme in &self.url_schemes {
+            if UNSAFE_URL_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()) {
+                return Err(format!("URL scheme '{}' can't be allowed", scheme));
+            }
+        }
+        if !(1..=16384).contains(&self.uploads.max_mb) {
+            return Err("Upload size limit must be between 1 and 16384 MB".to_string());
+        }
+        if self.idle.dim_percent > 100 {
+            return Err("Idle brightness must be between 0 and 100%".to_string());
+        }
+        if self.low_battery_percent > 100 {
+            return Err("Low battery warning must be between 0 and 100%".to_string());
+        }
+        if !(stats::MIN_INTERVAL_MS..=stats::MAX_INTERVAL_MS).contains(&self.stats_interval_ms) {
+            return Err(format!(
+                "Stats interval must be between {} and {} ms",
+                stats::MIN_INTERVAL_MS,
+                stats::MAX_INTERVAL_MS
+            ));
+        }
+        if self.media_roots.iter().any(|root| root.trim().is_empty()) {
+            return Err("Media folders can't be empty".to_string());
+        }
+        match self.media_server.kind {
+            MediaServerKind::None => {}
+            MediaServerKind::Plex | MediaServerKind::Jellyfin
+                if self.media_server.token.is_empty() =>
+            {
+                return Err("The media server needs a token".to_string());
+            }
+            _ => {
+                if http_client::parse_url(&self.media_server.url)?.tls {
+                    return Err("The media server has to be reached over http://".to_string());
+                }
+            }
+        }
+        let buttons = gamepad::default_bindings();
+        for (button, key) in &self.gamepad.bindings {
+            if !buttons.contains_key(button) {
+                return Err(format!("Unknown gamepad button: {}", button));
+            }
+            keymap::parse_key(key)?;
+        }
+        for (alias, key) in &self.keymaps {
+            if alias.trim().is_empty() || key.trim().is_empty() {
+                return Err("Keymap entries must have a name and a key".to_string());
+            }
+        }
+        for (name, alias) in &self.aliases {
+            aliases::validate(name, alias)?;
+        }
+        for (app, layout) in &self.app_layouts {
+            if app.trim().is_empty() || layout.trim().is_empty() {
+                return Err("App layout entries must have an app and a layout".to_string());
+            }
+        }
+        let mut names = Vec::new();
+        let mut ports = vec![self.port];
+        for listener in &self.listeners {
+            if listener.name.trim().is_empty() {
+                return Err("Listeners must have a name".to_string());
+            }
+            if names.contains(&&listener.name) {
+                return Err(format!("Listener name '{}' is used twice", listener.name));
+            }
+            names.push(&listener.name);
+            listener.bind_address.parse::<IpAddr>().map_err(|_| {
+                format!(
+                    "Invalid bind address for listener '{}': {}",
+                    listener.name, listener.bind_address
+                )
+            })?;
+            if listener.port == 0 || ports.contains(&listener.port) {
+                return Err(format!(
+                    "Listener '{}' needs a port of its own",
+                    listener.name
+                ));
+            }
+            ports.push(listener.port);
+        }
+        let mut hosts = Vec::new();
+        for host in &self.linked_hosts {
+            if host.name.trim().is_empty() || host.token.is_empty() {
+                return Err("Linked hosts must have a name and a token".to_string());
+            }
+            if !host_name_is_valid(&host.name) {
+                return Err(format!(
+                    "Linked host name '{}' may only have letters, digits, '-' and '_'",
+                    host.name
+                ));
+            }
+            if hosts.contains(&host.name.to_lowercase()) {
+                return Err(format!("Linked host name '{}' is used twice", host.name));
+            }
+            hosts.push(host.name.to_lowercase());
+            if http_client::parse_url(&host.url)?.tls
+                && !http_client::fingerprint_is_valid(&host.fingerprint)
+            {
+                return Err(format!(
+                    "Linked host '{}' needs its certificate fingerprint for https://",
+                    host.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the feature toggles allow a protocol command to run.
+    pub fn command_allowed(&self, command: &str) -> bool {
+        self.features.allows(command)
+    }
+}
+
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_DIR_NAME)
+}
+
+pub fn settings_path() -> PathBuf {
+    config_dir().join(SETTINGS_FILE)
+}
+
+fn file_mtime() -> Option<SystemTime> {
+    std::fs::metadata(settings_path())
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+// Linked host names end up in keychain entry names, which the Windows
+// backend puts in a PowerShell string
+fn host_name_is_valid(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// Settings kept in the OS keychain rather than the file, see `secrets`
+fn secret_fields(settings: &mut Settings) -> Vec<(String, &mut String)> {
+    let mut fields = vec![
+        ("api_token".to_string(), &mut settings.api_token),
+        ("admin_token".to_string(), &mut settings.admin_token),
+        (
+            "media_server_token".to_string(),
+            &mut settings.media_server.token,
+        ),
+        ("relay_room".to_string(), &mut settings.relay.room),
+    ];
+    // Secrets load before `validate`, so bad names are skipped here too
+    for host in &mut settings.linked_hosts {
+        if !host_name_is_valid(&host.name) {
+            continue;
+        }
+        fields.push((format!("linked_host_{}", host.name), &mut host.token));
+    }
+    fields
+}
+
+/// `settings` with the secrets blanked, for showing outside the host app.
+pub fn redacted(mut settings: Settings) -> Settings {
+    for (_, value) in secret_fields(&mut settings) {
+        value.clear();
+    }
+    settings
+}
+
+/// Fill in the secrets `settings` leaves blank from `current`, so settings
+/// from [`redacted`] can be sent back as they are.
+pub fn keep_secrets(settings: &mut Settings, current: &Settings) {
+    let mut current = current.clone();
+    let known: BTreeMap<String, String> = secret_fields(&mut current)
+        .into_iter()
+        .map(|(name, value)| (name, value.clone()))
+        .collect();
+    for (name, value) in secret_fields(settings) {
+        if let Some(known) = known.get(&name).filter(|_| value.is_empty()) {
+            *value = known.clone();
+        }
+    }
+}
+
+/// Clear the secret `name` (`api_token`, `admin_token`, `media_server_token`,
+/// `relay_room` or `linked_host_<name>`), which a blank value can't do
+/// through [`keep_secrets`].
+pub fn clear_secret(name: &str) -> Result<(), String> {
+    let mut settings = current();
+    let Some((_, value)) = secret_fields(&mut settings)
+        .into_iter()
+        .find(|(field, _)| field == name)
+    else {
+        return Err(format!("No secret named '{}'", name));
+    };
+    value.clear();
+    update(settings).map(|_| ())
+}
+
+// Fill in secrets from the keychain, returning whether any were still in the file
+fn load_secrets(settings: &mut Settings) -> bool {
+    let mut in_file = false;
+    let mut keychain = KEYCHAIN.write().unwrap_or_else(|e| e.into_inner());
+    for (name, value) in secret_fields(settings) {
+        if !value.is_empty() {
+            in_file = true;
+            continue;
+        }
+        match secrets::get(&name) {
+            Ok(secret) => {
+                *value = secret.unwrap_or_default();
+                keychain.insert(name, value.clone());
+            }
+            Err(e) => warn!("Failed to read {} from the keychain: {}", name, e),
+        }
+    }
+    in_file
+}
+
+fn read_from_disk() -> Result<Settings, String> {
+    let path = settings_path();
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read settings file {:?}: {}", path, e))?;
+    let mut settings: Settings =
+        toml::from_str(&contents).map_err(|e| format!("Invalid settings file: {}", e))?;
+    let secrets_in_file = load_secrets(&mut settings);
+    settings.validate()?;
+    // Older versions kept secrets in the file, move them to the keychain
+    if secrets_in_file {
+        save(&settings)?;
+    }
+    Ok(settings)
+}
+
+/// Load settings from disk, writing the defaults on first run.
+pub fn load() {
+    let path = settings_path();
+    if !path.exists() {
+        info!("No settings file found, writing defaults to {:?}", path);
+        if let Err(e) = save(&Settings::default()) {
+            warn!("{}", e);
+        }
+        return;
+    }
+
+    match read_from_disk() {
+        Ok(settings) => {
+            info!("Loaded settings from {:?}", path);
+            *SETTINGS.write().unwrap_or_else(|e| e.into_inner()) = settings;
+            *LOADED_MTIME.write().unwrap_or_else(|e| e.into_inner()) = file_mtime();
+        }
+        Err(e) => error!("{}, using defaults", e),
+    }
+}
+
+pub fn save(settings: &Settings) -> Result<(), String> {
+    let dir = config_dir();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create config directory {:?}: {}", dir, e))?;
+    let mut stored = settings.clone();
+    let mut keychain = KEYCHAIN.write().unwrap_or_else(|e| e.into_inner());
+    let fields = secret_fields(&mut stored);
+    let names: Vec<String> = fields.iter().map(|(name, _)| name.clone()).collect();
+    for (name, value) in fields {
+        if keychain.get(&name).is_some_and(|known| known == value) {
+            value.clear();
+            continue;
+        }
+        match secrets::set(&name, value) {
+            Ok(()) => {
+                keychain.insert(name, std::mem::take(value));
+            }
+            Err(e) if !value.is_empty() => {
+                warn!(
+                    "Keeping {} in the settings file, the keychain failed: {}",
+                    name, e
+                )
+            }
+            Err(_) => {}
+        }
+    }
+    // Tokens of linked hosts that were removed
+    let removed: Vec<String> = keychain
+        .iter()
+        .filter(|(name, value)| !value.is_empty() && !names.contains(name))
+        .map(|(name, _)| name.clone())
+        .collect();
+    for name in removed {
+        match secrets::set(&name, "") {
+            Ok(()) => {
+                keychain.remove(&name);
+            }
+            Err(e) => warn!("Failed to remove {} from the keychain: {}", name, e),
+        }
+    }
+    drop(keychain);
+    let contents = toml::to_string_pretty(&stored)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    // Rewriting an unchanged file would look like an outside edit
+    let path = settings_path();
+    if std::fs::read_to_string(&path).ok().as_deref() == Some(contents.as_str()) {
+        return Ok(());
+    }
+    std::fs::write(&path, contents).map_err(|e| format!("Failed to write settings file: {}", e))?;
+
+    *LOADED_MTIME.write().unwrap_or_else(|e| e.into_inner()) = file_mtime();
+    Ok(())
+}
+
+pub fn current() -> Settings {
+    SETTINGS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Validate, persist and apply new settings, returning the previous ones.
+pub fn update(settings: Settings) -> Result<Settings, String> {
+    settings.validate()?;
+    save(&settings)?;
+    let mut guard = SETTINGS.write().unwrap_or_else(|e| e.into_inner());
+    Ok(std::mem::replace(&mut *guard, settings))
+}
+
+/// Poll the settings file and apply edits made outside the app.
+pub fn watch_for_changes() {
+    std::thread::spawn(|| loop {
+        std::thread::sleep(RELOAD_POLL_INTERVAL);
+
+        let mtime = file_mtime();
+        let loaded = *LOADED_MTIME.read().unwrap_or_else(|e| e.into_inner());
+        if mtime.is_none() || mtime == loaded {
+            continue;
+        }
+
+        match read_from_disk() {
+            Ok(settings) => {
+                info!("Settings file changed on disk, reloading");
+                *SETTINGS.write().unwrap_or_else(|e| e.into_inner()) = settings;
+            }
+            Err(e) => warn!("Ignoring settings file change: {}", e),
+        }
+        *LOADED_MTIME.write().unwrap_or_else(|e| e.into_inner()) = mtime;
+    });
+}
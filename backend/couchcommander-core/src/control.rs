@@ -0,0 +1,214 @@
+//! Arbitration between remotes driving the pointer and keyboard at once,
+//! which otherwise makes the cursor jump between two phones' moves.
+//!
+//! With `input_policy = "exclusive"` a remote sends `take_control` and
+//! holds input until it sends `release_control`, disconnects or goes quiet.
+//! With `"last_writer"` whoever sent input last has it, once the current
+//! controller has paused briefly. Both announce the controller on the
+//! `control` topic so remotes can show "controlled by X".
+//!
+//! Whatever the policy, the remote sending input right now is also
+//! watched by the desktop app, which shows who is controlling the host.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::info;
+
+use crate::config::{self, InputPolicy};
+use crate::events;
+use crate::protocol::Topic;
+
+// Exclusive control lapses after this long without input
+const HOLD_TIMEOUT: Duration = Duration::from_secs(30);
+// Last writer: another remote takes over once the controller paused this long
+const HANDOVER_GAP: Duration = Duration::from_millis(500);
+
+// A remote stops counting as active this long after its last input
+const ACTIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The remote sending input right now.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActiveRemote {
+    pub client_id: String,
+    pub name: String,
+}
+
+struct Controller {
+    client_id: String,
+    last_input: Instant,
+}
+
+lazy_static::lazy_static! {
+    static ref CONTROLLER: Mutex<Option<Controller>> = Mutex::new(None);
+    static ref ACTIVE: watch::Sender<Option<ActiveRemote>> = watch::Sender::new(None);
+}
+
+static LAST_INPUT: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Note that `client_id` just sent input. It stays the active remote until
+/// another one sends input or it has been quiet for a few seconds.
+pub fn record_input(client_id: &str, name: &str) {
+    *LAST_INPUT.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    let mut became_active = false;
+    ACTIVE.send_if_modified(|active| {
+        if active
+            .as_ref()
+            .is_some_and(|remote| remote.client_id == client_id)
+        {
+            return false;
+        }
+        became_active = active.is_none();
+        *active = Some(ActiveRemote {
+            client_id: client_id.to_string(),
+            name: name.to_string(),
+        });
+        true
+    });
+
+    // One task per active stretch clears it once input stops
+    if became_active {
+        tokio::spawn(async {
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                let quiet = LAST_INPUT
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .is_none_or(|last| last.elapsed() >= ACTIVE_TIMEOUT);
+                if quiet {
+                    ACTIVE.send_replace(None);
+                    return;
+                }
+            }
+        });
+    }
+}
+
+/// Follow the active remote, `None` while nobody is sending input.
+pub fn watch_active() -> watch::Receiver<Option<ActiveRemote>> {
+    ACTIVE.subscribe()
+}
+
+/// Commands that move the pointer or type, the ones worth arbitrating.
+pub fn is_input(command: &str) -> bool {
+    matches!(
+        command,
+        "mouse_move"
+            | "mouse_click"
+            | "scroll"
+            | "send_key"
+            | "toggle_modifier_key"
+            | "clear_modifier_keys"
+            | "text_input"
+            | "text_stream"
+            | "open_search"
+            | "find_in_page"
+            | "gamepad_button"
+            | "key_down"
+            | "key_up"
+            | "edit_action"
+            | "send_raw_key"
+    )
+}
+
+fn announce(event: &str, client_id: &str) {
+    info!("Input control {} by {}", event, client_id);
+    events::publish(
+        Topic::Control,
+        serde_json::json!({ "event": event, "client_id": client_id }),
+    );
+}
+
+/// Check that `client_id` may send input now, taking control under the
+/// last-writer policy.
+pub fn claim(client_id: &str) -> Result<(), String> {
+    let policy = config::current().input_policy;
+    if policy == InputPolicy::Shared {
+        return Ok(());
+    }
+
+    let now = Instant::now();
+    let mut controller = CONTROLLER.lock().unwrap_or_else(|e| e.into_inner());
+    match controller.as_mut() {
+        Some(current) if current.client_id == client_id => {
+            current.last_input = now;
+            return Ok(());
+        }
+        Some(current) if policy == InputPolicy::Exclusive => {
+            if now.duration_since(current.last_input) < HOLD_TIMEOUT {
+                return Err(format!("Input is controlled by {}", current.client_id));
+            }
+            // The holder went quiet, input is free again
+            let lapsed = current.client_id.clone();
+            *controller = None;
+            drop(controller);
+            announce("released", &lapsed);
+            return Err("Input is free, send take_control first".to_string());
+        }
+        None if policy == InputPolicy::Exclusive => {
+            return Err("Send take_control before sending input".to_string());
+        }
+        Some(current) if now.duration_since(current.last_input) < HANDOVER_GAP => {
+            return Err(format!("Input is controlled by {}", current.client_id));
+        }
+        _ => {}
+    }
+
+    *controller = Some(Controller {
+        client_id: client_id.to_string(),
+        last_input: now,
+    });
+    drop(controller);
+    announce("taken", client_id);
+    Ok(())
+}
+
+/// Take control of input, unless another remote holds it.
+pub fn take(client_id: &str) -> Result<(), String> {
+    if config::current().input_policy == InputPolicy::Shared {
+        return Err("Input is shared between remotes in settings".to_string());
+    }
+
+    let now = Instant::now();
+    let mut controller = CONTROLLER.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(current) = controller.as_ref() {
+        if current.client_id == client_id {
+            return Ok(());
+        }
+        if now.duration_since(current.last_input) < HOLD_TIMEOUT {
+            return Err(format!("Input is controlled by {}", current.client_id));
+        }
+    }
+    *controller = Some(Controller {
+        client_id: client_id.to_string(),
+        last_input: now,
+    });
+    drop(controller);
+    announce("taken", client_id);
+    Ok(())
+}
+
+/// Give up control, returning false if `client_id` didn't have it.
+pub fn release(client_id: &str) -> bool {
+    let mut controller = CONTROLLER.lock().unwrap_or_else(|e| e.into_inner());
+    if controller
+        .as_ref()
+        .is_none_or(|current| current.client_id != client_id)
+    {
+        return false;
+    }
+    *controller = None;
+    drop(controller);
+    announce("released", client_id);
+    true
+}
+
+/// The remote in control, if any.
+pub fn controller() -> Option<String> {
+    CONTROLLER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|current| current.client_id.clone())
+}
@@ -0,0 +1,158 @@
+//! Virtual desktop (Spaces) switching, to hop between the media desktop and
+//! the work one from the couch. macOS and Windows use their desktop
+//! shortcuts, Linux asks the window manager through `wmctrl`.
+//!
+//! Switching to a desktop by number on macOS needs the "Switch to Desktop N"
+//! shortcuts turned on in the keyboard settings.
+
+use crate::input_backend::InputBackend;
+use crate::protocol::CommandResponse;
+
+/// Which desktop to go to. Numbered desktops start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DesktopTarget {
+    Next,
+    Prev,
+    Index(u32),
+}
+
+impl DesktopTarget {
+    /// Read `{"direction": "next"}`, `{"direction": "prev"}` or `{"index": 2}`.
+    pub fn from_data(data: &serde_json::Value) -> Result<Self, String> {
+        if let Some(index) = data.get("index").and_then(|v| v.as_u64()) {
+            return u32::try_from(index)
+                .ok()
+                .filter(|index| *index >= 1)
+                .map(DesktopTarget::Index)
+                .ok_or_else(|| "Desktop numbers start at 1".to_string());
+        }
+        match data.get("direction").and_then(|v| v.as_str()) {
+            Some("next") => Ok(DesktopTarget::Next),
+            Some("prev") | Some("previous") => Ok(DesktopTarget::Prev),
+            Some(other) => Err(format!("Unknown direction: {}", other)),
+            None => Err("Missing 'direction' or 'index' parameter".to_string()),
+        }
+    }
+}
+
+fn success(message: String) -> CommandResponse {
+    CommandResponse {
+        status: "success".to_string(),
+        message,
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod wm {
+    use std::process::Command;
+
+    use super::DesktopTarget;
+
+    fn wmctrl(args: &[&str]) -> Result<String, String> {
+        let output = Command::new("wmctrl")
+            .args(args)
+            .output()
+            .map_err(|e| format!("Desktop switching needs wmctrl: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    // (current, count), zero based. The current desktop is marked with `*`
+    fn desktops() -> Result<(u32, u32), String> {
+        let listing = wmctrl(&["-d"])?;
+        let lines: Vec<&str> = listing.lines().collect();
+        let current = lines
+            .iter()
+            .position(|line| line.split_whitespace().nth(1) == Some("*"))
+            .unwrap_or(0);
+        Ok((current as u32, lines.len().max(1) as u32))
+    }
+
+    // Zero based desktop number for a target, wrapping around the ends
+    pub fn resolve(target: DesktopTarget) -> Result<u32, String> {
+        let (current, count) = desktops()?;
+        match target {
+            DesktopTarget::Next => Ok((current + 1) % count),
+            DesktopTarget::Prev => Ok((current + count - 1) % count),
+            DesktopTarget::Index(index) if index <= count => Ok(index - 1),
+            DesktopTarget::Index(index) => {
+                Err(format!("There are only {} desktops, not {}", count, index))
+            }
+        }
+    }
+
+    pub fn switch(desktop: u32) -> Result<(), String> {
+        wmctrl(&["-s", &desktop.to_string()]).map(|_| ())
+    }
+
+    pub fn move_active_window(desktop: u32) -> Result<(), String> {
+        wmctrl(&["-r", ":ACTIVE:", "-t", &desktop.to_string()]).map(|_| ())
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub async fn switch_desktop<B: InputBackend>(
+    target: DesktopTarget,
+) -> Result<CommandResponse, String> {
+    tokio::task::spawn_blocking(move || {
+        let desktop = wm::resolve(target)?;
+        wm::switch(desktop)?;
+        Ok(success(format!("Switched to desktop {}", desktop + 1)))
+    })
+    .await
+    .map_err(|e| format!("Desktop task panicked: {:?}", e))?
+}
+
+#[cfg(target_os = "linux")]
+pub async fn move_window_to_desktop<B: InputBackend>(
+    target: DesktopTarget,
+) -> Result<CommandResponse, String> {
+    tokio::task::spawn_blocking(move || {
+        let desktop = wm::resolve(target)?;
+        wm::move_active_window(desktop)?;
+        wm::switch(desktop)?;
+        Ok(success(format!(
+            "Moved the window to desktop {}",
+            desktop + 1
+        )))
+    })
+    .await
+    .map_err(|e| format!("Desktop task panicked: {:?}", e))?
+}
+
+// Ctrl+Arrow or Ctrl+number on macOS, Win+Ctrl+Arrow on Windows
+#[cfg(not(target_os = "linux"))]
+pub async fn switch_desktop<B: InputBackend>(
+    target: DesktopTarget,
+) -> Result<CommandResponse, String> {
+    use enigo::Key;
+
+    let (modifiers, key) = match target {
+        DesktopTarget::Next if cfg!(target_os = "macos") => (vec![Key::Control], Key::RightArrow),
+        DesktopTarget::Prev if cfg!(target_os = "macos") => (vec![Key::Control], Key::LeftArrow),
+        DesktopTarget::Index(index @ 1..=9) if cfg!(target_os = "macos") => {
+            let digit = char::from_digit(index, 10).unwrap_or('1');
+            (vec![Key::Control], Key::Unicode(digit))
+        }
+        DesktopTarget::Next => (vec![Key::Meta, Key::Control], Key::RightArrow),
+        DesktopTarget::Prev => (vec![Key::Meta, Key::Control], Key::LeftArrow),
+        DesktopTarget::Index(_) => {
+            return Err("This host can only switch to the next or previous desktop".to_string())
+        }
+    };
+    crate::input_worker::run::<B, _, _>(move |input| {
+        crate::shortcuts::press_chord(input, &modifiers, key)
+            .map_err(|e| format!("Failed to switch desktop: {:?}", e))?;
+        Ok(success(format!("Switched desktop ({:?})", target)))
+    })
+    .await
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn move_window_to_desktop<B: InputBackend>(
+    _target: DesktopTarget,
+) -> Result<CommandResponse, String> {
+    Err("Moving windows between desktops is only supported on Linux".to_string())
+}
@@ -0,0 +1,338 @@
+//! Remotes the host has seen, saved to `devices.json`. A remote identifies
+//! itself with `?device=<id>` at the handshake, an id it makes up once and
+//! keeps; a paired remote's id is then bound to its session token. The host
+//! user can give a device a nickname, limit it to media controls, block
+//! commands or hours of the day for it (parental controls), or revoke it so
+//! it's refused at the handshake. A remote can also keep its button layout
+//! here so it survives clearing the browser's data.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use crate::{clock, config, websocket};
+
+const DEVICES_FILE: &str = "devices.json";
+// Past this the devices seen longest ago are forgotten, revoked ones are kept
+const MAX_DEVICES: usize = 200;
+const MAX_NICKNAME_CHARS: usize = 40;
+const MAX_BLOCKED_COMMANDS: usize = 100;
+const MAX_LAYOUT_BYTES: usize = 64 * 1024;
+
+lazy_static::lazy_static! {
+    // Loaded from disk on first use
+    static ref DEVICES: Mutex<Option<HashMap<String, Device>>> = Mutex::new(None);
+}
+
+/// What a device may do on top of what its connection allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceRole {
+    #[default]
+    Full,
+    /// Media keys and what's playing, like a guest.
+    Media,
+}
+
+/// Parental controls for one device, e.g. no typing and nothing after 21:00.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Restrictions {
+    #[serde(default)]
+    pub blocked_commands: Vec<String>,
+    // Local "HH:MM" times the device may be used between, from midnight and
+    // until midnight when unset. A window past midnight like 22:00 to 02:00
+    // works too
+    pub allowed_from: Option<String>,
+    pub allowed_until: Option<String>,
+}
+
+impl Restrictions {
+    fn validate(&self) -> Result<(), String> {
+        if self.blocked_commands.len() > MAX_BLOCKED_COMMANDS {
+            return Err(format!(
+                "At most {} commands can be blocked",
+                MAX_BLOCKED_COMMANDS
+            ));
+        }
+        for time in [&self.allowed_from, &self.allowed_until]
+            .into_iter()
+            .flatten()
+        {
+            if clock::parse_time(time).is_none() {
+                return Err(format!("Invalid time '{}', use HH:MM", time));
+            }
+        }
+        Ok(())
+    }
+
+    fn window(&self) -> Option<(u32, u32)> {
+        if self.allowed_from.is_none() && self.allowed_until.is_none() {
+            return None;
+        }
+        let from = self.allowed_from.as_deref().and_then(clock::parse_time);
+        let until = self.allowed_until.as_deref().and_then(clock::parse_time);
+        Some((from.unwrap_or(0), until.unwrap_or(24 * 60)))
+    }
+
+    /// Why `command` isn't allowed right now, if it isn't. Outside the time
+    /// window a device can still read state, e.g. what's playing.
+    pub fn check(&self, command: &str, read_only: bool) -> Result<(), String> {
+        if self
+            .blocked_commands
+            .iter()
+            .any(|blocked| blocked == command)
+        {
+            return Err(format!("Command '{}' is blocked for this device", command));
+        }
+        let Some((from, until)) = self.window() else {
+            return Ok(());
+        };
+        let now = clock::minutes_of_day();
+        let allowed = if from <= until {
+            (from..until).contains(&now)
+        } else {
+            now >= from || now < until
+        };
+        if allowed || read_only {
+            return Ok(());
+        }
+        Err(format!(
+            "This device can only be used between {} and {}",
+            self.allowed_from.as_deref().unwrap_or("00:00"),
+            self.allowed_until.as_deref().unwrap_or("24:00")
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Device {
+    pub id: String,
+    pub nickname: Option<String>,
+    #[serde(default)]
+    pub role: DeviceRole,
+    // Seconds since the epoch
+    pub first_seen: u64,
+    pub last_seen: u64,
+    #[serde(default)]
+    pub total_commands: u64,
+    #[serde(default)]
+    pub revoked: bool,
+    #[serde(default)]
+    pub restrictions: Restrictions,
+    // Whatever JSON the remote stores with `set_remote_layout`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<serde_json::Value>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or_default()
+}
+
+fn load() -> HashMap<String, Device> {
+    let path = config::config_dir().join(DEVICES_FILE);
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return HashMap::new();
+    };
+    match serde_json::from_str::<Vec<Device>>(&contents) {
+        Ok(devices) => devices
+            .into_iter()
+            .map(|device| (device.id.clone(), device))
+            .collect(),
+        Err(e) => {
+            warn!("Ignoring invalid devices file {:?}: {}", path, e);
+            HashMap::new()
+        }
+    }
+}
+
+fn save(devices: &HashMap<String, Device>) {
+    let path = config::config_dir().join(DEVICES_FILE);
+    let mut devices: Vec<&Device> = devices.values().collect();
+    devices.sort_by_key(|device| Reverse(device.last_seen));
+    let result = std::fs::create_dir_all(config::config_dir())
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::to_string_pretty(&devices).map_err(|e| e.to_string()))
+        .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!("Failed to save devices to {:?}: {}", path, e);
+    }
+}
+
+fn with_devices<T>(f: impl FnOnce(&mut HashMap<String, Device>) -> T) -> T {
+    let mut devices = DEVICES.lock().unwrap_or_else(|e| e.into_inner());
+    f(devices.get_or_insert_with(load))
+}
+
+// Change a known device and save the registry
+fn update(id: &str, change: impl FnOnce(&mut Device)) -> Result<Device, String> {
+    with_devices(|devices| {
+        let device = devices
+            .get_mut(id)
+            .ok_or_else(|| format!("Unknown device: {}", id))?;
+        change(device);
+        let device = device.clone();
+        save(devices);
+        Ok(device)
+    })
+}
+
+/// Read the registry from disk now rather than at the first connection,
+/// returning how many devices it has.
+pub fn preload() -> usize {
+    with_devices(|devices| devices.len())
+}
+
+/// Whether `id` works as a device id: 8 to 64 letters, digits, `-` or `_`.
+pub fn id_is_valid(id: &str) -> bool {
+    (8..=64).contains(&id.len())
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+pub fn is_revoked(id: &str) -> bool {
+    with_devices(|devices| devices.get(id).is_some_and(|device| device.revoked))
+}
+
+/// Note that a device connected, adding it if it's new.
+pub fn connected(id: &str) {
+    with_devices(|devices| {
+        let now = now();
+        devices
+            .entry(id.to_string())
+            .or_insert_with(|| {
+                info!("New device {}", id);
+                Device {
+                    id: id.to_string(),
+                    nickname: None,
+                    role: DeviceRole::Full,
+                    first_seen: now,
+                    last_seen: now,
+                    total_commands: 0,
+                    revoked: false,
+                    restrictions: Restrictions::default(),
+                    layout: None,
+                }
+            })
+            .last_seen = now;
+
+        if devices.len() > MAX_DEVICES {
+            let oldest = devices
+                .values()
+                .filter(|device| !device.revoked && device.id != id)
+                .min_by_key(|device| device.last_seen)
+                .map(|device| device.id.clone());
+            if let Some(oldest) = oldest {
+                devices.remove(&oldest);
+            }
+        }
+        save(devices);
+    });
+}
+
+/// Count a command from a device. Saved when it disconnects.
+pub fn record_command(id: &str) {
+    with_devices(|devices| {
+        if let Some(device) = devices.get_mut(id) {
+            device.total_commands += 1;
+            device.last_seen = now();
+        }
+    });
+}
+
+pub fn disconnected(id: &str) {
+    with_devices(|devices| {
+        if let Some(device) = devices.get_mut(id) {
+            device.last_seen = now();
+            save(devices);
+        }
+    });
+}
+
+pub fn role(id: &str) -> DeviceRole {
+    with_devices(|devices| {
+        devices
+            .get(id)
+            .map(|device| device.role)
+            .unwrap_or_default()
+    })
+}
+
+pub fn restrictions(id: &str) -> Restrictions {
+    with_devices(|devices| {
+        devices
+            .get(id)
+            .map(|device| device.restrictions.clone())
+            .unwrap_or_default()
+    })
+}
+
+pub fn nickname(id: &str) -> Option<String> {
+    with_devices(|devices| devices.get(id).and_then(|device| device.nickname.clone()))
+}
+
+/// The layout a device stored, `None` if it hasn't stored one.
+pub fn layout(id: &str) -> Option<serde_json::Value> {
+    with_devices(|devices| devices.get(id).and_then(|device| device.layout.clone()))
+}
+
+/// Keep a device's layout, replacing the one before. `null` clears it.
+pub fn set_layout(id: &str, layout: serde_json::Value) -> Result<Device, String> {
+    let size = serde_json::to_string(&layout)
+        .map_err(|e| e.to_string())?
+        .len();
+    if size > MAX_LAYOUT_BYTES {
+        return Err(format!(
+            "Layout is {} bytes, at most {} can be stored",
+            size, MAX_LAYOUT_BYTES
+        ));
+    }
+    let layout = Some(layout).filter(|layout| !layout.is_null());
+    update(id, |device| device.layout = layout)
+}
+
+/// Every known device, the most recently seen first.
+pub fn list() -> Vec<Device> {
+    let mut devices: Vec<Device> = with_devices(|devices| devices.values().cloned().collect());
+    devices.sort_by_key(|device| Reverse(device.last_seen));
+    devices
+}
+
+/// Set the name the host shows for a device, or clear it with an empty one.
+pub fn rename(id: &str, nickname: &str) -> Result<Device, String> {
+    let nickname: String = nickname
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NICKNAME_CHARS)
+        .collect();
+    let nickname = Some(nickname.trim().to_string()).filter(|name| !name.is_empty());
+    update(id, |device| device.nickname = nickname)
+}
+
+/// Change what a device may do. Applies to its open connections right away.
+pub fn set_role(id: &str, role: DeviceRole) -> Result<Device, String> {
+    update(id, |device| device.role = role)
+}
+
+/// Replace a device's parental controls. Applies to its open connections
+/// right away.
+pub fn set_restrictions(id: &str, restrictions: Restrictions) -> Result<Device, String> {
+    restrictions.validate()?;
+    update(id, |device| device.restrictions = restrictions)
+}
+
+/// Refuse a device from now on and disconnect it. Its session tokens are
+/// forgotten too, so it can't come back under another device id without
+/// pairing again.
+pub fn revoke(id: &str) -> Result<Device, String> {
+    let device = update(id, |device| device.revoked = true)?;
+    let kicked = websocket::kick_device(id);
+    info!("Device {} revoked, {} connections closed", id, kicked);
+    Ok(device)
+}
@@ -0,0 +1,266 @@
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+use crate::input_backend::{self, command_available, InputBackend};
+use crate::{config, input, input_worker, network, server};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticCheck {
+    pub id: &'static str,
+    pub label: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// Setup checklist for the desktop UI. `ok` is false if any check failed.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticReport {
+    pub ok: bool,
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+fn check(
+    id: &'static str,
+    label: &'static str,
+    status: CheckStatus,
+    detail: impl Into<String>,
+) -> DiagnosticCheck {
+    DiagnosticCheck {
+        id,
+        label,
+        status,
+        detail: detail.into(),
+    }
+}
+
+fn check_permissions() -> DiagnosticCheck {
+    if input::check_accessibility_permissions() {
+        check(
+            "permissions",
+            "Input permissions",
+            CheckStatus::Pass,
+            "Allowed to control keyboard and mouse",
+        )
+    } else {
+        check(
+            "permissions",
+            "Input permissions",
+            CheckStatus::Fail,
+            "Allow CouchCommander in System Settings > Privacy & Security > Accessibility",
+        )
+    }
+}
+
+async fn check_input_backend<B: InputBackend>() -> DiagnosticCheck {
+    // Connects on the input thread, where commands will use it
+    let result = input_worker::run::<B, _, _>(|_| Ok(())).await;
+    match result {
+        Ok(()) => check(
+            "input_backend",
+            "Input backend",
+            CheckStatus::Pass,
+            format!("Using {}", input_backend::capabilities().backend),
+        ),
+        Err(e) => check("input_backend", "Input backend", CheckStatus::Fail, e),
+    }
+}
+
+// The configured port, or the one actually in use while the server runs
+fn check_port(running_port: Option<u16>) -> DiagnosticCheck {
+    if let Some(port) = running_port {
+        return check(
+            "port",
+            "Server port",
+            CheckStatus::Pass,
+            format!("Server listening on port {}", port),
+        );
+    }
+
+    let settings = config::current();
+    let bind_ip: IpAddr = match settings.bind_address.parse() {
+        Ok(ip) => ip,
+        Err(_) => {
+            return check(
+                "port",
+                "Server port",
+                CheckStatus::Fail,
+                format!("Invalid bind address: {}", settings.bind_address),
+            )
+        }
+    };
+    match TcpListener::bind(SocketAddr::new(bind_ip, settings.port)) {
+        Ok(_) => check(
+            "port",
+            "Server port",
+            CheckStatus::Pass,
+            format!("Port {} is free", settings.port),
+        ),
+        Err(e) => check(
+            "port",
+            "Server port",
+            CheckStatus::Warn,
+            format!(
+                "Port {} is unavailable ({}), the server will try the next ports",
+                settings.port, e
+            ),
+        ),
+    }
+}
+
+// Connect to ourselves over the LAN address phones would use. A firewall
+// rule dropping inbound connections usually shows up as a timeout here.
+fn check_reachability(running_port: Option<u16>) -> DiagnosticCheck {
+    let Some(ip) = network::primary_ip() else {
+        return check(
+            "reachability",
+            "LAN reachability",
+            CheckStatus::Fail,
+            "No LAN address found, connect to a Wi-Fi or Ethernet network",
+        );
+    };
+
+    // Without a running server, listen on a throwaway port for the test
+    let (port, _listener) = match running_port {
+        Some(port) => (port, None),
+        None => match TcpListener::bind(SocketAddr::new(ip, 0)) {
+            Ok(listener) => match listener.local_addr() {
+                Ok(addr) => (addr.port(), Some(listener)),
+                Err(e) => {
+                    return check(
+                        "reachability",
+                        "LAN reachability",
+                        CheckStatus::Warn,
+                        format!("Could not test reachability: {}", e),
+                    )
+                }
+            },
+            Err(e) => {
+                return check(
+                    "reachability",
+                    "LAN reachability",
+                    CheckStatus::Warn,
+                    format!("Could not test reachability: {}", e),
+                )
+            }
+        },
+    };
+
+    let addr = SocketAddr::new(ip, port);
+    match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+        Ok(_) => check(
+            "reachability",
+            "LAN reachability",
+            CheckStatus::Pass,
+            format!("Reachable at {}", addr),
+        ),
+        Err(e) => check(
+            "reachability",
+            "LAN reachability",
+            CheckStatus::Fail,
+            format!(
+                "Could not connect to {} ({}), check that the firewall allows CouchCommander",
+                addr, e
+            ),
+        ),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn linux_mixer() -> &'static str {
+    crate::audio::mixer_name().unwrap_or("pactl")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn linux_mixer() -> &'static str {
+    "pactl"
+}
+
+// External tools volume_set and brightness_set shell out to
+fn check_system_controls() -> Vec<DiagnosticCheck> {
+    let tools: &[(&'static str, &'static str, Option<&str>)] = if cfg!(target_os = "macos") {
+        &[
+            ("volume", "Volume control", Some("osascript")),
+            ("brightness", "Brightness control", Some("brightness")),
+        ]
+    } else if cfg!(target_os = "linux") {
+        &[
+            ("volume", "Volume control", Some(linux_mixer())),
+            ("brightness", "Brightness control", Some("xrandr")),
+        ]
+    } else if cfg!(target_os = "windows") {
+        &[
+            ("volume", "Volume control", None),
+            ("brightness", "Brightness control", Some("powershell")),
+        ]
+    } else {
+        &[
+            ("volume", "Volume control", None),
+            ("brightness", "Brightness control", None),
+        ]
+    };
+
+    tools
+        .iter()
+        .map(|&(id, label, tool)| match tool {
+            Some(tool) if command_available(tool) => {
+                check(id, label, CheckStatus::Pass, format!("Using {}", tool))
+            }
+            Some(tool) => check(
+                id,
+                label,
+                CheckStatus::Warn,
+                format!("{} not found, setting an exact level won't work", tool),
+            ),
+            None => check(
+                id,
+                label,
+                CheckStatus::Warn,
+                "Setting an exact level is not supported on this platform yet",
+            ),
+        })
+        .collect()
+}
+
+/// Run every setup check in one pass.
+pub async fn run<B: InputBackend>() -> DiagnosticReport {
+    let running_port = server::get_server_status()
+        .await
+        .ok()
+        .filter(|status| status.running)
+        .map(|status| status.port);
+
+    let mut checks = vec![
+        check_permissions(),
+        check_input_backend::<B>().await,
+        check_port(running_port),
+    ];
+    checks.push(
+        tokio::task::spawn_blocking(move || check_reachability(running_port))
+            .await
+            .unwrap_or_else(|e| {
+                check(
+                    "reachability",
+                    "LAN reachability",
+                    CheckStatus::Warn,
+                    format!("Reachability check panicked: {:?}", e),
+                )
+            }),
+    );
+    checks.extend(check_system_controls());
+
+    DiagnosticReport {
+        ok: checks.iter().all(|c| c.status != CheckStatus::Fail),
+        checks,
+    }
+}
@@ -0,0 +1,135 @@
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+pub const SERVICE_TYPE: &str = "_couchcommander._tcp.local.";
+
+lazy_static::lazy_static! {
+    static ref ADVERTISEMENT: Mutex<Option<Advertisement>> = Mutex::new(None);
+}
+
+struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+    port: u16,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredHost {
+    pub name: String,
+    pub host_name: String,
+    pub addresses: Vec<String>,
+    pub port: u16,
+    pub version: Option<String>,
+}
+
+pub fn host_name() -> String {
+    gethostname::gethostname().to_string_lossy().to_string()
+}
+
+// mDNS labels may not contain dots, and the host name must end in ".local."
+fn instance_name() -> String {
+    let name = host_name();
+    let name = name.split('.').next().unwrap_or("couchcommander");
+    if name.is_empty() {
+        "couchcommander".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Advertise the WebSocket server on the LAN, replacing any previous advertisement.
+pub fn advertise(port: u16) -> Result<(), String> {
+    stop_advertising();
+
+    let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS daemon: {}", e))?;
+    let instance = instance_name();
+    let mdns_host = format!("{}.local.", instance);
+    let properties = [("version", env!("CARGO_PKG_VERSION")), ("path", "/")];
+
+    let service = ServiceInfo::new(
+        SERVICE_TYPE,
+        &instance,
+        &mdns_host,
+        "",
+        port,
+        &properties[..],
+    )
+    .map_err(|e| format!("Invalid mDNS service info: {}", e))?
+    .enable_addr_auto();
+    let fullname = service.get_fullname().to_string();
+
+    daemon
+        .register(service)
+        .map_err(|e| format!("Failed to register mDNS service: {}", e))?;
+    info!("Advertising {} on port {} via mDNS", fullname, port);
+
+    *ADVERTISEMENT.lock().unwrap_or_else(|e| e.into_inner()) = Some(Advertisement {
+        daemon,
+        fullname,
+        port,
+    });
+    Ok(())
+}
+
+pub fn stop_advertising() {
+    let advertisement = ADVERTISEMENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+
+    if let Some(advertisement) = advertisement {
+        if let Err(e) = advertisement.daemon.unregister(&advertisement.fullname) {
+            warn!("Failed to unregister mDNS service: {}", e);
+        }
+        if let Err(e) = advertisement.daemon.shutdown() {
+            warn!("Failed to shut down mDNS daemon: {}", e);
+        }
+        info!("Stopped mDNS advertisement of {}", advertisement.fullname);
+    }
+}
+
+/// Port currently advertised, if any.
+pub fn advertised_port() -> Option<u16> {
+    ADVERTISEMENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|a| a.port)
+}
+
+/// Browse the LAN for other CouchCommander hosts. Blocks for up to `timeout`.
+pub fn discover_hosts(timeout: Duration) -> Result<Vec<DiscoveredHost>, String> {
+    let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS daemon: {}", e))?;
+    let receiver = daemon
+        .browse(SERVICE_TYPE)
+        .map_err(|e| format!("Failed to browse mDNS services: {}", e))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut hosts: Vec<DiscoveredHost> = Vec::new();
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match receiver.recv_timeout(remaining) {
+            Ok(ServiceEvent::ServiceResolved(info)) => {
+                debug!("Discovered {}", info.get_fullname());
+                if hosts.iter().any(|h| h.name == info.get_fullname()) {
+                    continue;
+                }
+                hosts.push(DiscoveredHost {
+                    name: info.get_fullname().to_string(),
+                    host_name: info.get_hostname().to_string(),
+                    addresses: info.get_addresses().iter().map(|a| a.to_string()).collect(),
+                    port: info.get_port(),
+                    version: info.get_property_val_str("version").map(str::to_string),
+                });
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+
+    let _ = daemon.stop_browse(SERVICE_TYPE);
+    let _ = daemon.shutdown();
+    Ok(hosts)
+}
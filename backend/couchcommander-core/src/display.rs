@@ -0,0 +1,828 @@
+//! Display output switching for a laptop hooked up to the TV: mirror the
+//! screen, extend to it, or use only one of them. Windows goes through
+//! `DisplaySwitch.exe` and Linux through `xrandr` (X11 only). macOS can only
+//! toggle mirroring, with the Cmd+brightness up shortcut.
+//!
+//! It also lists the displays and where they sit on the desktop, so a pointer
+//! position can be matched to the screen it's on, and blanks them while the
+//! rest of the host keeps running, e.g. to listen to music with the TV off.
+//!
+//! Resolutions and refresh rates can be switched too, e.g. to 4K at 24Hz for
+//! a movie, through CoreGraphics on macOS, the display settings API on
+//! Windows and `xrandr` on Linux (X11 only).
+
+use serde::{Deserialize, Serialize};
+
+use crate::input_backend::InputBackend;
+use crate::protocol::CommandResponse;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayMode {
+    // Switch between mirrored and extended
+    Toggle,
+    Mirror,
+    Extend,
+    // Only the TV or monitor
+    External,
+    // Only the built-in screen
+    Internal,
+}
+
+/// A display's place on the desktop, in the same coordinates as the pointer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Screen {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub primary: bool,
+}
+
+/// A resolution and refresh rate a display supports.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VideoMode {
+    pub width: u32,
+    pub height: u32,
+    // Hz, 0 when the display doesn't say (built-in Mac screens)
+    pub refresh_rate: f64,
+    pub current: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DisplayModes {
+    // As in `Screen::name`
+    pub display: String,
+    pub modes: Vec<VideoMode>,
+}
+
+impl Screen {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let (x, y) = (i64::from(x), i64::from(y));
+        let (left, top) = (i64::from(self.x), i64::from(self.y));
+        (left..left + i64::from(self.width)).contains(&x)
+            && (top..top + i64::from(self.height)).contains(&y)
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn apply(mode: DisplayMode) -> Result<(), String> {
+    use crate::applescript::{self, Script};
+
+    match mode {
+        DisplayMode::Toggle => applescript::run(&Script::ToggleMirroring).map(|_| ()),
+        _ => Err("macOS can only toggle display mirroring".to_string()),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn apply(mode: DisplayMode) -> Result<(), String> {
+    let flag = match mode {
+        DisplayMode::Mirror => "/clone",
+        DisplayMode::Extend => "/extend",
+        DisplayMode::External => "/external",
+        DisplayMode::Internal => "/internal",
+        DisplayMode::Toggle => return Err("Pick mirror or extend on Windows".to_string()),
+    };
+    run("DisplaySwitch.exe", &[flag]).map(|_| ())
+}
+
+// Connected outputs from `xrandr --query`, the built-in panel first
+#[cfg(target_os = "linux")]
+fn connected_outputs() -> Result<(String, String), String> {
+    let query = run("xrandr", &["--query"])?;
+    let mut outputs: Vec<&str> = query
+        .lines()
+        .filter(|line| line.split_whitespace().nth(1) == Some("connected"))
+        .filter_map(|line| line.split_whitespace().next())
+        .collect();
+    let is_internal = |name: &&str| {
+        ["eDP", "LVDS", "DSI"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    };
+    outputs.sort_by_key(|name| !is_internal(name));
+    match outputs.as_slice() {
+        [internal, external, ..] => Ok((internal.to_string(), external.to_string())),
+        _ => Err("Only one display is connected".to_string()),
+    }
+}
+
+// xrandr arguments to turn `off` off and make `on` the primary display
+#[cfg(target_os = "linux")]
+fn only<'a>(on: &'a str, off: &'a str) -> Vec<&'a str> {
+    vec![
+        "--output",
+        off,
+        "--off",
+        "--output",
+        on,
+        "--auto",
+        "--primary",
+    ]
+}
+
+#[cfg(target_os = "linux")]
+fn apply(mode: DisplayMode) -> Result<(), String> {
+    let (internal, external) = connected_outputs()?;
+    let (internal, external) = (internal.as_str(), external.as_str());
+    let args = match mode {
+        DisplayMode::Mirror => vec!["--output", external, "--auto", "--same-as", internal],
+        DisplayMode::Extend => vec!["--output", external, "--auto", "--right-of", internal],
+        DisplayMode::External => only(external, internal),
+        DisplayMode::Internal => only(internal, external),
+        DisplayMode::Toggle => return Err("Pick mirror or extend on Linux".to_string()),
+    };
+    run("xrandr", &args).map(|_| ())
+}
+
+#[cfg(target_os = "macos")]
+fn screens() -> Result<Vec<Screen>, String> {
+    #[repr(C)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+    #[repr(C)]
+    struct CGSize {
+        width: f64,
+        height: f64,
+    }
+    #[repr(C)]
+    struct CGRect {
+        origin: CGPoint,
+        size: CGSize,
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGGetActiveDisplayList(max: u32, displays: *mut u32, count: *mut u32) -> i32;
+        fn CGDisplayBounds(display: u32) -> CGRect;
+        fn CGMainDisplayID() -> u32;
+    }
+
+    let mut displays = [0u32; 16];
+    let mut count = 0;
+    let error =
+        unsafe { CGGetActiveDisplayList(displays.len() as u32, displays.as_mut_ptr(), &mut count) };
+    if error != 0 {
+        return Err(format!("Failed to list displays: error {}", error));
+    }
+    let main = unsafe { CGMainDisplayID() };
+    Ok(displays[..count as usize]
+        .iter()
+        .map(|&display| {
+            // Points, like the pointer position
+            let bounds = unsafe { CGDisplayBounds(display) };
+            Screen {
+                name: format!("Display {}", display),
+                x: bounds.origin.x as i32,
+                y: bounds.origin.y as i32,
+                width: bounds.size.width as u32,
+                height: bounds.size.height as u32,
+                primary: display == main,
+            }
+        })
+        .collect())
+}
+
+#[cfg(target_os = "windows")]
+fn screens() -> Result<Vec<Screen>, String> {
+    type Bool = i32;
+    type Hdc = isize;
+    type Hmonitor = isize;
+    type Lparam = isize;
+
+    const MONITORINFOF_PRIMARY: u32 = 0x1;
+
+    #[repr(C)]
+    struct Rect {
+        left: i32,
+        top: i32,
+        right: i32,
+        bottom: i32,
+    }
+    #[repr(C)]
+    struct MonitorInfoEx {
+        size: u32,
+        monitor: Rect,
+        _work: Rect,
+        flags: u32,
+        device: [u16; 32],
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn EnumDisplayMonitors(
+            hdc: Hdc,
+            clip: *const Rect,
+            callback: unsafe extern "system" fn(Hmonitor, Hdc, *mut Rect, Lparam) -> Bool,
+            data: Lparam,
+        ) -> Bool;
+        fn GetMonitorInfoW(monitor: Hmonitor, info: *mut MonitorInfoEx) -> Bool;
+    }
+
+    unsafe extern "system" fn collect(
+        monitor: Hmonitor,
+        _hdc: Hdc,
+        _rect: *mut Rect,
+        data: Lparam,
+    ) -> Bool {
+        let mut info: MonitorInfoEx = std::mem::zeroed();
+        info.size = std::mem::size_of::<MonitorInfoEx>() as u32;
+        if GetMonitorInfoW(monitor, &mut info) != 0 {
+            let screens = &mut *(data as *mut Vec<Screen>);
+            let length = info.device.iter().position(|&c| c == 0).unwrap_or(32);
+            let rect = &info.monitor;
+            screens.push(Screen {
+                // e.g. \\.\DISPLAY1
+                name: String::from_utf16_lossy(&info.device[..length]),
+                x: rect.left,
+                y: rect.top,
+                width: (rect.right - rect.left).max(0) as u32,
+                height: (rect.bottom - rect.top).max(0) as u32,
+                primary: info.flags & MONITORINFOF_PRIMARY != 0,
+            });
+        }
+        1
+    }
+
+    let mut screens: Vec<Screen> = Vec::new();
+    let ok = unsafe {
+        EnumDisplayMonitors(
+            0,
+            std::ptr::null(),
+            collect,
+            &mut screens as *mut Vec<Screen> as Lparam,
+        )
+    };
+    if ok == 0 {
+        return Err("Failed to list displays".to_string());
+    }
+    Ok(screens)
+}
+
+// `HDMI-1 connected primary 1920x1080+0+0 (normal left inverted ...`, outputs
+// that are connected but off have no geometry and are skipped
+#[cfg(target_os = "linux")]
+fn screens() -> Result<Vec<Screen>, String> {
+    let geometry = |text: &str| -> Option<(u32, u32, i32, i32)> {
+        let (width, rest) = text.split_once('x')?;
+        let (height, rest) = rest.split_once('+')?;
+        let (x, y) = rest.split_once('+')?;
+        Some((
+            width.parse().ok()?,
+            height.parse().ok()?,
+            x.parse().ok()?,
+            y.parse().ok()?,
+        ))
+    };
+    let query = run("xrandr", &["--query"])?;
+    Ok(query
+        .lines()
+        .filter(|line| line.split_whitespace().nth(1) == Some("connected"))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let name = words.next()?;
+            let (width, height, x, y) = words.find_map(geometry)?;
+            Some(Screen {
+                name: name.to_string(),
+                x,
+                y,
+                width,
+                height,
+                primary: line.contains(" primary "),
+            })
+        })
+        .collect())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn screens() -> Result<Vec<Screen>, String> {
+    Err("Displays can't be listed on this platform".to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn set_power(on: bool) -> Result<(), String> {
+    use crate::applescript::{self, Script};
+
+    if !on {
+        return applescript::run(&Script::SleepDisplay).map(|_| ());
+    }
+    // Declares user activity, which wakes the displays
+    let status = std::process::Command::new("caffeinate")
+        .args(["-u", "-t", "1"])
+        .status()
+        .map_err(|e| format!("Failed to run caffeinate: {}", e))?;
+    if !status.success() {
+        return Err("caffeinate failed".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn set_power(on: bool) -> Result<(), String> {
+    use std::ffi::c_void;
+
+    const HWND_BROADCAST: isize = 0xFFFF;
+    const WM_SYSCOMMAND: u32 = 0x0112;
+    const SC_MONITORPOWER: usize = 0xF170;
+    // -1 is on, 2 is off
+    const POWER_ON: isize = -1;
+    const POWER_OFF: isize = 2;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn PostMessageW(window: *mut c_void, message: u32, wparam: usize, lparam: isize) -> i32;
+    }
+
+    // Posted, a broadcast that's sent waits on every window including hung ones
+    let power = if on { POWER_ON } else { POWER_OFF };
+    let ok = unsafe {
+        PostMessageW(
+            HWND_BROADCAST as *mut c_void,
+            WM_SYSCOMMAND,
+            SC_MONITORPOWER,
+            power,
+        )
+    };
+    if ok == 0 {
+        return Err("Failed to switch the displays".to_string());
+    }
+    Ok(())
+}
+
+// DPMS through `xset` on X11; Wayland has no common way, so GNOME's power
+// save mode or KDE's `kscreen-doctor`
+#[cfg(target_os = "linux")]
+fn set_power(on: bool) -> Result<(), String> {
+    if !crate::wayland::is_wayland_session() {
+        return run("xset", &["dpms", "force", if on { "on" } else { "off" }]).map(|_| ());
+    }
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    if desktop.contains("KDE") {
+        return run("kscreen-doctor", &["--dpms", if on { "on" } else { "off" }]).map(|_| ());
+    }
+    run(
+        "busctl",
+        &[
+            "--user",
+            "set-property",
+            "org.gnome.Mutter.DisplayConfig",
+            "/org/gnome/Mutter/DisplayConfig",
+            "org.gnome.Mutter.DisplayConfig",
+            "PowerSaveMode",
+            "i",
+            // 0 is on, 3 is off
+            if on { "0" } else { "3" },
+        ],
+    )
+    .map(|_| ())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn set_power(_on: bool) -> Result<(), String> {
+    Err("Displays can't be put to sleep on this platform".to_string())
+}
+
+// Screens are named `Display <id>`, see `screens`
+#[cfg(target_os = "macos")]
+fn display_id(name: &str) -> Result<u32, String> {
+    name.strip_prefix("Display ")
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| format!("No display named '{}'", name))
+}
+
+#[cfg(target_os = "macos")]
+mod core_graphics {
+    use std::ffi::c_void;
+
+    pub type CFArrayRef = *const c_void;
+    pub type CGDisplayModeRef = *const c_void;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        pub fn CGDisplayCopyAllDisplayModes(display: u32, options: *const c_void) -> CFArrayRef;
+        pub fn CGDisplayCopyDisplayMode(display: u32) -> CGDisplayModeRef;
+        pub fn CGDisplayModeGetWidth(mode: CGDisplayModeRef) -> usize;
+        pub fn CGDisplayModeGetHeight(mode: CGDisplayModeRef) -> usize;
+        pub fn CGDisplayModeGetRefreshRate(mode: CGDisplayModeRef) -> f64;
+        pub fn CGDisplayModeRelease(mode: CGDisplayModeRef);
+        pub fn CGDisplaySetDisplayMode(
+            display: u32,
+            mode: CGDisplayModeRef,
+            options: *const c_void,
+        ) -> i32;
+    }
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFArrayGetCount(array: CFArrayRef) -> isize;
+        pub fn CFArrayGetValueAtIndex(array: CFArrayRef, index: isize) -> *const c_void;
+        pub fn CFRelease(object: *const c_void);
+    }
+
+    pub fn same(a: CGDisplayModeRef, b: CGDisplayModeRef) -> bool {
+        unsafe {
+            CGDisplayModeGetWidth(a) == CGDisplayModeGetWidth(b)
+                && CGDisplayModeGetHeight(a) == CGDisplayModeGetHeight(b)
+                && CGDisplayModeGetRefreshRate(a) == CGDisplayModeGetRefreshRate(b)
+        }
+    }
+
+    // Calls `visit` with every mode of `display`, in points like the screens
+    pub fn each_mode(display: u32, mut visit: impl FnMut(CGDisplayModeRef)) -> Result<(), String> {
+        unsafe {
+            let modes = CGDisplayCopyAllDisplayModes(display, std::ptr::null());
+            if modes.is_null() {
+                return Err(format!("Failed to list the modes of display {}", display));
+            }
+            for index in 0..CFArrayGetCount(modes) {
+                visit(CFArrayGetValueAtIndex(modes, index));
+            }
+            CFRelease(modes);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn video_modes(screen: &Screen) -> Result<Vec<VideoMode>, String> {
+    use core_graphics::*;
+
+    let display = display_id(&screen.name)?;
+    let current = unsafe { CGDisplayCopyDisplayMode(display) };
+    let mut modes = Vec::new();
+    each_mode(display, |mode| unsafe {
+        modes.push(VideoMode {
+            width: CGDisplayModeGetWidth(mode) as u32,
+            height: CGDisplayModeGetHeight(mode) as u32,
+            refresh_rate: CGDisplayModeGetRefreshRate(mode),
+            current: !current.is_null() && same(mode, current),
+        });
+    })?;
+    if !current.is_null() {
+        unsafe { CGDisplayModeRelease(current) };
+    }
+    Ok(modes)
+}
+
+// Lasts until the user logs out, like a change in System Settings that
+// hasn't been confirmed
+#[cfg(target_os = "macos")]
+fn apply_video_mode(screen: &Screen, wanted: &VideoMode) -> Result<(), String> {
+    use core_graphics::*;
+
+    let display = display_id(&screen.name)?;
+    let mut error = None;
+    let mut found = false;
+    each_mode(display, |mode| unsafe {
+        let matches = CGDisplayModeGetWidth(mode) as u32 == wanted.width
+            && CGDisplayModeGetHeight(mode) as u32 == wanted.height
+            && CGDisplayModeGetRefreshRate(mode) == wanted.refresh_rate;
+        if matches && !found {
+            found = true;
+            let result = CGDisplaySetDisplayMode(display, mode, std::ptr::null());
+            if result != 0 {
+                error = Some(format!(
+                    "Failed to switch the display mode: error {}",
+                    result
+                ));
+            }
+        }
+    })?;
+    match error {
+        Some(error) => Err(error),
+        None if !found => Err("The display no longer offers that mode".to_string()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod display_settings {
+    // DEVMODEW with the display half of its union
+    #[repr(C)]
+    pub struct DevMode {
+        pub device_name: [u16; 32],
+        pub spec_version: u16,
+        pub driver_version: u16,
+        pub size: u16,
+        pub driver_extra: u16,
+        pub fields: u32,
+        pub position: [i32; 2],
+        pub display_orientation: u32,
+        pub display_fixed_output: u32,
+        pub color: i16,
+        pub duplex: i16,
+        pub y_resolution: i16,
+        pub tt_option: i16,
+        pub collate: i16,
+        pub form_name: [u16; 32],
+        pub log_pixels: u16,
+        pub bits_per_pel: u32,
+        pub pels_width: u32,
+        pub pels_height: u32,
+        pub display_flags: u32,
+        pub display_frequency: u32,
+        pub icm: [u32; 8],
+    }
+
+    pub const ENUM_CURRENT_SETTINGS: u32 = 0xFFFF_FFFF;
+    pub const DM_PELSWIDTH: u32 = 0x0008_0000;
+    pub const DM_PELSHEIGHT: u32 = 0x0010_0000;
+    pub const DM_DISPLAYFREQUENCY: u32 = 0x0040_0000;
+    pub const CDS_UPDATEREGISTRY: u32 = 0x1;
+    pub const DISP_CHANGE_SUCCESSFUL: i32 = 0;
+
+    #[link(name = "user32")]
+    extern "system" {
+        pub fn EnumDisplaySettingsW(device: *const u16, mode: u32, settings: *mut DevMode) -> i32;
+        pub fn ChangeDisplaySettingsExW(
+            device: *const u16,
+            settings: *mut DevMode,
+            window: *mut std::ffi::c_void,
+            flags: u32,
+            parameter: *mut std::ffi::c_void,
+        ) -> i32;
+    }
+
+    pub fn wide(name: &str) -> Vec<u16> {
+        name.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    pub fn settings(device: &[u16], mode: u32) -> Option<DevMode> {
+        unsafe {
+            let mut settings: DevMode = std::mem::zeroed();
+            settings.size = std::mem::size_of::<DevMode>() as u16;
+            (EnumDisplaySettingsW(device.as_ptr(), mode, &mut settings) != 0).then_some(settings)
+        }
+    }
+}
+
+// The same resolution and rate come up once per color depth, listed once
+#[cfg(target_os = "windows")]
+fn video_modes(screen: &Screen) -> Result<Vec<VideoMode>, String> {
+    use display_settings::*;
+
+    let device = wide(&screen.name);
+    let current = settings(&device, ENUM_CURRENT_SETTINGS)
+        .ok_or_else(|| format!("Failed to read the mode of {}", screen.name))?;
+    let mut modes: Vec<VideoMode> = Vec::new();
+    for settings in (0..).map_while(|index| settings(&device, index)) {
+        let mode = VideoMode {
+            width: settings.pels_width,
+            height: settings.pels_height,
+            refresh_rate: f64::from(settings.display_frequency),
+            current: settings.pels_width == current.pels_width
+                && settings.pels_height == current.pels_height
+                && settings.display_frequency == current.display_frequency,
+        };
+        if !modes.contains(&mode) {
+            modes.push(mode);
+        }
+    }
+    Ok(modes)
+}
+
+#[cfg(target_os = "windows")]
+fn apply_video_mode(screen: &Screen, wanted: &VideoMode) -> Result<(), String> {
+    use display_settings::*;
+
+    let device = wide(&screen.name);
+    let mut settings = settings(&device, ENUM_CURRENT_SETTINGS)
+        .ok_or_else(|| format!("Failed to read the mode of {}", screen.name))?;
+    settings.pels_width = wanted.width;
+    settings.pels_height = wanted.height;
+    settings.display_frequency = wanted.refresh_rate.round() as u32;
+    settings.fields = DM_PELSWIDTH | DM_PELSHEIGHT | DM_DISPLAYFREQUENCY;
+    let result = unsafe {
+        ChangeDisplaySettingsExW(
+            device.as_ptr(),
+            &mut settings,
+            std::ptr::null_mut(),
+            CDS_UPDATEREGISTRY,
+            std::ptr::null_mut(),
+        )
+    };
+    if result != DISP_CHANGE_SUCCESSFUL {
+        return Err(format!(
+            "Failed to switch the display mode: error {}",
+            result
+        ));
+    }
+    Ok(())
+}
+
+// Modes follow their output in `xrandr --query`, one resolution per line
+// with its rates, `*` marking the current one and `+` the preferred one:
+// `   1920x1080     60.00*+  50.00    59.94`
+#[cfg(target_os = "linux")]
+fn video_modes(screen: &Screen) -> Result<Vec<VideoMode>, String> {
+    if crate::wayland::is_wayland_session() {
+        return Err("Display modes can't be switched on Wayland".to_string());
+    }
+    let query = run("xrandr", &["--query"])?;
+    let mut modes = Vec::new();
+    let mut in_output = false;
+    for line in query.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            in_output = line.split_whitespace().next() == Some(screen.name.as_str());
+            continue;
+        }
+        if !in_output {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        // Interlaced modes like `1920x1080i` are left out
+        let Some((width, height)) = words.next().and_then(|size| size.split_once('x')) else {
+            continue;
+        };
+        let (Ok(width), Ok(height)) = (width.parse(), height.parse()) else {
+            continue;
+        };
+        for rate in words {
+            let Ok(refresh_rate) = rate.trim_end_matches(['*', '+']).parse() else {
+                continue;
+            };
+            modes.push(VideoMode {
+                width,
+                height,
+                refresh_rate,
+                current: rate.contains('*'),
+            });
+        }
+    }
+    Ok(modes)
+}
+
+#[cfg(target_os = "linux")]
+fn apply_video_mode(screen: &Screen, wanted: &VideoMode) -> Result<(), String> {
+    let mode = format!("{}x{}", wanted.width, wanted.height);
+    let rate = format!("{:.2}", wanted.refresh_rate);
+    run(
+        "xrandr",
+        &["--output", &screen.name, "--mode", &mode, "--rate", &rate],
+    )
+    .map(|_| ())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn video_modes(_screen: &Screen) -> Result<Vec<VideoMode>, String> {
+    Err("Display modes can't be listed on this platform".to_string())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn apply_video_mode(_screen: &Screen, _wanted: &VideoMode) -> Result<(), String> {
+    Err("Display modes can't be switched on this platform".to_string())
+}
+
+// The display named `name`, or the primary one
+fn find_screen(name: Option<&str>) -> Result<Screen, String> {
+    let screens = screens()?;
+    let found = match name {
+        Some(name) => screens.into_iter().find(|screen| screen.name == name),
+        None => {
+            let primary = screens.iter().position(|screen| screen.primary);
+            screens.into_iter().nth(primary.unwrap_or(0))
+        }
+    };
+    found.ok_or_else(|| match name {
+        Some(name) => format!("No display named '{}'", name),
+        None => "No display is connected".to_string(),
+    })
+}
+
+// Rates are matched to within half a hertz, so 24 picks 23.976 when that's
+// all the display offers; without a rate the highest one wins
+fn choose(
+    modes: Vec<VideoMode>,
+    width: u32,
+    height: u32,
+    refresh_rate: Option<f64>,
+) -> Result<VideoMode, String> {
+    let sized = modes
+        .into_iter()
+        .filter(|mode| mode.width == width && mode.height == height);
+    let chosen = match refresh_rate {
+        Some(rate) => sized
+            .filter(|mode| (mode.refresh_rate - rate).abs() < 0.5)
+            .min_by(|a, b| {
+                let distance = |mode: &VideoMode| (mode.refresh_rate - rate).abs();
+                distance(a).total_cmp(&distance(b))
+            }),
+        None => sized.max_by(|a, b| a.refresh_rate.total_cmp(&b.refresh_rate)),
+    };
+    chosen.ok_or_else(|| match refresh_rate {
+        Some(rate) => format!("The display can't do {}x{} at {}Hz", width, height, rate),
+        None => format!("The display can't do {}x{}", width, height),
+    })
+}
+
+/// Every active display.
+pub async fn list() -> Result<Vec<Screen>, String> {
+    tokio::task::spawn_blocking(screens)
+        .await
+        .map_err(|e| format!("Display task panicked: {:?}", e))?
+}
+
+/// Switch how the host drives its displays.
+pub async fn set_mode(mode: DisplayMode) -> Result<CommandResponse, String> {
+    tokio::task::spawn_blocking(move || apply(mode))
+        .await
+        .map_err(|e| format!("Display task panicked: {:?}", e))??;
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Display mode set to {:?}", mode),
+    })
+}
+
+/// Turn the displays off without sleeping the host. They come back on with
+/// `wake` or any input.
+pub async fn sleep() -> Result<CommandResponse, String> {
+    tokio::task::spawn_blocking(|| set_power(false))
+        .await
+        .map_err(|e| format!("Display task panicked: {:?}", e))??;
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: "Displays off".to_string(),
+    })
+}
+
+/// Turn the displays back on.
+pub async fn wake<B: InputBackend>() -> Result<CommandResponse, String> {
+    tokio::task::spawn_blocking(|| set_power(true))
+        .await
+        .map_err(|e| format!("Display task panicked: {:?}", e))??;
+    // Some drivers ignore the wake up call but not the pointer moving
+    crate::input_worker::run::<B, _, _>(|input| {
+        for dx in [1, -1] {
+            input
+                .move_mouse(dx, 0, enigo::Coordinate::Rel)
+                .map_err(|e| format!("Failed to wake the displays: {:?}", e))?;
+        }
+        Ok(())
+    })
+    .await?;
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: "Displays on".to_string(),
+    })
+}
+
+/// The resolutions and refresh rates of every active display.
+pub async fn list_modes() -> Result<Vec<DisplayModes>, String> {
+    tokio::task::spawn_blocking(|| {
+        screens()?
+            .into_iter()
+            .map(|screen| {
+                Ok(DisplayModes {
+                    modes: video_modes(&screen)?,
+                    display: screen.name,
+                })
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Display task panicked: {:?}", e))?
+}
+
+/// Switch `display`, or the primary display, to `width`x`height` at
+/// `refresh_rate`, or the highest rate it has for that resolution.
+pub async fn set_resolution(
+    display: Option<String>,
+    width: u32,
+    height: u32,
+    refresh_rate: Option<f64>,
+) -> Result<CommandResponse, String> {
+    let message = tokio::task::spawn_blocking(move || {
+        let screen = find_screen(display.as_deref())?;
+        let mode = choose(video_modes(&screen)?, width, height, refresh_rate)?;
+        apply_video_mode(&screen, &mode)?;
+        Ok::<_, String>(format!(
+            "{} set to {}x{} at {}Hz",
+            screen.name, mode.width, mode.height, mode.refresh_rate
+        ))
+    })
+    .await
+    .map_err(|e| format!("Display task panicked: {:?}", e))??;
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message,
+    })
+}
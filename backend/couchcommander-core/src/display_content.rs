@@ -0,0 +1,115 @@
+//! Content a remote puts up full screen on the host, e.g. a Wi-Fi QR code or
+//! a note on the living-room TV. The desktop app follows `watch` and shows it
+//! in a borderless window on the chosen display; a headless host has nowhere
+//! to show it.
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::display::Screen;
+
+const MAX_TEXT_CHARS: usize = 2000;
+// Images arrive base64 encoded inside the command
+const MAX_IMAGE_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Content {
+    Text { text: String },
+    // A `data:image/...` URL or an http(s) address
+    Image { src: String },
+    // Drawn on the host, e.g. `WIFI:T:WPA;S:Home;P:secret;;`
+    Qr { text: String },
+    // A web page loaded in the window
+    Url { url: String },
+}
+
+/// What the desktop app should show, and on which display.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Shown {
+    pub content: Content,
+    // `None` when the displays can't be listed, the app then picks one
+    pub screen: Option<Screen>,
+}
+
+lazy_static::lazy_static! {
+    static ref SHOWN: watch::Sender<Option<Shown>> = watch::Sender::new(None);
+}
+
+/// Follow the shown content, `None` once it's hidden.
+pub fn watch() -> watch::Receiver<Option<Shown>> {
+    SHOWN.subscribe()
+}
+
+/// The content up right now, for a window that's still loading.
+pub fn current() -> Option<Shown> {
+    SHOWN.borrow().clone()
+}
+
+fn is_web_address(address: &str) -> bool {
+    address.starts_with("https://") || address.starts_with("http://")
+}
+
+// QR codes are turned into images here so the window only draws three kinds
+fn prepare(content: Content) -> Result<Content, String> {
+    match content {
+        Content::Text { text } => {
+            if text.trim().is_empty() {
+                return Err("Nothing to show".to_string());
+            }
+            if text.chars().count() > MAX_TEXT_CHARS {
+                return Err(format!("Text is over {} characters", MAX_TEXT_CHARS));
+            }
+            Ok(Content::Text { text })
+        }
+        Content::Image { src } => {
+            if !src.starts_with("data:image/") && !is_web_address(&src) {
+                return Err("Images must be a data: URL or an http(s) address".to_string());
+            }
+            if src.len() > MAX_IMAGE_BYTES {
+                return Err("Image is too large".to_string());
+            }
+            Ok(Content::Image { src })
+        }
+        Content::Qr { text } => Ok(Content::Image {
+            src: crate::qr::png_data_url(&text)?,
+        }),
+        Content::Url { url } => {
+            if !is_web_address(&url) {
+                return Err("Only http(s) pages can be shown".to_string());
+            }
+            Ok(Content::Url { url })
+        }
+    }
+}
+
+/// Show `content` on the display named `display`, or the primary display.
+pub async fn show(content: Content, display: Option<String>) -> Result<String, String> {
+    if SHOWN.receiver_count() == 0 {
+        return Err("Only the desktop app can show content on the host".to_string());
+    }
+    let content = prepare(content)?;
+    let screens = crate::display::list().await;
+    let screen = match (screens, display) {
+        (Ok(screens), Some(name)) => Some(
+            screens
+                .into_iter()
+                .find(|screen| screen.name == name)
+                .ok_or_else(|| format!("No display named '{}'", name))?,
+        ),
+        (Ok(screens), None) => screens.into_iter().find(|screen| screen.primary),
+        (Err(e), Some(_)) => return Err(e),
+        (Err(_), None) => None,
+    };
+    let message = match &screen {
+        Some(screen) => format!("Showing on {}", screen.name),
+        None => "Showing on the host".to_string(),
+    };
+    SHOWN.send_replace(Some(Shown { content, screen }));
+    Ok(message)
+}
+
+/// Take down whatever is shown.
+pub fn hide() -> bool {
+    SHOWN.send_replace(None).is_some()
+}
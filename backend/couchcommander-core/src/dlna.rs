@@ -0,0 +1,119 @@
+//! DLNA/UPnP renderers for `cast`: SSDP discovery of devices with an
+//! AVTransport service, and the SOAP actions to play a URL on them, read
+//! the transport state and stop. Most smart TVs and many receivers
+//! implement this.
+
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::cast::{CastDevice, CastKind, Endpoint, PlaybackState};
+use crate::http_client;
+use crate::upnp::{self, element, escape, unescape};
+
+const SEARCH_TARGET: &str = "urn:schemas-upnp-org:service:AVTransport:1";
+
+// Read a renderer's description and find its AVTransport control URL
+async fn describe(location: &str) -> Result<CastDevice, String> {
+    let response = http_client::request("GET", location, &[], None).await?;
+    let xml = response.body;
+    let (service_type, control_url) = upnp::find_service(location, &xml, ":AVTransport:")
+        .ok_or_else(|| format!("{} has no AVTransport service", location))?;
+    let udn = element(&xml, "UDN").unwrap_or(location);
+    Ok(CastDevice {
+        id: format!("dlna:{}", udn.trim_start_matches("uuid:")),
+        name: element(&xml, "friendlyName")
+            .map(unescape)
+            .unwrap_or_else(|| location.to_string()),
+        kind: CastKind::Dlna,
+        model: element(&xml, "modelName").map(unescape),
+        endpoint: Endpoint::Dlna {
+            control_url,
+            service_type,
+        },
+    })
+}
+
+/// Renderers that answer an SSDP search within `timeout`.
+pub async fn discover(timeout: Duration) -> Result<Vec<CastDevice>, String> {
+    let locations = upnp::search(SEARCH_TARGET, timeout).await?;
+    let mut devices: Vec<CastDevice> = Vec::new();
+    for location in locations {
+        match describe(&location).await {
+            Ok(device) if devices.iter().all(|known| known.id != device.id) => devices.push(device),
+            Ok(_) => {}
+            Err(e) => debug!("Skipping renderer at {}: {}", location, e),
+        }
+    }
+    Ok(devices)
+}
+
+fn endpoint(device: &CastDevice) -> Result<(&str, &str), String> {
+    match &device.endpoint {
+        Endpoint::Dlna {
+            control_url,
+            service_type,
+        } => Ok((control_url, service_type)),
+        _ => Err(format!("{} isn't a DLNA renderer", device.name)),
+    }
+}
+
+// Call an AVTransport action on instance 0 and return the reply body
+async fn action(device: &CastDevice, name: &str, arguments: &str) -> Result<String, String> {
+    let (control_url, service_type) = endpoint(device)?;
+    let arguments = format!("<InstanceID>0</InstanceID>{}", arguments);
+    upnp::action(control_url, service_type, name, &arguments)
+        .await
+        .map_err(|e| format!("{}: {}", device.name, e))
+}
+
+// "0:01:23" or "01:23:45.500"
+fn seconds(time: &str) -> Option<f64> {
+    let mut total = 0.0;
+    for part in time.split(':') {
+        total = total * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(total)
+}
+
+/// Play `url` on a renderer.
+pub async fn cast(device: &CastDevice, url: &str) -> Result<(), String> {
+    let arguments = format!(
+        "<CurrentURI>{}</CurrentURI><CurrentURIMetaData></CurrentURIMetaData>",
+        escape(url)
+    );
+    action(device, "SetAVTransportURI", &arguments).await?;
+    action(device, "Play", "<Speed>1</Speed>").await?;
+    Ok(())
+}
+
+pub async fn status(device: &CastDevice) -> Result<PlaybackState, String> {
+    let info = action(device, "GetTransportInfo", "").await?;
+    let state = match element(&info, "CurrentTransportState") {
+        Some("PLAYING") => "playing",
+        Some("PAUSED_PLAYBACK") | Some("PAUSED_RECORDING") => "paused",
+        Some("TRANSITIONING") => "buffering",
+        Some("NO_MEDIA_PRESENT") => "idle",
+        _ => "stopped",
+    };
+    let (position, duration) = match action(device, "GetPositionInfo", "").await {
+        Ok(position) => (
+            element(&position, "RelTime").and_then(seconds),
+            element(&position, "TrackDuration")
+                .and_then(seconds)
+                .filter(|duration| *duration > 0.0),
+        ),
+        Err(e) => {
+            warn!("Failed to read the position from {}: {}", device.name, e);
+            (None, None)
+        }
+    };
+    Ok(PlaybackState {
+        state: state.to_string(),
+        position,
+        duration,
+    })
+}
+
+pub async fn stop(device: &CastDevice) -> Result<(), String> {
+    action(device, "Stop", "").await.map(|_| ())
+}
@@ -0,0 +1,236 @@
+//! Server-initiated events. Each client subscribes to the topics it wants
+//! and only gets those. Volume, now playing, the clipboard and camera or
+//! microphone use have no change notifications of their own, so a poller
+//! watches them while someone is subscribed and publishes changes.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::protocol::{EventMessage, Topic};
+use crate::{
+    clipboard, config, frontmost, input, layouts, notifications, privacy, stats, websocket,
+};
+
+// Every client starts with these so host messages and warnings reach it
+const DEFAULT_TOPICS: [Topic; 4] = [
+    Topic::Messages,
+    Topic::Battery,
+    Topic::Schedules,
+    Topic::Control,
+];
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// now_playing talks to the player, poll it less often
+const NOW_PLAYING_EVERY: u64 = 2;
+// Checking for camera and microphone use is slow on Windows
+const PRIVACY_EVERY: u64 = 2;
+// Larger clipboard contents are announced without the text
+const MAX_CLIPBOARD_BYTES: usize = 64 * 1024;
+
+lazy_static::lazy_static! {
+    static ref SUBSCRIPTIONS: Mutex<HashMap<String, HashSet<Topic>>> = Mutex::new(HashMap::new());
+    // Last value published per polled topic, cleared so new subscribers get one
+    static ref LAST_VALUES: Mutex<HashMap<Topic, serde_json::Value>> = Mutex::new(HashMap::new());
+}
+
+static POLLING: AtomicBool = AtomicBool::new(false);
+
+pub fn register_client(client_id: &str) {
+    SUBSCRIPTIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(client_id.to_string(), DEFAULT_TOPICS.into_iter().collect());
+}
+
+pub fn remove_client(client_id: &str) {
+    SUBSCRIPTIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(client_id);
+}
+
+/// Topics a client is subscribed to.
+pub fn topics(client_id: &str) -> Vec<Topic> {
+    SUBSCRIPTIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(client_id)
+        .map(|topics| topics.iter().copied().collect())
+        .unwrap_or_default()
+}
+
+pub(crate) fn has_subscribers(topic: Topic) -> bool {
+    SUBSCRIPTIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .values()
+        .any(|topics| topics.contains(&topic))
+}
+
+/// Add topics for a client, returning everything it's now subscribed to.
+pub fn subscribe(client_id: &str, topics: &[Topic]) -> Result<Vec<Topic>, String> {
+    if topics.contains(&Topic::Clipboard) && !config::current().share_clipboard {
+        return Err("Clipboard sharing is disabled in settings".to_string());
+    }
+    if topics.contains(&Topic::Notifications) {
+        if !notifications::supported() {
+            return Err("Notification mirroring isn't supported on this host".to_string());
+        }
+        if !config::current().mirror_notifications {
+            return Err("Notification mirroring is disabled in settings".to_string());
+        }
+    }
+    if topics.contains(&Topic::Privacy) && !privacy::supported() {
+        return Err("Camera and microphone use can't be read on this host".to_string());
+    }
+
+    let subscribed = {
+        let mut subscriptions = SUBSCRIPTIONS.lock().unwrap_or_else(|e| e.into_inner());
+        let subscribed = subscriptions
+            .get_mut(client_id)
+            .ok_or_else(|| "Events need a WebSocket connection".to_string())?;
+        subscribed.extend(topics.iter().copied());
+        subscribed.iter().copied().collect()
+    };
+
+    let mut last_values = LAST_VALUES.lock().unwrap_or_else(|e| e.into_inner());
+    for topic in topics {
+        last_values.remove(topic);
+    }
+    drop(last_values);
+    if topics.contains(&Topic::Stats) {
+        stats::subscribe(client_id, config::current().stats_interval_ms);
+    }
+    start_polling();
+    Ok(subscribed)
+}
+
+pub fn unsubscribe(client_id: &str, topics: &[Topic]) -> Vec<Topic> {
+    if topics.contains(&Topic::Stats) {
+        stats::unsubscribe(client_id);
+    }
+    let mut subscriptions = SUBSCRIPTIONS.lock().unwrap_or_else(|e| e.into_inner());
+    match subscriptions.get_mut(client_id) {
+        Some(subscribed) => {
+            subscribed.retain(|topic| !topics.contains(topic));
+            subscribed.iter().copied().collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Send an event to every client subscribed to its topic, returning how
+/// many clients it went to.
+pub fn publish(topic: Topic, data: serde_json::Value) -> usize {
+    let clients: Vec<String> = SUBSCRIPTIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|(_, topics)| topics.contains(&topic))
+        .map(|(client, _)| client.clone())
+        .collect();
+    if clients.is_empty() {
+        return 0;
+    }
+    let event = EventMessage::new(topic, data);
+    clients
+        .iter()
+        .filter(|client| websocket::push_to_client(client, &event))
+        .count()
+}
+
+/// Send an event to one client whatever it's subscribed to.
+pub fn publish_to(client_id: &str, topic: Topic, data: serde_json::Value) -> bool {
+    websocket::push_to_client(client_id, &EventMessage::new(topic, data))
+}
+
+// Publish `value` if it differs from what subscribers last saw
+fn publish_changed(topic: Topic, value: serde_json::Value) {
+    let mut last_values = LAST_VALUES.lock().unwrap_or_else(|e| e.into_inner());
+    if last_values.get(&topic) == Some(&value) {
+        return;
+    }
+    last_values.insert(topic, value.clone());
+    drop(last_values);
+    publish(topic, value);
+}
+
+async fn poll_once(tick: u64) {
+    if has_subscribers(Topic::Volume) {
+        match input::volume_get().await {
+            Ok(value) => publish_changed(Topic::Volume, serde_json::json!({ "value": value })),
+            Err(e) => debug!("Volume poll failed: {}", e),
+        }
+    }
+
+    if tick.is_multiple_of(NOW_PLAYING_EVERY) && has_subscribers(Topic::NowPlaying) {
+        match input::now_playing().await {
+            Ok(playing) => publish_changed(Topic::NowPlaying, playing),
+            Err(e) => debug!("Now playing poll failed: {}", e),
+        }
+    }
+
+    if tick.is_multiple_of(PRIVACY_EVERY) && has_subscribers(Topic::Privacy) {
+        match privacy::current().await {
+            Ok(indicators) => match serde_json::to_value(indicators) {
+                Ok(data) => publish_changed(Topic::Privacy, data),
+                Err(e) => warn!("Failed to serialize privacy indicators: {}", e),
+            },
+            Err(e) => debug!("Privacy poll failed: {}", e),
+        }
+    }
+
+    let wants_context = has_subscribers(Topic::Context);
+    if wants_context || has_subscribers(Topic::FrontmostApp) {
+        match frontmost::current().await {
+            Ok(app) => {
+                if wants_context {
+                    match serde_json::to_value(layouts::context(&app)) {
+                        Ok(data) => publish_changed(Topic::Context, data),
+                        Err(e) => warn!("Failed to serialize the context: {}", e),
+                    }
+                }
+                match serde_json::to_value(app) {
+                    Ok(data) => publish_changed(Topic::FrontmostApp, data),
+                    Err(e) => warn!("Failed to serialize the frontmost app: {}", e),
+                }
+            }
+            Err(e) => debug!("Frontmost app poll failed: {}", e),
+        }
+    }
+
+    if has_subscribers(Topic::Clipboard) && config::current().share_clipboard {
+        let generation = clipboard::secret_generation();
+        match tokio::task::spawn_blocking(clipboard::get).await {
+            // A sensitive `text_input` had its text on the clipboard
+            Ok(Ok(_)) if generation % 2 == 1 || clipboard::secret_generation() != generation => {}
+            Ok(Ok(text)) => {
+                let data = if text.len() > MAX_CLIPBOARD_BYTES {
+                    serde_json::json!({ "text": null, "length": text.len() })
+                } else {
+                    serde_json::json!({ "text": text, "length": text.len() })
+                };
+                publish_changed(Topic::Clipboard, data);
+            }
+            Ok(Err(e)) => debug!("Clipboard poll failed: {}", e),
+            Err(e) => warn!("Clipboard poll panicked: {:?}", e),
+        }
+    }
+}
+
+fn start_polling() {
+    if POLLING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async {
+        let mut ticker = tokio::time::interval(POLL_INTERVAL);
+        let mut tick = 0u64;
+        loop {
+            ticker.tick().await;
+            poll_once(tick).await;
+            tick = tick.wrapping_add(1);
+        }
+    });
+}
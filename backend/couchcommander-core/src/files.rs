@@ -0,0 +1,147 @@
+//! Browsing and opening files on the host, limited to the folders listed in
+//! `media_roots`. Every path a remote sends is resolved first, so `..` and
+//! symlinks can't lead outside a root.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+use tracing::info;
+
+use crate::config;
+
+// Keep huge folders from turning into multi-megabyte replies
+const MAX_ENTRIES: usize = 2000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub size: Option<u64>,
+    // Seconds since the epoch
+    pub modified: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirListing {
+    // None for the list of roots
+    pub path: Option<String>,
+    pub entries: Vec<DirEntry>,
+    pub truncated: bool,
+}
+
+fn roots() -> Vec<PathBuf> {
+    config::current()
+        .media_roots
+        .iter()
+        .filter_map(|root| {
+            let root = match root.strip_prefix("~/") {
+                Some(rest) => dirs::home_dir()?.join(rest),
+                None => PathBuf::from(root),
+            };
+            root.canonicalize().ok()
+        })
+        .collect()
+}
+
+// Resolve `path` and make sure it's inside one of the roots
+fn resolve(path: &str) -> Result<PathBuf, String> {
+    let roots = roots();
+    if roots.is_empty() {
+        return Err("No media folders are set up in settings".to_string());
+    }
+    let resolved = Path::new(path)
+        .canonicalize()
+        .map_err(|e| format!("Can't open {}: {}", path, e))?;
+    if roots.iter().any(|root| resolved.starts_with(root)) {
+        Ok(resolved)
+    } else {
+        Err(format!("{} is outside the media folders", path))
+    }
+}
+
+fn entry(path: PathBuf) -> Option<DirEntry> {
+    let name = path.file_name()?.to_string_lossy().into_owned();
+    let metadata = std::fs::metadata(&path).ok()?;
+    Some(DirEntry {
+        name,
+        is_dir: metadata.is_dir(),
+        size: metadata.is_file().then_some(metadata.len()),
+        modified: metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_secs()),
+        path: path.to_string_lossy().into_owned(),
+    })
+}
+
+/// List a folder inside the media roots, or the roots themselves for `None`.
+/// Folders come first, hidden files are left out.
+pub fn list_directory(path: Option<&str>) -> Result<DirListing, String> {
+    let Some(path) = path else {
+        let entries = roots().into_iter().filter_map(entry).collect();
+        return Ok(DirListing {
+            path: None,
+            entries,
+            truncated: false,
+        });
+    };
+
+    let dir = resolve(path)?;
+    let mut entries: Vec<DirEntry> = std::fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to list {:?}: {}", dir, e))?
+        .filter_map(|item| item.ok())
+        .filter(|item| !item.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|item| entry(item.path()))
+        .collect();
+    entries.sort_by_cached_key(|entry| (!entry.is_dir, entry.name.to_lowercase()));
+    let truncated = entries.len() > MAX_ENTRIES;
+    entries.truncate(MAX_ENTRIES);
+
+    Ok(DirListing {
+        path: Some(dir.to_string_lossy().into_owned()),
+        entries,
+        truncated,
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn open_command(path: &Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg(path);
+    command
+}
+
+// Explorer opens it with its default app, without `cmd` reading `&` in
+// the name as the start of another command
+#[cfg(target_os = "windows")]
+fn open_command(path: &Path) -> Command {
+    let mut command = Command::new("explorer");
+    command.arg(path);
+    command
+}
+
+#[cfg(target_os = "linux")]
+fn open_command(path: &Path) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(path);
+    command
+}
+
+/// Open a file inside the media roots with its default app.
+pub fn open_file(path: &str) -> Result<String, String> {
+    let file = resolve(path)?;
+    if !file.is_file() {
+        return Err(format!("{} is not a file", path));
+    }
+    info!("Opening {:?}", file);
+    open_command(&file)
+        .spawn()
+        .map_err(|e| format!("Failed to open {:?}: {}", file, e))?;
+    Ok(file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default())
+}
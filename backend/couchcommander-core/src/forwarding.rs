@@ -0,0 +1,78 @@
+//! Control chaining: a remote paired with this host can run commands on the
+//! other hosts listed under `[[linked_hosts]]` in `settings.toml`, e.g. the
+//! HTPC and the desktop from one phone connection. Commands go to each
+//! host's HTTP API with its `api_token`, so its own settings still decide
+//! what runs there. A host with TLS on is reached over `https://`, checked
+//! against its pinned certificate fingerprint.
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::config::{self, LinkedHost};
+use crate::http_client;
+use crate::protocol::{WebSocketCommand, WebSocketResponse};
+
+/// The command that forwards, which can't itself be forwarded so two hosts
+/// can't pass a command back and forth.
+pub const FORWARD_COMMAND: &str = "forward_to";
+
+/// A linked host as remotes see it, without its token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HostSummary {
+    pub name: String,
+    pub url: String,
+}
+
+pub fn list() -> Vec<HostSummary> {
+    config::current()
+        .linked_hosts
+        .into_iter()
+        .map(|host| HostSummary {
+            name: host.name,
+            url: host.url,
+        })
+        .collect()
+}
+
+fn find(name: &str) -> Result<LinkedHost, String> {
+    config::current()
+        .linked_hosts
+        .into_iter()
+        .find(|host| host.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("No linked host named '{}'", name))
+}
+
+/// Run `command` on the linked host `name` and return its reply.
+pub async fn forward(name: &str, command: WebSocketCommand) -> Result<WebSocketResponse, String> {
+    if command.command == FORWARD_COMMAND {
+        return Err("Forwarded commands can't be forwarded again".to_string());
+    }
+    let host = find(name)?;
+    let url = format!("{}/api/command", host.url.trim_end_matches('/'));
+    let body = serde_json::to_string(&command)
+        .map_err(|e| format!("Failed to encode the command: {}", e))?;
+    let authorization = format!("Bearer {}", host.token);
+    info!("Forwarding {} to {}", command.command, host.name);
+    if !http_client::parse_url(&url)?.tls {
+        warn!(
+            "Sending the token for {} unencrypted, use https:// if it has TLS on",
+            host.name
+        );
+    }
+    let response = http_client::request_pinned(
+        "POST",
+        &url,
+        &[
+            ("Authorization", authorization.as_str()),
+            ("Content-Type", "application/json"),
+        ],
+        Some(&body),
+        &host.fingerprint,
+    )
+    .await?;
+    // Failed commands come back as 422 with the reason in the body
+    serde_json::from_str(&response.body).map_err(|_| match response.status {
+        401 | 403 => format!("{} refused the token", host.name),
+        status => format!("{} answered with HTTP {}", host.name, status),
+    })
+}
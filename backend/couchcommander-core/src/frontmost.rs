@@ -0,0 +1,202 @@
+//! The application in front on the host, so remotes can adapt their buttons
+//! to it, e.g. show seek buttons for a video player. Switches are pushed on
+//! the `frontmost_app` topic; the events poller checks every second while
+//! someone is subscribed.
+//!
+//! macOS asks `lsappinfo`, Windows the foreground window and Linux the X11
+//! active window through `xprop`. Wayland doesn't tell other apps which
+//! window is active.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FrontmostApp {
+    pub name: String,
+    // Bundle id on macOS, executable on Windows, window class on Linux
+    pub id: String,
+    // Title of its focused window, when there's one
+    pub title: Option<String>,
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::process::Command;
+
+    use super::FrontmostApp;
+
+    // `lsappinfo` prints `"Key"="value"` pairs
+    fn field(output: &str, key: &str) -> Option<String> {
+        let prefix = format!("\"{}\"=\"", key);
+        output
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(&prefix)?.strip_suffix('"'))
+            .map(str::to_string)
+    }
+
+    pub fn current() -> Result<FrontmostApp, String> {
+        let front = Command::new("lsappinfo")
+            .arg("front")
+            .output()
+            .map_err(|e| format!("Failed to run lsappinfo: {}", e))?;
+        let asn = String::from_utf8_lossy(&front.stdout).trim().to_string();
+        if asn.is_empty() {
+            return Err("No application is in front".to_string());
+        }
+        let info = Command::new("lsappinfo")
+            .args(["info", "-only", "name", "-only", "bundleid", &asn])
+            .output()
+            .map_err(|e| format!("Failed to run lsappinfo: {}", e))?;
+        let info = String::from_utf8_lossy(&info.stdout);
+        let name = field(&info, "LSDisplayName")
+            .ok_or_else(|| "Couldn't read the frontmost application".to_string())?;
+        Ok(FrontmostApp {
+            id: field(&info, "CFBundleIdentifier").unwrap_or_else(|| name.clone()),
+            name,
+            title: None,
+        })
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::ffi::{c_void, OsStr};
+    use std::path::Path;
+
+    use super::FrontmostApp;
+
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    const MAX_PATH_CHARS: usize = 1024;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetForegroundWindow() -> *mut c_void;
+        fn GetWindowThreadProcessId(window: *mut c_void, process_id: *mut u32) -> u32;
+        fn GetWindowTextW(window: *mut c_void, text: *mut u16, max_count: i32) -> i32;
+    }
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenProcess(access: u32, inherit: i32, process_id: u32) -> *mut c_void;
+        fn QueryFullProcessImageNameW(
+            process: *mut c_void,
+            flags: u32,
+            name: *mut u16,
+            size: *mut u32,
+        ) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    fn executable(process_id: u32) -> Option<String> {
+        unsafe {
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, process_id);
+            if process.is_null() {
+                return None;
+            }
+            let mut path = [0u16; MAX_PATH_CHARS];
+            let mut size = path.len() as u32;
+            let ok = QueryFullProcessImageNameW(process, 0, path.as_mut_ptr(), &mut size);
+            CloseHandle(process);
+            (ok != 0).then(|| String::from_utf16_lossy(&path[..size as usize]))
+        }
+    }
+
+    pub fn current() -> Result<FrontmostApp, String> {
+        unsafe {
+            let window = GetForegroundWindow();
+            if window.is_null() {
+                return Err("No application is in front".to_string());
+            }
+            let mut process_id = 0;
+            GetWindowThreadProcessId(window, &mut process_id);
+            let path = executable(process_id)
+                .ok_or_else(|| "Couldn't read the frontmost application".to_string())?;
+
+            let mut title = [0u16; 512];
+            let length = GetWindowTextW(window, title.as_mut_ptr(), title.len() as i32);
+            let title = String::from_utf16_lossy(&title[..length.max(0) as usize]);
+
+            let path = Path::new(&path);
+            let text = |part: Option<&OsStr>| {
+                part.map(|part| part.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            };
+            Ok(FrontmostApp {
+                name: text(path.file_stem()),
+                id: text(path.file_name()),
+                title: Some(title).filter(|title| !title.is_empty()),
+            })
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::process::Command;
+
+    use super::FrontmostApp;
+    use crate::wayland::is_wayland_session;
+
+    fn xprop(args: &[&str]) -> Result<String, String> {
+        let output = Command::new("xprop")
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to run xprop: {}", e))?;
+        if !output.status.success() {
+            return Err("xprop failed".to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    // What follows ` = ` on a property's line, e.g.
+    // `WM_CLASS(STRING) = "navigator", "firefox"`
+    fn value<'a>(output: &'a str, property: &str) -> Option<&'a str> {
+        output
+            .lines()
+            .find(|line| line.starts_with(property))?
+            .split_once(" = ")
+            .map(|(_, value)| value)
+    }
+
+    pub fn current() -> Result<FrontmostApp, String> {
+        if is_wayland_session() {
+            return Err("The frontmost application can't be read on Wayland".to_string());
+        }
+        // `_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007`
+        let active = xprop(&["-root", "_NET_ACTIVE_WINDOW"])?;
+        let window = active
+            .rsplit(' ')
+            .next()
+            .map(str::trim)
+            .filter(|id| id.starts_with("0x") && *id != "0x0")
+            .ok_or_else(|| "No application is in front".to_string())?;
+        let properties = xprop(&["-id", window, "WM_CLASS", "_NET_WM_NAME"])?;
+        // Instance then class, the class being the app, e.g. "firefox"
+        let id = value(&properties, "WM_CLASS")
+            .and_then(|class| class.rsplit(", ").next())
+            .map(|class| class.trim_matches('"').to_string())
+            .ok_or_else(|| "Couldn't read the frontmost application".to_string())?;
+        let title = value(&properties, "_NET_WM_NAME")
+            .map(|title| title.trim_matches('"').to_string())
+            .filter(|title| !title.is_empty());
+        Ok(FrontmostApp {
+            name: id.clone(),
+            id,
+            title,
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod platform {
+    use super::FrontmostApp;
+
+    pub fn current() -> Result<FrontmostApp, String> {
+        Err("The frontmost application can't be read on this platform".to_string())
+    }
+}
+
+/// The application in front right now.
+pub async fn current() -> Result<FrontmostApp, String> {
+    tokio::task::spawn_blocking(platform::current)
+        .await
+        .map_err(|e| format!("Frontmost app check panicked: {}", e))?
+}
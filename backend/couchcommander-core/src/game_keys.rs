@@ -0,0 +1,74 @@
+//! Key hold semantics for WASD games. `key_down` and `key_up` don't wait for
+//! the input thread: they're queued to it and acknowledged straight away, so
+//! holding W while tapping space doesn't lag. Keys a remote still holds are released
+//! when it disconnects.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use enigo::{Direction, Key};
+use tracing::warn;
+
+use crate::input_backend::InputBackend;
+use crate::input_worker;
+use crate::keymap;
+
+lazy_static::lazy_static! {
+    // Key names each remote is holding down
+    static ref HELD: Mutex<HashMap<String, HashSet<String>>> = Mutex::new(HashMap::new());
+}
+
+fn queue<B: InputBackend>(key: Key, direction: Direction) -> Result<(), String> {
+    input_worker::queue::<B, _>(move |input| {
+        input
+            .key(key, direction)
+            .map_err(|e| format!("Game key {:?} failed: {}", key, e))
+    })
+}
+
+/// Hold a key down for a remote until `key_up`.
+pub fn key_down<B: InputBackend>(client_id: &str, key_name: &str) -> Result<(), String> {
+    let key_name = keymap::resolve_alias(key_name.to_lowercase());
+    let key = keymap::parse_key(&key_name)?;
+    let newly_held = HELD
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(client_id.to_string())
+        .or_default()
+        .insert(key_name);
+    // Phones auto-repeat touches, the OS repeats held keys itself
+    if newly_held {
+        queue::<B>(key, Direction::Press)?;
+    }
+    Ok(())
+}
+
+pub fn key_up<B: InputBackend>(client_id: &str, key_name: &str) -> Result<(), String> {
+    let key_name = keymap::resolve_alias(key_name.to_lowercase());
+    let key = keymap::parse_key(&key_name)?;
+    if let Some(held) = HELD
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_mut(client_id)
+    {
+        held.remove(&key_name);
+    }
+    queue::<B>(key, Direction::Release)
+}
+
+/// Release every key a remote holds, returning how many there were.
+pub fn release_all<B: InputBackend>(client_id: &str) -> usize {
+    let held = HELD
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(client_id)
+        .unwrap_or_default();
+    for key_name in &held {
+        let released =
+            keymap::parse_key(key_name).and_then(|key| queue::<B>(key, Direction::Release));
+        if let Err(e) = released {
+            warn!("Failed to release game key {}: {}", key_name, e);
+        }
+    }
+    held.len()
+}
@@ -0,0 +1,293 @@
+//! Gamepad emulation for couch games and emulators. The phone sends D-pad
+//! and button presses, which become key presses from `[gamepad.bindings]`
+//! or, on Linux, a virtual controller created through uinput.
+//!
+//! Buttons a remote still holds are released when it disconnects.
+
+use enigo::Direction;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::config::{self, GamepadDevice};
+use crate::input_backend::InputBackend;
+use crate::input_worker;
+use crate::keymap;
+use crate::protocol::CommandResponse;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Button {
+    Up,
+    Down,
+    Left,
+    Right,
+    A,
+    B,
+    X,
+    Y,
+    L,
+    R,
+    Start,
+    Select,
+}
+
+impl Button {
+    const ALL: [Button; 12] = [
+        Button::Up,
+        Button::Down,
+        Button::Left,
+        Button::Right,
+        Button::A,
+        Button::B,
+        Button::X,
+        Button::Y,
+        Button::L,
+        Button::R,
+        Button::Start,
+        Button::Select,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Button::Up => "up",
+            Button::Down => "down",
+            Button::Left => "left",
+            Button::Right => "right",
+            Button::A => "a",
+            Button::B => "b",
+            Button::X => "x",
+            Button::Y => "y",
+            Button::L => "l",
+            Button::R => "r",
+            Button::Start => "start",
+            Button::Select => "select",
+        }
+    }
+
+    // Keys most emulators use out of the box
+    fn default_key(self) -> &'static str {
+        match self {
+            Button::Up => "up",
+            Button::Down => "down",
+            Button::Left => "left",
+            Button::Right => "right",
+            Button::A => "x",
+            Button::B => "z",
+            Button::X => "s",
+            Button::Y => "a",
+            Button::L => "q",
+            Button::R => "w",
+            Button::Start => "enter",
+            Button::Select => "shift",
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref HELD: Mutex<HashMap<String, HashSet<Button>>> = Mutex::new(HashMap::new());
+}
+
+/// Button to key bindings used when the settings don't override them.
+pub fn default_bindings() -> BTreeMap<String, String> {
+    Button::ALL
+        .iter()
+        .map(|button| (button.name().to_string(), button.default_key().to_string()))
+        .collect()
+}
+
+fn bound_key(button: Button) -> String {
+    config::current()
+        .gamepad
+        .bindings
+        .get(button.name())
+        .cloned()
+        .unwrap_or_else(|| button.default_key().to_string())
+}
+
+fn direction(pressed: bool) -> Direction {
+    if pressed {
+        Direction::Press
+    } else {
+        Direction::Release
+    }
+}
+
+async fn send<B: InputBackend>(button: Button, pressed: bool) -> Result<(), String> {
+    if config::current().gamepad.device == GamepadDevice::Virtual {
+        return tokio::task::spawn_blocking(move || virtual_pad::send(button, pressed))
+            .await
+            .map_err(|e| format!("Gamepad task panicked: {:?}", e))?;
+    }
+
+    let key = keymap::parse_key(&bound_key(button))?;
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .key(key, direction(pressed))
+            .map_err(|e| format!("Failed to send gamepad key: {}", e))
+    })
+    .await
+}
+
+/// Press or release a button for a remote.
+pub async fn button<B: InputBackend>(
+    client_id: &str,
+    button: Button,
+    pressed: bool,
+) -> Result<CommandResponse, String> {
+    send::<B>(button, pressed).await?;
+    {
+        let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+        let buttons = held.entry(client_id.to_string()).or_default();
+        if pressed {
+            buttons.insert(button);
+        } else {
+            buttons.remove(&button);
+        }
+    }
+    let state = if pressed { "pressed" } else { "released" };
+    debug!("Gamepad {} {}", button.name(), state);
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Gamepad {} {}", button.name(), state),
+    })
+}
+
+/// Release every button a remote holds, e.g. when it disconnects.
+pub async fn release_all<B: InputBackend>(client_id: &str) -> Result<CommandResponse, String> {
+    let buttons = HELD
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(client_id)
+        .unwrap_or_default();
+    for button in &buttons {
+        if let Err(e) = send::<B>(*button, false).await {
+            warn!("Failed to release gamepad {}: {}", button.name(), e);
+        }
+    }
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Released {} gamepad buttons", buttons.len()),
+    })
+}
+
+// A virtual controller through /dev/uinput, which needs write access to it
+// (e.g. a udev rule giving the `input` group access)
+#[cfg(target_os = "linux")]
+mod virtual_pad {
+    use std::ffi::{c_int, c_long, c_ulong};
+    use std::fs::{File, OpenOptions};
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+    use std::sync::Mutex;
+    use tracing::info;
+
+    use super::Button;
+
+    const UI_SET_EVBIT: c_ulong = 0x4004_5564;
+    const UI_SET_KEYBIT: c_ulong = 0x4004_5565;
+    const UI_DEV_CREATE: c_ulong = 0x5501;
+    const EV_SYN: u16 = 0x00;
+    const EV_KEY: u16 = 0x01;
+    const SYN_REPORT: u16 = 0;
+    const BUS_VIRTUAL: u16 = 0x06;
+    const ABS_CNT: usize = 64;
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    lazy_static::lazy_static! {
+        static ref DEVICE: Mutex<Option<File>> = Mutex::new(None);
+    }
+
+    // Linux gamepad codes, laid out like an Xbox pad
+    fn code(button: Button) -> u16 {
+        match button {
+            Button::A => 0x130,
+            Button::B => 0x131,
+            Button::Y => 0x133,
+            Button::X => 0x134,
+            Button::L => 0x136,
+            Button::R => 0x137,
+            Button::Select => 0x13a,
+            Button::Start => 0x13b,
+            Button::Up => 0x220,
+            Button::Down => 0x221,
+            Button::Left => 0x222,
+            Button::Right => 0x223,
+        }
+    }
+
+    // struct uinput_user_dev
+    fn device_description() -> Vec<u8> {
+        let mut description = Vec::with_capacity(80 + 8 + 4 + ABS_CNT * 4 * 4);
+        let mut name = [0u8; 80];
+        let label = b"CouchCommander Gamepad";
+        name[..label.len()].copy_from_slice(label);
+        description.extend_from_slice(&name);
+        for id in [BUS_VIRTUAL, 0x1209, 0xcc01, 1] {
+            description.extend_from_slice(&id.to_ne_bytes());
+        }
+        description.extend_from_slice(&0u32.to_ne_bytes());
+        description.resize(description.len() + ABS_CNT * 4 * 4, 0);
+        description
+    }
+
+    fn create() -> Result<File, String> {
+        let mut device = OpenOptions::new()
+            .write(true)
+            .open("/dev/uinput")
+            .map_err(|e| format!("Can't open /dev/uinput for a virtual gamepad: {}", e))?;
+        let fd = device.as_raw_fd();
+        let mut result = unsafe { ioctl(fd, UI_SET_EVBIT, EV_KEY as c_int) };
+        for button in Button::ALL {
+            result |= unsafe { ioctl(fd, UI_SET_KEYBIT, code(button) as c_int) };
+        }
+        if result < 0 {
+            return Err("Failed to set up the virtual gamepad".to_string());
+        }
+        device
+            .write_all(&device_description())
+            .map_err(|e| format!("Failed to describe the virtual gamepad: {}", e))?;
+        if unsafe { ioctl(fd, UI_DEV_CREATE) } < 0 {
+            return Err("Failed to create the virtual gamepad".to_string());
+        }
+        info!("Created virtual gamepad");
+        Ok(device)
+    }
+
+    // struct input_event, with a zero timestamp the kernel fills in
+    fn event(events: &mut Vec<u8>, kind: u16, code: u16, value: i32) {
+        events.resize(events.len() + 2 * std::mem::size_of::<c_long>(), 0);
+        events.extend_from_slice(&kind.to_ne_bytes());
+        events.extend_from_slice(&code.to_ne_bytes());
+        events.extend_from_slice(&value.to_ne_bytes());
+    }
+
+    pub fn send(button: Button, pressed: bool) -> Result<(), String> {
+        let mut device = DEVICE.lock().unwrap_or_else(|e| e.into_inner());
+        if device.is_none() {
+            *device = Some(create()?);
+        }
+        let Some(file) = device.as_mut() else {
+            return Err("Virtual gamepad is unavailable".to_string());
+        };
+        let mut events = Vec::new();
+        event(&mut events, EV_KEY, code(button), pressed as i32);
+        event(&mut events, EV_SYN, SYN_REPORT, 0);
+        file.write_all(&events)
+            .map_err(|e| format!("Failed to send gamepad event: {}", e))
+    }
+}
+
+// Windows would need the ViGEmBus driver, macOS has no public API for it
+#[cfg(not(target_os = "linux"))]
+mod virtual_pad {
+    use super::Button;
+
+    pub fn send(_button: Button, _pressed: bool) -> Result<(), String> {
+        Err("Virtual gamepads are only supported on Linux, use key bindings".to_string())
+    }
+}
@@ -0,0 +1,323 @@
+//! Keyboard backlight and performance profiles, the rest of the host's
+//! hardware next to volume and brightness.
+//!
+//! The backlight level goes through UPower on Linux. macOS only has the
+//! brighter and dimmer keys, and Windows leaves keyboard backlights to each
+//! vendor's own software. Profiles are power-profiles-daemon's on Linux,
+//! power plans on Windows and Low Power Mode on macOS, which asks the host
+//! user for their password.
+
+use serde::{Deserialize, Serialize};
+
+use crate::input_backend::InputBackend;
+
+/// What this host can do, for remotes to hide the controls it can't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct HardwareCapabilities {
+    // The backlight level can be read and set
+    pub keyboard_backlight: bool,
+    // It can at least be turned up and down
+    pub keyboard_backlight_steps: bool,
+    pub power_profiles: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    Up,
+    Down,
+}
+
+/// A performance profile, e.g. `{"id": "power-saver", "name": "Power saver"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PowerProfile {
+    pub id: String,
+    pub name: String,
+    pub active: bool,
+}
+
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(target_os = "linux")]
+mod upower {
+    use tokio::sync::OnceCell;
+    use zbus::{Connection, Proxy};
+
+    const DESTINATION: &str = "org.freedesktop.UPower";
+    const PATH: &str = "/org/freedesktop/UPower/KbdBacklight";
+    const INTERFACE: &str = "org.freedesktop.UPower.KbdBacklight";
+
+    static SYSTEM_BUS: OnceCell<Connection> = OnceCell::const_new();
+
+    async fn backlight() -> Result<Proxy<'static>, String> {
+        let bus = SYSTEM_BUS
+            .get_or_try_init(Connection::system)
+            .await
+            .map_err(|e| format!("Failed to connect to the system bus: {}", e))?;
+        Proxy::new(bus, DESTINATION, PATH, INTERFACE)
+            .await
+            .map_err(|e| format!("Failed to reach UPower: {}", e))
+    }
+
+    async fn call(method: &str) -> Result<i32, String> {
+        backlight()
+            .await?
+            .call(method, &())
+            .await
+            .map_err(|_| "This host has no keyboard backlight".to_string())
+    }
+
+    // UPower counts in steps, e.g. 0 to 3
+    pub async fn level() -> Result<u8, String> {
+        let max = call("GetMaxBrightness").await?;
+        if max <= 0 {
+            return Err("This host has no keyboard backlight".to_string());
+        }
+        let level = call("GetBrightness").await?;
+        Ok((f64::from(level) * 100.0 / f64::from(max)).round() as u8)
+    }
+
+    pub async fn set_level(percent: u8) -> Result<u8, String> {
+        let max = call("GetMaxBrightness").await?;
+        if max <= 0 {
+            return Err("This host has no keyboard backlight".to_string());
+        }
+        let steps = (f64::from(percent.min(100)) * f64::from(max) / 100.0).round() as i32;
+        backlight()
+            .await?
+            .call_method("SetBrightness", &(steps,))
+            .await
+            .map_err(|e| format!("Failed to set the keyboard backlight: {}", e))?;
+        Ok((f64::from(steps) * 100.0 / f64::from(max)).round() as u8)
+    }
+
+    pub async fn available() -> bool {
+        matches!(call("GetMaxBrightness").await, Ok(max) if max > 0)
+    }
+}
+
+/// The keyboard backlight level in percent.
+pub async fn keyboard_backlight() -> Result<u8, String> {
+    #[cfg(target_os = "linux")]
+    {
+        upower::level().await
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err("The keyboard backlight level can't be read on this platform".to_string())
+    }
+}
+
+/// Set the keyboard backlight to `percent` and return where it landed, as
+/// the hardware only has a few steps.
+pub async fn set_keyboard_backlight(percent: u8) -> Result<u8, String> {
+    #[cfg(target_os = "linux")]
+    {
+        upower::set_level(percent).await
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = percent;
+        Err("The keyboard backlight level can't be set on this platform".to_string())
+    }
+}
+
+/// Turn the keyboard backlight up or down a step.
+pub async fn step_keyboard_backlight<B: InputBackend>(step: Step) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        use enigo::{Direction::Click, Key};
+
+        let key = match step {
+            Step::Up => Key::IlluminationUp,
+            Step::Down => Key::IlluminationDown,
+        };
+        crate::input_worker::run::<B, _, _>(move |input| {
+            input
+                .key(key, Click)
+                .map_err(|e| format!("Failed to send the backlight key: {:?}", e))
+        })
+        .await
+    }
+
+    // A tenth of the way, which is a step on most keyboards
+    #[cfg(target_os = "linux")]
+    {
+        let level = upower::level().await?;
+        let level = match step {
+            Step::Up => level.saturating_add(10).min(100),
+            Step::Down => level.saturating_sub(10),
+        };
+        upower::set_level(level).await.map(|_| ())
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        let _ = step;
+        Err("The keyboard backlight can't be changed on this platform".to_string())
+    }
+}
+
+// `pmset -g` lists ` lowpowermode         1` on Macs that have it
+#[cfg(target_os = "macos")]
+fn profiles() -> Result<Vec<PowerProfile>, String> {
+    let output = std::process::Command::new("pmset")
+        .arg("-g")
+        .output()
+        .map_err(|e| format!("Failed to run pmset: {}", e))?;
+    let settings = String::from_utf8_lossy(&output.stdout);
+    let low_power = settings
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("lowpowermode"))
+        .map(|value| value.trim() == "1")
+        .ok_or_else(|| "This Mac has no Low Power Mode".to_string())?;
+    Ok(vec![
+        PowerProfile {
+            id: "automatic".to_string(),
+            name: "Automatic".to_string(),
+            active: !low_power,
+        },
+        PowerProfile {
+            id: "low_power".to_string(),
+            name: "Low Power".to_string(),
+            active: low_power,
+        },
+    ])
+}
+
+#[cfg(target_os = "macos")]
+fn activate(profile: &PowerProfile) -> Result<(), String> {
+    use crate::applescript::{self, Script};
+
+    applescript::run(&Script::SetLowPowerMode {
+        enabled: profile.id == "low_power",
+    })
+    .map(|_| ())
+}
+
+// `Power Scheme GUID: 381b4222-f694-41f0-9685-ff5bb260df2e  (Balanced) *`
+#[cfg(target_os = "windows")]
+fn profiles() -> Result<Vec<PowerProfile>, String> {
+    let list = run("powercfg", &["/list"])?;
+    Ok(list
+        .lines()
+        .filter_map(|line| {
+            let (_, scheme) = line.split_once("GUID:")?;
+            let scheme = scheme.trim();
+            let (id, rest) = scheme.split_once(char::is_whitespace)?;
+            let rest = rest.trim();
+            let name = rest
+                .strip_prefix('(')
+                .and_then(|rest| rest.split_once(')'))
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_else(|| id.to_string());
+            Some(PowerProfile {
+                id: id.to_string(),
+                name,
+                active: rest.ends_with('*'),
+            })
+        })
+        .collect())
+}
+
+#[cfg(target_os = "windows")]
+fn activate(profile: &PowerProfile) -> Result<(), String> {
+    run("powercfg", &["/setactive", &profile.id]).map(|_| ())
+}
+
+// `powerprofilesctl list` prints each profile's name followed by its
+// details, the active one starred:
+// `* balanced:`
+#[cfg(target_os = "linux")]
+fn profiles() -> Result<Vec<PowerProfile>, String> {
+    let list = run("powerprofilesctl", &["list"])?;
+    Ok(list
+        .lines()
+        .filter_map(|line| {
+            let header = line.strip_suffix(':')?;
+            let (active, id) = match header.strip_prefix("* ") {
+                Some(id) => (true, id),
+                None => (false, header.strip_prefix("  ")?),
+            };
+            if id.starts_with(char::is_whitespace) {
+                return None;
+            }
+            let mut name = id.replace('-', " ");
+            if let Some(first) = name.get_mut(..1) {
+                first.make_ascii_uppercase();
+            }
+            Some(PowerProfile {
+                id: id.to_string(),
+                name,
+                active,
+            })
+        })
+        .collect())
+}
+
+#[cfg(target_os = "linux")]
+fn activate(profile: &PowerProfile) -> Result<(), String> {
+    run("powerprofilesctl", &["set", &profile.id]).map(|_| ())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn profiles() -> Result<Vec<PowerProfile>, String> {
+    Err("Performance profiles aren't supported on this platform".to_string())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn activate(_profile: &PowerProfile) -> Result<(), String> {
+    Err("Performance profiles aren't supported on this platform".to_string())
+}
+
+/// The host's performance profiles, the active one marked.
+pub async fn power_profiles() -> Result<Vec<PowerProfile>, String> {
+    tokio::task::spawn_blocking(profiles)
+        .await
+        .map_err(|e| format!("Power profile task panicked: {:?}", e))?
+}
+
+/// Switch to the profile with `id`, or with that name ignoring case.
+pub async fn set_power_profile(id: String) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let profile = profiles()?
+            .into_iter()
+            .find(|profile| profile.id == id || profile.name.eq_ignore_ascii_case(&id))
+            .ok_or_else(|| format!("No power profile named '{}'", id))?;
+        activate(&profile)?;
+        Ok(format!("Power profile set to {}", profile.name))
+    })
+    .await
+    .map_err(|e| format!("Power profile task panicked: {:?}", e))?
+}
+
+pub async fn capabilities() -> HardwareCapabilities {
+    #[cfg(target_os = "linux")]
+    let keyboard_backlight = upower::available().await;
+    #[cfg(not(target_os = "linux"))]
+    let keyboard_backlight = false;
+
+    HardwareCapabilities {
+        keyboard_backlight,
+        keyboard_backlight_steps: keyboard_backlight || cfg!(target_os = "macos"),
+        power_profiles: power_profiles()
+            .await
+            .is_ok_and(|profiles| !profiles.is_empty()),
+    }
+}
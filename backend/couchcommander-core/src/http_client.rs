@@ -0,0 +1,325 @@
+//! A minimal HTTP/1.1 client for services on the local network, such as a
+//! media server. One request per connection, over plain `http://`, or
+//! `https://` to a host whose certificate fingerprint is pinned: hosts on
+//! the LAN have self-signed certificates no CA vouches for.
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+use crate::tls;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_RESPONSE_BYTES: u64 = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+/// The parts of an `http://host:port/path` URL needed to send a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Url {
+    // Host and port as given, for the Host header
+    pub authority: String,
+    pub host: String,
+    pub port: u16,
+    // Path and query, at least "/"
+    pub path: String,
+    // `https://`
+    pub tls: bool,
+}
+
+pub fn parse_url(url: &str) -> Result<Url, String> {
+    let (rest, tls) = match url.trim().split_once("://") {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => (rest, false),
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("https") => (rest, true),
+        Some((scheme, _)) => {
+            return Err(format!(
+                "Only http:// and https:// URLs are supported, not {}",
+                scheme
+            ))
+        }
+        None => return Err(format!("URL needs to start with http://: {}", url)),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    // "[::1]:8080" or "host:8080"
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (
+            host,
+            port.parse::<u16>()
+                .map_err(|_| format!("Invalid port in URL: {}", url))?,
+        ),
+        _ => (authority, if tls { 443 } else { 80 }),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err(format!("URL has no host: {}", url));
+    }
+    Ok(Url {
+        authority: authority.to_string(),
+        host: host.to_string(),
+        port,
+        path: path.to_string(),
+        tls,
+    })
+}
+
+/// Whether `fingerprint` is a SHA-256 fingerprint as [`tls::fingerprint`]
+/// writes it. Colons and case don't matter.
+pub fn fingerprint_is_valid(fingerprint: &str) -> bool {
+    let hex = normalize_fingerprint(fingerprint);
+    hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.replace(':', "").trim().to_ascii_uppercase()
+}
+
+// Accepts only the certificate with the pinned fingerprint, see the module docs
+#[derive(Debug)]
+struct PinnedCertificate {
+    provider: Arc<CryptoProvider>,
+    fingerprint: String,
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if normalize_fingerprint(&tls::fingerprint(end_entity)) == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "certificate doesn't match the pinned fingerprint".to_string(),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+fn connector(fingerprint: &str) -> Result<TlsConnector, String> {
+    let provider = Arc::new(ring::default_provider());
+    let verifier = PinnedCertificate {
+        provider: provider.clone(),
+        fingerprint: normalize_fingerprint(fingerprint),
+    };
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Failed to configure TLS: {}", e))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+// Undo `Transfer-Encoding: chunked`
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>, String> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|pair| pair == b"\r\n")
+            .ok_or_else(|| "Truncated chunked response".to_string())?;
+        let size_line = String::from_utf8_lossy(&body[..line_end]);
+        let size_hex = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_hex, 16)
+            .map_err(|_| format!("Invalid chunk size: {}", size_hex))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(decoded);
+        }
+        if body.len() < size {
+            return Err("Truncated chunked response".to_string());
+        }
+        decoded.extend_from_slice(&body[..size]);
+        body = body.get(size + 2..).unwrap_or_default();
+    }
+}
+
+fn parse_response(raw: &[u8]) -> Result<Response, String> {
+    let header_end = raw
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| "Malformed HTTP response".to_string())?;
+    let head = String::from_utf8_lossy(&raw[..header_end]);
+    let mut lines = head.lines();
+    // "HTTP/1.1 200 OK"
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| "Malformed HTTP status line".to_string())?;
+    let chunked = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("transfer-encoding")
+                && value.to_ascii_lowercase().contains("chunked")
+        })
+    });
+
+    let body = &raw[header_end + 4..];
+    let body = if chunked {
+        dechunk(body)?
+    } else {
+        body.to_vec()
+    };
+    Ok(Response {
+        status,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+async fn exchange<S>(mut stream: S, request: &str, url: &Url) -> Result<Response, String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to send request to {}: {}", url.authority, e))?;
+
+    let mut raw = Vec::new();
+    let read = (&mut stream)
+        .take(MAX_RESPONSE_BYTES)
+        .read_to_end(&mut raw)
+        .await;
+    match read {
+        Ok(_) => {}
+        // Servers often close a TLS connection without saying goodbye first
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && !raw.is_empty() => {}
+        Err(e) => {
+            return Err(format!(
+                "Failed to read reply from {}: {}",
+                url.authority, e
+            ))
+        }
+    }
+    parse_response(&raw)
+}
+
+async fn send(
+    method: &str,
+    url: &Url,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+    fingerprint: Option<&str>,
+) -> Result<Response, String> {
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nUser-Agent: CouchCommander\r\n",
+        method, url.path, url.authority
+    );
+    for (name, value) in headers {
+        if value.contains(['\r', '\n']) {
+            return Err(format!("Invalid value for header {}", name));
+        }
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if let Some(body) = body {
+        request.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    request.push_str("\r\n");
+    request.push_str(body.unwrap_or_default());
+
+    // Checked before connecting, so nothing is sent to an unverified host
+    let connector = match (url.tls, fingerprint) {
+        (false, _) => None,
+        (true, Some(fingerprint)) => Some(connector(fingerprint)?),
+        (true, None) => {
+            return Err(format!(
+                "{} needs a pinned certificate fingerprint for https://",
+                url.authority
+            ))
+        }
+    };
+    let stream = TcpStream::connect((url.host.as_str(), url.port))
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {}", url.authority, e))?;
+    let Some(connector) = connector else {
+        return exchange(stream, &request, url).await;
+    };
+    let server_name = ServerName::try_from(url.host.clone())
+        .map_err(|_| format!("Invalid host name in URL: {}", url.host))?;
+    let stream = connector
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| format!("TLS handshake with {} failed: {}", url.authority, e))?;
+    exchange(stream, &request, url).await
+}
+
+/// Send a request and read the whole reply. `https://` URLs need
+/// [`request_pinned`].
+pub async fn request(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+) -> Result<Response, String> {
+    let url = parse_url(url)?;
+    tokio::time::timeout(REQUEST_TIMEOUT, send(method, &url, headers, body, None))
+        .await
+        .map_err(|_| format!("{} didn't answer in time", url.authority))?
+}
+
+/// [`request`] that also reaches `https://` URLs, when the server's
+/// certificate has the SHA-256 `fingerprint`.
+pub async fn request_pinned(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+    fingerprint: &str,
+) -> Result<Response, String> {
+    let url = parse_url(url)?;
+    let sending = send(method, &url, headers, body, Some(fingerprint));
+    tokio::time::timeout(REQUEST_TIMEOUT, sending)
+        .await
+        .map_err(|_| format!("{} didn't answer in time", url.authority))?
+}
@@ -0,0 +1,335 @@
+//! Reply messages in the language set in `settings.toml`. Commands keep
+//! building their messages in English, and `localize` rewrites the reply
+//! on its way out: Rust debug output such as `Simulate("...")` is cut down
+//! to its readable part, then known messages are translated. Messages
+//! without a translation stay in English.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Locale {
+    #[default]
+    En,
+    Es,
+    Fr,
+    De,
+}
+
+// English message, then Spanish, French and German. `{}` stands for any
+// text and is carried over in order
+const MESSAGES: &[(&str, [&str; 3])] = &[
+    (
+        "Input is paused on the host",
+        [
+            "La entrada está en pausa en el equipo",
+            "La saisie est en pause sur l'ordinateur",
+            "Die Eingabe ist am Computer pausiert",
+        ],
+    ),
+    (
+        "Command '{}' is disabled in settings",
+        [
+            "El comando '{}' está desactivado en los ajustes",
+            "La commande '{}' est désactivée dans les réglages",
+            "Der Befehl '{}' ist in den Einstellungen deaktiviert",
+        ],
+    ),
+    (
+        "Command '{}' needs an admin remote",
+        [
+            "El comando '{}' necesita un mando administrador",
+            "La commande '{}' nécessite une télécommande administrateur",
+            "Der Befehl '{}' braucht eine Admin-Fernbedienung",
+        ],
+    ),
+    (
+        "Command timed out",
+        [
+            "El comando tardó demasiado",
+            "La commande a pris trop de temps",
+            "Der Befehl hat zu lange gedauert",
+        ],
+    ),
+    (
+        "Unknown command: {}",
+        [
+            "Comando desconocido: {}",
+            "Commande inconnue : {}",
+            "Unbekannter Befehl: {}",
+        ],
+    ),
+    (
+        "Missing '{}' parameter",
+        [
+            "Falta el parámetro '{}'",
+            "Le paramètre '{}' est manquant",
+            "Der Parameter '{}' fehlt",
+        ],
+    ),
+    (
+        "Missing data for {} command",
+        [
+            "Faltan datos para el comando {}",
+            "Données manquantes pour la commande {}",
+            "Daten für den Befehl {} fehlen",
+        ],
+    ),
+    (
+        "Input is controlled by {}",
+        [
+            "{} controla la entrada",
+            "{} contrôle la saisie",
+            "{} steuert die Eingabe",
+        ],
+    ),
+    (
+        "Input is free, send take_control first",
+        [
+            "La entrada está libre, envía take_control primero",
+            "La saisie est libre, envoyez d'abord take_control",
+            "Die Eingabe ist frei, zuerst take_control senden",
+        ],
+    ),
+    (
+        "Send take_control before sending input",
+        [
+            "Envía take_control antes de enviar entradas",
+            "Envoyez take_control avant toute saisie",
+            "Vor der Eingabe take_control senden",
+        ],
+    ),
+    (
+        "Input is shared between remotes in settings",
+        [
+            "Los ajustes comparten la entrada entre los mandos",
+            "Les réglages partagent la saisie entre les télécommandes",
+            "Die Eingabe wird laut Einstellungen zwischen Fernbedienungen geteilt",
+        ],
+    ),
+    (
+        "Text input too long (max {} characters)",
+        [
+            "Texto demasiado largo (máximo {} caracteres)",
+            "Texte trop long ({} caractères maximum)",
+            "Text zu lang (höchstens {} Zeichen)",
+        ],
+    ),
+    (
+        "Failed to press key '{}'",
+        [
+            "No se pudo pulsar la tecla '{}'",
+            "Impossible d'appuyer sur la touche '{}'",
+            "Taste '{}' konnte nicht gedrückt werden",
+        ],
+    ),
+    (
+        "Failed to send {} key",
+        [
+            "No se pudo enviar la tecla {}",
+            "Impossible d'envoyer la touche {}",
+            "Taste {} konnte nicht gesendet werden",
+        ],
+    ),
+    (
+        "Failed to move mouse",
+        [
+            "No se pudo mover el ratón",
+            "Impossible de déplacer la souris",
+            "Die Maus konnte nicht bewegt werden",
+        ],
+    ),
+    (
+        "Failed to click mouse button '{}'",
+        [
+            "No se pudo pulsar el botón del ratón '{}'",
+            "Impossible de cliquer sur le bouton '{}'",
+            "Maustaste '{}' konnte nicht geklickt werden",
+        ],
+    ),
+    (
+        "{} task panicked{}",
+        [
+            "Error inesperado ({})",
+            "Erreur inattendue ({})",
+            "Unerwarteter Fehler ({})",
+        ],
+    ),
+    (
+        "Volume is {}%",
+        [
+            "El volumen está al {}%",
+            "Le volume est à {} %",
+            "Die Lautstärke ist {}%",
+        ],
+    ),
+    (
+        "Search opened",
+        ["Búsqueda abierta", "Recherche ouverte", "Suche geöffnet"],
+    ),
+    (
+        "Nothing is being cast",
+        [
+            "No se está transmitiendo nada",
+            "Rien n'est diffusé",
+            "Es wird nichts übertragen",
+        ],
+    ),
+    (
+        "Casting to {}",
+        [
+            "Transmitiendo a {}",
+            "Diffusion vers {}",
+            "Wird an {} übertragen",
+        ],
+    ),
+    (
+        "No cast device named {}",
+        [
+            "No hay ningún dispositivo llamado {}",
+            "Aucun appareil nommé {}",
+            "Kein Gerät namens {}",
+        ],
+    ),
+    (
+        "Too many schedules, cancel one first",
+        [
+            "Demasiadas programaciones, cancela una primero",
+            "Trop de programmations, annulez-en une d'abord",
+            "Zu viele Zeitpläne, zuerst einen abbrechen",
+        ],
+    ),
+];
+
+// The text of `Simulate("no permission")`, or nothing for `NoEmptyKeycodes`
+fn debug_value_text(value: &str) -> Option<&str> {
+    let start = value.find('"')? + 1;
+    let length = value[start..].find('"')?;
+    Some(&value[start..start + length]).filter(|text| !text.is_empty())
+}
+
+// Length of a Rust debug value like `Simulate("...")` or
+// `JoinError::Panic(Id(3), ..)` at the start of `text`
+fn debug_value_length(text: &str) -> Option<usize> {
+    let name_length = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == ':' || c == '_'))
+        .unwrap_or(text.len());
+    let name = &text[..name_length];
+    if !name.starts_with(|c: char| c.is_ascii_uppercase()) {
+        return None;
+    }
+    let rest = &text[name_length..];
+    let open = match rest.chars().next() {
+        // A unit variant like `NoEmptyKeycodes` rather than a plain word
+        None => {
+            let unit_variant = name.chars().skip(1).any(|c| c.is_ascii_uppercase());
+            return unit_variant.then_some(name_length);
+        }
+        Some('(') => '(',
+        Some(' ') if rest.starts_with(" {") => '{',
+        // A capitalised word in a sentence, not a debug value
+        Some(_) => return None,
+    };
+    let close = if open == '(' { ')' } else { '}' };
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if !quoted && c == open => depth += 1,
+            c if !quoted && c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(name_length + index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Replace debug values after a ": " with the text inside them, or drop
+/// them with their ": " when there's none.
+pub fn tidy(message: &str) -> String {
+    let mut tidied = String::new();
+    let mut rest = message;
+    while let Some(colon) = rest.find(": ") {
+        let after = &rest[colon + 2..];
+        match debug_value_length(after) {
+            Some(length) => {
+                tidied.push_str(&rest[..colon]);
+                if let Some(text) = debug_value_text(&after[..length]) {
+                    tidied.push_str(": ");
+                    tidied.push_str(text);
+                }
+                rest = &after[length..];
+            }
+            None => {
+                tidied.push_str(&rest[..colon + 2]);
+                rest = after;
+            }
+        }
+    }
+    tidied.push_str(rest);
+    tidied
+}
+
+// The texts standing in for `{}` if `message` fits `template`
+fn captures<'a>(template: &str, message: &'a str) -> Option<Vec<&'a str>> {
+    let parts: Vec<&str> = template.split("{}").collect();
+    let (first, others) = parts.split_first()?;
+    let mut rest = message.strip_prefix(first)?;
+    let mut captured = Vec::new();
+    for (index, part) in others.iter().enumerate() {
+        let end = if index == others.len() - 1 {
+            rest.strip_suffix(part).map(str::len)?
+        } else if part.is_empty() {
+            return None;
+        } else {
+            rest.find(part)?
+        };
+        captured.push(&rest[..end]);
+        rest = &rest[end + part.len()..];
+    }
+    rest.is_empty().then_some(captured)
+}
+
+fn fill(template: &str, values: &[&str]) -> String {
+    let mut filled = String::new();
+    for (index, part) in template.split("{}").enumerate() {
+        if index > 0 {
+            filled.push_str(values.get(index - 1).copied().unwrap_or_default());
+        }
+        filled.push_str(part);
+    }
+    filled
+}
+
+fn translate(message: &str, column: usize) -> Option<String> {
+    MESSAGES.iter().find_map(|(english, translations)| {
+        captures(english, message).map(|values| fill(translations[column], &values))
+    })
+}
+
+/// A reply message as the user should see it in `locale`.
+pub fn localize(message: &str, locale: Locale) -> String {
+    let message = tidy(message);
+    let column = match locale {
+        Locale::En => return message,
+        Locale::Es => 0,
+        Locale::Fr => 1,
+        Locale::De => 2,
+    };
+    // "Failed to send play/pause key: no permission" translates the part
+    // before the detail when the whole message isn't known
+    translate(&message, column)
+        .or_else(|| {
+            let (head, detail) = message.split_once(": ")?;
+            translate(head, column).map(|head| format!("{}: {}", head, detail))
+        })
+        .unwrap_or(message)
+}
@@ -0,0 +1,69 @@
+//! Replies to recent command ids, so a remote on a lossy connection can
+//! resend a command it got no answer for, e.g. a `mouse_click`, and get the
+//! original reply back instead of clicking twice. Ids are scoped per client
+//! and kept across a resumed session.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::protocol::WebSocketResponse;
+
+// How long a retry is answered from the cache
+const REPLY_TTL: Duration = Duration::from_secs(120);
+const MAX_IDS_PER_CLIENT: usize = 256;
+
+enum Entry {
+    Running,
+    Done(WebSocketResponse),
+}
+
+lazy_static::lazy_static! {
+    static ref REPLIES: Mutex<HashMap<(String, String), (Instant, Entry)>> =
+        Mutex::new(HashMap::new());
+}
+
+pub enum Begin {
+    /// First time this id was seen, run the command.
+    New,
+    /// A retry of a command still running on another connection.
+    Running,
+    /// A retry of a finished command, with the original reply.
+    Done(WebSocketResponse),
+}
+
+/// Record that a client is running command `id`, unless it already did.
+pub fn begin(client_id: &str, id: &str) -> Begin {
+    let now = Instant::now();
+    let mut replies = REPLIES.lock().unwrap_or_else(|e| e.into_inner());
+    replies.retain(|_, (at, _)| now.duration_since(*at) < REPLY_TTL);
+
+    let key = (client_id.to_string(), id.to_string());
+    match replies.get(&key) {
+        Some((_, Entry::Running)) => return Begin::Running,
+        Some((_, Entry::Done(response))) => return Begin::Done(response.clone()),
+        None => {}
+    }
+
+    // Forget the client's oldest id once it has too many
+    let client_ids: Vec<_> = replies
+        .iter()
+        .filter(|((client, _), _)| client == client_id)
+        .map(|(key, (at, _))| (key.clone(), *at))
+        .collect();
+    if client_ids.len() >= MAX_IDS_PER_CLIENT {
+        if let Some((oldest, _)) = client_ids.into_iter().min_by_key(|(_, at)| *at) {
+            replies.remove(&oldest);
+        }
+    }
+    replies.insert(key, (now, Entry::Running));
+    Begin::New
+}
+
+/// Keep the reply to command `id` for retries.
+pub fn finish(client_id: &str, id: &str, response: &WebSocketResponse) {
+    REPLIES.lock().unwrap_or_else(|e| e.into_inner()).insert(
+        (client_id.to_string(), id.to_string()),
+        (Instant::now(), Entry::Done(response.clone())),
+    );
+}
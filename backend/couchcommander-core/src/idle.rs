@@ -0,0 +1,249 @@
+//! Idle detection. When no remote has sent input for `idle.timeout_mins`
+//! and the host itself is untouched, playing media can be paused and the
+//! screen dimmed. The brightness comes back with the next command.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::{config, input};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+lazy_static::lazy_static! {
+    static ref LAST_ACTIVITY: Mutex<Instant> = Mutex::new(Instant::now());
+    // Brightness before dimming, restored on the next command
+    static ref DIMMED_FROM: Mutex<Option<u8>> = Mutex::new(None);
+}
+
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IdleTime {
+    // Since the last command from any remote
+    pub remote_secs: u64,
+    // Since the last keyboard or mouse input on the host, None when the
+    // platform doesn't report it
+    pub host_secs: Option<u64>,
+}
+
+/// Note a command from a remote, undoing the idle dimming if it happened.
+pub fn record_activity() {
+    *LAST_ACTIVITY.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    let dimmed_from = DIMMED_FROM.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some(brightness) = dimmed_from {
+        tokio::spawn(async move {
+            if let Err(e) = input::brightness_set(brightness).await {
+                warn!("Failed to restore brightness after idle: {}", e);
+            }
+        });
+    }
+}
+
+fn remote_idle() -> Duration {
+    LAST_ACTIVITY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .elapsed()
+}
+
+#[cfg(target_os = "linux")]
+fn host_idle() -> Option<Duration> {
+    let run = |program: &str, args: &[&str]| {
+        let output = std::process::Command::new(program)
+            .args(args)
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    // xprintidle covers X11, Mutter's idle monitor covers GNOME on Wayland
+    let millis = run("xprintidle", &[])
+        .and_then(|output| output.trim().parse().ok())
+        .or_else(|| {
+            // Prints "(uint64 12345,)"
+            let output = run(
+                "gdbus",
+                &[
+                    "call",
+                    "--session",
+                    "--dest",
+                    "org.gnome.Mutter.IdleMonitor",
+                    "--object-path",
+                    "/org/gnome/Mutter/IdleMonitor/Core",
+                    "--method",
+                    "org.gnome.Mutter.IdleMonitor.GetIdletime",
+                ],
+            )?;
+            output
+                .trim()
+                .trim_start_matches("(uint64")
+                .trim_end_matches(",)")
+                .trim()
+                .parse()
+                .ok()
+        })?;
+    Some(Duration::from_millis(millis))
+}
+
+// ioreg reports "HIDIdleTime" = <nanoseconds>
+#[cfg(target_os = "macos")]
+fn host_idle() -> Option<Duration> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4"])
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let nanos = output
+        .lines()
+        .find(|line| line.contains("\"HIDIdleTime\""))?
+        .rsplit('=')
+        .next()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_nanos(nanos))
+}
+
+#[cfg(target_os = "windows")]
+fn host_idle() -> Option<Duration> {
+    #[repr(C)]
+    struct LastInputInfo {
+        size: u32,
+        time: u32,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetLastInputInfo(info: *mut LastInputInfo) -> i32;
+    }
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetTickCount() -> u32;
+    }
+
+    let mut info = LastInputInfo {
+        size: std::mem::size_of::<LastInputInfo>() as u32,
+        time: 0,
+    };
+    if unsafe { GetLastInputInfo(&mut info) } == 0 {
+        return None;
+    }
+    // Both are 32-bit tick counts that wrap after 49 days
+    let millis = unsafe { GetTickCount() }.wrapping_sub(info.time);
+    Some(Duration::from_millis(millis as u64))
+}
+
+pub fn idle_time() -> IdleTime {
+    IdleTime {
+        remote_secs: remote_idle().as_secs(),
+        host_secs: host_idle().map(|idle| idle.as_secs()),
+    }
+}
+
+// Whether media is playing, pausing it if `pause` is set. False when
+// nothing is playing or we can't tell.
+#[cfg(target_os = "linux")]
+async fn check_playing(pause: bool) -> Result<bool, String> {
+    match crate::mpris::now_playing().await? {
+        Some(playing) if playing.status == "Playing" => {
+            if pause {
+                crate::mpris::call("Pause").await?;
+            }
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+#[cfg(target_os = "macos")]
+async fn check_playing(pause: bool) -> Result<bool, String> {
+    use crate::applescript::{self, MediaApp, PlayerAction, Script};
+
+    for app in [MediaApp::Music, MediaApp::Spotify] {
+        if applescript::run(&Script::PlayerState { app })? == "playing" {
+            if pause {
+                applescript::run(&Script::Player {
+                    app,
+                    action: PlayerAction::PlayPause,
+                })?;
+            }
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Windows has no player state to ask, a blind play/pause could start playback
+#[cfg(target_os = "windows")]
+async fn check_playing(_pause: bool) -> Result<bool, String> {
+    Ok(false)
+}
+
+async fn go_idle(settings: &config::IdleSettings) {
+    let playing = match check_playing(settings.pause_media).await {
+        Ok(playing) => playing,
+        Err(e) => {
+            warn!("Failed to check playback while idle: {}", e);
+            false
+        }
+    };
+    if !playing {
+        debug!("Idle but nothing is playing, leaving the host alone");
+        return;
+    }
+    if settings.pause_media {
+        info!("Host idle, paused playback");
+    }
+
+    if settings.dim_brightness {
+        match input::brightness_get().await {
+            Ok(current) if current > settings.dim_percent => {
+                match input::brightness_set(settings.dim_percent).await {
+                    Ok(_) => *DIMMED_FROM.lock().unwrap_or_else(|e| e.into_inner()) = Some(current),
+                    Err(e) => warn!("Failed to dim brightness: {}", e),
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to read brightness before dimming: {}", e),
+        }
+    }
+}
+
+/// Check for idleness in the background, once per process.
+pub fn watch() {
+    if WATCHING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async {
+        // Only act once per idle stretch
+        let mut handled = false;
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let settings = config::current().idle;
+            if settings.timeout_mins == 0 {
+                handled = false;
+                continue;
+            }
+            let timeout = Duration::from_secs(settings.timeout_mins * 60);
+            // Someone using the host directly isn't idle either
+            let idle = match host_idle() {
+                Some(host) => host.min(remote_idle()),
+                None => remote_idle(),
+            };
+            if idle < timeout {
+                handled = false;
+            } else if !handled {
+                handled = true;
+                if settings.pause_media || settings.dim_brightness {
+                    go_idle(&settings).await;
+                }
+            }
+        }
+    });
+}
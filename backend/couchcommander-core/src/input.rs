@@ -0,0 +1,1239 @@
+use enigo::{
+    Axis, Button, Coordinate,
+    Direction::{Press, Release},
+    Key,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::applescript::{self, Script};
+use crate::input_backend::InputBackend;
+use crate::input_worker;
+use crate::media_server::{self, MediaAction};
+use crate::protocol::CommandResponse;
+use crate::display::{self, Screen};
+use crate::{browser, clipboard, config, keymap, text_stream, url_policy};
+
+const TEXT_INPUT_MAX_CHARS: usize = 1000;
+// Text is typed in pieces so a character the backend chokes on only costs
+// a retry of its own chunk
+const TEXT_CHUNK_GRAPHEMES: usize = 32;
+const TEXT_CHUNK_RETRIES: usize = 2;
+const TEXT_CHUNK_RETRY_DELAY: Duration = Duration::from_millis(20);
+
+const SEARCH_OPEN_DELAY: Duration = Duration::from_millis(400);
+const SEARCH_RESULTS_DELAY: Duration = Duration::from_millis(300);
+
+// Panic switch for the host user, remotes can't send any input while set
+static INPUT_PAUSED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_paused() -> bool {
+    INPUT_PAUSED.load(Ordering::SeqCst)
+}
+
+pub fn set_paused(paused: bool) {
+    INPUT_PAUSED.store(paused, Ordering::SeqCst);
+    info!("Remote input {}", if paused { "paused" } else { "resumed" });
+}
+
+/// Flip the pause switch, returning the new state.
+pub fn toggle_paused() -> bool {
+    let paused = !INPUT_PAUSED.fetch_xor(true, Ordering::SeqCst);
+    info!("Remote input {}", if paused { "paused" } else { "resumed" });
+    paused
+}
+
+// MPRIS reaches the player without focus, keys are the fallback
+#[cfg(target_os = "linux")]
+async fn mpris_call(method: &str, message: &str) -> Option<CommandResponse> {
+    match crate::mpris::call(method).await {
+        Ok(true) => Some(CommandResponse {
+            status: "success".to_string(),
+            message: message.to_string(),
+        }),
+        Ok(false) => None,
+        Err(e) => {
+            warn!("MPRIS {} failed, falling back to keys: {}", method, e);
+            None
+        }
+    }
+}
+
+// Simple media control commands
+pub async fn play_pause<B: InputBackend>() -> Result<CommandResponse, String> {
+    if let Some(response) = media_server::route(MediaAction::PlayPause).await {
+        return Ok(response);
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(response) = mpris_call("PlayPause", "Play/pause command sent").await {
+        return Ok(response);
+    }
+
+    info!("Executing play_pause command");
+
+    input_worker::run::<B, _, _>(move |input| {
+        input.key(Key::Space, Press).map_err(|e| {
+            error!("Failed to send play/pause key: {:?}", e);
+            format!("Failed to send play/pause key: {:?}", e)
+        })?;
+
+        info!("Play/pause command executed successfully");
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Play/pause command sent".to_string(),
+        })
+    })
+    .await
+}
+
+pub async fn media_previous<B: InputBackend>() -> Result<CommandResponse, String> {
+    if let Some(response) = media_server::route(MediaAction::Previous).await {
+        return Ok(response);
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(response) = mpris_call("Previous", "Media previous command sent").await {
+        return Ok(response);
+    }
+
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .key(Key::Unicode('j'), Press) // Previous/rewind key
+            .map_err(|e| format!("Failed to send media previous key: {:?}", e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Media previous command sent".to_string(),
+        })
+    })
+    .await
+}
+
+pub async fn media_next<B: InputBackend>() -> Result<CommandResponse, String> {
+    if let Some(response) = media_server::route(MediaAction::Next).await {
+        return Ok(response);
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(response) = mpris_call("Next", "Media next command sent").await {
+        return Ok(response);
+    }
+
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .key(Key::Unicode('l'), Press) // Next/fast forward key
+            .map_err(|e| format!("Failed to send media next key: {:?}", e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Media next command sent".to_string(),
+        })
+    })
+    .await
+}
+
+pub async fn volume_up<B: InputBackend>() -> Result<CommandResponse, String> {
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .key(Key::VolumeUp, Press)
+            .map_err(|e| format!("Failed to send volume up key: {:?}", e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Volume up command sent".to_string(),
+        })
+    })
+    .await
+}
+
+pub async fn volume_down<B: InputBackend>() -> Result<CommandResponse, String> {
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .key(Key::VolumeDown, Press)
+            .map_err(|e| format!("Failed to send volume down key: {:?}", e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Volume down command sent".to_string(),
+        })
+    })
+    .await
+}
+
+pub async fn volume_mute<B: InputBackend>() -> Result<CommandResponse, String> {
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .key(Key::VolumeMute, Press)
+            .map_err(|e| format!("Failed to send volume mute key: {:?}", e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Volume mute command sent".to_string(),
+        })
+    })
+    .await
+}
+
+// Generic key sending command for flexibility (original version)
+pub async fn send_key<B: InputBackend>(key_name: String) -> Result<CommandResponse, String> {
+    debug!("=== SEND_KEY DEBUG START ===");
+    debug!("Received key_name: '{}'", key_name);
+    debug!("Key name length: {}", key_name.len());
+
+    // Resolve user-defined key names from the settings file
+    let key_name = keymap::resolve_alias(key_name);
+    
+    let key = keymap::parse_key(&key_name)?;
+
+    input_worker::run::<B, _, _>(move |input| {
+        debug!("=== SEND_KEY TASK START ===");
+
+        // For Unicode characters, use the text() method instead of Key::Unicode
+        // This avoids the crash that happens with Key::Unicode on macOS
+        if let Key::Unicode(ch) = key {
+            debug!("Using text() method for Unicode character '{}'", ch);
+            input.text(&ch.to_string()).map_err(|e| {
+                error!("Failed to input text for character '{}': {:?}", ch, e);
+                format!("Failed to input text for character '{}': {:?}", ch, e)
+            })?;
+            info!("Text input successful for character '{}'", ch);
+        } else {
+            // For non-Unicode keys, use the regular key() method
+            input.key(key, Press).map_err(|e| {
+                error!("Failed to press key '{}': {:?}", key_name, e);
+                if cfg!(target_os = "macos") {
+                    format!("Failed to press key '{}': {:?}. This might be due to missing accessibility permissions. Please check System Preferences > Security & Privacy > Privacy > Accessibility and ensure the app has permission.", key_name, e)
+                } else {
+                    format!("Failed to press key '{}': {:?}", key_name, e)
+                }
+            })?;
+            info!("Key press successful");
+        }
+
+        debug!("=== SEND_KEY TASK SUCCESS ===");
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Key '{}' sent successfully", key_name),
+        })
+    })
+    .await
+}
+
+// Test command for debugging text input
+pub async fn test_text_input<B: InputBackend>() -> Result<CommandResponse, String> {
+    info!("Testing text input with simple text");
+
+    let test_text = "Hello World! ❤️";
+    match text_input::<B>(text_stream::LOCAL_CLIENT, test_text.to_string()).await {
+        Ok(response) => {
+            info!("Test successful: {:?}", response);
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message: format!("Test completed: {}", response.message),
+            })
+        }
+        Err(e) => {
+            error!("Test failed: {}", e);
+            Err(format!("Test failed: {}", e))
+        }
+    }
+}
+
+// Text input command - using Enigo best practices with shared instance and text() method
+pub async fn text_input<B: InputBackend>(
+    client: &str,
+    text: String,
+) -> Result<CommandResponse, String> {
+    let length = text_length(&text);
+    info!("Executing text_input command with text length: {}", length);
+
+    // Validate input
+    if text.is_empty() {
+        debug!("Empty text input provided");
+        return Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Empty text input".to_string(),
+        });
+    }
+
+    // Limit text length to prevent overwhelming the system
+    if length > TEXT_INPUT_MAX_CHARS {
+        warn!(
+            "Text input too long: {} characters (max {})",
+            length, TEXT_INPUT_MAX_CHARS
+        );
+        return Err(format!(
+            "Text input too long (max {} characters)",
+            TEXT_INPUT_MAX_CHARS
+        ));
+    }
+
+    // Long URLs and passwords go in far faster and more reliably as one paste
+    let paste_threshold = config::current().paste_threshold;
+    let paste = paste_threshold > 0 && length >= paste_threshold;
+
+    // Wait behind this client's earlier text, other clients have their own queue
+    let _queue = text_stream::queue(client).await;
+
+    input_worker::run::<B, _, _>(move |input| {
+
+        debug!("Typing {} characters", length);
+
+        // Small delay before typing for stability
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        if paste {
+            clipboard::paste(input, &text)?;
+            info!("Text input pasted successfully");
+            return Ok(CommandResponse {
+                status: "success".to_string(),
+                message: format!("Text input pasted ({} characters)", length),
+            });
+        }
+
+        let pasted = type_text(input, &text)?;
+
+        info!("Text input completed successfully");
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: if pasted > 0 {
+                format!(
+                    "Text input successful ({} characters, {} chunk(s) pasted)",
+                    length, pasted
+                )
+            } else {
+                format!("Text input successful ({} characters)", length)
+            },
+        })
+    })
+    .await
+}
+
+/// `text_input` for a password: always pasted with the clipboard restored,
+/// and neither the text nor its length is logged or put in the response.
+pub async fn sensitive_text_input<B: InputBackend>(
+    client: &str,
+    text: String,
+) -> Result<CommandResponse, String> {
+    info!("Executing sensitive text_input command");
+    if text_length(&text) > TEXT_INPUT_MAX_CHARS {
+        return Err(format!(
+            "Text input too long (max {} characters)",
+            TEXT_INPUT_MAX_CHARS
+        ));
+    }
+
+    let _queue = text_stream::queue(client).await;
+
+    input_worker::run::<B, _, _>(move |input| {
+        clipboard::paste_secret(input, &text)?;
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Sensitive text entered".to_string(),
+        })
+    })
+    .await
+}
+
+/// Length as the user sees it, so an emoji or an accented letter built from
+/// several code points counts once.
+pub fn text_length(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+// Grapheme-aligned pieces, so no character is ever split across two chunks
+fn text_chunks(text: &str) -> Vec<String> {
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    graphemes
+        .chunks(TEXT_CHUNK_GRAPHEMES)
+        .map(|chunk| chunk.concat())
+        .collect()
+}
+
+/// Type `text` chunk by chunk, retrying failed chunks and pasting the ones
+/// the backend can't type at all. Returns how many chunks were pasted.
+pub(crate) fn type_text<B: InputBackend>(input: &mut B, text: &str) -> Result<usize, String> {
+    let mut pasted = 0;
+    for chunk in text_chunks(text) {
+        let mut typed = false;
+        for attempt in 0..=TEXT_CHUNK_RETRIES {
+            match input.text(&chunk) {
+                Ok(()) => {
+                    typed = true;
+                    break;
+                }
+                Err(e) => {
+                    debug!("Typing chunk failed (attempt {}): {:?}", attempt + 1, e);
+                    std::thread::sleep(TEXT_CHUNK_RETRY_DELAY);
+                }
+            }
+        }
+        if !typed {
+            warn!("Could not type a chunk of text, pasting it instead");
+            clipboard::paste(input, &chunk)
+                .map_err(|e| format!("Text input failed, paste fallback failed too: {}", e))?;
+            pasted += 1;
+        }
+    }
+    Ok(pasted)
+}
+
+// Mouse movement command
+pub async fn mouse_move<B: InputBackend>(delta_x: i32, delta_y: i32) -> Result<CommandResponse, String> {
+    info!("Executing mouse_move command: ({}, {})", delta_x, delta_y);
+
+    let sensitivity = config::current().pointer_sensitivity;
+    let delta_x = (delta_x as f64 * sensitivity).round() as i32;
+    let delta_y = (delta_y as f64 * sensitivity).round() as i32;
+
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .move_mouse(delta_x, delta_y, Coordinate::Rel)
+            .map_err(|e| {
+                error!(
+                    "Failed to move mouse by ({}, {}): {:?}",
+                    delta_x, delta_y, e
+                );
+                format!("Failed to move mouse: {:?}", e)
+            })?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Mouse moved by ({}, {})", delta_x, delta_y),
+        })
+    })
+    .await
+}
+
+// Mouse click command
+pub async fn mouse_click<B: InputBackend>(button: String) -> Result<CommandResponse, String> {
+    let mouse_button = match button.as_str() {
+        "left" => Button::Left,
+        "right" => Button::Right,
+        "middle" => Button::Middle,
+        _ => return Err(format!("Unsupported mouse button: {}", button)),
+    };
+
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .button(mouse_button, Press)
+            .map_err(|e| format!("Failed to click mouse button '{}': {:?}", button, e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Mouse {} clicked", button),
+        })
+    })
+    .await
+}
+
+// Scroll command
+pub async fn scroll<B: InputBackend>(delta_x: i32, delta_y: i32) -> Result<CommandResponse, String> {
+    let sensitivity = config::current().scroll_sensitivity;
+    let delta_x = (delta_x as f64 * sensitivity).round() as i32;
+    let delta_y = (delta_y as f64 * sensitivity).round() as i32;
+
+    input_worker::run::<B, _, _>(move |input| {
+        if delta_x != 0 {
+            input
+                .scroll(delta_x, Axis::Horizontal)
+                .map_err(|e| format!("Failed to scroll horizontally: {:?}", e))?;
+        }
+
+        if delta_y != 0 {
+            input
+                .scroll(delta_y, Axis::Vertical)
+                .map_err(|e| format!("Failed to scroll vertically: {:?}", e))?;
+        }
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Scrolled by ({}, {})", delta_x, delta_y),
+        })
+    })
+    .await
+}
+
+/// The pointer in desktop coordinates and the display it's on.
+#[derive(Debug, Clone, Serialize)]
+pub struct PointerPosition {
+    pub x: i32,
+    pub y: i32,
+    // None when the displays couldn't be listed
+    pub display: Option<Screen>,
+}
+
+// Pointer position query
+pub async fn mouse_position<B: InputBackend>() -> Result<PointerPosition, String> {
+    let (x, y) = input_worker::run::<B, _, _>(move |input| {
+        input
+            .location()
+            .map_err(|e| format!("Failed to read the mouse position: {:?}", e))
+    })
+    .await?;
+
+    let display = match display::list().await {
+        Ok(screens) => screens.into_iter().find(|screen| screen.contains(x, y)),
+        Err(e) => {
+            debug!("Couldn't list displays: {}", e);
+            None
+        }
+    };
+    Ok(PointerPosition { x, y, display })
+}
+
+// Volume set command
+pub async fn volume_set(value: u8) -> Result<CommandResponse, String> {
+    #[cfg(target_os = "macos")]
+    {
+        applescript::run(&Script::SetVolume { value })
+            .map_err(|e| format!("Failed to set volume: {}", e))?;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // On Windows, you'd typically use Windows APIs or third-party tools
+        // For simplicity, we'll just return success
+        return Ok(CommandResponse {
+            status: "info".to_string(),
+            message: "Volume set not implemented on Windows yet".to_string(),
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        tokio::task::spawn_blocking(move || crate::audio::set_volume(value))
+            .await
+            .map_err(|e| format!("Volume task panicked: {:?}", e))??;
+    }
+
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Volume set to {}%", value),
+    })
+}
+
+// Volume get command, in percent
+pub async fn volume_get() -> Result<u8, String> {
+    #[cfg(target_os = "macos")]
+    {
+        applescript::run(&Script::GetVolume)
+            .map_err(|e| format!("Failed to get volume: {}", e))?
+            .parse()
+            .map_err(|_| "Could not read the current volume".to_string())
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        tokio::task::spawn_blocking(crate::audio::volume)
+            .await
+            .map_err(|e| format!("Volume task panicked: {:?}", e))?
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        Err("Reading the volume is not supported on this platform yet".to_string())
+    }
+}
+
+// Explicit mute, unlike volume_mute which toggles
+pub async fn volume_set_muted(muted: bool) -> Result<CommandResponse, String> {
+    #[cfg(target_os = "macos")]
+    {
+        applescript::run(&Script::SetMuted { muted })
+            .map_err(|e| format!("Failed to set mute: {}", e))?;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        tokio::task::spawn_blocking(move || crate::audio::set_muted(muted))
+            .await
+            .map_err(|e| format!("Volume task panicked: {:?}", e))??;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        return Ok(CommandResponse {
+            status: "info".to_string(),
+            message: "Mute set not implemented on Windows yet".to_string(),
+        });
+    }
+
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: if muted { "Volume muted" } else { "Volume unmuted" }.to_string(),
+    })
+}
+
+// Audio outputs the remote can switch between
+pub async fn audio_outputs() -> Result<serde_json::Value, String> {
+    #[cfg(target_os = "linux")]
+    {
+        let sinks = tokio::task::spawn_blocking(crate::audio::sinks)
+            .await
+            .map_err(|e| format!("Audio task panicked: {:?}", e))??;
+        serde_json::to_value(sinks).map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err("Listing audio outputs is not supported on this platform yet".to_string())
+    }
+}
+
+pub async fn set_audio_output(name: String) -> Result<CommandResponse, String> {
+    #[cfg(target_os = "linux")]
+    {
+        let sink = name.clone();
+        tokio::task::spawn_blocking(move || crate::audio::set_default_sink(&sink))
+            .await
+            .map_err(|e| format!("Audio task panicked: {:?}", e))??;
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Audio output switched to {}", name),
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = name;
+        Err("Switching audio outputs is not supported on this platform yet".to_string())
+    }
+}
+
+// Brightness set command
+pub async fn brightness_set(value: u8) -> Result<CommandResponse, String> {
+    #[cfg(target_os = "macos")]
+    {
+        // Use brightness command line tool or AppleScript
+        let brightness_value = (value as f32 / 100.0).min(1.0).max(0.0);
+        // Note: This is a simplified approach. In practice, you'd use the brightness command or other methods
+        std::process::Command::new("brightness")
+            .arg(brightness_value.to_string())
+            .output()
+            .map_err(|_| {
+                "brightness command not available, install via: brew install brightness".to_string()
+            })?;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let value = value.min(100);
+        tokio::task::spawn_blocking(move || crate::windows_brightness::set(value))
+            .await
+            .map_err(|e| format!("Brightness task panicked: {:?}", e))??;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // On Linux, we can use xrandr or write to /sys/class/backlight
+        if let Ok(output) = std::process::Command::new("xrandr")
+            .arg("--output")
+            .arg("eDP-1") // This might vary by system
+            .arg("--brightness")
+            .arg((value as f32 / 100.0).to_string())
+            .output()
+        {
+            if !output.status.success() {
+                return Err("Failed to set brightness via xrandr".to_string());
+            }
+        } else {
+            return Err("xrandr not available".to_string());
+        }
+    }
+
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Brightness set to {}%", value),
+    })
+}
+
+// Brightness get command, in percent
+pub async fn brightness_get() -> Result<u8, String> {
+    #[cfg(target_os = "macos")]
+    {
+        // `brightness -l` prints lines like "display 0: brightness 0.750000"
+        let output = std::process::Command::new("brightness")
+            .arg("-l")
+            .output()
+            .map_err(|_| {
+                "brightness command not available, install via: brew install brightness".to_string()
+            })?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        stdout
+            .lines()
+            .find_map(|line| line.split("brightness ").nth(1)?.trim().parse::<f32>().ok())
+            .map(|level| (level * 100.0).round().clamp(0.0, 100.0) as u8)
+            .ok_or_else(|| "No display reports its brightness".to_string())
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        tokio::task::spawn_blocking(crate::windows_brightness::get)
+            .await
+            .map_err(|e| format!("Brightness task panicked: {:?}", e))?
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // xrandr only knows the software gamma level it set itself
+        let output = std::process::Command::new("xrandr")
+            .arg("--verbose")
+            .output()
+            .map_err(|_| "xrandr not available".to_string())?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        stdout
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("Brightness:")?.trim().parse::<f32>().ok())
+            .map(|level| (level * 100.0).round().clamp(0.0, 100.0) as u8)
+            .ok_or_else(|| "xrandr did not report a brightness".to_string())
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        Err("Reading brightness is not supported on this platform".to_string())
+    }
+}
+
+// Brightness up command
+pub async fn brightness_up<B: InputBackend>() -> Result<CommandResponse, String> {
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .key(Key::F2, Press)
+            .map_err(|e| format!("Failed to send F2 key: {:?}", e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Brightness up command sent".to_string(),
+        })
+    })
+    .await
+}
+
+// Brightness down command
+pub async fn brightness_down<B: InputBackend>() -> Result<CommandResponse, String> {
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .key(Key::F1, Press)
+            .map_err(|e| format!("Failed to send F1 key: {:?}", e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Brightness down command sent".to_string(),
+        })
+    })
+    .await
+}
+
+// Media stop command
+pub async fn media_stop<B: InputBackend>() -> Result<CommandResponse, String> {
+    if let Some(response) = media_server::route(MediaAction::Stop).await {
+        return Ok(response);
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(response) = mpris_call("Stop", "Media stop command sent").await {
+        return Ok(response);
+    }
+
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .key(Key::Unicode('k'), Press) // Stop/pause key
+            .map_err(|e| format!("Failed to send media stop key: {:?}", e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Media stop command sent".to_string(),
+        })
+    })
+    .await
+}
+
+// Seek by `offset` seconds, negative to rewind
+pub async fn media_seek<B: InputBackend>(offset: f64) -> Result<CommandResponse, String> {
+    if let Some(response) = media_server::route(MediaAction::Seek(offset)).await {
+        return Ok(response);
+    }
+
+    #[cfg(target_os = "linux")]
+    match crate::mpris::seek(offset).await {
+        Ok(true) => {
+            return Ok(CommandResponse {
+                status: "success".to_string(),
+                message: format!("Seeked {}s", offset),
+            })
+        }
+        Ok(false) => {}
+        Err(e) => warn!("MPRIS seek failed, falling back to keys: {}", e),
+    }
+
+    // Arrow keys skip a few seconds in most players
+    input_worker::run::<B, _, _>(move |input| {
+        let key = if offset < 0.0 {
+            Key::LeftArrow
+        } else {
+            Key::RightArrow
+        };
+        input
+            .key(key, enigo::Direction::Click)
+            .map_err(|e| format!("Failed to send seek key: {:?}", e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Media seek command sent".to_string(),
+        })
+    })
+    .await
+}
+
+// Track info from the active player, null when nothing is playing
+pub async fn now_playing() -> Result<serde_json::Value, String> {
+    if let Some(playing) = media_server::now_playing().await {
+        return Ok(playing);
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let playing = crate::mpris::now_playing().await?;
+        serde_json::to_value(playing).map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err("Now playing is not supported on this platform yet".to_string())
+    }
+}
+
+// Run a script from the AppleScript library
+pub async fn applescript(script: Script) -> Result<CommandResponse, String> {
+    let output = tokio::task::spawn_blocking(move || applescript::run(&script))
+        .await
+        .map_err(|e| format!("AppleScript task panicked: {:?}", e))??;
+
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: if output.is_empty() {
+            "Script ran".to_string()
+        } else {
+            output
+        },
+    })
+}
+
+// Open Spotlight, the Start menu or the Linux launcher, then optionally type
+// a query and press Enter, all in one go so other input can't land between
+/// Tap a key by its platform code, for keys with no name such as eject or
+/// the language switch. See `InputCapabilities::raw_key_codes` for what the
+/// code means on this host.
+pub async fn send_raw_key<B: InputBackend>(code: u32) -> Result<CommandResponse, String> {
+    input_worker::run::<B, _, _>(move |input| {
+        input
+            .key(Key::Other(code), enigo::Direction::Click)
+            .map_err(|e| format!("Failed to send raw key {}: {:?}", code, e))?;
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Sent raw key {}", code),
+        })
+    })
+    .await
+}
+
+pub async fn open_search<B: InputBackend>(
+    query: Option<String>,
+    submit: bool,
+) -> Result<CommandResponse, String> {
+    let query = query.filter(|q| !q.is_empty());
+    if let Some(query) = &query {
+        if text_length(query) > TEXT_INPUT_MAX_CHARS {
+            return Err(format!(
+                "Search query too long (max {} characters)",
+                TEXT_INPUT_MAX_CHARS
+            ));
+        }
+    }
+
+    input_worker::run::<B, _, _>(move |input| {
+        // Cmd+Space on macOS, a Super/Windows key tap elsewhere
+        if cfg!(target_os = "macos") {
+            input
+                .key(Key::Meta, Press)
+                .and_then(|_| input.key(Key::Space, enigo::Direction::Click))
+                .and_then(|_| input.key(Key::Meta, Release))
+        } else {
+            input.key(Key::Meta, enigo::Direction::Click)
+        }
+        .map_err(|e| format!("Failed to open search: {:?}", e))?;
+
+        let Some(query) = query else {
+            return Ok(CommandResponse {
+                status: "success".to_string(),
+                message: "Search opened".to_string(),
+            });
+        };
+
+        // The search field takes a moment to appear and grab focus
+        std::thread::sleep(SEARCH_OPEN_DELAY);
+        type_text(input, &query)?;
+        if submit {
+            std::thread::sleep(SEARCH_RESULTS_DELAY);
+            input
+                .key(Key::Return, enigo::Direction::Click)
+                .map_err(|e| format!("Failed to submit search: {:?}", e))?;
+        }
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Searched for \"{}\"", query),
+        })
+    })
+    .await
+}
+
+// Open website command. `kiosk` overrides the kiosk_domains setting.
+pub async fn open_website(url: String, kiosk: Option<bool>) -> Result<CommandResponse, String> {
+    let settings = config::current();
+    url_policy::check(&settings, &url)?;
+
+    let web = url.starts_with("http://") || url.starts_with("https://");
+    let kiosk =
+        web && kiosk.unwrap_or_else(|| url_policy::host_matches(&url, &settings.kiosk_domains));
+    if kiosk {
+        let browser = browser::open_kiosk(&settings.kiosk_browser, &url)?;
+        return Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Opened website in {} kiosk mode: {}", browser, url),
+        });
+    }
+
+    // Use system default to open URL
+    #[cfg(target_os = "macos")]
+    let cmd = "open";
+    #[cfg(target_os = "windows")]
+    let cmd = "start";
+    #[cfg(target_os = "linux")]
+    let cmd = "xdg-open";
+
+    std::process::Command::new(cmd)
+        .arg(&url)
+        .spawn()
+        .map_err(|e| format!("Failed to open URL: {}", e))?;
+
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Opened website: {}", url),
+    })
+}
+
+// Modifier key state management
+lazy_static::lazy_static! {
+    static ref MODIFIER_KEY_STATES: Mutex<HashMap<String, bool>> = Mutex::new(HashMap::new());
+}
+
+// Get current modifier key states
+pub async fn get_modifier_key_states() -> Result<serde_json::Value, String> {
+    debug!("Getting modifier key states");
+    
+    let states = MODIFIER_KEY_STATES.lock().map_err(|e| {
+        error!("Failed to lock modifier key states: {:?}", e);
+        "Failed to get modifier key states".to_string()
+    })?;
+    
+    let states_json = serde_json::json!({
+        "cmd": states.get("cmd").unwrap_or(&false),
+        "shift": states.get("shift").unwrap_or(&false),
+        "alt": states.get("alt").unwrap_or(&false),
+        "option": states.get("option").unwrap_or(&false),
+        "ctrl": states.get("ctrl").unwrap_or(&false),
+        "control": states.get("control").unwrap_or(&false),
+    });
+    
+    debug!("Current modifier key states: {:?}", states_json);
+    Ok(states_json)
+}
+
+// Toggle a modifier key state
+pub async fn toggle_modifier_key<B: InputBackend>(key_name: String) -> Result<CommandResponse, String> {
+    info!("Toggling modifier key: {}", key_name);
+    
+    // Get current state and calculate new state
+    let new_state = {
+        let states = MODIFIER_KEY_STATES.lock().map_err(|e| {
+            error!("Failed to lock modifier key states: {:?}", e);
+            "Failed to toggle modifier key".to_string()
+        })?;
+        
+        !states.get(&key_name).copied().unwrap_or(false)
+    };
+    
+    let key_name_clone = key_name.clone();
+    // Map the key name to the actual Key enum
+    let key = keymap::modifier_key(&key_name_clone).ok_or_else(|| {
+        warn!("Unknown modifier key: {}", key_name_clone);
+        format!("Unknown modifier key: {}", key_name_clone)
+    });
+    
+    // Actually send the modifier key to the system
+    let result: Result<(), String> = match key {
+        Ok(key) => {
+            input_worker::run::<B, _, _>(move |input| {
+                // Send the key press or release based on the new state
+                let direction = if new_state { Press } else { Release };
+                debug!("Sending modifier key '{}' with direction: {:?}", key_name_clone, direction);
+
+                input.key(key, direction).map_err(|e| {
+                    error!("Failed to send modifier key '{}': {:?}", key_name_clone, e);
+                    format!("Failed to send modifier key '{}': {:?}", key_name_clone, e)
+                })?;
+                info!("Modifier key '{}' sent successfully", key_name_clone);
+                Ok(())
+            })
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    
+    // Update the state based on the result
+    {
+        let mut states = MODIFIER_KEY_STATES.lock().map_err(|e| {
+            error!("Failed to lock modifier key states: {:?}", e);
+            "Failed to update modifier key state".to_string()
+        })?;
+        
+        if result.is_err() {
+            // If the key operation failed, keep the old state
+            warn!("Key operation failed, keeping original state for '{}'", key_name);
+        } else {
+            // Update the state
+            states.insert(key_name.clone(), new_state);
+            
+            // Also handle aliases
+            match key_name.as_str() {
+                "alt" => {
+                    states.insert("option".to_string(), new_state);
+                },
+                "ctrl" => {
+                    states.insert("control".to_string(), new_state);
+                },
+                "cmd" => {
+                    states.insert("meta".to_string(), new_state);
+                },
+                _ => {}
+            }
+        }
+    }
+    
+    // Return appropriate response
+    match result {
+        Ok(_) => {
+            info!("Modifier key '{}' toggled to: {}", key_name, new_state);
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message: format!("Modifier key '{}' toggled to {}", key_name, new_state),
+            })
+        },
+        Err(e) => {
+            info!("Modifier key '{}' toggle failed: {}", key_name, e);
+            Err(e)
+        }
+    }
+}
+
+// Clear all modifier key states
+pub async fn clear_modifier_keys<B: InputBackend>() -> Result<CommandResponse, String> {
+    info!("Clearing all modifier key states");
+    
+    // Get the currently pressed keys before clearing
+    let pressed_keys: Vec<String> = {
+        let states = MODIFIER_KEY_STATES.lock().map_err(|e| {
+            error!("Failed to lock modifier key states: {:?}", e);
+            "Failed to clear modifier keys".to_string()
+        })?;
+        
+        states
+            .iter()
+            .filter(|(_, &pressed)| pressed)
+            .map(|(key, _)| key.clone())
+            .collect()
+    };
+    
+    // Clear the states
+    {
+        let mut states = MODIFIER_KEY_STATES.lock().map_err(|e| {
+            error!("Failed to lock modifier key states: {:?}", e);
+            "Failed to clear modifier keys".to_string()
+        })?;
+        states.clear();
+    }
+    
+    // Actually release any pressed modifier keys
+    if !pressed_keys.is_empty() {
+        info!("Releasing {} pressed modifier keys: {:?}", pressed_keys.len(), pressed_keys);
+        
+        let result: Result<(), String> = input_worker::run::<B, _, _>(move |input| {
+            for key_name in pressed_keys {
+                debug!("Releasing modifier key: {}", key_name);
+                
+                // Map the key name to the actual Key enum
+                let Some(key) = keymap::modifier_key(&key_name) else {
+                    warn!("Unknown modifier key: {}", key_name);
+                    continue; // Skip unknown keys
+                };
+                
+                // Release the key
+                match input.key(key, Release) {
+                    Ok(_) => {
+                        info!("Modifier key '{}' released successfully", key_name);
+                    },
+                    Err(e) => {
+                        error!("Failed to release modifier key '{}': {:?}", key_name, e);
+                    }
+                }
+            }
+            
+            Ok(())
+        })
+        .await;
+        
+        if let Err(e) = result {
+            error!("Failed to release some modifier keys: {}", e);
+            // Don't return error here, just log it since we've already cleared the states
+        }
+    }
+    
+    info!("All modifier key states cleared");
+    
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: "All modifier keys cleared".to_string(),
+    })
+}
+
+// Check accessibility permissions on macOS
+#[cfg(target_os = "macos")]
+pub fn check_accessibility_permissions() -> bool {
+    crate::macos_ax::is_trusted(false)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn check_accessibility_permissions() -> bool {
+    // On non-macOS platforms, assume permissions are available
+    true
+}
+
+// Test accessibility permissions
+pub async fn test_accessibility_permissions() -> Result<CommandResponse, String> {
+    info!("Testing accessibility permissions...");
+    
+    #[cfg(target_os = "macos")]
+    {
+        debug!("Running on macOS - checking accessibility permissions");
+        let has_permissions = check_accessibility_permissions();
+        
+        if has_permissions {
+            info!("Accessibility permissions granted");
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message: "Accessibility permissions granted".to_string(),
+            })
+        } else {
+            warn!("Accessibility permissions missing");
+            Err("Accessibility permissions missing. Please allow CouchCommander in System Settings > Privacy & Security > Accessibility.".to_string())
+        }
+    }
+    
+    #[cfg(not(target_os = "macos"))]
+    {
+        info!("Not running on macOS - accessibility permissions not required");
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Accessibility permissions not required on this platform".to_string(),
+        })
+    }
+}
+
+// Ask macOS for the accessibility grant and open the Settings pane to give it
+pub async fn request_accessibility_permissions() -> Result<CommandResponse, String> {
+    #[cfg(target_os = "macos")]
+    {
+        if crate::macos_ax::is_trusted(true) {
+            return Ok(CommandResponse {
+                status: "success".to_string(),
+                message: "Accessibility permissions already granted".to_string(),
+            });
+        }
+
+        crate::macos_ax::open_settings_pane()?;
+        Ok(CommandResponse {
+            status: "info".to_string(),
+            message: "Allow CouchCommander in the Accessibility list, then return to the app"
+                .to_string(),
+        })
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Accessibility permissions not required on this platform".to_string(),
+        })
+    }
+}
+
+// Simple test to check if Enigo can be created (permissions test)
+pub async fn test_enigo_creation<B: InputBackend>() -> Result<CommandResponse, String> {
+    debug!("=== TESTING ENIGO CREATION ===");
+    
+    tokio::task::spawn_blocking(move || {
+        debug!("Attempting to create Enigo instance...");
+        
+        match B::connect() {
+            Ok(_) => {
+                info!("✅ Enigo instance created successfully!");
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: "Enigo instance created successfully. Permissions appear to be working.".to_string(),
+                })
+            },
+            Err(e) => {
+                error!("❌ Failed to create Enigo instance: {}", e);
+                let error_msg = if cfg!(target_os = "macos") {
+                    format!("Failed to create Enigo instance: {}. This is likely due to missing accessibility permissions. Please check System Preferences > Security & Privacy > Privacy > Accessibility and ensure the app has permission.", e)
+                } else {
+                    format!("Failed to create Enigo instance: {}", e)
+                };
+                Err(error_msg)
+            }
+        }
+    })
+    .await
+    .map_err(|e| {
+        error!("Test task panicked: {:?}", e);
+        "Test operation failed".to_string()
+    })?
+}
+
+// Test function to try sending a space key (known working key type)
+pub async fn test_space_key<B: InputBackend>() -> Result<CommandResponse, String> {
+    debug!("=== TESTING SPACE KEY ===");
+    
+    debug!("Attempting to send space key...");
+    
+    input_worker::run::<B, _, _>(|input| {
+        input.key(Key::Space, Press).map_err(|e| {
+            error!("Failed to press space key: {:?}", e);
+            format!("Failed to press space key: {:?}", e)
+        })?;
+        info!("Space key press successful");
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Space key sent successfully".to_string(),
+        })
+    })
+    .await
+}
@@ -0,0 +1,256 @@
+use enigo::{
+    Axis, Button, Coordinate, Direction, Enigo, InputError, Key, Keyboard, Mouse, Settings,
+};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+
+#[cfg(target_os = "linux")]
+use crate::wayland::{self, YdotoolBackend};
+
+lazy_static::lazy_static! {
+    static ref RECORDED_EVENTS: Mutex<Vec<InputEvent>> = Mutex::new(Vec::new());
+}
+
+/// Where the input commands send keys, text and mouse events.
+///
+/// Connections live on the input thread, see [`crate::input_worker`], since OS
+/// input handles can't be shared across threads.
+pub trait InputBackend: Sized + 'static {
+    fn connect() -> Result<Self, String>;
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), InputError>;
+    fn text(&mut self, text: &str) -> Result<(), InputError>;
+    fn move_mouse(&mut self, x: i32, y: i32, coordinate: Coordinate) -> Result<(), InputError>;
+    fn button(&mut self, button: Button, direction: Direction) -> Result<(), InputError>;
+    fn scroll(&mut self, length: i32, axis: Axis) -> Result<(), InputError>;
+    /// Where the pointer is, in desktop coordinates.
+    fn location(&mut self) -> Result<(i32, i32), InputError>;
+}
+
+/// Injects real OS input through enigo.
+pub struct EnigoBackend(Enigo);
+
+impl InputBackend for EnigoBackend {
+    fn connect() -> Result<Self, String> {
+        Enigo::new(&Settings::default())
+            .map(EnigoBackend)
+            .map_err(|e| format!("Failed to create Enigo: {:?}", e))
+    }
+
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), InputError> {
+        self.0.key(key, direction)
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), InputError> {
+        self.0.text(text)
+    }
+
+    fn move_mouse(&mut self, x: i32, y: i32, coordinate: Coordinate) -> Result<(), InputError> {
+        self.0.move_mouse(x, y, coordinate)
+    }
+
+    fn button(&mut self, button: Button, direction: Direction) -> Result<(), InputError> {
+        self.0.button(button, direction)
+    }
+
+    fn scroll(&mut self, length: i32, axis: Axis) -> Result<(), InputError> {
+        self.0.scroll(length, axis)
+    }
+
+    fn location(&mut self) -> Result<(i32, i32), InputError> {
+        self.0.location()
+    }
+}
+
+/// Which backend [`SystemBackend`] picked and what it can do, reported to
+/// remotes so they can hide controls that won't work.
+#[derive(Debug, Clone, Serialize)]
+pub struct InputCapabilities {
+    pub backend: &'static str,
+    pub session: &'static str,
+    pub keyboard: bool,
+    pub text: bool,
+    pub mouse: bool,
+    pub absolute_pointer: bool,
+    // What send_raw_key codes mean here: "virtual_key" (Windows), "keycode"
+    // (macOS), "keysym" (X11) or "evdev" (Wayland)
+    pub raw_key_codes: &'static str,
+}
+
+pub(crate) fn command_available(name: &str) -> bool {
+    let Some(paths) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&paths).any(|dir| {
+        Path::new(&dir).join(name).is_file()
+            || (cfg!(windows) && Path::new(&dir).join(format!("{}.exe", name)).is_file())
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn use_ydotool() -> bool {
+    static USE_YDOTOOL: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *USE_YDOTOOL.get_or_init(|| {
+        let wayland = wayland::is_wayland_session();
+        tracing::info!(
+            "{} session detected, using {} for input",
+            if wayland { "Wayland" } else { "X11" },
+            if wayland { "ydotool" } else { "enigo" }
+        );
+        wayland
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn use_ydotool() -> bool {
+    false
+}
+
+pub fn capabilities() -> InputCapabilities {
+    if use_ydotool() {
+        let installed = command_available("ydotool");
+        return InputCapabilities {
+            backend: "ydotool",
+            session: "wayland",
+            keyboard: installed,
+            text: installed,
+            mouse: installed,
+            absolute_pointer: false,
+            raw_key_codes: "evdev",
+        };
+    }
+
+    InputCapabilities {
+        backend: "enigo",
+        session: if cfg!(target_os = "linux") {
+            "x11"
+        } else {
+            "native"
+        },
+        keyboard: true,
+        text: true,
+        mouse: true,
+        absolute_pointer: true,
+        raw_key_codes: if cfg!(target_os = "windows") {
+            "virtual_key"
+        } else if cfg!(target_os = "macos") {
+            "keycode"
+        } else {
+            "keysym"
+        },
+    }
+}
+
+/// The backend for the current session: ydotool on Wayland, enigo elsewhere.
+pub enum SystemBackend {
+    Enigo(EnigoBackend),
+    #[cfg(target_os = "linux")]
+    Ydotool(YdotoolBackend),
+}
+
+// Forward a call to whichever backend was picked
+macro_rules! dispatch {
+    ($self:ident, $backend:ident => $call:expr) => {
+        match $self {
+            SystemBackend::Enigo($backend) => $call,
+            #[cfg(target_os = "linux")]
+            SystemBackend::Ydotool($backend) => $call,
+        }
+    };
+}
+
+impl InputBackend for SystemBackend {
+    fn connect() -> Result<Self, String> {
+        #[cfg(target_os = "linux")]
+        if use_ydotool() {
+            return YdotoolBackend::connect().map(SystemBackend::Ydotool);
+        }
+        EnigoBackend::connect().map(SystemBackend::Enigo)
+    }
+
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), InputError> {
+        dispatch!(self, backend => backend.key(key, direction))
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), InputError> {
+        dispatch!(self, backend => backend.text(text))
+    }
+
+    fn move_mouse(&mut self, x: i32, y: i32, coordinate: Coordinate) -> Result<(), InputError> {
+        dispatch!(self, backend => backend.move_mouse(x, y, coordinate))
+    }
+
+    fn button(&mut self, button: Button, direction: Direction) -> Result<(), InputError> {
+        dispatch!(self, backend => backend.button(button, direction))
+    }
+
+    fn scroll(&mut self, length: i32, axis: Axis) -> Result<(), InputError> {
+        dispatch!(self, backend => backend.scroll(length, axis))
+    }
+
+    fn location(&mut self) -> Result<(i32, i32), InputError> {
+        dispatch!(self, backend => backend.location())
+    }
+}
+
+/// An input event captured by [`RecordingBackend`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    Key(Key, Direction),
+    Text(String),
+    MoveMouse(i32, i32, Coordinate),
+    Button(Button, Direction),
+    Scroll(i32, Axis),
+}
+
+/// Records input instead of sending it, so commands can be exercised in CI.
+///
+/// Every connection appends to one shared log; tests using it should not run
+/// in parallel with each other.
+pub struct RecordingBackend;
+
+impl RecordingBackend {
+    /// Drain and return everything recorded so far.
+    pub fn take_events() -> Vec<InputEvent> {
+        std::mem::take(&mut *RECORDED_EVENTS.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn record(event: InputEvent) -> Result<(), InputError> {
+        RECORDED_EVENTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(event);
+        Ok(())
+    }
+}
+
+impl InputBackend for RecordingBackend {
+    fn connect() -> Result<Self, String> {
+        Ok(RecordingBackend)
+    }
+
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), InputError> {
+        Self::record(InputEvent::Key(key, direction))
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), InputError> {
+        Self::record(InputEvent::Text(text.to_string()))
+    }
+
+    fn move_mouse(&mut self, x: i32, y: i32, coordinate: Coordinate) -> Result<(), InputError> {
+        Self::record(InputEvent::MoveMouse(x, y, coordinate))
+    }
+
+    fn button(&mut self, button: Button, direction: Direction) -> Result<(), InputError> {
+        Self::record(InputEvent::Button(button, direction))
+    }
+
+    fn scroll(&mut self, length: i32, axis: Axis) -> Result<(), InputError> {
+        Self::record(InputEvent::Scroll(length, axis))
+    }
+
+    // Nothing really moves, the pointer stays at the origin
+    fn location(&mut self) -> Result<(i32, i32), InputError> {
+        Ok((0, 0))
+    }
+}
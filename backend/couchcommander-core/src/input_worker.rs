@@ -0,0 +1,205 @@
+//! The one thread that injects input. Commands hand it a closure and await
+//! the result; it keeps a connection per backend open between commands and
+//! runs them one at a time, so keystrokes from different remotes never
+//! interleave.
+//!
+//! A closure that fails or panics drops the connection, and the next command
+//! reconnects. If the thread itself dies it's started again on the next
+//! command. How that's going is reported in `get_server_status`.
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tracing::{error, info, warn};
+
+use crate::input_backend::{self, InputBackend};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// How input injection is doing, for the host to spot a backend that keeps
+/// failing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputHealth {
+    pub backend: String,
+    // The last command got through
+    pub healthy: bool,
+    // Connections dropped after a failure
+    pub restarts: u64,
+    pub last_error: Option<String>,
+}
+
+static WORKER: Mutex<Option<Sender<Job>>> = Mutex::new(None);
+
+static HEALTH: Mutex<Health> = Mutex::new(Health {
+    healthy: true,
+    restarts: 0,
+    last_error: None,
+});
+
+struct Health {
+    healthy: bool,
+    restarts: u64,
+    last_error: Option<String>,
+}
+
+thread_local! {
+    // Input connections of the worker thread, one per backend type
+    static BACKENDS: RefCell<HashMap<TypeId, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+pub fn health() -> InputHealth {
+    let health = HEALTH.lock().unwrap_or_else(|e| e.into_inner());
+    InputHealth {
+        backend: input_backend::capabilities().backend.to_string(),
+        healthy: health.healthy,
+        restarts: health.restarts,
+        last_error: health.last_error.clone(),
+    }
+}
+
+fn record(result: Result<(), &str>, restarted: bool) {
+    let mut health = HEALTH.lock().unwrap_or_else(|e| e.into_inner());
+    health.healthy = result.is_ok();
+    if restarted {
+        health.restarts += 1;
+    }
+    if let Err(e) = result {
+        health.last_error = Some(e.to_string());
+    }
+}
+
+fn spawn() -> Result<Sender<Job>, String> {
+    let (tx, rx) = mpsc::channel::<Job>();
+    std::thread::Builder::new()
+        .name("input".to_string())
+        .spawn(move || {
+            for job in rx {
+                job();
+            }
+        })
+        .map_err(|e| format!("Failed to start the input thread: {}", e))?;
+    Ok(tx)
+}
+
+fn submit(job: Job) -> Result<(), String> {
+    let mut worker = WORKER.lock().unwrap_or_else(|e| e.into_inner());
+    let job = match worker.as_ref() {
+        Some(tx) => match tx.send(job) {
+            Ok(()) => return Ok(()),
+            // The thread is gone, the job comes back with the error
+            Err(mpsc::SendError(job)) => {
+                warn!("Input thread stopped, starting it again");
+                record(Err("Input thread stopped"), true);
+                job
+            }
+        },
+        None => job,
+    };
+    let tx = spawn()?;
+    tx.send(job)
+        .map_err(|_| "Input thread stopped".to_string())?;
+    *worker = Some(tx);
+    Ok(())
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+// Run `f` on this thread's connection for `B`, connecting first if needed
+fn run_here<B, T>(f: impl FnOnce(&mut B) -> Result<T, String>) -> Result<T, String>
+where
+    B: InputBackend,
+{
+    BACKENDS.with(|backends| {
+        let mut backends = backends.borrow_mut();
+        let id = TypeId::of::<B>();
+        let backend = match backends.entry(id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match B::connect() {
+                Ok(backend) => entry.insert(Box::new(backend)),
+                Err(e) => {
+                    record(Err(&e), false);
+                    return Err(e);
+                }
+            },
+        };
+        let backend = backend
+            .downcast_mut::<B>()
+            .ok_or_else(|| "Input connection has the wrong type".to_string())?;
+        let result = match panic::catch_unwind(AssertUnwindSafe(|| f(backend))) {
+            Ok(result) => result,
+            Err(panic) => {
+                let message = panic_message(panic.as_ref());
+                error!("Input backend panicked: {}", message);
+                Err(format!("Input backend panicked: {}", message))
+            }
+        };
+        match &result {
+            Ok(_) => record(Ok(()), false),
+            Err(e) => {
+                // The connection may be what failed, start over with a new one
+                info!("Reconnecting input backend after: {}", e);
+                backends.remove(&id);
+                record(Err(e), true);
+            }
+        }
+        result
+    })
+}
+
+/// Run `f` with the input connection for `B` on the input thread.
+pub async fn run<B, T, F>(f: F) -> Result<T, String>
+where
+    B: InputBackend,
+    T: Send + 'static,
+    F: FnOnce(&mut B) -> Result<T, String> + Send + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+    submit(Box::new(move || {
+        let _ = tx.send(run_here::<B, T>(f));
+    }))?;
+    rx.await
+        .map_err(|_| "Input thread stopped before finishing".to_string())?
+}
+
+/// [`run`] for blocking code, e.g. between registry calls in
+/// `spawn_blocking`. Calling it from inside a closure on the input thread
+/// would wait on itself forever.
+pub fn run_blocking<B, T, F>(f: F) -> Result<T, String>
+where
+    B: InputBackend,
+    T: Send + 'static,
+    F: FnOnce(&mut B) -> Result<T, String> + Send + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+    submit(Box::new(move || {
+        let _ = tx.send(run_here::<B, T>(f));
+    }))?;
+    rx.blocking_recv()
+        .map_err(|_| "Input thread stopped before finishing".to_string())?
+}
+
+/// Queue `f` without waiting for it, logging a failure.
+pub fn queue<B, F>(f: F) -> Result<(), String>
+where
+    B: InputBackend,
+    F: FnOnce(&mut B) -> Result<(), String> + Send + 'static,
+{
+    submit(Box::new(move || {
+        if let Err(e) = run_here::<B, ()>(f) {
+            warn!("Queued input failed: {}", e);
+        }
+    }))
+}
@@ -0,0 +1,68 @@
+use enigo::Key;
+use tracing::{debug, warn};
+
+use crate::config;
+
+/// Apply the user-defined key names from the settings file, e.g. "rewind" = "j".
+pub fn resolve_alias(key_name: String) -> String {
+    match config::current().keymaps.get(&key_name.to_lowercase()) {
+        Some(mapped) => {
+            debug!("Keymap resolved '{}' to '{}'", key_name, mapped);
+            mapped.clone()
+        }
+        None => key_name,
+    }
+}
+
+/// Map a `send_key` key name to the key to press. Any other single character
+/// is sent as a `Key::Unicode`.
+pub fn parse_key(key_name: &str) -> Result<Key, String> {
+    let name = key_name.to_lowercase();
+    let key = match name.as_str() {
+        "space" => Key::Space,
+        "enter" | "return" => Key::Return,
+        "escape" | "esc" => Key::Escape,
+        "up" => Key::UpArrow,
+        "down" => Key::DownArrow,
+        "left" => Key::LeftArrow,
+        "right" => Key::RightArrow,
+        "backspace" => Key::Backspace,
+        "tab" => Key::Tab,
+        "shift" => Key::Shift,
+        "ctrl" | "control" => Key::Control,
+        "alt" => Key::Alt,
+        "cmd" | "meta" => Key::Meta,
+        "f1" => Key::F1,
+        "f2" => Key::F2,
+        "f3" => Key::F3,
+        "f4" => Key::F4,
+        "f5" => Key::F5,
+        "f6" => Key::F6,
+        "f7" => Key::F7,
+        "f8" => Key::F8,
+        "f9" => Key::F9,
+        "f10" => Key::F10,
+        "f11" => Key::F11,
+        "f12" => Key::F12,
+        // Single character keys, letters are sent lowercase. Counted in chars
+        // so "é" or "ß" from AZERTY and QWERTZ keyboards work too
+        _ if name.chars().count() == 1 => Key::Unicode(name.chars().next().unwrap_or(' ')),
+        _ => {
+            warn!("Unknown key: '{}'", key_name);
+            return Err(format!("Unknown key: {}", key_name));
+        }
+    };
+    debug!("Mapped '{}' to {:?}", key_name, key);
+    Ok(key)
+}
+
+/// Key for a name accepted by `toggle_modifier_key`.
+pub fn modifier_key(key_name: &str) -> Option<Key> {
+    match key_name.to_lowercase().as_str() {
+        "shift" => Some(Key::Shift),
+        "ctrl" | "control" => Some(Key::Control),
+        "alt" | "option" => Some(Key::Alt),
+        "cmd" | "meta" => Some(Key::Meta),
+        _ => None,
+    }
+}
@@ -0,0 +1,146 @@
+//! Host keyboard layout detection. Typing through `text()` is layout
+//! independent, but anything that presses physical keys (ydotool's evdev
+//! codes, shortcuts) needs to know where letters sit on AZERTY and QWERTZ.
+
+use serde::Serialize;
+use tracing::debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutFamily {
+    Qwerty,
+    Azerty,
+    Qwertz,
+    Other,
+}
+
+/// The host's active layout. `id` is whatever the platform calls it, e.g.
+/// "de" from xkb, "com.apple.keylayout.German" or the Windows KLID "00000407".
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyboardLayout {
+    pub id: String,
+    pub family: LayoutFamily,
+}
+
+// xkb layout names and Windows KLIDs
+const AZERTY_IDS: &[&str] = &["fr", "be", "0000040c", "0000080c"];
+const QWERTZ_IDS: &[&str] = &[
+    "de", "at", "ch", "cz", "sk", "hu", "si", "hr", "00000407", "00000807", "0000100c", "00000405",
+    "0000041b", "0000040e", "00000424",
+];
+const QWERTY_IDS: &[&str] = &["us", "gb", "00000409", "00000809"];
+// macOS names layouts after the language, e.g. "com.apple.keylayout.German"
+const AZERTY_NAMES: &[&str] = &["french", "belgian"];
+const QWERTZ_NAMES: &[&str] = &[
+    "german",
+    "swiss",
+    "austrian",
+    "czech",
+    "slovak",
+    "hungarian",
+];
+const QWERTY_NAMES: &[&str] = &["us", "abc", "british"];
+
+fn family(id: &str) -> LayoutFamily {
+    let id = id.to_lowercase();
+    let name = id.rsplit('.').next().unwrap_or(&id);
+    let matches = |ids: &[&str], names: &[&str]| {
+        ids.contains(&id.as_str()) || names.iter().any(|prefix| name.starts_with(prefix))
+    };
+    if matches(AZERTY_IDS, AZERTY_NAMES) {
+        LayoutFamily::Azerty
+    } else if matches(QWERTZ_IDS, QWERTZ_NAMES) {
+        LayoutFamily::Qwertz
+    } else if matches(QWERTY_IDS, QWERTY_NAMES) {
+        LayoutFamily::Qwerty
+    } else {
+        LayoutFamily::Other
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Value after "key:" on the first matching line of `output`
+#[cfg(target_os = "linux")]
+fn field(output: &str, key: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let value = line.trim().strip_prefix(key)?.trim();
+        // Only the first of several configured layouts is active by default
+        let first = value.split(',').next()?.trim();
+        (!first.is_empty()).then(|| first.to_string())
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn detect_id() -> Option<String> {
+    command_output("setxkbmap", &["-query"])
+        .and_then(|output| field(&output, "layout:"))
+        .or_else(|| {
+            command_output("localectl", &["status"])
+                .and_then(|output| field(&output, "X11 Layout:"))
+        })
+        .or_else(|| std::env::var("XKB_DEFAULT_LAYOUT").ok())
+}
+
+#[cfg(target_os = "macos")]
+fn detect_id() -> Option<String> {
+    let output = command_output(
+        "defaults",
+        &[
+            "read",
+            "com.apple.HIToolbox",
+            "AppleCurrentKeyboardLayoutInputSourceID",
+        ],
+    )?;
+    let id = output.trim();
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn detect_id() -> Option<String> {
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetKeyboardLayoutNameW(name: *mut u16) -> i32;
+    }
+
+    // KL_NAMELENGTH, eight hex digits plus the terminator
+    let mut name = [0u16; 9];
+    if unsafe { GetKeyboardLayoutNameW(name.as_mut_ptr()) } == 0 {
+        return None;
+    }
+    let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+    Some(String::from_utf16_lossy(&name[..len]))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn detect_id() -> Option<String> {
+    None
+}
+
+/// Look up the layout now, "unknown" when the platform won't say.
+pub fn detect() -> KeyboardLayout {
+    let id = detect_id().unwrap_or_else(|| "unknown".to_string());
+    let layout = KeyboardLayout {
+        family: family(&id),
+        id,
+    };
+    debug!("Detected keyboard layout {:?}", layout);
+    layout
+}
+
+/// The layout family seen on first use, for ydotool's key mapping.
+#[cfg(target_os = "linux")]
+pub fn current_family() -> LayoutFamily {
+    static FAMILY: std::sync::OnceLock<LayoutFamily> = std::sync::OnceLock::new();
+    *FAMILY.get_or_init(|| detect().family)
+}
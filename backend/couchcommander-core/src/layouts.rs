@@ -0,0 +1,77 @@
+//! Remote layouts suggested for the application in front, so the web remote
+//! can switch screens on its own: seek buttons for a video player, tabs and
+//! scrolling for a browser, slide keys for a presentation. Remotes
+//! subscribed to the `context` topic get a `context_changed` event whenever
+//! another app comes to the front.
+//!
+//! `app_layouts` in `settings.toml` maps apps to layouts and is checked
+//! before the built-in list. A key matches an app when its id or name
+//! contains it, ignoring case.
+
+use serde::Serialize;
+
+use crate::config;
+use crate::frontmost::FrontmostApp;
+
+/// Layout for apps nothing matches.
+pub const DEFAULT_LAYOUT: &str = "default";
+
+const BUILT_IN: &[(&str, &str)] = &[
+    ("vlc", "video_player"),
+    ("mpv", "video_player"),
+    ("iina", "video_player"),
+    ("quicktime", "video_player"),
+    ("plex", "video_player"),
+    ("jellyfin", "video_player"),
+    ("kodi", "video_player"),
+    ("spotify", "music_player"),
+    ("com.apple.music", "music_player"),
+    ("rhythmbox", "music_player"),
+    ("powerpnt", "presentation"),
+    ("powerpoint", "presentation"),
+    ("keynote", "presentation"),
+    ("impress", "presentation"),
+    ("chrome", "browser"),
+    ("firefox", "browser"),
+    ("safari", "browser"),
+    ("msedge", "browser"),
+    ("brave", "browser"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Context {
+    pub event: &'static str,
+    pub layout: String,
+    // Name and id of the app, see `FrontmostApp`. Its window title is left
+    // out so switching tabs isn't a new context
+    pub app: String,
+    pub app_id: String,
+}
+
+fn matches(app: &FrontmostApp, key: &str) -> bool {
+    let key = key.to_lowercase();
+    app.id.to_lowercase().contains(&key) || app.name.to_lowercase().contains(&key)
+}
+
+/// The layout suggested while `app` is in front.
+pub fn layout_for(app: &FrontmostApp) -> String {
+    let settings = config::current();
+    settings
+        .app_layouts
+        .iter()
+        .map(|(key, layout)| (key.as_str(), layout.as_str()))
+        .chain(BUILT_IN.iter().copied())
+        .find(|(key, _)| matches(app, key))
+        .map_or(DEFAULT_LAYOUT, |(_, layout)| layout)
+        .to_string()
+}
+
+/// The `context_changed` event for `app`.
+pub fn context(app: &FrontmostApp) -> Context {
+    Context {
+        event: "context_changed",
+        layout: layout_for(app),
+        app: app.name.clone(),
+        app_id: app.id.clone(),
+    }
+}
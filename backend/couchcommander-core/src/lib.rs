@@ -0,0 +1,95 @@
+//! Input injection, the remote protocol and the embedded server behind
+//! CouchCommander, with no dependency on Tauri. The desktop app and the
+//! headless mode are thin frontends over this crate.
+
+pub mod accessibility;
+pub mod aliases;
+pub mod api;
+pub mod applescript;
+#[cfg(target_os = "linux")]
+pub mod audio;
+pub mod battery;
+pub mod bluetooth;
+pub mod browser;
+pub mod cast;
+pub mod chromecast;
+pub mod clipboard;
+pub mod clock;
+pub mod color_picker;
+pub mod config;
+pub mod control;
+pub mod desktops;
+pub mod devices;
+pub mod diagnostics;
+pub mod discovery;
+pub mod display;
+pub mod display_content;
+pub mod dlna;
+pub mod events;
+pub mod files;
+pub mod forwarding;
+pub mod frontmost;
+pub mod game_keys;
+pub mod gamepad;
+pub mod hardware;
+pub mod http_client;
+pub mod i18n;
+pub mod idempotency;
+pub mod idle;
+pub mod input;
+pub mod input_backend;
+pub mod input_worker;
+pub mod keymap;
+pub mod layout;
+pub mod layouts;
+pub mod logging;
+#[cfg(target_os = "macos")]
+mod macos_ax;
+pub mod media_server;
+pub mod metrics;
+pub mod microphone;
+#[cfg(target_os = "linux")]
+pub mod mpris;
+pub mod network;
+pub mod notifications;
+pub mod pairing;
+pub mod port_mapping;
+pub mod privacy;
+pub mod protocol;
+pub mod qr;
+pub mod relay;
+pub mod repeat;
+pub mod schedule;
+pub mod secrets;
+pub mod secure_input;
+pub mod self_test;
+pub mod server;
+pub mod session;
+pub mod share;
+pub mod shortcuts;
+pub mod signing;
+pub mod speech;
+pub mod stats;
+pub mod text_stream;
+pub mod tls;
+pub mod upload;
+pub mod upnp;
+pub mod url_policy;
+pub mod volume_fade;
+#[cfg(target_os = "linux")]
+pub mod wayland;
+pub mod web;
+pub mod websocket;
+pub mod wifi;
+pub mod wol;
+pub mod zoom;
+#[cfg(target_os = "windows")]
+mod windows_brightness;
+#[cfg(target_os = "windows")]
+mod windows_com;
+#[cfg(target_os = "windows")]
+mod windows_mic;
+#[cfg(target_os = "windows")]
+mod windows_uia;
+
+pub use protocol::{CommandResponse, ServerStatus};
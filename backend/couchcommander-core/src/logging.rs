@@ -0,0 +1,102 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+// Number of formatted log lines kept in memory for the diagnostics panel
+const RECENT_LOG_CAPACITY: usize = 500;
+const LOG_FILE_PREFIX: &str = "couchcommander.log";
+// Days of log files kept before the oldest is deleted
+const MAX_LOG_FILES: usize = 14;
+const DEFAULT_FILTER: &str = "info,backend_lib=debug,couchcommander_core=debug";
+
+lazy_static::lazy_static! {
+    static ref RECENT_LOGS: Mutex<VecDeque<String>> =
+        Mutex::new(VecDeque::with_capacity(RECENT_LOG_CAPACITY));
+}
+
+// Keeps the non-blocking file writer flushing for the lifetime of the process
+static FILE_GUARD: OnceLock<WorkerGuard> = OnceLock::new();
+
+// Writer that stores each formatted event in the in-memory ring buffer.
+// The fmt layer writes a whole event per call, so one write is one line.
+struct RecentLogWriter;
+
+impl Write for RecentLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let line = String::from_utf8_lossy(buf).trim_end().to_string();
+        if !line.is_empty() {
+            if let Ok(mut logs) = RECENT_LOGS.lock() {
+                if logs.len() >= RECENT_LOG_CAPACITY {
+                    logs.pop_front();
+                }
+                logs.push_back(line);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Install the global tracing subscriber: stdout, the recent-log buffer and,
+/// when a directory is given, a daily rotating log file inside it, keeping
+/// the last `MAX_LOG_FILES` days.
+/// The level can be overridden with `RUST_LOG`.
+pub fn init(log_dir: Option<&Path>) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+
+    let file_layer = log_dir.and_then(|dir| {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("Failed to create log directory {:?}: {}", dir, e);
+            return None;
+        }
+        let appender = match RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(LOG_FILE_PREFIX)
+            .max_log_files(MAX_LOG_FILES)
+            .build(dir)
+        {
+            Ok(appender) => appender,
+            Err(e) => {
+                eprintln!("Failed to open log file in {:?}: {}", dir, e);
+                return None;
+            }
+        };
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+        let _ = FILE_GUARD.set(guard);
+        Some(fmt::layer().with_ansi(false).with_writer(writer))
+    });
+
+    let result = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(|| RecentLogWriter),
+        )
+        .with(file_layer)
+        .try_init();
+
+    if let Err(e) = result {
+        eprintln!("Logging already initialized: {}", e);
+    }
+}
+
+/// Most recent log lines, oldest first, capped to `limit` entries.
+pub fn recent_logs(limit: usize) -> Vec<String> {
+    match RECENT_LOGS.lock() {
+        Ok(logs) => {
+            let skip = logs.len().saturating_sub(limit);
+            logs.iter().skip(skip).cloned().collect()
+        }
+        Err(_) => Vec::new(),
+    }
+}
@@ -0,0 +1,132 @@
+//! macOS only lets trusted processes post keyboard and mouse events. Without
+//! the grant enigo still "succeeds" but the events are silently dropped.
+//! The same grant lets us read which UI element has focus. Announcements for
+//! VoiceOver go through AppKit's accessibility notifications.
+
+use core_foundation::base::{CFType, CFTypeRef, TCFType};
+use core_foundation::boolean::CFBoolean;
+use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+use core_foundation::number::CFNumber;
+use core_foundation::string::{CFString, CFStringRef};
+use std::ffi::{c_char, c_void};
+
+// Privacy & Security > Accessibility in System Settings
+const SETTINGS_PANE_URL: &str =
+    "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility";
+
+const SECURE_TEXT_FIELD: &str = "AXSecureTextField";
+// NSAccessibilityPriorityHigh, interrupts whatever VoiceOver is reading
+const ANNOUNCEMENT_PRIORITY_HIGH: i64 = 90;
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    static kAXTrustedCheckOptionPrompt: CFStringRef;
+    fn AXIsProcessTrustedWithOptions(options: CFDictionaryRef) -> bool;
+    fn AXUIElementCreateSystemWide() -> CFTypeRef;
+    fn AXUIElementCopyAttributeValue(
+        element: CFTypeRef,
+        attribute: CFStringRef,
+        value: *mut CFTypeRef,
+    ) -> i32;
+}
+
+// NSString constants are toll-free bridged to CFString
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {
+    static NSAccessibilityAnnouncementRequestedNotification: CFStringRef;
+    static NSAccessibilityAnnouncementKey: CFStringRef;
+    static NSAccessibilityPriorityKey: CFStringRef;
+    fn NSAccessibilityPostNotificationWithUserInfo(
+        element: *mut c_void,
+        notification: CFStringRef,
+        user_info: CFDictionaryRef,
+    );
+}
+
+#[link(name = "objc")]
+extern "C" {
+    fn objc_getClass(name: *const c_char) -> *mut c_void;
+    fn sel_registerName(name: *const c_char) -> *mut c_void;
+    // Declared for the one call made, one without arguments
+    fn objc_msgSend(receiver: *mut c_void, selector: *mut c_void) -> *mut c_void;
+}
+
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    // On while a password field anywhere has keyboard focus
+    fn IsSecureEventInputEnabled() -> u8;
+}
+
+/// Whether this process may inject input. With `prompt` set, macOS shows its
+/// own dialog offering to open System Settings when it may not.
+pub fn is_trusted(prompt: bool) -> bool {
+    let key = unsafe { CFString::wrap_under_get_rule(kAXTrustedCheckOptionPrompt) };
+    let options =
+        CFDictionary::from_CFType_pairs(&[(key.as_CFType(), CFBoolean::from(prompt).as_CFType())]);
+    unsafe { AXIsProcessTrustedWithOptions(options.as_concrete_TypeRef()) }
+}
+
+fn copy_attribute(element: &CFType, name: &str) -> Option<CFType> {
+    let attribute = CFString::new(name);
+    let mut value: CFTypeRef = std::ptr::null();
+    let error = unsafe {
+        AXUIElementCopyAttributeValue(
+            element.as_CFTypeRef(),
+            attribute.as_concrete_TypeRef(),
+            &mut value,
+        )
+    };
+    (error == 0 && !value.is_null()).then(|| unsafe { CFType::wrap_under_create_rule(value) })
+}
+
+/// Whether the focused UI element is a password field.
+pub fn secure_field_focused() -> bool {
+    if unsafe { IsSecureEventInputEnabled() } != 0 {
+        return true;
+    }
+    let system = unsafe { CFType::wrap_under_create_rule(AXUIElementCreateSystemWide()) };
+    copy_attribute(&system, "AXFocusedUIElement")
+        .and_then(|focused| copy_attribute(&focused, "AXSubrole"))
+        .and_then(|subrole| subrole.downcast::<CFString>())
+        .is_some_and(|subrole| subrole.to_string() == SECURE_TEXT_FIELD)
+}
+
+pub fn open_settings_pane() -> Result<(), String> {
+    std::process::Command::new("open")
+        .arg(SETTINGS_PANE_URL)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to open System Settings: {}", e))
+}
+
+/// Have VoiceOver read `text` out, posted on behalf of the app.
+pub fn announce(text: &str) -> Result<(), String> {
+    let app = unsafe {
+        let class = objc_getClass(c"NSApplication".as_ptr());
+        objc_msgSend(class, sel_registerName(c"sharedApplication".as_ptr()))
+    };
+    if app.is_null() {
+        return Err("Failed to reach the application for the announcement".to_string());
+    }
+    let (announcement, priority) = unsafe {
+        (
+            CFString::wrap_under_get_rule(NSAccessibilityAnnouncementKey),
+            CFString::wrap_under_get_rule(NSAccessibilityPriorityKey),
+        )
+    };
+    let info = CFDictionary::from_CFType_pairs(&[
+        (announcement.as_CFType(), CFString::new(text).as_CFType()),
+        (
+            priority.as_CFType(),
+            CFNumber::from(ANNOUNCEMENT_PRIORITY_HIGH).as_CFType(),
+        ),
+    ]);
+    unsafe {
+        NSAccessibilityPostNotificationWithUserInfo(
+            app,
+            NSAccessibilityAnnouncementRequestedNotification,
+            info.as_concrete_TypeRef(),
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,644 @@
+//! Media server adapters. With `[media_server]` set up in the settings,
+//! media commands go to Plex, Jellyfin or Kodi over their HTTP APIs, which
+//! reaches the player whatever window has focus, and remotes can browse the
+//! libraries and the continue-watching list. When nothing is playing on the
+//! server or it can't be reached, MPRIS and key presses take over as usual.
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::atomic::AtomicU64;
+use tracing::{debug, warn};
+
+use crate::config::{self, MediaServerKind, MediaServerSettings};
+use crate::http_client;
+use crate::protocol::CommandResponse;
+
+const MAX_ITEMS: usize = 100;
+// Jellyfin counts time in 100 ns ticks
+const TICKS_PER_SECOND: f64 = 10_000_000.0;
+
+// Plex wants an increasing id on every player command
+static PLEX_COMMAND_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaAction {
+    PlayPause,
+    Next,
+    Previous,
+    Stop,
+    // Seconds, negative to rewind
+    Seek(f64),
+}
+
+/// An entry in a library listing. Passing `id` back as the parent lists
+/// what's inside it.
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaItem {
+    pub id: String,
+    pub title: String,
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subtitle: Option<String>,
+}
+
+fn settings() -> Option<MediaServerSettings> {
+    let settings = config::current().media_server;
+    (settings.kind != MediaServerKind::None).then_some(settings)
+}
+
+fn required_settings() -> Result<MediaServerSettings, String> {
+    settings().ok_or_else(|| "No media server is set up in settings".to_string())
+}
+
+fn text(value: &Value) -> Option<String> {
+    value
+        .as_str()
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+// Ids end up in URL paths and queries
+fn check_id(id: &str) -> Result<(), String> {
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ':' | '-' | '_'));
+    if !valid {
+        return Err(format!("Invalid library id: {}", id));
+    }
+    Ok(())
+}
+
+async fn call(
+    settings: &MediaServerSettings,
+    method: &str,
+    path: &str,
+    extra_headers: &[(&str, &str)],
+    body: Option<Value>,
+) -> Result<Value, String> {
+    let url = format!("{}{}", settings.url.trim_end_matches('/'), path);
+    let authorization;
+    let mut headers: Vec<(&str, &str)> = vec![("Accept", "application/json")];
+    match settings.kind {
+        MediaServerKind::Plex => {
+            headers.push(("X-Plex-Token", &settings.token));
+            headers.push(("X-Plex-Client-Identifier", "couchcommander"));
+        }
+        MediaServerKind::Jellyfin => headers.push(("X-Emby-Token", &settings.token)),
+        MediaServerKind::Kodi => {
+            headers.push(("Content-Type", "application/json"));
+            if !settings.token.is_empty() {
+                authorization = format!(
+                    "Basic {}",
+                    general_purpose::STANDARD.encode(&settings.token)
+                );
+                headers.push(("Authorization", &authorization));
+            }
+        }
+        MediaServerKind::None => {}
+    }
+    headers.extend_from_slice(extra_headers);
+
+    let body = body.map(|body| body.to_string());
+    let response = http_client::request(method, &url, &headers, body.as_deref()).await?;
+    if !(200..300).contains(&response.status) {
+        return Err(format!(
+            "{:?} answered with HTTP {}",
+            settings.kind, response.status
+        ));
+    }
+    if response.body.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(&response.body)
+        .map_err(|e| format!("Unexpected reply from {:?}: {}", settings.kind, e))
+}
+
+mod plex {
+    use serde_json::{json, Value};
+    use std::sync::atomic::Ordering;
+
+    use super::{call, text, MediaAction, MediaItem, MAX_ITEMS, PLEX_COMMAND_ID};
+    use crate::config::MediaServerSettings;
+
+    fn metadata(reply: &Value) -> &[Value] {
+        reply["MediaContainer"]["Metadata"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    fn item(metadata: &Value) -> MediaItem {
+        MediaItem {
+            id: text(&metadata["ratingKey"]).unwrap_or_default(),
+            title: text(&metadata["title"]).unwrap_or_default(),
+            kind: text(&metadata["type"]).unwrap_or_default(),
+            subtitle: text(&metadata["grandparentTitle"])
+                .or_else(|| text(&metadata["parentTitle"])),
+        }
+    }
+
+    async fn session(settings: &MediaServerSettings) -> Result<Option<Value>, String> {
+        let reply = call(settings, "GET", "/status/sessions", &[], None).await?;
+        Ok(metadata(&reply).first().cloned())
+    }
+
+    pub async fn control(
+        settings: &MediaServerSettings,
+        action: MediaAction,
+    ) -> Result<bool, String> {
+        let Some(session) = session(settings).await? else {
+            return Ok(false);
+        };
+        let Some(client) = text(&session["Player"]["machineIdentifier"]) else {
+            return Ok(false);
+        };
+        let media_type = match session["type"].as_str() {
+            Some("track") => "music",
+            Some("photo") => "photo",
+            _ => "video",
+        };
+        // Seeks go to an absolute position in milliseconds
+        let (command, seek_to) = match action {
+            MediaAction::PlayPause if session["Player"]["state"] == "paused" => ("play", None),
+            MediaAction::PlayPause => ("pause", None),
+            MediaAction::Next => ("skipNext", None),
+            MediaAction::Previous => ("skipPrevious", None),
+            MediaAction::Stop => ("stop", None),
+            MediaAction::Seek(offset) => {
+                let position = session["viewOffset"].as_f64().unwrap_or(0.0);
+                ("seekTo", Some((position + offset * 1000.0).max(0.0) as u64))
+            }
+        };
+        let mut path = format!(
+            "/player/playback/{}?type={}&commandID={}",
+            command,
+            media_type,
+            PLEX_COMMAND_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        if let Some(offset) = seek_to {
+            path.push_str(&format!("&offset={}", offset));
+        }
+        call(
+            settings,
+            "GET",
+            &path,
+            &[("X-Plex-Target-Client-Identifier", &client)],
+            None,
+        )
+        .await?;
+        Ok(true)
+    }
+
+    pub async fn now_playing(settings: &MediaServerSettings) -> Result<Value, String> {
+        let Some(session) = session(settings).await? else {
+            return Ok(Value::Null);
+        };
+        let status = match session["Player"]["state"].as_str() {
+            Some("paused") => "Paused",
+            Some("stopped") => "Stopped",
+            _ => "Playing",
+        };
+        Ok(json!({
+            "player": "plex",
+            "status": status,
+            "title": text(&session["title"]),
+            "artist": text(&session["grandparentTitle"]),
+            "album": text(&session["parentTitle"]),
+            "length": session["duration"].as_f64().map(|ms| ms / 1000.0),
+            "position": session["viewOffset"].as_f64().map(|ms| ms / 1000.0),
+        }))
+    }
+
+    // Libraries are "section:<key>", anything else is an item with children
+    pub async fn browse(
+        settings: &MediaServerSettings,
+        parent: Option<&str>,
+    ) -> Result<Vec<MediaItem>, String> {
+        let path = match parent {
+            None => {
+                let reply = call(settings, "GET", "/library/sections", &[], None).await?;
+                let sections = reply["MediaContainer"]["Directory"]
+                    .as_array()
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                return Ok(sections
+                    .iter()
+                    .map(|section| MediaItem {
+                        id: format!("section:{}", text(&section["key"]).unwrap_or_default()),
+                        title: text(&section["title"]).unwrap_or_default(),
+                        kind: "library".to_string(),
+                        subtitle: text(&section["type"]),
+                    })
+                    .collect());
+            }
+            Some(parent) => match parent.strip_prefix("section:") {
+                Some(section) => format!("/library/sections/{}/all", section),
+                None => format!("/library/metadata/{}/children", parent),
+            },
+        };
+        let reply = call(settings, "GET", &path, &[], None).await?;
+        Ok(metadata(&reply).iter().take(MAX_ITEMS).map(item).collect())
+    }
+
+    pub async fn on_deck(settings: &MediaServerSettings) -> Result<Vec<MediaItem>, String> {
+        let reply = call(settings, "GET", "/library/onDeck", &[], None).await?;
+        Ok(metadata(&reply).iter().take(MAX_ITEMS).map(item).collect())
+    }
+}
+
+mod jellyfin {
+    use serde_json::{json, Value};
+
+    use super::{call, check_id, text, MediaAction, MediaItem, MAX_ITEMS, TICKS_PER_SECOND};
+    use crate::config::MediaServerSettings;
+
+    fn items(reply: &Value) -> Vec<MediaItem> {
+        reply["Items"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .take(MAX_ITEMS)
+            .map(|item| MediaItem {
+                id: text(&item["Id"]).unwrap_or_default(),
+                title: text(&item["Name"]).unwrap_or_default(),
+                kind: text(&item["Type"]).unwrap_or_default().to_lowercase(),
+                subtitle: text(&item["SeriesName"]).or_else(|| text(&item["AlbumArtist"])),
+            })
+            .collect()
+    }
+
+    async fn session(settings: &MediaServerSettings) -> Result<Option<Value>, String> {
+        let sessions = call(settings, "GET", "/Sessions", &[], None).await?;
+        Ok(sessions
+            .as_array()
+            .and_then(|sessions| {
+                sessions
+                    .iter()
+                    .find(|session| session["NowPlayingItem"].is_object())
+            })
+            .cloned())
+    }
+
+    async fn user_id(settings: &MediaServerSettings) -> Result<String, String> {
+        if !settings.user_id.is_empty() {
+            return Ok(settings.user_id.clone());
+        }
+        let users = call(settings, "GET", "/Users", &[], None).await?;
+        users
+            .as_array()
+            .and_then(|users| users.first())
+            .and_then(|user| text(&user["Id"]))
+            .ok_or_else(|| "Jellyfin has no users".to_string())
+    }
+
+    pub async fn control(
+        settings: &MediaServerSettings,
+        action: MediaAction,
+    ) -> Result<bool, String> {
+        let Some(session) = session(settings).await? else {
+            return Ok(false);
+        };
+        let Some(id) = text(&session["Id"]) else {
+            return Ok(false);
+        };
+        check_id(&id)?;
+        let command = match action {
+            MediaAction::PlayPause => "PlayPause".to_string(),
+            MediaAction::Next => "NextTrack".to_string(),
+            MediaAction::Previous => "PreviousTrack".to_string(),
+            MediaAction::Stop => "Stop".to_string(),
+            MediaAction::Seek(offset) => {
+                let position = session["PlayState"]["PositionTicks"]
+                    .as_f64()
+                    .unwrap_or(0.0);
+                let target = (position + offset * TICKS_PER_SECOND).max(0.0) as u64;
+                format!("Seek?SeekPositionTicks={}", target)
+            }
+        };
+        let path = format!("/Sessions/{}/Playing/{}", id, command);
+        call(settings, "POST", &path, &[], None).await?;
+        Ok(true)
+    }
+
+    pub async fn now_playing(settings: &MediaServerSettings) -> Result<Value, String> {
+        let Some(session) = session(settings).await? else {
+            return Ok(Value::Null);
+        };
+        let item = &session["NowPlayingItem"];
+        let state = &session["PlayState"];
+        Ok(json!({
+            "player": "jellyfin",
+            "status": if state["IsPaused"] == true { "Paused" } else { "Playing" },
+            "title": text(&item["Name"]),
+            "artist": text(&item["SeriesName"]).or_else(|| text(&item["Artists"][0])),
+            "album": text(&item["Album"]),
+            "length": item["RunTimeTicks"].as_f64().map(|ticks| ticks / TICKS_PER_SECOND),
+            "position": state["PositionTicks"].as_f64().map(|ticks| ticks / TICKS_PER_SECOND),
+        }))
+    }
+
+    pub async fn browse(
+        settings: &MediaServerSettings,
+        parent: Option<&str>,
+    ) -> Result<Vec<MediaItem>, String> {
+        let user = user_id(settings).await?;
+        check_id(&user)?;
+        let path = match parent {
+            None => format!("/Users/{}/Views", user),
+            Some(parent) => format!(
+                "/Users/{}/Items?ParentId={}&SortBy=SortName&Limit={}",
+                user, parent, MAX_ITEMS
+            ),
+        };
+        let reply = call(settings, "GET", &path, &[], None).await?;
+        let mut items = items(&reply);
+        if parent.is_none() {
+            for item in &mut items {
+                item.kind = "library".to_string();
+            }
+        }
+        Ok(items)
+    }
+
+    // Started items first, then the next episodes of shows in progress
+    pub async fn on_deck(settings: &MediaServerSettings) -> Result<Vec<MediaItem>, String> {
+        let user = user_id(settings).await?;
+        check_id(&user)?;
+        let resume = format!("/Users/{}/Items/Resume?Limit={}", user, MAX_ITEMS);
+        let next_up = format!("/Shows/NextUp?UserId={}&Limit={}", user, MAX_ITEMS);
+        let mut deck = items(&call(settings, "GET", &resume, &[], None).await?);
+        deck.extend(items(&call(settings, "GET", &next_up, &[], None).await?));
+        deck.truncate(MAX_ITEMS);
+        Ok(deck)
+    }
+}
+
+mod kodi {
+    use serde_json::{json, Value};
+
+    use super::{call, text, MediaAction, MediaItem, MAX_ITEMS};
+    use crate::config::MediaServerSettings;
+
+    async fn rpc(
+        settings: &MediaServerSettings,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, String> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let reply = call(settings, "POST", "/jsonrpc", &[], Some(request)).await?;
+        if let Some(error) = reply.get("error") {
+            return Err(format!(
+                "Kodi {} failed: {}",
+                method,
+                error["message"].as_str().unwrap_or("unknown error")
+            ));
+        }
+        Ok(reply["result"].clone())
+    }
+
+    async fn active_player(settings: &MediaServerSettings) -> Result<Option<u64>, String> {
+        let players = rpc(settings, "Player.GetActivePlayers", json!({})).await?;
+        Ok(players
+            .as_array()
+            .and_then(|players| players.first())
+            .and_then(|player| player["playerid"].as_u64()))
+    }
+
+    // {"hours": 0, "minutes": 3, "seconds": 12, "milliseconds": 500}
+    fn seconds(time: &Value) -> Option<f64> {
+        let part = |name: &str| time[name].as_f64().unwrap_or(0.0);
+        time.is_object().then(|| {
+            part("hours") * 3600.0
+                + part("minutes") * 60.0
+                + part("seconds")
+                + part("milliseconds") / 1000.0
+        })
+    }
+
+    fn items(list: &Value, kind: &str, id_field: &str) -> Vec<MediaItem> {
+        list.as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|item| MediaItem {
+                id: format!("{}:{}", kind, item[id_field].as_u64().unwrap_or_default()),
+                title: text(&item["label"]).unwrap_or_default(),
+                kind: kind.to_string(),
+                subtitle: text(&item["showtitle"])
+                    .or_else(|| text(&item["artist"][0]))
+                    .or_else(|| item["year"].as_u64().map(|year| year.to_string())),
+            })
+            .collect()
+    }
+
+    pub async fn control(
+        settings: &MediaServerSettings,
+        action: MediaAction,
+    ) -> Result<bool, String> {
+        let Some(player) = active_player(settings).await? else {
+            return Ok(false);
+        };
+        let (method, params) = match action {
+            MediaAction::PlayPause => ("Player.PlayPause", json!({ "playerid": player })),
+            MediaAction::Next => ("Player.GoTo", json!({ "playerid": player, "to": "next" })),
+            MediaAction::Previous => (
+                "Player.GoTo",
+                json!({ "playerid": player, "to": "previous" }),
+            ),
+            MediaAction::Stop => ("Player.Stop", json!({ "playerid": player })),
+            MediaAction::Seek(offset) => (
+                "Player.Seek",
+                json!({ "playerid": player, "value": { "seconds": offset.round() as i64 } }),
+            ),
+        };
+        rpc(settings, method, params).await?;
+        Ok(true)
+    }
+
+    pub async fn now_playing(settings: &MediaServerSettings) -> Result<Value, String> {
+        let Some(player) = active_player(settings).await? else {
+            return Ok(Value::Null);
+        };
+        let item = rpc(
+            settings,
+            "Player.GetItem",
+            json!({ "playerid": player, "properties": ["title", "artist", "album", "showtitle"] }),
+        )
+        .await?;
+        let properties = rpc(
+            settings,
+            "Player.GetProperties",
+            json!({ "playerid": player, "properties": ["time", "totaltime", "speed"] }),
+        )
+        .await?;
+        let item = &item["item"];
+        let paused = properties["speed"].as_f64() == Some(0.0);
+        Ok(json!({
+            "player": "kodi",
+            "status": if paused { "Paused" } else { "Playing" },
+            "title": text(&item["title"]).or_else(|| text(&item["label"])),
+            "artist": text(&item["showtitle"]).or_else(|| text(&item["artist"][0])),
+            "album": text(&item["album"]),
+            "length": seconds(&properties["totaltime"]),
+            "position": seconds(&properties["time"]),
+        }))
+    }
+
+    // Kodi has fixed libraries, their items are "<kind>:<id>"
+    pub async fn browse(
+        settings: &MediaServerSettings,
+        parent: Option<&str>,
+    ) -> Result<Vec<MediaItem>, String> {
+        let limits = json!({ "start": 0, "end": MAX_ITEMS });
+        let Some(parent) = parent else {
+            return Ok([
+                ("movies", "Movies"),
+                ("tvshows", "TV Shows"),
+                ("albums", "Music"),
+            ]
+            .into_iter()
+            .map(|(id, title)| MediaItem {
+                id: id.to_string(),
+                title: title.to_string(),
+                kind: "library".to_string(),
+                subtitle: None,
+            })
+            .collect());
+        };
+        let (method, params, list, kind, id_field) = match parent.split_once(':') {
+            None if parent == "movies" => (
+                "VideoLibrary.GetMovies",
+                json!({ "properties": ["year"], "limits": limits }),
+                "movies",
+                "movie",
+                "movieid",
+            ),
+            None if parent == "tvshows" => (
+                "VideoLibrary.GetTVShows",
+                json!({ "properties": ["year"], "limits": limits }),
+                "tvshows",
+                "tvshow",
+                "tvshowid",
+            ),
+            None if parent == "albums" => (
+                "AudioLibrary.GetAlbums",
+                json!({ "properties": ["artist"], "limits": limits }),
+                "albums",
+                "album",
+                "albumid",
+            ),
+            Some(("tvshow", show)) => (
+                "VideoLibrary.GetEpisodes",
+                json!({
+                    "tvshowid": show.parse::<u64>().map_err(|_| format!("Invalid show id: {}", show))?,
+                    "properties": ["showtitle"],
+                    "limits": limits,
+                }),
+                "episodes",
+                "episode",
+                "episodeid",
+            ),
+            _ => return Err(format!("{} has nothing to list", parent)),
+        };
+        let reply = rpc(settings, method, params).await?;
+        Ok(items(&reply[list], kind, id_field))
+    }
+
+    // Movies and shows that were started but not finished
+    pub async fn on_deck(settings: &MediaServerSettings) -> Result<Vec<MediaItem>, String> {
+        let limits = json!({ "start": 0, "end": MAX_ITEMS });
+        let movies = rpc(
+            settings,
+            "VideoLibrary.GetMovies",
+            json!({
+                "properties": ["year"],
+                "filter": { "field": "inprogress", "operator": "true", "value": "" },
+                "limits": limits,
+            }),
+        )
+        .await?;
+        let shows = rpc(
+            settings,
+            "VideoLibrary.GetInProgressTVShows",
+            json!({ "properties": ["year"], "limits": limits }),
+        )
+        .await?;
+        let mut deck = items(&movies["movies"], "movie", "movieid");
+        deck.extend(items(&shows["tvshows"], "tvshow", "tvshowid"));
+        deck.truncate(MAX_ITEMS);
+        Ok(deck)
+    }
+}
+
+/// Send a media command to the configured server. `None` when there's no
+/// server, nothing is playing on it or it can't be reached, so the caller
+/// falls back to MPRIS or keys.
+pub async fn route(action: MediaAction) -> Option<CommandResponse> {
+    let settings = settings()?;
+    let sent = match settings.kind {
+        MediaServerKind::Plex => plex::control(&settings, action).await,
+        MediaServerKind::Jellyfin => jellyfin::control(&settings, action).await,
+        MediaServerKind::Kodi => kodi::control(&settings, action).await,
+        MediaServerKind::None => Ok(false),
+    };
+    match sent {
+        Ok(true) => Some(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Sent {:?} to {:?}", action, settings.kind),
+        }),
+        Ok(false) => {
+            debug!("Nothing playing on {:?}, falling back", settings.kind);
+            None
+        }
+        Err(e) => {
+            warn!(
+                "{:?} {:?} failed, falling back: {}",
+                settings.kind, action, e
+            );
+            None
+        }
+    }
+}
+
+/// What the server is playing, `None` without a server or when it can't
+/// be reached. Same shape as the MPRIS reply, null when nothing plays.
+pub async fn now_playing() -> Option<Value> {
+    let settings = settings()?;
+    let playing = match settings.kind {
+        MediaServerKind::Plex => plex::now_playing(&settings).await,
+        MediaServerKind::Jellyfin => jellyfin::now_playing(&settings).await,
+        MediaServerKind::Kodi => kodi::now_playing(&settings).await,
+        MediaServerKind::None => return None,
+    };
+    playing
+        .map_err(|e| warn!("{:?} now playing failed: {}", settings.kind, e))
+        .ok()
+}
+
+/// List the libraries, or the items in `parent`.
+pub async fn browse(parent: Option<&str>) -> Result<Vec<MediaItem>, String> {
+    let settings = required_settings()?;
+    if let Some(parent) = parent {
+        check_id(parent)?;
+    }
+    match settings.kind {
+        MediaServerKind::Plex => plex::browse(&settings, parent).await,
+        MediaServerKind::Jellyfin => jellyfin::browse(&settings, parent).await,
+        MediaServerKind::Kodi => kodi::browse(&settings, parent).await,
+        MediaServerKind::None => Ok(Vec::new()),
+    }
+}
+
+/// Items to carry on with: started movies and episodes, next episodes.
+pub async fn on_deck() -> Result<Vec<MediaItem>, String> {
+    let settings = required_settings()?;
+    match settings.kind {
+        MediaServerKind::Plex => plex::on_deck(&settings).await,
+        MediaServerKind::Jellyfin => jellyfin::on_deck(&settings).await,
+        MediaServerKind::Kodi => kodi::on_deck(&settings).await,
+        MediaServerKind::None => Ok(Vec::new()),
+    }
+}
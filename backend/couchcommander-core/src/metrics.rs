@@ -0,0 +1,425 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+use crate::events;
+use crate::protocol::Topic;
+
+// Upper bounds (in milliseconds) of the latency histogram buckets
+const LATENCY_BUCKETS_MS: [f64; 10] = [
+    1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 5000.0,
+];
+// Window used for the commands-per-second rate
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+// Events a slow activity feed can fall behind before it misses some
+const ACTIVITY_CAPACITY: usize = 256;
+// Handled commands kept for dashboards that open later, see `recent_activity`
+const ACTIVITY_HISTORY: usize = 50;
+// Command names come from clients, past this many new ones share one entry
+const MAX_COMMAND_NAMES: usize = 200;
+
+static EXPORTER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: Mutex<MetricsRegistry> = Mutex::new(MetricsRegistry::new());
+    static ref ACTIVITY: broadcast::Sender<CommandEvent> = broadcast::channel(ACTIVITY_CAPACITY).0;
+    static ref ACTIVITY_LOG: Mutex<VecDeque<CommandEvent>> = Mutex::new(VecDeque::new());
+}
+
+#[derive(Debug, Clone, Default)]
+struct CommandMetrics {
+    count: u64,
+    errors: u64,
+    latency_sum_ms: f64,
+    // Cumulative counts per bucket, plus the +Inf bucket at the end
+    buckets: [u64; LATENCY_BUCKETS_MS.len() + 1],
+}
+
+struct MetricsRegistry {
+    started_at: Instant,
+    connected_clients: usize,
+    total_connections: u64,
+    commands: BTreeMap<String, CommandMetrics>,
+    recent: VecDeque<Instant>,
+}
+
+impl MetricsRegistry {
+    fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            connected_clients: 0,
+            total_connections: 0,
+            commands: BTreeMap::new(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    fn prune_recent(&mut self, now: Instant) {
+        while let Some(front) = self.recent.front() {
+            if now.duration_since(*front) > RATE_WINDOW {
+                self.recent.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommandStats {
+    pub count: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub avg_latency_ms: f64,
+    pub latency_buckets: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricsSnapshot {
+    pub uptime_secs: u64,
+    pub connected_clients: usize,
+    pub total_connections: u64,
+    pub total_commands: u64,
+    pub total_errors: u64,
+    pub error_rate: f64,
+    pub commands_per_sec: f64,
+    pub commands: BTreeMap<String, CommandStats>,
+}
+
+/// One handled command, as streamed to the desktop app's activity feed.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandEvent {
+    pub client_id: String,
+    /// The client's name, or its IP address while it has none.
+    pub client: String,
+    pub command: String,
+    pub status: String,
+    pub message: String,
+    pub duration_ms: f64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+// Command names come from clients, keep them safe to use as label values
+fn command_label(name: &str) -> &str {
+    if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        name
+    } else {
+        "invalid"
+    }
+}
+
+/// Record one handled command with its wall-clock latency.
+pub fn record_command(name: &str, latency: Duration, success: bool) {
+    let Ok(mut registry) = REGISTRY.lock() else {
+        return;
+    };
+
+    let now = Instant::now();
+    registry.recent.push_back(now);
+    registry.prune_recent(now);
+
+    let name = command_label(name);
+    let name =
+        if registry.commands.len() >= MAX_COMMAND_NAMES && !registry.commands.contains_key(name) {
+            "unknown"
+        } else {
+            name
+        };
+
+    let latency_ms = latency.as_secs_f64() * 1000.0;
+    let entry = registry.commands.entry(name.to_string()).or_default();
+    entry.count += 1;
+    if !success {
+        entry.errors += 1;
+    }
+    entry.latency_sum_ms += latency_ms;
+    for (i, bound) in LATENCY_BUCKETS_MS.iter().enumerate() {
+        if latency_ms <= *bound {
+            entry.buckets[i] += 1;
+        }
+    }
+    entry.buckets[LATENCY_BUCKETS_MS.len()] += 1;
+}
+
+/// Announce a handled command to whoever follows the activity feed: the
+/// desktop app and remotes subscribed to the `activity` topic.
+pub fn publish_activity(
+    client_id: &str,
+    client: &str,
+    command: &str,
+    status: &str,
+    message: &str,
+    duration: Duration,
+) {
+    let event = CommandEvent {
+        client_id: client_id.to_string(),
+        client: client.to_string(),
+        command: command_label(command).to_string(),
+        status: status.to_string(),
+        message: message.to_string(),
+        duration_ms: duration.as_secs_f64() * 1000.0,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_millis() as u64)
+            .unwrap_or(0),
+    };
+    {
+        let mut log = ACTIVITY_LOG.lock().unwrap_or_else(|e| e.into_inner());
+        if log.len() == ACTIVITY_HISTORY {
+            log.pop_front();
+        }
+        log.push_back(event.clone());
+    }
+    if events::has_subscribers(Topic::Activity) {
+        if let Ok(data) = serde_json::to_value(&event) {
+            events::publish(Topic::Activity, data);
+        }
+    }
+    // Nobody listening is fine, the event is just dropped
+    let _ = ACTIVITY.send(event);
+}
+
+/// The last commands handled, oldest first.
+pub fn recent_activity() -> Vec<CommandEvent> {
+    ACTIVITY_LOG
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .cloned()
+        .collect()
+}
+
+/// Follow every command handled from now on.
+pub fn subscribe_activity() -> broadcast::Receiver<CommandEvent> {
+    ACTIVITY.subscribe()
+}
+
+pub fn client_connected() {
+    if let Ok(mut registry) = REGISTRY.lock() {
+        registry.connected_clients += 1;
+        registry.total_connections += 1;
+    }
+}
+
+pub fn client_disconnected() {
+    if let Ok(mut registry) = REGISTRY.lock() {
+        registry.connected_clients = registry.connected_clients.saturating_sub(1);
+    }
+}
+
+fn ratio(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+pub fn snapshot() -> MetricsSnapshot {
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry.prune_recent(Instant::now());
+
+    let uptime = registry.started_at.elapsed();
+    let window = uptime.min(RATE_WINDOW).as_secs_f64().max(1.0);
+
+    let mut total_commands = 0;
+    let mut total_errors = 0;
+    let commands = registry
+        .commands
+        .iter()
+        .map(|(name, m)| {
+            total_commands += m.count;
+            total_errors += m.errors;
+            let latency_buckets = LATENCY_BUCKETS_MS
+                .iter()
+                .map(|b| b.to_string())
+                .chain(std::iter::once("+Inf".to_string()))
+                .zip(m.buckets.iter().copied())
+                .collect();
+            let stats = CommandStats {
+                count: m.count,
+                errors: m.errors,
+                error_rate: ratio(m.errors, m.count),
+                avg_latency_ms: if m.count == 0 {
+                    0.0
+                } else {
+                    m.latency_sum_ms / m.count as f64
+                },
+                latency_buckets,
+            };
+            (name.clone(), stats)
+        })
+        .collect();
+
+    MetricsSnapshot {
+        uptime_secs: uptime.as_secs(),
+        connected_clients: registry.connected_clients,
+        total_connections: registry.total_connections,
+        total_commands,
+        total_errors,
+        error_rate: ratio(total_errors, total_commands),
+        commands_per_sec: registry.recent.len() as f64 / window,
+        commands,
+    }
+}
+
+/// Render the registry in the Prometheus text exposition format.
+pub fn render_prometheus() -> String {
+    let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "# HELP couchcommander_uptime_seconds Time since the backend started."
+    );
+    let _ = writeln!(out, "# TYPE couchcommander_uptime_seconds gauge");
+    let _ = writeln!(
+        out,
+        "couchcommander_uptime_seconds {}",
+        registry.started_at.elapsed().as_secs()
+    );
+
+    let _ = writeln!(
+        out,
+        "# HELP couchcommander_connected_clients Currently connected WebSocket clients."
+    );
+    let _ = writeln!(out, "# TYPE couchcommander_connected_clients gauge");
+    let _ = writeln!(
+        out,
+        "couchcommander_connected_clients {}",
+        registry.connected_clients
+    );
+
+    let _ = writeln!(
+        out,
+        "# HELP couchcommander_connections_total WebSocket connections accepted."
+    );
+    let _ = writeln!(out, "# TYPE couchcommander_connections_total counter");
+    let _ = writeln!(
+        out,
+        "couchcommander_connections_total {}",
+        registry.total_connections
+    );
+
+    let _ = writeln!(
+        out,
+        "# HELP couchcommander_commands_total Commands handled, by command."
+    );
+    let _ = writeln!(out, "# TYPE couchcommander_commands_total counter");
+    for (name, m) in &registry.commands {
+        let _ = writeln!(
+            out,
+            "couchcommander_commands_total{{command=\"{}\"}} {}",
+            name, m.count
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP couchcommander_command_errors_total Commands that returned an error."
+    );
+    let _ = writeln!(out, "# TYPE couchcommander_command_errors_total counter");
+    for (name, m) in &registry.commands {
+        let _ = writeln!(
+            out,
+            "couchcommander_command_errors_total{{command=\"{}\"}} {}",
+            name, m.errors
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP couchcommander_command_latency_ms Command handling latency."
+    );
+    let _ = writeln!(out, "# TYPE couchcommander_command_latency_ms histogram");
+    for (name, m) in &registry.commands {
+        for (bound, count) in LATENCY_BUCKETS_MS.iter().zip(m.buckets.iter()) {
+            let _ = writeln!(
+                out,
+                "couchcommander_command_latency_ms_bucket{{command=\"{}\",le=\"{}\"}} {}",
+                name, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "couchcommander_command_latency_ms_bucket{{command=\"{}\",le=\"+Inf\"}} {}",
+            name,
+            m.buckets[LATENCY_BUCKETS_MS.len()]
+        );
+        let _ = writeln!(
+            out,
+            "couchcommander_command_latency_ms_sum{{command=\"{}\"}} {}",
+            name, m.latency_sum_ms
+        );
+        let _ = writeln!(
+            out,
+            "couchcommander_command_latency_ms_count{{command=\"{}\"}} {}",
+            name, m.count
+        );
+    }
+
+    out
+}
+
+/// Serve `render_prometheus` on localhost. Any request path returns the metrics.
+pub async fn start_exporter(port: u16) -> Result<(), String> {
+    if EXPORTER_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("Metrics exporter is already running".to_string());
+    }
+
+    let listener = match TcpListener::bind(("127.0.0.1", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            EXPORTER_RUNNING.store(false, Ordering::SeqCst);
+            return Err(format!(
+                "Failed to bind metrics exporter on port {}: {}",
+                port, e
+            ));
+        }
+    };
+    info!(
+        "Prometheus metrics exporter listening on 127.0.0.1:{}",
+        port
+    );
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    error!("Metrics exporter accept failed: {}", e);
+                    continue;
+                }
+            };
+
+            tokio::spawn(async move {
+                // Only the request line matters, the body is ignored
+                let mut buf = [0u8; 1024];
+                if let Err(e) = stream.read(&mut buf).await {
+                    warn!("Failed to read metrics request: {}", e);
+                    return;
+                }
+
+                let body = render_prometheus();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                if let Err(e) = stream.write_all(response.as_bytes()).await {
+                    warn!("Failed to write metrics response: {}", e);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
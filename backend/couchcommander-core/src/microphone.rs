@@ -0,0 +1,69 @@
+//! Muting the host's microphone from the couch, for calls run on the TV.
+//! Linux goes through `pactl` (or `amixer`), Windows through Core Audio.
+//! macOS has no scriptable input mute, so muting sets the input volume to
+//! zero and unmuting puts the previous level back.
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    use crate::applescript::{self, Script};
+
+    // Input volume from before muting, restored when unmuting
+    static LEVEL_BEFORE_MUTE: AtomicU8 = AtomicU8::new(75);
+
+    fn level() -> Result<u8, String> {
+        applescript::run(&Script::GetInputVolume)?
+            .parse()
+            .map_err(|_| "Could not read the microphone level".to_string())
+    }
+
+    pub fn is_muted() -> Result<bool, String> {
+        Ok(level()? == 0)
+    }
+
+    pub fn set_muted(muted: bool) -> Result<(), String> {
+        let current = level()?;
+        let value = match (muted, current) {
+            (true, 0) | (false, 1..) => return Ok(()),
+            (true, _) => {
+                LEVEL_BEFORE_MUTE.store(current, Ordering::Relaxed);
+                0
+            }
+            (false, _) => LEVEL_BEFORE_MUTE.load(Ordering::Relaxed),
+        };
+        applescript::run(&Script::SetInputVolume { value }).map(|_| ())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    pub use crate::audio::{is_mic_muted as is_muted, set_mic_muted as set_muted};
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    pub use crate::windows_mic::{is_muted, set_muted};
+}
+
+/// Whether the host's default microphone is muted.
+pub async fn is_muted() -> Result<bool, String> {
+    tokio::task::spawn_blocking(platform::is_muted)
+        .await
+        .map_err(|e| format!("Microphone task panicked: {:?}", e))?
+}
+
+/// Mute or unmute the microphone, or flip it when `muted` is `None`.
+/// Returns whether it's muted now.
+pub async fn set_muted(muted: Option<bool>) -> Result<bool, String> {
+    tokio::task::spawn_blocking(move || {
+        let muted = match muted {
+            Some(muted) => muted,
+            None => !platform::is_muted()?,
+        };
+        platform::set_muted(muted)?;
+        Ok(muted)
+    })
+    .await
+    .map_err(|e| format!("Microphone task panicked: {:?}", e))?
+}
@@ -0,0 +1,179 @@
+//! Media control on Linux through MPRIS, the D-Bus interface every desktop
+//! player implements. Unlike key injection it reaches the player without
+//! focus and can report what is playing.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::sync::OnceCell;
+use tracing::debug;
+use zbus::proxy::{Builder, CacheProperties};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+use zbus::{Connection, Proxy};
+
+const PLAYER_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const PLAYER_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+static SESSION_BUS: OnceCell<Connection> = OnceCell::const_new();
+
+/// Track metadata from the active player. Times are in seconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct NowPlaying {
+    pub player: String,
+    pub status: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub length: Option<f64>,
+    pub position: Option<f64>,
+}
+
+async fn session_bus() -> Result<&'static Connection, String> {
+    SESSION_BUS
+        .get_or_try_init(Connection::session)
+        .await
+        .map_err(|e| format!("Failed to connect to the session bus: {}", e))
+}
+
+async fn player_proxy(bus_name: &str) -> Result<Proxy<'static>, String> {
+    Builder::new(session_bus().await?)
+        .destination(bus_name.to_string())
+        .and_then(|b| b.path(PLAYER_PATH))
+        .and_then(|b| b.interface(PLAYER_INTERFACE))
+        .map_err(|e| format!("Invalid MPRIS player {}: {}", bus_name, e))?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+        .map_err(|e| format!("Failed to reach MPRIS player {}: {}", bus_name, e))
+}
+
+async fn playback_status(proxy: &Proxy<'_>) -> String {
+    proxy
+        .get_property::<String>("PlaybackStatus")
+        .await
+        .unwrap_or_default()
+}
+
+// The player to control: one that is playing, else one that is paused,
+// else whichever registered first
+async fn active_player() -> Result<Option<(String, Proxy<'static>)>, String> {
+    let dbus = zbus::fdo::DBusProxy::new(session_bus().await?)
+        .await
+        .map_err(|e| format!("Failed to reach D-Bus: {}", e))?;
+    let names = dbus
+        .list_names()
+        .await
+        .map_err(|e| format!("Failed to list D-Bus names: {}", e))?;
+
+    let mut best: Option<(u8, String, Proxy<'static>)> = None;
+    for name in names.iter().map(|n| n.to_string()) {
+        if !name.starts_with(PLAYER_PREFIX) {
+            continue;
+        }
+        let Ok(proxy) = player_proxy(&name).await else {
+            continue;
+        };
+        let rank = match playback_status(&proxy).await.as_str() {
+            "Playing" => 2,
+            "Paused" => 1,
+            _ => 0,
+        };
+        if best
+            .as_ref()
+            .is_none_or(|(best_rank, ..)| rank > *best_rank)
+        {
+            best = Some((rank, name, proxy));
+        }
+    }
+    Ok(best.map(|(_, name, proxy)| (name, proxy)))
+}
+
+/// Call a no-argument Player method such as "PlayPause" or "Next" on the
+/// active player. Returns false when no MPRIS player is running.
+pub async fn call(method: &str) -> Result<bool, String> {
+    let Some((name, proxy)) = active_player().await? else {
+        return Ok(false);
+    };
+    debug!("Calling {} on {}", method, name);
+    proxy
+        .call_method(method, &())
+        .await
+        .map_err(|e| format!("{} failed on {}: {}", method, name, e))?;
+    Ok(true)
+}
+
+/// Seek the active player by `offset` seconds, negative to rewind.
+pub async fn seek(offset: f64) -> Result<bool, String> {
+    let Some((name, proxy)) = active_player().await? else {
+        return Ok(false);
+    };
+    let micros = (offset * 1_000_000.0) as i64;
+    proxy
+        .call_method("Seek", &(micros,))
+        .await
+        .map_err(|e| format!("Seek failed on {}: {}", name, e))?;
+    Ok(true)
+}
+
+/// Jump to an absolute position in the current track, in seconds.
+pub async fn set_position(position: f64) -> Result<bool, String> {
+    let Some((name, proxy)) = active_player().await? else {
+        return Ok(false);
+    };
+    let metadata: HashMap<String, OwnedValue> = proxy
+        .get_property("Metadata")
+        .await
+        .map_err(|e| format!("Failed to read metadata from {}: {}", name, e))?;
+    let track_id = metadata
+        .get("mpris:trackid")
+        .and_then(|v| OwnedObjectPath::try_from(v.try_clone().ok()?).ok())
+        .ok_or_else(|| format!("{} does not report a track id", name))?;
+    let micros = (position.max(0.0) * 1_000_000.0) as i64;
+    proxy
+        .call_method("SetPosition", &(track_id, micros))
+        .await
+        .map_err(|e| format!("SetPosition failed on {}: {}", name, e))?;
+    Ok(true)
+}
+
+fn string_value(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    String::try_from(metadata.get(key)?.try_clone().ok()?).ok()
+}
+
+// Track length arrives as x or t depending on the player
+fn micros_value(value: &OwnedValue) -> Option<f64> {
+    i64::try_from(value)
+        .map(|v| v as f64)
+        .or_else(|_| u64::try_from(value).map(|v| v as f64))
+        .ok()
+        .map(|micros| micros / 1_000_000.0)
+}
+
+/// What the active player is playing, or None without an MPRIS player.
+pub async fn now_playing() -> Result<Option<NowPlaying>, String> {
+    let Some((name, proxy)) = active_player().await? else {
+        return Ok(None);
+    };
+    let metadata: HashMap<String, OwnedValue> =
+        proxy.get_property("Metadata").await.unwrap_or_default();
+    let position = proxy
+        .get_property::<OwnedValue>("Position")
+        .await
+        .ok()
+        .and_then(|v| micros_value(&v));
+
+    let artist = metadata
+        .get("xesam:artist")
+        .and_then(|v| Vec::<String>::try_from(v.try_clone().ok()?).ok())
+        .map(|artists| artists.join(", "));
+
+    Ok(Some(NowPlaying {
+        player: name.trim_start_matches(PLAYER_PREFIX).to_string(),
+        status: playback_status(&proxy).await,
+        title: string_value(&metadata, "xesam:title"),
+        artist,
+        album: string_value(&metadata, "xesam:album"),
+        length: metadata.get("mpris:length").and_then(micros_value),
+        position,
+    }))
+}
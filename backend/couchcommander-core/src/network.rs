@@ -0,0 +1,199 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket};
+use tracing::{debug, warn};
+
+use crate::wifi::WifiInfo;
+
+// Interface name prefixes of VPN tunnels and of local virtual bridges
+const VPN_PREFIXES: [&str; 7] = ["tun", "utun", "wg", "tailscale", "zt", "ppp", "ipsec"];
+const VIRTUAL_PREFIXES: [&str; 6] = ["docker", "br-", "veth", "vmnet", "vboxnet", "virbr"];
+
+/// Kinds of address, in the order they are offered to users.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressKind {
+    Lan,
+    Vpn,
+    Public,
+    Ipv6,
+    Virtual,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CandidateAddress {
+    pub ip: IpAddr,
+    pub interface: String,
+    pub kind: AddressKind,
+    pub default_route: bool,
+}
+
+/// How the host and a remote are connected, to debug a laggy remote.
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkInfo {
+    pub client_ip: Option<IpAddr>,
+    // The host address on the remote's subnet, else the default route's
+    pub host_ip: Option<IpAddr>,
+    pub interface: Option<String>,
+    // False for remotes coming through a VPN, the relay or another router
+    pub same_subnet: bool,
+    // None while the host isn't on Wi-Fi
+    pub wifi: Option<WifiInfo>,
+}
+
+// Address of the interface the OS would use to reach the internet.
+// Nothing is sent, connecting a UDP socket only picks a route.
+fn default_route_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+fn classify(interface: &str, ip: &IpAddr) -> AddressKind {
+    let name = interface.to_ascii_lowercase();
+    if VIRTUAL_PREFIXES.iter().any(|p| name.starts_with(p)) {
+        return AddressKind::Virtual;
+    }
+    if VPN_PREFIXES.iter().any(|p| name.starts_with(p)) {
+        return AddressKind::Vpn;
+    }
+
+    match ip {
+        // 100.64.0.0/10 is carrier-grade NAT space, used by Tailscale
+        IpAddr::V4(v4) if v4.octets()[0] == 100 && (v4.octets()[1] & 0xC0) == 64 => {
+            AddressKind::Vpn
+        }
+        IpAddr::V4(v4) if v4.is_private() => AddressKind::Lan,
+        IpAddr::V4(_) => AddressKind::Public,
+        IpAddr::V6(_) => AddressKind::Ipv6,
+    }
+}
+
+// Whether a client can reach `ip` on a server bound to `bind`
+fn reachable(bind: IpAddr, ip: &IpAddr) -> bool {
+    match bind {
+        IpAddr::V4(v4) if v4.is_unspecified() => ip.is_ipv4(),
+        // An IPv6 wildcard socket is bound dual-stack
+        IpAddr::V6(v6) if v6.is_unspecified() => true,
+        _ => bind == *ip,
+    }
+}
+
+/// Every non-loopback address a client could use to reach a server bound to
+/// `bind`, best candidate first.
+pub fn candidate_addresses(bind: IpAddr) -> Vec<CandidateAddress> {
+    let default_route = default_route_ip();
+    let interfaces = match if_addrs::get_if_addrs() {
+        Ok(interfaces) => interfaces,
+        Err(e) => {
+            warn!("Failed to list network interfaces: {}", e);
+            Vec::new()
+        }
+    };
+
+    let mut candidates: Vec<CandidateAddress> = interfaces
+        .into_iter()
+        // Link-local addresses need a zone id that browsers won't accept
+        .filter(|iface| !iface.is_loopback() && !iface.is_link_local())
+        .filter(|iface| reachable(bind, &iface.ip()))
+        .map(|iface| {
+            let ip = iface.ip();
+            CandidateAddress {
+                kind: classify(&iface.name, &ip),
+                default_route: default_route == Some(ip),
+                interface: iface.name,
+                ip,
+            }
+        })
+        .collect();
+
+    // Fall back to the routed address when enumeration turned up nothing
+    if candidates.is_empty() {
+        if let Some(ip) = default_route.filter(|ip| reachable(bind, ip)) {
+            candidates.push(CandidateAddress {
+                kind: classify("", &ip),
+                default_route: true,
+                interface: String::new(),
+                ip,
+            });
+        }
+    }
+
+    candidates.sort_by_key(|c| (c.kind, !c.default_route));
+    let mut seen = HashSet::new();
+    candidates.retain(|c| seen.insert(c.ip));
+    candidates
+}
+
+/// Best guess at the address phones on the LAN should use.
+pub fn primary_ip() -> Option<IpAddr> {
+    candidate_addresses(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+        .first()
+        .map(|c| c.ip)
+}
+
+/// Every usable local address, IPv4 and IPv6.
+pub fn all_addresses() -> Vec<IpAddr> {
+    candidate_addresses(IpAddr::V6(Ipv6Addr::UNSPECIFIED))
+        .into_iter()
+        .map(|c| c.ip)
+        .collect()
+}
+
+/// Host part of a URL, with IPv6 addresses in brackets.
+pub fn url_host(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => format!("[{}]", v6),
+    }
+}
+
+fn in_subnet(ip: IpAddr, interface: &if_addrs::IfAddr, client: IpAddr) -> bool {
+    match (interface, client) {
+        (if_addrs::IfAddr::V4(v4), IpAddr::V4(client)) => {
+            let mask = u32::from(v4.netmask);
+            u32::from(v4.ip) & mask == u32::from(client) & mask && ip != IpAddr::V4(client)
+        }
+        (if_addrs::IfAddr::V6(v6), IpAddr::V6(client)) => {
+            let mask = u128::from(v6.netmask);
+            u128::from(v6.ip) & mask == u128::from(client) & mask && ip != IpAddr::V6(client)
+        }
+        _ => false,
+    }
+}
+
+/// The host's side of the connection to a remote at `client`, and the Wi-Fi
+/// link when it's on one.
+pub async fn info(client: Option<IpAddr>) -> NetworkInfo {
+    // A dual-stack socket sees IPv4 remotes as ::ffff:a.b.c.d
+    let client = client.map(|ip| ip.to_canonical());
+    let interfaces = if_addrs::get_if_addrs().unwrap_or_default();
+    let local = client.and_then(|client| {
+        interfaces
+            .iter()
+            .filter(|iface| !iface.is_loopback())
+            .find(|iface| in_subnet(iface.ip(), &iface.addr, client))
+    });
+    let (host_ip, interface) = match local {
+        Some(iface) => (Some(iface.ip()), Some(iface.name.clone())),
+        None => {
+            let ip = default_route_ip();
+            let name = interfaces
+                .iter()
+                .find(|iface| Some(iface.ip()) == ip)
+                .map(|iface| iface.name.clone());
+            (ip, name)
+        }
+    };
+    let wifi = crate::wifi::status().await.unwrap_or_else(|e| {
+        debug!("No Wi-Fi details: {}", e);
+        None
+    });
+    NetworkInfo {
+        client_ip: client,
+        host_ip,
+        interface,
+        same_subnet: local.is_some(),
+        wifi,
+    }
+}
@@ -0,0 +1,255 @@
+//! Mirrors host desktop notifications to remotes subscribed to the
+//! `notifications` topic, so a doorbell or chat ping shows up on the phone
+//! while the host drives the TV. Opt-in with `mirror_notifications`.
+//!
+//! Only Linux can watch other apps' notifications, by monitoring calls to
+//! `org.freedesktop.Notifications` on the session bus. macOS and Windows
+//! don't let an unsandboxed app read them without private APIs.
+//!
+//! Remotes can also post a notification on the host with `notify_host`.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+const MAX_POST_TITLE_CHARS: usize = 100;
+const MAX_POST_BODY_CHARS: usize = 500;
+// Each remote can post one notification this often
+const POST_INTERVAL: Duration = Duration::from_secs(10);
+
+lazy_static::lazy_static! {
+    static ref LAST_POSTED: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
+}
+
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+#[cfg(target_os = "linux")]
+fn show(title: &str, body: &str) -> Result<(), String> {
+    let status = Command::new("notify-send")
+        .args(["--app-name=CouchCommander", "--", title, body])
+        .status()
+        .map_err(|e| format!("Failed to run notify-send: {}", e))?;
+    if !status.success() {
+        return Err("notify-send failed".to_string());
+    }
+    Ok(())
+}
+
+// Title and body go in as arguments so they're never parsed as script
+#[cfg(target_os = "macos")]
+fn show(title: &str, body: &str) -> Result<(), String> {
+    let status = Command::new("osascript")
+        .args([
+            "-e",
+            "on run argv",
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+            "-e",
+            "end run",
+            title,
+            body,
+        ])
+        .status()
+        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+    if !status.success() {
+        return Err("osascript failed".to_string());
+    }
+    Ok(())
+}
+
+// A toast through the WinRT API, with the text passed in the environment
+#[cfg(target_os = "windows")]
+fn show(title: &str, body: &str) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    const SCRIPT: &str = "\
+        $manager = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, \
+            ContentType = WindowsRuntime]; \
+        $xml = $manager::GetTemplateContent( \
+            [Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+        $text = $xml.GetElementsByTagName('text'); \
+        $text.Item(0).AppendChild($xml.CreateTextNode($env:CC_TITLE)) | Out-Null; \
+        $text.Item(1).AppendChild($xml.CreateTextNode($env:CC_BODY)) | Out-Null; \
+        $toast = [Windows.UI.Notifications.ToastNotification]::new($xml); \
+        $manager::CreateToastNotifier('CouchCommander').Show($toast)";
+    let status = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .env("CC_TITLE", title)
+        .env("CC_BODY", body)
+        .creation_flags(CREATE_NO_WINDOW)
+        .status()
+        .map_err(|e| format!("Failed to run powershell: {}", e))?;
+    if !status.success() {
+        return Err("Failed to show the notification".to_string());
+    }
+    Ok(())
+}
+
+fn clean(text: &str, max_chars: usize) -> String {
+    text.chars()
+        .filter(|c| !c.is_control() || *c == '\n')
+        .take(max_chars)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Show a notification from a remote on the host, e.g. "Dinner is ready".
+pub fn post(client_id: &str, title: &str, body: &str) -> Result<(), String> {
+    let title = clean(title, MAX_POST_TITLE_CHARS);
+    let body = clean(body, MAX_POST_BODY_CHARS);
+    if title.is_empty() {
+        return Err("Notification title can't be empty".to_string());
+    }
+
+    {
+        let mut last_posted = LAST_POSTED.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if let Some(last) = last_posted.get(client_id) {
+            if now.duration_since(*last) < POST_INTERVAL {
+                return Err(format!(
+                    "Wait {} seconds between notifications",
+                    POST_INTERVAL.as_secs()
+                ));
+            }
+        }
+        last_posted.retain(|_, last| now.duration_since(*last) < POST_INTERVAL);
+        last_posted.insert(client_id.to_string(), now);
+    }
+
+    show(&title, &body)?;
+    info!("Posted notification from {}", client_id);
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::time::Duration;
+    use tracing::{debug, info, warn};
+
+    use crate::protocol::Topic;
+    use crate::{config, events};
+
+    const MATCH_RULE: &str =
+        "type='method_call',interface='org.freedesktop.Notifications',member='Notify'";
+    // How often to check whether mirroring was turned on
+    const SETTINGS_POLL: Duration = Duration::from_secs(5);
+    // Wait before restarting dbus-monitor if it exits
+    const RESTART_DELAY: Duration = Duration::from_secs(30);
+    const MAX_TITLE_CHARS: usize = 200;
+
+    // `string "Slack"` -> `Slack`, only for single-line values
+    fn string_arg(line: &str) -> Option<String> {
+        let value = line.trim().strip_prefix("string \"")?.strip_suffix('"')?;
+        Some(
+            value
+                .replace("\\\"", "\"")
+                .chars()
+                .take(MAX_TITLE_CHARS)
+                .collect(),
+        )
+    }
+
+    fn forward(app: &str, title: &str) {
+        if !config::current().mirror_notifications || title.is_empty() {
+            return;
+        }
+        debug!("Mirroring notification from {}", app);
+        events::publish(
+            Topic::Notifications,
+            serde_json::json!({ "app": app, "title": title }),
+        );
+    }
+
+    // Runs until dbus-monitor exits or mirroring is turned off
+    fn monitor() -> Result<(), String> {
+        let mut child = Command::new("dbus-monitor")
+            .args(["--session", "--monitor", MATCH_RULE])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start dbus-monitor: {}", e))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| "dbus-monitor has no output".to_string())?;
+        info!("Mirroring host notifications to remotes");
+
+        // Notify(app_name, replaces_id, app_icon, summary, body, ...)
+        let mut strings: Option<Vec<String>> = None;
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            if line.contains("member=Notify") {
+                strings = Some(Vec::new());
+                continue;
+            }
+            let Some(args) = strings.as_mut() else {
+                continue;
+            };
+            if let Some(value) = string_arg(&line) {
+                args.push(value);
+            }
+            if args.len() == 3 {
+                forward(&args[0], &args[2]);
+                strings = None;
+            }
+            if !config::current().mirror_notifications {
+                break;
+            }
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+        Ok(())
+    }
+
+    pub fn run() {
+        loop {
+            if !config::current().mirror_notifications {
+                std::thread::sleep(SETTINGS_POLL);
+                continue;
+            }
+            if let Err(e) = monitor() {
+                warn!("Notification mirroring stopped: {}", e);
+            }
+            if config::current().mirror_notifications {
+                std::thread::sleep(RESTART_DELAY);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use std::time::Duration;
+    use tracing::info;
+
+    use crate::config;
+
+    const SETTINGS_POLL: Duration = Duration::from_secs(5);
+
+    pub fn run() {
+        while !config::current().mirror_notifications {
+            std::thread::sleep(SETTINGS_POLL);
+        }
+        info!("Notification mirroring isn't supported on this platform");
+    }
+}
+
+/// Whether host notifications can be mirrored on this platform.
+pub fn supported() -> bool {
+    cfg!(target_os = "linux")
+}
+
+/// Start watching host notifications, once per process. Nothing is read
+/// until `mirror_notifications` is turned on.
+pub fn watch() {
+    if WATCHING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    std::thread::spawn(platform::run);
+}
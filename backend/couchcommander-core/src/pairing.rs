@@ -0,0 +1,386 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::config::{self, AuthMode};
+use crate::{secrets, signing};
+
+/// URI scheme a companion app registers to pair from a link or NFC tag.
+pub const DEEP_LINK_SCHEME: &str = "couchcommander";
+
+// Keychain entry the session tokens are kept in, so remotes stay paired
+// across restarts
+const SESSIONS_SECRET: &str = "session_tokens";
+
+lazy_static::lazy_static! {
+    static ref TOKENS: Mutex<TokenManager> = Mutex::new(TokenManager::restored());
+}
+
+/// Everything a remote needs to connect, as encoded in the QR code.
+#[derive(Debug, Clone, Serialize)]
+pub struct PairingPayload {
+    pub host: String,
+    pub port: u16,
+    pub token: String,
+    pub tls_fingerprint: Option<String>,
+}
+
+impl PairingPayload {
+    /// Web app URL carrying the payload as query parameters, so scanning with
+    /// a phone camera opens the remote already paired.
+    pub fn web_app_url(&self) -> String {
+        let scheme = if self.tls_fingerprint.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        let mut url = format!(
+            "{}://{}:{}/?ip={}&port={}&token={}",
+            scheme, self.host, self.port, self.host, self.port, self.token
+        );
+        // The fingerprint lets the remote show which certificate to accept
+        if let Some(fingerprint) = &self.tls_fingerprint {
+            url.push_str(&format!("&tls=1&fp={}", fingerprint));
+        }
+        url
+    }
+
+    pub fn websocket_url(&self) -> String {
+        let scheme = if self.tls_fingerprint.is_some() {
+            "wss"
+        } else {
+            "ws"
+        };
+        format!("{}://{}:{}", scheme, self.host, self.port)
+    }
+
+    /// `couchcommander://connect?host=...&port=...&token=...` link for companion apps.
+    pub fn deep_link(&self) -> String {
+        let mut link = format!(
+            "{}://connect?host={}&port={}&token={}",
+            DEEP_LINK_SCHEME, self.host, self.port, self.token
+        );
+        if let Some(fingerprint) = &self.tls_fingerprint {
+            link.push_str(&format!("&fp={}", fingerprint));
+        }
+        link
+    }
+
+    pub fn from_deep_link(link: &str) -> Result<Self, String> {
+        let query = link
+            .strip_prefix(DEEP_LINK_SCHEME)
+            .and_then(|rest| rest.strip_prefix("://connect"))
+            .map(|rest| rest.trim_start_matches('/'))
+            .and_then(|rest| rest.strip_prefix('?'))
+            .ok_or_else(|| format!("Not a {}://connect link: {}", DEEP_LINK_SCHEME, link))?;
+
+        let host = query_param(Some(query), "host")
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| "Deep link is missing the host".to_string())?;
+        let port = query_param(Some(query), "port")
+            .and_then(|port| port.parse().ok())
+            .ok_or_else(|| "Deep link has a missing or invalid port".to_string())?;
+        let token = query_param(Some(query), "token")
+            .ok_or_else(|| "Deep link is missing the token".to_string())?;
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            token: token.to_string(),
+            tls_fingerprint: query_param(Some(query), "fp").map(str::to_string),
+        })
+    }
+}
+
+fn new_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+struct PairingToken {
+    value: String,
+    issued_at: SystemTime,
+}
+
+impl PairingToken {
+    fn new() -> Self {
+        Self {
+            value: new_token(),
+            issued_at: SystemTime::now(),
+        }
+    }
+
+    // A zero TTL means the token only rotates on use or on request
+    fn expires_at(&self, ttl: Duration) -> Option<SystemTime> {
+        (!ttl.is_zero()).then(|| self.issued_at + ttl)
+    }
+
+    fn is_expired(&self, ttl: Duration) -> bool {
+        self.expires_at(ttl)
+            .is_some_and(|expires_at| SystemTime::now() >= expires_at)
+    }
+}
+
+/// Pairing token shown in the QR code plus the session tokens handed to
+/// clients that paired with it, so they can reconnect after the pairing
+/// token has rotated.
+#[derive(Default)]
+struct TokenManager {
+    pairing: Option<PairingToken>,
+    // Session tokens and the device id each one is bound to
+    sessions: HashMap<String, Option<String>>,
+    // Token of the guest pairing code and when it stops working
+    guest: Option<(String, SystemTime)>,
+}
+
+impl TokenManager {
+    // Start with the session tokens saved by an earlier run
+    fn restored() -> Self {
+        let sessions = match secrets::get(SESSIONS_SECRET) {
+            Ok(Some(json)) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Ignoring invalid saved session tokens: {}", e);
+                HashMap::new()
+            }),
+            Ok(None) => HashMap::new(),
+            Err(e) => {
+                warn!("Failed to read session tokens from the keychain: {}", e);
+                HashMap::new()
+            }
+        };
+        Self {
+            sessions,
+            ..Self::default()
+        }
+    }
+
+    // Save the session tokens, which otherwise last until the app quits
+    fn persist(&self) {
+        let json = if self.sessions.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&self.sessions).unwrap_or_default()
+        };
+        if let Err(e) = secrets::set(SESSIONS_SECRET, &json) {
+            warn!("Failed to save session tokens to the keychain: {}", e);
+        }
+    }
+
+    fn pairing_token(&mut self, ttl: Duration) -> &PairingToken {
+        if self
+            .pairing
+            .as_ref()
+            .is_none_or(|token| token.is_expired(ttl))
+        {
+            debug!("Pairing token missing or expired, rotating");
+            self.pairing = Some(PairingToken::new());
+        }
+        self.pairing.get_or_insert_with(PairingToken::new)
+    }
+
+    fn rotate(&mut self) {
+        self.pairing = Some(PairingToken::new());
+    }
+}
+
+/// How a WebSocket client was let in.
+pub enum Authorization {
+    /// Token auth is off.
+    Open,
+    /// Reconnected with a session token from an earlier pairing.
+    Session,
+    /// Paired with the current pairing token and was issued a session token.
+    Paired { session_token: String },
+    /// Connected with the guest pairing token, limited to media commands.
+    Guest,
+}
+
+fn pairing_ttl() -> Duration {
+    Duration::from_secs(config::current().pairing_token_ttl_secs)
+}
+
+/// Token handed out in pairing payloads, replaced once it expires.
+pub fn current_token() -> String {
+    let ttl = pairing_ttl();
+    let mut manager = TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+    manager.pairing_token(ttl).value.clone()
+}
+
+/// When the current pairing token stops working, as seconds since the epoch.
+pub fn current_token_expires_at() -> Option<u64> {
+    let ttl = pairing_ttl();
+    let mut manager = TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+    manager
+        .pairing_token(ttl)
+        .expires_at(ttl)
+        .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+        .map(|since_epoch| since_epoch.as_secs())
+}
+
+/// Invalidate the current pairing token, e.g. after it was shown to the wrong person.
+pub fn rotate() {
+    TOKENS.lock().unwrap_or_else(|e| e.into_inner()).rotate();
+    info!("Pairing token rotated");
+}
+
+/// Forget every session token and rotate the pairing token, so all remotes
+/// have to pair again.
+pub fn reset() {
+    let mut manager = TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+    manager.sessions.clear();
+    manager.guest = None;
+    manager.rotate();
+    manager.persist();
+    info!("Pairing reset, every remote has to pair again");
+}
+
+/// Forget one session token, so the remote holding it has to pair again.
+pub fn revoke_session(token: &str) {
+    let mut manager = TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+    if manager.sessions.remove(token).is_some() {
+        manager.persist();
+    }
+}
+
+/// The device id a session token is bound to. The first id it connects
+/// with sticks, so a remote can't shed a revocation or a role by sending a
+/// new one. Other tokens keep the id they came with.
+pub fn bind_device(token: &str, device: Option<&str>) -> Option<String> {
+    let mut manager = TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(bound) = manager.sessions.get_mut(token) else {
+        return device.map(str::to_string);
+    };
+    if bound.is_some() || device.is_none() {
+        return bound.clone();
+    }
+    *bound = device.map(str::to_string);
+    let bound = bound.clone();
+    manager.persist();
+    bound
+}
+
+/// Start a guest pairing code that works until `duration` has passed or
+/// `end_guest` is called, replacing any earlier one. Returns its token and
+/// when it expires, as seconds since the epoch.
+pub fn start_guest(duration: Duration) -> (String, u64) {
+    let token = new_token();
+    let expires_at = SystemTime::now() + duration;
+    TOKENS.lock().unwrap_or_else(|e| e.into_inner()).guest = Some((token.clone(), expires_at));
+    info!(
+        "Guest access started for {} minutes",
+        duration.as_secs() / 60
+    );
+    let expires_at = expires_at
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or_default();
+    (token, expires_at)
+}
+
+/// Stop the guest pairing code from working.
+pub fn end_guest() {
+    TOKENS.lock().unwrap_or_else(|e| e.into_inner()).guest = None;
+}
+
+/// Check the token a client presented at the handshake. Only enforced in token
+/// auth mode, which each listener sets for itself; the error is the reason
+/// reported back to the client.
+pub fn authorize(presented: Option<&str>, auth_mode: AuthMode) -> Result<Authorization, String> {
+    let settings = config::current();
+    if auth_mode == AuthMode::None {
+        return Ok(Authorization::Open);
+    }
+    let presented = presented.ok_or_else(|| "Missing pairing token".to_string())?;
+
+    let ttl = Duration::from_secs(settings.pairing_token_ttl_secs);
+    let mut manager = TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+    let is_session = manager
+        .sessions
+        .keys()
+        .any(|token| signing::same(token.as_bytes(), presented.as_bytes()));
+    if is_session {
+        return Ok(Authorization::Session);
+    }
+    if let Some((guest, expires_at)) = &manager.guest {
+        if signing::same(guest.as_bytes(), presented.as_bytes()) {
+            if SystemTime::now() >= *expires_at {
+                return Err("Guest access has ended".to_string());
+            }
+            return Ok(Authorization::Guest);
+        }
+    }
+
+    let Some(pairing) = manager.pairing.as_ref() else {
+        return Err("Invalid pairing token".to_string());
+    };
+    if !signing::same(pairing.value.as_bytes(), presented.as_bytes()) {
+        return Err("Invalid pairing token".to_string());
+    }
+    if pairing.is_expired(ttl) {
+        return Err("Pairing code expired, scan the new QR code".to_string());
+    }
+
+    if settings.pairing_single_use {
+        manager.rotate();
+        info!("Pairing token used, rotated");
+    }
+    let session_token = new_token();
+    manager.sessions.insert(session_token.clone(), None);
+    manager.persist();
+    Ok(Authorization::Paired { session_token })
+}
+
+fn query_param<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Pull the `token` query parameter out of a request URI query string.
+pub fn token_from_query(query: Option<&str>) -> Option<&str> {
+    query_param(query, "token")
+}
+
+/// Pull the `admin` query parameter, the admin token from the settings.
+pub fn admin_from_query(query: Option<&str>) -> Option<&str> {
+    query_param(query, "admin")
+}
+
+/// Pull the `device` query parameter, the id a remote keeps for itself.
+pub fn device_from_query(query: Option<&str>) -> Option<&str> {
+    query_param(query, "device").filter(|id| crate::devices::id_is_valid(id))
+}
+
+/// Whether the connection is a status dashboard, `view=dashboard`.
+pub fn dashboard_from_query(query: Option<&str>) -> bool {
+    query_param(query, "view") == Some("dashboard")
+}
+
+/// Pull the `sign` query parameter, asking for signed messages.
+pub fn sign_from_query(query: Option<&str>) -> Option<&str> {
+    query_param(query, "sign")
+}
+
+/// Pull the `verify_fingerprint` query parameter, the nonce a remote wants
+/// the certificate fingerprint proven with.
+pub fn verify_fingerprint_from_query(query: Option<&str>) -> Option<&str> {
+    query_param(query, "verify_fingerprint").filter(|nonce| (8..=128).contains(&nonce.len()))
+}
+
+/// The session token whose hash a signing remote presented instead of it.
+pub fn session_for_hash(hash: &str) -> Option<String> {
+    let manager = TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+    manager
+        .sessions
+        .keys()
+        .find(|token| signing::same(signing::token_hash(token).as_bytes(), hash.as_bytes()))
+        .cloned()
+}
+
+/// Pull the `resume` query parameter, a session resume token.
+pub fn resume_from_query(query: Option<&str>) -> Option<&str> {
+    query_param(query, "resume")
+}
@@ -0,0 +1,190 @@
+//! Opt-in router port mapping over UPnP IGD, so the host can be reached
+//! from outside the LAN. The mapping is leased for an hour and renewed while
+//! the server runs, and removed when it stops.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+use crate::http_client;
+use crate::upnp::{self, element};
+
+const SEARCH_TARGET: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+const SEARCH_TIME: Duration = Duration::from_secs(3);
+const LEASE_SECS: u32 = 60 * 60;
+const RENEW_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const DESCRIPTION: &str = "CouchCommander";
+// Routers that refuse leases answer with this, a permanent mapping works
+const ONLY_PERMANENT_LEASES: &str = "(725)";
+
+#[derive(Debug, Clone)]
+struct Mapping {
+    control_url: String,
+    service_type: String,
+    port: u16,
+    external_ip: String,
+}
+
+static MAPPING: Mutex<Option<Mapping>> = Mutex::new(None);
+// Bumped on every open and close so only the newest renewal task runs
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// The router's WANIPConnection or WANPPPConnection service
+async fn find_gateway() -> Result<(String, String), String> {
+    for location in upnp::search(SEARCH_TARGET, SEARCH_TIME).await? {
+        let xml = match http_client::request("GET", &location, &[], None).await {
+            Ok(response) => response.body,
+            Err(e) => {
+                debug!("Skipping gateway at {}: {}", location, e);
+                continue;
+            }
+        };
+        let service = upnp::find_service(&location, &xml, ":WANIPConnection:")
+            .or_else(|| upnp::find_service(&location, &xml, ":WANPPPConnection:"));
+        if let Some(service) = service {
+            return Ok(service);
+        }
+    }
+    Err("No router with UPnP port mapping found, it may be turned off on the router".to_string())
+}
+
+// Our address on the interface that routes to the gateway
+async fn local_ip(control_url: &str) -> Result<IpAddr, String> {
+    let url = http_client::parse_url(control_url)?;
+    let gateway: IpAddr = url
+        .host
+        .parse()
+        .map_err(|_| format!("Gateway host isn't an address: {}", url.host))?;
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| format!("Failed to open a socket: {}", e))?;
+    // Connecting a UDP socket sends nothing, it only picks the route
+    socket
+        .connect(SocketAddr::new(gateway, url.port))
+        .await
+        .map_err(|e| format!("No route to the gateway: {}", e))?;
+    socket
+        .local_addr()
+        .map(|addr| addr.ip())
+        .map_err(|e| format!("Failed to read the local address: {}", e))
+}
+
+async fn add_mapping(
+    control_url: &str,
+    service_type: &str,
+    port: u16,
+    internal_client: IpAddr,
+) -> Result<(), String> {
+    let arguments = |lease_secs: u32| {
+        format!(
+            "<NewRemoteHost></NewRemoteHost><NewExternalPort>{port}</NewExternalPort>\
+             <NewProtocol>TCP</NewProtocol><NewInternalPort>{port}</NewInternalPort>\
+             <NewInternalClient>{client}</NewInternalClient><NewEnabled>1</NewEnabled>\
+             <NewPortMappingDescription>{description}</NewPortMappingDescription>\
+             <NewLeaseDuration>{lease}</NewLeaseDuration>",
+            port = port,
+            client = internal_client,
+            description = DESCRIPTION,
+            lease = lease_secs
+        )
+    };
+    let leased = arguments(LEASE_SECS);
+    match upnp::action(control_url, service_type, "AddPortMapping", &leased).await {
+        Err(e) if e.contains(ONLY_PERMANENT_LEASES) => {
+            let permanent = arguments(0);
+            upnp::action(control_url, service_type, "AddPortMapping", &permanent)
+                .await
+                .map(|_| ())
+        }
+        result => result.map(|_| ()),
+    }
+}
+
+// Keep the lease alive until the mapping is closed or replaced
+fn renew(generation: u64, mapping: Mapping, internal_client: IpAddr) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(RENEW_INTERVAL).await;
+            if GENERATION.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            match add_mapping(
+                &mapping.control_url,
+                &mapping.service_type,
+                mapping.port,
+                internal_client,
+            )
+            .await
+            {
+                Ok(()) => debug!("Renewed the port mapping for {}", mapping.port),
+                Err(e) => warn!("Failed to renew the port mapping: {}", e),
+            }
+        }
+    });
+}
+
+/// Map `port` on the router to this host and return the external address.
+pub async fn open(port: u16) -> Result<String, String> {
+    close().await;
+    let (service_type, control_url) = find_gateway().await?;
+    let internal_client = local_ip(&control_url).await?;
+    add_mapping(&control_url, &service_type, port, internal_client).await?;
+
+    let reply = upnp::action(&control_url, &service_type, "GetExternalIPAddress", "").await?;
+    let external_ip = element(&reply, "NewExternalIPAddress")
+        .filter(|ip| !ip.is_empty())
+        .ok_or_else(|| "The router didn't report its external address".to_string())?
+        .to_string();
+
+    let mapping = Mapping {
+        control_url,
+        service_type,
+        port,
+        external_ip,
+    };
+    let address = format!("{}:{}", mapping.external_ip, port);
+    info!(
+        "Mapped external {} to {}:{}",
+        address, internal_client, port
+    );
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    *MAPPING.lock().unwrap_or_else(|e| e.into_inner()) = Some(mapping.clone());
+    renew(generation, mapping, internal_client);
+    Ok(address)
+}
+
+/// Remove the mapping, if there is one.
+pub async fn close() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    let Some(mapping) = MAPPING.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return;
+    };
+    let arguments = format!(
+        "<NewRemoteHost></NewRemoteHost><NewExternalPort>{}</NewExternalPort>\
+         <NewProtocol>TCP</NewProtocol>",
+        mapping.port
+    );
+    match upnp::action(
+        &mapping.control_url,
+        &mapping.service_type,
+        "DeletePortMapping",
+        &arguments,
+    )
+    .await
+    {
+        Ok(_) => info!("Removed the port mapping for {}", mapping.port),
+        Err(e) => warn!("Failed to remove the port mapping: {}", e),
+    }
+}
+
+/// The external `ip:port` while a mapping is open.
+pub fn external_address() -> Option<String> {
+    MAPPING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|mapping| format!("{}:{}", mapping.external_ip, mapping.port))
+}
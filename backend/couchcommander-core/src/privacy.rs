@@ -0,0 +1,157 @@
+//! Whether any app on the host is using the camera or microphone, for an
+//! "on air" light on the remote. Changes are pushed on the `privacy` topic.
+//!
+//! Linux checks for open `/dev/video*` devices and recording streams in
+//! PulseAudio or PipeWire. It can only see processes of the user running
+//! CouchCommander. Windows reads the same in-use records that drive its
+//! own privacy icons. macOS has no public API for this yet.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Indicators {
+    pub camera: bool,
+    pub microphone: bool,
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::process::Command;
+
+    use super::Indicators;
+    use crate::input_backend::command_available;
+
+    fn run(program: &str, args: &[&str]) -> Result<String, String> {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+        if !output.status.success() {
+            return Err(format!("{} failed", program));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    // Any process with a video device open
+    fn camera_in_use() -> bool {
+        let Ok(processes) = std::fs::read_dir("/proc") else {
+            return false;
+        };
+        processes
+            .flatten()
+            .filter(|process| {
+                let name = process.file_name();
+                name.to_string_lossy().chars().all(|c| c.is_ascii_digit())
+            })
+            .filter_map(|process| std::fs::read_dir(process.path().join("fd")).ok())
+            .flat_map(|fds| fds.flatten())
+            .filter_map(|fd| std::fs::read_link(fd.path()).ok())
+            .any(|target| target.starts_with("/dev/video"))
+    }
+
+    // Recording streams, leaving out ones that capture what's playing
+    // (monitor sources), like level meters do
+    fn pulse_recording() -> Result<bool, String> {
+        let monitors: Vec<String> = run("pactl", &["list", "short", "sources"])?
+            .lines()
+            .filter(|line| {
+                line.split_whitespace()
+                    .nth(1)
+                    .is_some_and(|n| n.ends_with(".monitor"))
+            })
+            .filter_map(|line| line.split_whitespace().next().map(str::to_string))
+            .collect();
+        Ok(run("pactl", &["list", "short", "source-outputs"])?
+            .lines()
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .any(|source| !monitors.iter().any(|monitor| monitor == source)))
+    }
+
+    // Without a sound server, any ALSA capture device that's running
+    fn alsa_recording() -> bool {
+        let Ok(cards) = std::fs::read_dir("/proc/asound") else {
+            return false;
+        };
+        cards
+            .flatten()
+            .filter_map(|card| std::fs::read_dir(card.path()).ok())
+            .flat_map(|devices| devices.flatten())
+            .filter(|device| device.file_name().to_string_lossy().ends_with('c'))
+            .filter_map(|device| std::fs::read_dir(device.path()).ok())
+            .flat_map(|subdevices| subdevices.flatten())
+            .filter_map(|subdevice| std::fs::read_to_string(subdevice.path().join("status")).ok())
+            .any(|status| status.contains("state: RUNNING"))
+    }
+
+    pub fn read() -> Result<Indicators, String> {
+        let microphone = if command_available("pactl") {
+            pulse_recording()?
+        } else {
+            alsa_recording()
+        };
+        Ok(Indicators {
+            camera: camera_in_use(),
+            microphone,
+        })
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    use super::Indicators;
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    // An app is using the device while its last use has a start but no stop
+    const SCRIPT: &str = r#"
+        $store = 'HKCU:\Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore';
+        foreach ($device in 'webcam', 'microphone') {
+            $active = Get-ChildItem "$store\$device" -Recurse -ErrorAction SilentlyContinue |
+                Where-Object { $_.GetValue('LastUsedTimeStart') -gt 0 -and $_.GetValue('LastUsedTimeStop') -eq 0 };
+            "$device=$([bool]$active)"
+        }"#;
+
+    pub fn read() -> Result<Indicators, String> {
+        let output = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .map_err(|e| format!("Failed to run powershell: {}", e))?;
+        if !output.status.success() {
+            return Err("Could not read camera and microphone use".to_string());
+        }
+        let output = String::from_utf8_lossy(&output.stdout);
+        let in_use = |device: &str| {
+            output.lines().any(|line| {
+                line.trim()
+                    .eq_ignore_ascii_case(&format!("{}=True", device))
+            })
+        };
+        Ok(Indicators {
+            camera: in_use("webcam"),
+            microphone: in_use("microphone"),
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::Indicators;
+
+    pub fn read() -> Result<Indicators, String> {
+        Err("Camera and microphone use can't be read on macOS yet".to_string())
+    }
+}
+
+/// Whether this platform can tell when the camera or microphone is in use.
+pub fn supported() -> bool {
+    cfg!(any(target_os = "linux", target_os = "windows"))
+}
+
+pub async fn current() -> Result<Indicators, String> {
+    tokio::task::spawn_blocking(platform::read)
+        .await
+        .map_err(|e| format!("Privacy task panicked: {:?}", e))?
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{AuthMode, FeatureToggles};
+use crate::input_worker::InputHealth;
+use crate::self_test::SelfTest;
+
+/// Result of a command, returned to the desktop UI and the remotes alike.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommandResponse {
+    pub status: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerStatus {
+    pub running: bool,
+    pub port: u16,
+    pub clients: usize,
+    pub local_ip: Option<String>,
+    pub mdns_advertising: bool,
+    pub tls: bool,
+    pub input_paused: bool,
+    // False on macOS until the accessibility grant is given
+    pub permissions_ok: bool,
+    // Extra listeners from the settings that are running
+    pub listeners: Vec<ListenerStatus>,
+    // Router address forwarded to the server, see `port_mapping`
+    pub external_address: Option<String>,
+    pub input_health: InputHealth,
+    // What worked when the server last started, `None` while it's checking
+    pub self_test: Option<SelfTest>,
+}
+
+/// An extra listener and what it lets its remotes do.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListenerStatus {
+    pub name: String,
+    pub address: String,
+    pub auth_mode: AuthMode,
+    pub features: FeatureToggles,
+}
+
+/// A command sent by a remote over the WebSocket.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSocketCommand {
+    pub id: Option<String>,
+    pub command: String,
+    pub data: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSocketResponse {
+    pub id: Option<String>,
+    pub status: String,
+    pub message: String,
+    pub data: Option<serde_json::Value>,
+    // Time the server spent on the command, in microseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_us: Option<u64>,
+}
+
+/// What a server-initiated event is about. Clients pick topics with the
+/// `subscribe` and `unsubscribe` commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    Clients,
+    Volume,
+    NowPlaying,
+    Stats,
+    Clipboard,
+    Battery,
+    Schedules,
+    // Which remote controls input, see `control`
+    Control,
+    // Host desktop notifications, when mirroring is on
+    Notifications,
+    // Messages from the host user
+    Messages,
+    // Camera and microphone use on the host, see `privacy`
+    Privacy,
+    // Playback on a cast device, see `cast`
+    Cast,
+    // The application in front on the host, see `frontmost`
+    FrontmostApp,
+    // The remote layout suggested for it, see `layouts`
+    Context,
+    // Commands handled for every remote, see `metrics::publish_activity`
+    Activity,
+}
+
+/// A push to subscribed clients, e.g.
+/// `{"type": "event", "topic": "volume", "data": {"value": 40}}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventMessage {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub topic: Topic,
+    pub data: serde_json::Value,
+}
+
+impl EventMessage {
+    pub fn new(topic: Topic, data: serde_json::Value) -> Self {
+        Self {
+            kind: "event".to_string(),
+            topic,
+            data,
+        }
+    }
+}
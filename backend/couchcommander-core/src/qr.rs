@@ -0,0 +1,35 @@
+use base64::{engine::general_purpose, Engine as _};
+use image::{DynamicImage, ImageFormat, Luma};
+use qrcode::render::unicode;
+use qrcode::QrCode;
+use std::io::Cursor;
+
+/// QR code for `url` as a PNG `data:` URL, ready for an `<img>` tag.
+pub fn png_data_url(url: &str) -> Result<String, String> {
+    let qr_code = QrCode::new(url).map_err(|e| format!("Failed to generate QR code: {:?}", e))?;
+
+    let image = qr_code
+        .render::<Luma<u8>>()
+        .min_dimensions(320, 320)
+        .build();
+
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(image)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode QR code: {}", e))?;
+
+    let base64_string = general_purpose::STANDARD.encode(&png);
+    Ok(format!("data:image/png;base64,{}", base64_string))
+}
+
+/// QR code for `url` drawn with block characters, for printing to a terminal.
+pub fn terminal(url: &str) -> Result<String, String> {
+    let code = QrCode::new(url).map_err(|e| format!("Failed to generate QR code: {:?}", e))?;
+
+    // Inverted so the code scans on dark terminal backgrounds
+    Ok(code
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build())
+}
@@ -0,0 +1,301 @@
+//! Relay mode, for reaching the host from a phone on cellular without port
+//! forwarding. Host and remote both connect out to a relay, which pairs them
+//! up by room and copies bytes between them. The remote then runs TLS with
+//! the host inside that pipe, checking the fingerprint from pairing, so the
+//! relay only ever sees ciphertext.
+//!
+//! Each side sends one line before the bytes flow:
+//! - the host's control connection sends `HOST <room>` and gets `OK` or
+//!   `ERR <reason>`, then `CONNECT <id>` for every remote that joins
+//! - a remote sends `JOIN <room>`, answered with `ERR <reason>` on failure only
+//! - the host answers each `CONNECT` on a new connection with
+//!   `ACCEPT <room> <id>`
+//!
+//! `serve` runs a relay, for `--relay <address>` on a self-hosted machine.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::AbortHandle;
+use tracing::{debug, info, warn};
+
+use crate::config::RelaySettings;
+use crate::websocket::WebSocketServer;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_LINE_BYTES: usize = 256;
+// Keeps NAT entries for the idle control connection alive
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(90);
+const RETRY_MIN: Duration = Duration::from_secs(2);
+const RETRY_MAX: Duration = Duration::from_secs(60);
+pub const MIN_ROOM_LENGTH: usize = 16;
+
+// Relay side: hosts by room, and remotes waiting for their host to accept
+#[derive(Default)]
+struct Rooms {
+    hosts: HashMap<String, UnboundedSender<u64>>,
+    waiting: HashMap<u64, (String, TcpStream)>,
+}
+
+lazy_static::lazy_static! {
+    static ref ROOMS: Mutex<Rooms> = Mutex::new(Rooms::default());
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+// Host side: the task holding the control connection
+static HOST_TASK: Mutex<Option<AbortHandle>> = Mutex::new(None);
+
+// One handshake line, read a byte at a time so nothing after it is consumed
+async fn read_line<S: AsyncRead + Unpin>(stream: &mut S) -> Result<String, String> {
+    let mut line = Vec::new();
+    loop {
+        let byte = stream
+            .read_u8()
+            .await
+            .map_err(|e| format!("connection closed: {}", e))?;
+        if byte == b'\n' {
+            break;
+        }
+        if line.len() >= MAX_LINE_BYTES {
+            return Err("line too long".to_string());
+        }
+        line.push(byte);
+    }
+    Ok(String::from_utf8_lossy(&line).trim().to_string())
+}
+
+async fn write_line(stream: &mut TcpStream, line: &str) -> Result<(), String> {
+    stream
+        .write_all(format!("{}\n", line).as_bytes())
+        .await
+        .map_err(|e| format!("write failed: {}", e))
+}
+
+// Connect back to the relay for one remote and serve it like a local client
+async fn accept(server: Arc<WebSocketServer>, settings: RelaySettings, id: String) {
+    let result = async {
+        let mut stream = TcpStream::connect(&settings.address)
+            .await
+            .map_err(|e| format!("failed to connect: {}", e))?;
+        write_line(&mut stream, &format!("ACCEPT {} {}", settings.room, id)).await?;
+        let relay = stream
+            .peer_addr()
+            .map_err(|e| format!("failed to read the relay address: {}", e))?;
+        server.serve_relayed(stream, relay);
+        Ok::<_, String>(())
+    };
+    if let Err(e) = result.await {
+        warn!("Failed to accept relayed remote {}: {}", id, e);
+    }
+}
+
+// Hold one control connection until it drops
+async fn host_session(
+    server: &Arc<WebSocketServer>,
+    settings: &RelaySettings,
+) -> Result<(), String> {
+    let mut control = TcpStream::connect(&settings.address)
+        .await
+        .map_err(|e| format!("failed to connect: {}", e))?;
+    write_line(&mut control, &format!("HOST {}", settings.room)).await?;
+    let reply = tokio::time::timeout(HANDSHAKE_TIMEOUT, read_line(&mut control))
+        .await
+        .map_err(|_| "no answer".to_string())??;
+    if reply != "OK" {
+        return Err(reply.trim_start_matches("ERR ").to_string());
+    }
+    info!("Reachable through relay {}", settings.address);
+
+    loop {
+        let line = tokio::time::timeout(CONTROL_TIMEOUT, read_line(&mut control))
+            .await
+            .map_err(|_| "relay stopped answering".to_string())??;
+        match line.strip_prefix("CONNECT ") {
+            Some(id) => {
+                debug!("Remote {} joined through the relay", id);
+                tokio::spawn(accept(Arc::clone(server), settings.clone(), id.to_string()));
+            }
+            None if line == "PING" => {}
+            None => debug!("Ignoring relay message: {}", line),
+        }
+    }
+}
+
+async fn host_loop(server: Arc<WebSocketServer>, settings: RelaySettings) {
+    let mut delay = RETRY_MIN;
+    loop {
+        match host_session(&server, &settings).await {
+            Ok(()) => delay = RETRY_MIN,
+            Err(e) => warn!("Relay {}: {}", settings.address, e),
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(RETRY_MAX);
+    }
+}
+
+/// Keep `server` registered with the relay until `stop`, reconnecting when
+/// the relay drops.
+pub fn start(server: Arc<WebSocketServer>, settings: RelaySettings, runtime: &Runtime) {
+    stop();
+    let task = runtime.spawn(host_loop(server, settings));
+    *HOST_TASK.lock().unwrap_or_else(|e| e.into_inner()) = Some(task.abort_handle());
+}
+
+pub fn stop() {
+    if let Some(task) = HOST_TASK.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        task.abort();
+    }
+}
+
+async fn reject(mut stream: TcpStream, reason: &str) -> Result<(), String> {
+    let _ = write_line(&mut stream, &format!("ERR {}", reason)).await;
+    Err(reason.to_string())
+}
+
+// Relay side of a host's control connection
+async fn relay_host(mut stream: TcpStream, room: String) -> Result<(), String> {
+    if room.len() < MIN_ROOM_LENGTH {
+        return reject(stream, "room id too short").await;
+    }
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let taken = {
+        let mut rooms = ROOMS.lock().unwrap_or_else(|e| e.into_inner());
+        let taken = rooms.hosts.get(&room).is_some_and(|host| !host.is_closed());
+        if !taken {
+            rooms.hosts.insert(room.clone(), tx.clone());
+        }
+        taken
+    };
+    if taken {
+        return reject(stream, "room is taken").await;
+    }
+    let room_prefix: String = room.chars().take(4).collect();
+    info!("Host joined room {}…", room_prefix);
+
+    let result = async {
+        write_line(&mut stream, "OK").await?;
+        let mut ping = tokio::time::interval(PING_INTERVAL);
+        let mut buffer = [0u8; 64];
+        loop {
+            tokio::select! {
+                id = rx.recv() => {
+                    let Some(id) = id else {
+                        return Ok(());
+                    };
+                    write_line(&mut stream, &format!("CONNECT {}", id)).await?;
+                }
+                _ = ping.tick() => write_line(&mut stream, "PING").await?,
+                // The host sends nothing more, a read ending means it left
+                read = stream.read(&mut buffer) => match read {
+                    Ok(0) | Err(_) => return Ok(()),
+                    Ok(_) => {}
+                },
+            }
+        }
+    }
+    .await;
+
+    let mut rooms = ROOMS.lock().unwrap_or_else(|e| e.into_inner());
+    if rooms
+        .hosts
+        .get(&room)
+        .is_some_and(|host| host.same_channel(&tx))
+    {
+        rooms.hosts.remove(&room);
+    }
+    result
+}
+
+// Relay side of a remote: park it until its host connects back for it
+async fn relay_join(stream: TcpStream, room: &str) -> Result<(), String> {
+    let host = ROOMS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .hosts
+        .get(room)
+        .cloned();
+    let Some(host) = host else {
+        return reject(stream, "no host in this room").await;
+    };
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    ROOMS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .waiting
+        .insert(id, (room.to_string(), stream));
+    let _ = host.send(id);
+
+    tokio::time::sleep(HANDSHAKE_TIMEOUT).await;
+    let unclaimed = ROOMS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .waiting
+        .remove(&id);
+    match unclaimed {
+        Some((_, stream)) => reject(stream, "host didn't answer").await,
+        None => Ok(()),
+    }
+}
+
+// Relay side of a host connecting back: splice it to the waiting remote
+async fn relay_accept(mut stream: TcpStream, room: &str, id: &str) -> Result<(), String> {
+    let id: u64 = id.parse().map_err(|_| "invalid id".to_string())?;
+    // The room is the secret, an id alone doesn't claim a remote
+    let remote = {
+        let mut rooms = ROOMS.lock().unwrap_or_else(|e| e.into_inner());
+        match rooms.waiting.remove(&id) {
+            Some((waiting_room, remote)) if waiting_room == room => Some(remote),
+            Some(waiting) => {
+                rooms.waiting.insert(id, waiting);
+                None
+            }
+            None => None,
+        }
+    };
+    let Some(mut remote) = remote else {
+        return reject(stream, "no such remote").await;
+    };
+    tokio::io::copy_bidirectional(&mut remote, &mut stream)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn relay_connection(mut stream: TcpStream) -> Result<(), String> {
+    let line = tokio::time::timeout(HANDSHAKE_TIMEOUT, read_line(&mut stream))
+        .await
+        .map_err(|_| "handshake timed out".to_string())??;
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["HOST", room] => relay_host(stream, room.to_string()).await,
+        ["JOIN", room] => relay_join(stream, room).await,
+        ["ACCEPT", room, id] => relay_accept(stream, room, id).await,
+        _ => reject(stream, "unknown request").await,
+    }
+}
+
+/// Run a relay on `address` until the process exits.
+pub async fn serve(address: SocketAddr) -> Result<(), String> {
+    let listener = TcpListener::bind(address)
+        .await
+        .map_err(|e| format!("Failed to bind the relay on {}: {}", address, e))?;
+    info!("Relay listening on {}", address);
+    loop {
+        let (stream, peer) = listener
+            .accept()
+            .await
+            .map_err(|e| format!("Relay stopped accepting: {}", e))?;
+        tokio::spawn(async move {
+            if let Err(e) = relay_connection(stream).await {
+                debug!("Relay connection from {} ended: {}", peer, e);
+            }
+        });
+    }
+}
@@ -0,0 +1,97 @@
+//! Hold-to-repeat. While a volume or arrow button is held the remote sends
+//! one `start_repeat`, and one `stop_repeat` when it's let go; the host runs
+//! the command on a steady interval in between, so a jittery network doesn't
+//! make the volume or the cursor stutter. Each run goes through the same
+//! checks as a command sent by the client itself.
+//!
+//! A lost `stop_repeat` mustn't turn the volume all the way up, so a repeat
+//! also ends after `MAX_REPEAT`, on the first failing run and when the client
+//! disconnects.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
+use tracing::debug;
+
+use crate::input_backend::SystemBackend;
+use crate::protocol::WebSocketCommand;
+use crate::websocket;
+
+pub const MIN_INTERVAL_MS: u64 = 30;
+pub const MAX_INTERVAL_MS: u64 = 2_000;
+pub const DEFAULT_INTERVAL_MS: u64 = 150;
+const MAX_REPEAT: Duration = Duration::from_secs(15);
+
+// Commands that make sense to hold down
+const REPEATABLE: &[&str] = &[
+    "volume_up",
+    "volume_down",
+    "brightness_up",
+    "brightness_down",
+    "send_key",
+    "scroll",
+    "mouse_move",
+];
+
+lazy_static::lazy_static! {
+    // Running repeats by client, with the id each was started under
+    static ref REPEATS: Mutex<HashMap<String, (u64, AbortHandle)>> = Mutex::new(HashMap::new());
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Run `command` for a client now and then every `interval_ms` until
+/// [`stop`], replacing a repeat it already had. Returns the interval used.
+pub fn start(client_id: &str, command: WebSocketCommand, interval_ms: u64) -> Result<u64, String> {
+    if !REPEATABLE.contains(&command.command.as_str()) {
+        return Err(format!("Command '{}' can't be repeated", command.command));
+    }
+    let interval_ms = interval_ms.clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS);
+    let client = client_id.to_string();
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let task = tokio::spawn(async move {
+        let started = Instant::now();
+        let mut ticker = tokio::time::interval(Duration::from_millis(interval_ms));
+        // Late runs are skipped rather than bunched up after a slow one
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        while started.elapsed() < MAX_REPEAT {
+            ticker.tick().await;
+            let response = websocket::dispatch::<SystemBackend>(&client, command.clone()).await;
+            if response.status == "error" {
+                debug!(
+                    "Repeat of {} stopped: {}",
+                    command.command, response.message
+                );
+                break;
+            }
+        }
+        // Leave a newer repeat of the same client alone
+        let mut repeats = REPEATS.lock().unwrap_or_else(|e| e.into_inner());
+        if repeats
+            .get(&client)
+            .is_some_and(|(running, _)| *running == id)
+        {
+            repeats.remove(&client);
+        }
+    });
+
+    let previous = REPEATS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(client_id.to_string(), (id, task.abort_handle()));
+    if let Some((_, previous)) = previous {
+        previous.abort();
+    }
+    Ok(interval_ms)
+}
+
+/// Stop a client's repeat. False if it had none running.
+pub fn stop(client_id: &str) -> bool {
+    let repeat = REPEATS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(client_id);
+    repeat.map(|(_, task)| task.abort()).is_some()
+}
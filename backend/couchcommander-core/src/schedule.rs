@@ -0,0 +1,273 @@
+//! Commands scheduled to run later, e.g. a sleep timer that pauses playback
+//! and sleeps the display in 45 minutes. Schedules are saved to
+//! `schedules.json` so they survive a restart, and every remote is told
+//! when one runs.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::input_backend::SystemBackend;
+use crate::protocol::{Topic, WebSocketCommand};
+use crate::{aliases, config, devices, events, websocket};
+
+const SCHEDULES_FILE: &str = "schedules.json";
+const MAX_SCHEDULES: usize = 50;
+const MAX_COMMANDS: usize = 10;
+const MAX_DELAY_SECS: u64 = 7 * 24 * 60 * 60;
+// Schedules that were due while the app was closed are dropped past this
+const MISSED_GRACE_SECS: u64 = 5 * 60;
+const TICK: Duration = Duration::from_secs(1);
+// Client id scheduled commands run as
+const SCHEDULER_CLIENT: &str = "scheduler";
+
+lazy_static::lazy_static! {
+    static ref SCHEDULES: Mutex<Vec<Schedule>> = Mutex::new(Vec::new());
+}
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// One command of a schedule, as a remote would send it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledCommand {
+    pub command: String,
+    pub data: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    pub label: Option<String>,
+    // Seconds since the epoch
+    pub run_at: u64,
+    pub commands: Vec<ScheduledCommand>,
+    // The device that scheduled it, whose limits still apply when it runs
+    #[serde(default)]
+    pub device: Option<String>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or_default()
+}
+
+fn save(schedules: &[Schedule]) {
+    let path = config::config_dir().join(SCHEDULES_FILE);
+    let result = std::fs::create_dir_all(config::config_dir())
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::to_string_pretty(schedules).map_err(|e| e.to_string()))
+        .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!("Failed to save schedules to {:?}: {}", path, e);
+    }
+}
+
+fn load() {
+    let path = config::config_dir().join(SCHEDULES_FILE);
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return;
+    };
+    let schedules: Vec<Schedule> = match serde_json::from_str(&contents) {
+        Ok(schedules) => schedules,
+        Err(e) => {
+            warn!("Ignoring invalid schedules file {:?}: {}", path, e);
+            return;
+        }
+    };
+
+    let now = now();
+    let (kept, missed): (Vec<_>, Vec<_>) = schedules
+        .into_iter()
+        .partition(|schedule| schedule.run_at + MISSED_GRACE_SECS >= now);
+    for schedule in &missed {
+        info!("Dropping schedule {} missed while closed", schedule.id);
+    }
+    info!("Loaded {} schedules", kept.len());
+    *SCHEDULES.lock().unwrap_or_else(|e| e.into_inner()) = kept.clone();
+    if !missed.is_empty() {
+        save(&kept);
+    }
+}
+
+// Whether a scheduled command may run, with its aliases resolved. Checked
+// again when it runs, as an alias can change in between
+fn check(scheduled: &ScheduledCommand) -> Result<(), String> {
+    let command = WebSocketCommand {
+        id: None,
+        command: scheduled.command.clone(),
+        data: scheduled.data.clone(),
+    };
+    for step in aliases::expand(command) {
+        if matches!(
+            step.command.as_str(),
+            "schedule_command" | "list_schedules" | "cancel_schedule"
+        ) {
+            return Err("Schedules can't manage other schedules".to_string());
+        }
+        // It would be saved to disk and shown to every remote
+        if websocket::is_sensitive(&step) {
+            return Err("Sensitive text can't be scheduled".to_string());
+        }
+    }
+    Ok(())
+}
+
+// Whether the device that scheduled a command may run it now, e.g. a
+// command scheduled just before its allowed hours end
+fn device_allows(device: &str, scheduled: &ScheduledCommand) -> Result<(), String> {
+    if devices::is_revoked(device) {
+        return Err("The device that scheduled this was revoked".to_string());
+    }
+    let restrictions = devices::restrictions(device);
+    let command = WebSocketCommand {
+        id: None,
+        command: scheduled.command.clone(),
+        data: None,
+    };
+    aliases::expand(command)
+        .iter()
+        .try_for_each(|step| restrictions.check(&step.command, false))
+}
+
+/// Run `commands` in `delay_secs` seconds, on behalf of `device` if a
+/// device scheduled them.
+pub fn add(
+    commands: Vec<ScheduledCommand>,
+    delay_secs: u64,
+    label: Option<String>,
+    device: Option<String>,
+) -> Result<Schedule, String> {
+    if commands.is_empty() || commands.len() > MAX_COMMANDS {
+        return Err(format!(
+            "A schedule needs between 1 and {} commands",
+            MAX_COMMANDS
+        ));
+    }
+    if delay_secs > MAX_DELAY_SECS {
+        return Err("Schedules can be at most a week out".to_string());
+    }
+    let settings = config::current();
+    for scheduled in &commands {
+        check(scheduled)?;
+        if !settings.command_allowed(&scheduled.command) {
+            return Err(format!(
+                "Command '{}' is disabled in settings",
+                scheduled.command
+            ));
+        }
+    }
+
+    let schedule = Schedule {
+        id: Uuid::new_v4().to_string(),
+        label,
+        run_at: now() + delay_secs,
+        commands,
+        device,
+    };
+    let mut schedules = SCHEDULES.lock().unwrap_or_else(|e| e.into_inner());
+    if schedules.len() >= MAX_SCHEDULES {
+        return Err("Too many schedules, cancel one first".to_string());
+    }
+    schedules.push(schedule.clone());
+    schedules.sort_by_key(|schedule| schedule.run_at);
+    save(&schedules);
+    info!("Scheduled {:?} in {} s", schedule.id, delay_secs);
+    Ok(schedule)
+}
+
+pub fn list() -> Vec<Schedule> {
+    SCHEDULES.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn cancel(id: &str) -> Result<Schedule, String> {
+    let mut schedules = SCHEDULES.lock().unwrap_or_else(|e| e.into_inner());
+    let index = schedules
+        .iter()
+        .position(|schedule| schedule.id == id)
+        .ok_or_else(|| format!("No schedule {}", id))?;
+    let schedule = schedules.remove(index);
+    save(&schedules);
+    info!("Cancelled schedule {}", id);
+    Ok(schedule)
+}
+
+fn take_due() -> Vec<Schedule> {
+    let now = now();
+    let mut schedules = SCHEDULES.lock().unwrap_or_else(|e| e.into_inner());
+    let (due, pending): (Vec<_>, Vec<_>) = schedules
+        .drain(..)
+        .partition(|schedule| schedule.run_at <= now);
+    *schedules = pending;
+    if !due.is_empty() {
+        save(&schedules);
+    }
+    due
+}
+
+async fn execute(schedule: Schedule) {
+    info!("Running schedule {}", schedule.id);
+    let mut results = Vec::new();
+    for scheduled in schedule.commands.iter().cloned() {
+        let allowed = check(&scheduled).and_then(|_| match &schedule.device {
+            Some(device) => device_allows(device, &scheduled),
+            None => Ok(()),
+        });
+        let (status, message) = match allowed {
+            Ok(()) => {
+                let command = WebSocketCommand {
+                    id: None,
+                    command: scheduled.command.clone(),
+                    data: scheduled.data,
+                };
+                let response =
+                    websocket::dispatch::<SystemBackend>(SCHEDULER_CLIENT, command).await;
+                (response.status, response.message)
+            }
+            Err(message) => ("error".to_string(), message),
+        };
+        if status == "error" {
+            warn!("Scheduled {} failed: {}", scheduled.command, message);
+        }
+        results.push(serde_json::json!({
+            "command": scheduled.command,
+            "status": status,
+            "message": message,
+        }));
+    }
+
+    let message = match &schedule.label {
+        Some(label) => format!("Ran scheduled \"{}\"", label),
+        None => "Ran scheduled commands".to_string(),
+    };
+    events::publish(
+        Topic::Schedules,
+        serde_json::json!({
+            "event": "executed",
+            "message": message,
+            "schedule": schedule,
+            "results": results,
+        }),
+    );
+}
+
+/// Load saved schedules and start running them, once per process.
+pub fn start() {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    load();
+    tokio::spawn(async {
+        let mut ticker = tokio::time::interval(TICK);
+        loop {
+            ticker.tick().await;
+            for schedule in take_due() {
+                execute(schedule).await;
+            }
+        }
+    });
+}
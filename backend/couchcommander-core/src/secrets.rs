@@ -0,0 +1,248 @@
+//! Secrets kept in the OS keychain instead of in plain text: from
+//! `settings.toml` the API and admin tokens, the media server token, the
+//! relay room and the linked hosts' tokens, and the session tokens of
+//! paired remotes (see `pairing`). The keychain is reached through its
+//! command line tool: `security` on macOS, `secret-tool` (libsecret) on
+//! Linux and the PasswordVault through PowerShell on Windows. Where none
+//! works, settings secrets stay in the settings file and session tokens only
+//! last until the app quits.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const SERVICE: &str = "CouchCommander";
+
+// Run a keychain tool, passing the secret on stdin so it never shows up in
+// the process list
+fn run(command: &mut Command, input: Option<&str>) -> Result<Output, String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run the keychain tool: {}", e))?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| "Failed to open the keychain tool's input".to_string())?;
+    if let Some(input) = input {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("Failed to pass the secret: {}", e))?;
+    }
+    drop(stdin);
+    child
+        .wait_with_output()
+        .map_err(|e| format!("Keychain tool failed: {}", e))
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).trim().to_string()
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::process::Command;
+
+    use super::{run, stderr, SERVICE};
+
+    // `security` exits with this when there's no such item
+    const NOT_FOUND: i32 = 44;
+
+    fn quote(text: &str) -> String {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    pub fn get(name: &str) -> Result<Option<String>, String> {
+        let output = run(
+            Command::new("security").args([
+                "find-generic-password",
+                "-s",
+                SERVICE,
+                "-a",
+                name,
+                "-w",
+            ]),
+            None,
+        )?;
+        match output.status.code() {
+            Some(0) => Ok(Some(
+                String::from_utf8_lossy(&output.stdout)
+                    .trim_end_matches('\n')
+                    .to_string(),
+            )),
+            Some(NOT_FOUND) => Ok(None),
+            _ => Err(stderr(&output)),
+        }
+    }
+
+    pub fn set(name: &str, value: &str) -> Result<(), String> {
+        // In interactive mode the password comes from stdin, not the arguments
+        let command = format!(
+            "add-generic-password -U -s {} -a {} -w {}\n",
+            quote(SERVICE),
+            quote(name),
+            quote(value)
+        );
+        let output = run(Command::new("security").arg("-i"), Some(&command))?;
+        let error = stderr(&output);
+        if !output.status.success() || !error.is_empty() {
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    pub fn delete(name: &str) -> Result<(), String> {
+        let output = run(
+            Command::new("security").args(["delete-generic-password", "-s", SERVICE, "-a", name]),
+            None,
+        )?;
+        match output.status.code() {
+            Some(0) | Some(NOT_FOUND) => Ok(()),
+            _ => Err(stderr(&output)),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::process::Command;
+
+    use super::{run, stderr, SERVICE};
+
+    fn secret_tool() -> Command {
+        Command::new("secret-tool")
+    }
+
+    pub fn get(name: &str) -> Result<Option<String>, String> {
+        let output = run(
+            secret_tool().args(["lookup", "service", SERVICE, "key", name]),
+            None,
+        )?;
+        let error = stderr(&output);
+        match (output.status.success(), error.is_empty()) {
+            (true, _) => Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned())),
+            // A missing item fails without saying anything
+            (false, true) => Ok(None),
+            (false, false) => Err(error),
+        }
+    }
+
+    pub fn set(name: &str, value: &str) -> Result<(), String> {
+        let label = format!("{} {}", SERVICE, name);
+        let output = run(
+            secret_tool().args(["store", "--label", &label, "service", SERVICE, "key", name]),
+            Some(value),
+        )?;
+        if !output.status.success() {
+            return Err(stderr(&output));
+        }
+        Ok(())
+    }
+
+    pub fn delete(name: &str) -> Result<(), String> {
+        let output = run(
+            secret_tool().args(["clear", "service", SERVICE, "key", name]),
+            None,
+        )?;
+        let error = stderr(&output);
+        if !output.status.success() && !error.is_empty() {
+            return Err(error);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    use super::{run, stderr, SERVICE};
+
+    // Keeps powershell from flashing a console window
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    const OPEN_VAULT: &str = "[void][Windows.Security.Credentials.PasswordVault,\
+        Windows.Security.Credentials,ContentType=WindowsRuntime]; \
+        $vault = New-Object Windows.Security.Credentials.PasswordVault;";
+
+    // Names are ours and never quoted text from settings
+    fn powershell(script: &str, input: Option<&str>) -> Result<String, String> {
+        let script = format!("{} {}", OPEN_VAULT, script);
+        let output = run(
+            Command::new("powershell")
+                .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+                .creation_flags(CREATE_NO_WINDOW),
+            input,
+        )?;
+        if !output.status.success() {
+            return Err(stderr(&output));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    pub fn get(name: &str) -> Result<Option<String>, String> {
+        let value = powershell(
+            &format!(
+                "try {{ $c = $vault.Retrieve('{}', '{}'); $c.RetrievePassword(); $c.Password }} \
+                 catch {{ }}",
+                SERVICE, name
+            ),
+            None,
+        )?;
+        Ok(Some(value).filter(|value| !value.is_empty()))
+    }
+
+    pub fn set(name: &str, value: &str) -> Result<(), String> {
+        delete(name)?;
+        powershell(
+            &format!(
+                "$secret = [Console]::In.ReadToEnd(); \
+                 $vault.Add((New-Object Windows.Security.Credentials.PasswordCredential(\
+                 '{}', '{}', $secret)))",
+                SERVICE, name
+            ),
+            Some(value),
+        )
+        .map(|_| ())
+    }
+
+    pub fn delete(name: &str) -> Result<(), String> {
+        powershell(
+            &format!(
+                "try {{ $vault.Remove($vault.Retrieve('{}', '{}')) }} catch {{ }}",
+                SERVICE, name
+            ),
+            None,
+        )
+        .map(|_| ())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+mod platform {
+    pub fn get(_name: &str) -> Result<Option<String>, String> {
+        Err("No keychain on this platform".to_string())
+    }
+
+    pub fn set(_name: &str, _value: &str) -> Result<(), String> {
+        Err("No keychain on this platform".to_string())
+    }
+
+    pub fn delete(_name: &str) -> Result<(), String> {
+        Err("No keychain on this platform".to_string())
+    }
+}
+
+/// The secret stored under `name`, if there is one.
+pub fn get(name: &str) -> Result<Option<String>, String> {
+    platform::get(name)
+}
+
+/// Store `value` under `name`. An empty value removes it.
+pub fn set(name: &str, value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return platform::delete(name);
+    }
+    platform::set(name, value)
+}
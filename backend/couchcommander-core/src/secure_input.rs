@@ -0,0 +1,71 @@
+//! Do-not-type zones. While a password field has focus on the host, remotes
+//! other than admins can't type, so they can neither read along with a
+//! password being entered nor garble it. Focus is read through the
+//! accessibility API on macOS and UI Automation on Windows; elsewhere
+//! nothing is refused.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Typing comes in bursts, which share one look at the focused element
+const CHECK_TTL: Duration = Duration::from_millis(250);
+
+static LAST_CHECK: Mutex<Option<(Instant, bool)>> = Mutex::new(None);
+
+#[cfg(target_os = "macos")]
+mod platform {
+    pub fn password_field_focused() -> bool {
+        crate::macos_ax::secure_field_focused()
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use tracing::debug;
+
+    pub fn password_field_focused() -> bool {
+        crate::windows_uia::password_field_focused().unwrap_or_else(|e| {
+            debug!("Couldn't check the focused element: {}", e);
+            false
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    pub fn password_field_focused() -> bool {
+        false
+    }
+}
+
+/// Commands that send keys to whatever has focus, including gamepad buttons
+/// bound to keys and held modifiers.
+pub fn types_text(command: &str) -> bool {
+    matches!(
+        command,
+        "text_input"
+            | "text_stream"
+            | "send_key"
+            | "send_raw_key"
+            | "key_down"
+            | "edit_action"
+            | "toggle_modifier_key"
+            | "gamepad_button"
+            | "open_search"
+            | "find_in_page"
+            | "test_space_key"
+    )
+}
+
+/// Whether a password field has keyboard focus right now.
+pub fn password_field_focused() -> bool {
+    let mut last = LAST_CHECK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((checked_at, focused)) = *last {
+        if checked_at.elapsed() < CHECK_TTL {
+            return focused;
+        }
+    }
+    let focused = platform::password_field_focused();
+    *last = Some((Instant::now(), focused));
+    focused
+}
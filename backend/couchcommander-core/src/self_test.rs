@@ -0,0 +1,90 @@
+//! Probes each backend once when the server starts, so remotes learn up
+//! front which controls work on this host instead of finding out from a
+//! failing command.
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use crate::input_backend::InputBackend;
+use crate::{clipboard, color_picker, input, input_worker};
+
+// A probe stuck on a permission prompt counts as a failure
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Probe {
+    pub available: bool,
+    pub error: Option<String>,
+}
+
+/// What worked when the server started.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelfTest {
+    pub input: Probe,
+    pub volume: Probe,
+    pub brightness: Probe,
+    pub screenshot: Probe,
+    pub clipboard: Probe,
+    // Seconds since the Unix epoch
+    pub checked_at: u64,
+}
+
+static RESULTS: Mutex<Option<SelfTest>> = Mutex::new(None);
+
+async fn probe<T>(name: &str, check: impl Future<Output = Result<T, String>>) -> Probe {
+    let result = match tokio::time::timeout(PROBE_TIMEOUT, check).await {
+        Ok(result) => result.map(|_| ()),
+        Err(_) => Err("Timed out".to_string()),
+    };
+    match result {
+        Ok(()) => Probe {
+            available: true,
+            error: None,
+        },
+        Err(e) => {
+            warn!("Self-test: {} unavailable: {}", name, e);
+            Probe {
+                available: false,
+                error: Some(e),
+            }
+        }
+    }
+}
+
+/// The results of the last self-test, `None` until the first one finishes.
+pub fn results() -> Option<SelfTest> {
+    RESULTS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Probe every backend at once and keep the results for `results`.
+pub async fn run<B: InputBackend>() -> SelfTest {
+    let (input, volume, brightness, screenshot, clipboard) = tokio::join!(
+        probe("input", input_worker::run::<B, _, _>(|_| Ok(()))),
+        probe("volume", input::volume_get()),
+        probe("brightness", input::brightness_get()),
+        // The top left pixel is enough to know the screen can be captured
+        probe("screenshot", color_picker::pick(0, 0)),
+        probe("clipboard", async {
+            tokio::task::spawn_blocking(clipboard::get)
+                .await
+                .map_err(|e| format!("Clipboard task panicked: {:?}", e))?
+        }),
+    );
+    let results = SelfTest {
+        input,
+        volume,
+        brightness,
+        screenshot,
+        clipboard,
+        checked_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_secs())
+            .unwrap_or_default(),
+    };
+    info!("Self-test finished");
+    *RESULTS.lock().unwrap_or_else(|e| e.into_inner()) = Some(results.clone());
+    results
+}
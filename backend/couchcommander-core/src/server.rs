@@ -0,0 +1,689 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tracing::{error, info, warn};
+
+use crate::config::{AuthMode, MediaServerKind, RelaySettings};
+use crate::input_backend::SystemBackend;
+use crate::protocol::{CommandResponse, ListenerStatus, ServerStatus, Topic};
+use crate::tls::TlsIdentity;
+use crate::websocket::{self, ClientInfo, WebSocketServer};
+use crate::{
+    config, devices, discovery, events, idle, input, input_backend, input_worker, layout, network,
+    pairing, port_mapping, privacy, relay, schedule, self_test, share, tls,
+};
+
+// Global WebSocket server state
+static WEBSOCKET_SERVER: Mutex<Option<Arc<WebSocketServer>>> = Mutex::new(None);
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+// Extra listeners from the settings, started and stopped with the main one
+static EXTRA_LISTENERS: Mutex<Vec<Arc<WebSocketServer>>> = Mutex::new(Vec::new());
+// Bumped whenever guest access starts or ends, so only the latest expiry runs
+static GUEST_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+const MAX_GUEST_HOURS: u64 = 48;
+
+// Whether the server was running and on which port, for `start_on_launch`
+const STATE_FILE: &str = "server_state.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SavedState {
+    running: bool,
+    port: u16,
+}
+
+fn load_state() -> SavedState {
+    std::fs::read_to_string(config::config_dir().join(STATE_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &SavedState) {
+    let path = config::config_dir().join(STATE_FILE);
+    let result = std::fs::create_dir_all(config::config_dir())
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::to_string(state).map_err(|e| e.to_string()))
+        .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!("Failed to save server state to {:?}: {}", path, e);
+    }
+}
+
+// Start the extra listeners next to `main`. One that can't bind is skipped
+// so the main server still comes up
+fn start_extra_listeners(
+    main: &WebSocketServer,
+    tls_identity: Option<TlsIdentity>,
+    runtime: &Runtime,
+) -> Vec<String> {
+    let mut failed = Vec::new();
+    let mut listeners = EXTRA_LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+    for profile in config::current().listeners {
+        let bound = profile
+            .bind_address
+            .parse::<IpAddr>()
+            .map_err(|_| format!("invalid bind address {}", profile.bind_address))
+            .and_then(|ip| websocket::bind_listener(ip, profile.port).map_err(|e| e.to_string()))
+            .and_then(|listener| {
+                let addr = listener.local_addr().map_err(|e| e.to_string())?;
+                Ok((listener, addr))
+            });
+        let (listener, addr) = match bound {
+            Ok(bound) => bound,
+            Err(e) => {
+                warn!("Listener '{}' not started: {}", profile.name, e);
+                failed.push(profile.name);
+                continue;
+            }
+        };
+
+        let server = Arc::new(WebSocketServer::with_profile(
+            addr,
+            tls_identity.clone(),
+            Arc::clone(&main.clients),
+            profile,
+        ));
+        let server_clone = Arc::clone(&server);
+        runtime.spawn(async move {
+            if let Err(e) = server_clone.start(listener).await {
+                error!("Listener on {} failed: {}", server_clone.addr, e);
+            }
+        });
+        listeners.push(server);
+    }
+    failed
+}
+
+pub async fn start_websocket_server(port: Option<u16>) -> Result<CommandResponse, String> {
+    let settings = config::current();
+    let server_port = port.unwrap_or(settings.port);
+    let bind_ip: IpAddr = settings
+        .bind_address
+        .parse()
+        .map_err(|_| format!("Invalid bind address: {}", settings.bind_address))?;
+
+    let bound_port;
+    let mut failed_listeners = Vec::new();
+    {
+        // Held until the server is stored, so two starts can't both bind
+        let mut running = WEBSOCKET_SERVER.lock().unwrap_or_else(|e| e.into_inner());
+        if running.is_some() {
+            return Ok(CommandResponse {
+                status: "info".to_string(),
+                message: "WebSocket server is already running".to_string(),
+            });
+        }
+
+        // Initialize runtime if not exists
+        if RUNTIME.get().is_none() {
+            let rt = Runtime::new().map_err(|e| format!("Failed to create runtime: {}", e))?;
+            let _ = RUNTIME.set(rt);
+        }
+
+        let listener = websocket::bind_listener(bind_ip, server_port).map_err(|e| {
+            format!(
+                "Failed to bind WebSocket server on {}:{}: {}",
+                bind_ip, server_port, e
+            )
+        })?;
+        let bound_addr = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to read bound address: {}", e))?;
+        bound_port = bound_addr.port();
+
+        let tls_identity = if settings.tls_enabled {
+            Some(tls::load_or_generate(tls_subject_names())?)
+        } else {
+            None
+        };
+
+        let server = Arc::new(WebSocketServer::new(bound_addr, tls_identity.clone()));
+        *running = Some(Arc::clone(&server));
+        drop(running);
+
+        let server_clone = Arc::clone(&server);
+        if let Some(rt) = RUNTIME.get() {
+            rt.spawn(async move {
+                if let Err(e) = server_clone.start(listener).await {
+                    error!("WebSocket server error: {}", e);
+                }
+            });
+            // Remotes get the results in their welcome message
+            rt.spawn(self_test::run::<SystemBackend>());
+            failed_listeners = start_extra_listeners(&server, tls_identity, rt);
+            if settings.port_mapping {
+                rt.spawn(open_port_mapping(bound_addr.port()));
+            }
+            if settings.relay.enabled() {
+                relay::start(Arc::clone(&server), settings.relay.clone(), rt);
+            }
+            let _guard = rt.enter();
+            idle::watch();
+            schedule::start();
+        }
+    }
+
+    save_state(&SavedState {
+        running: true,
+        port: bound_port,
+    });
+
+    // Let phones on the LAN find the server without scanning the QR code
+    if settings.mdns_advertise {
+        if let Err(e) = discovery::advertise(bound_port) {
+            warn!("mDNS advertisement failed: {}", e);
+        }
+    }
+
+    if !failed_listeners.is_empty() {
+        return Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!(
+                "WebSocket server started on port {}, but listeners {} couldn't start",
+                bound_port,
+                failed_listeners.join(", ")
+            ),
+        });
+    }
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!(
+            "WebSocket server and web app started on port {}",
+            bound_port
+        ),
+    })
+}
+
+pub async fn stop_websocket_server() -> Result<CommandResponse, String> {
+    let Some(server) = WEBSOCKET_SERVER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+    else {
+        return Ok(CommandResponse {
+            status: "info".to_string(),
+            message: "WebSocket server is not running".to_string(),
+        });
+    };
+    server.shutdown();
+    for listener in EXTRA_LISTENERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain(..)
+    {
+        listener.shutdown();
+    }
+
+    discovery::stop_advertising();
+    relay::stop();
+    port_mapping::close().await;
+    save_state(&SavedState {
+        running: false,
+        ..load_state()
+    });
+
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: "WebSocket server and frontend stopped".to_string(),
+    })
+}
+
+/// With `start_on_launch` set, start the server again if it was running when
+/// the app last quit, on the port it had then. The devices registry is read
+/// up front so revoked remotes are refused from the first connection.
+pub async fn restore_on_launch() {
+    let settings = config::current();
+    if !settings.start_on_launch {
+        return;
+    }
+    info!(
+        "Restoring server state with {} devices and {} aliases",
+        devices::preload(),
+        settings.aliases.len()
+    );
+    let state = load_state();
+    if !state.running {
+        info!("Server was stopped when the app quit, leaving it stopped");
+        return;
+    }
+    match start_websocket_server(Some(state.port)).await {
+        Ok(response) => info!("{}", response.message),
+        Err(e) => error!("Failed to restore the server: {}", e),
+    }
+}
+
+/// Start the server if it is stopped and stop it otherwise, for the tray and hotkeys.
+pub async fn toggle_websocket_server() -> Result<CommandResponse, String> {
+    let running = running_server().is_some();
+    if running {
+        stop_websocket_server().await
+    } else {
+        start_websocket_server(None).await
+    }
+}
+
+pub async fn get_server_status() -> Result<ServerStatus, String> {
+    let local_ip = get_local_ip();
+
+    if let Some(server) = running_server() {
+        Ok(ServerStatus {
+            running: true,
+            port: server.addr.port(),
+            clients: server.get_client_count(),
+            local_ip,
+            mdns_advertising: discovery::advertised_port().is_some(),
+            tls: server.is_tls(),
+            input_paused: input::is_paused(),
+            permissions_ok: input::check_accessibility_permissions(),
+            listeners: listener_statuses(),
+            external_address: port_mapping::external_address(),
+            input_health: input_worker::health(),
+            self_test: self_test::results(),
+        })
+    } else {
+        Ok(ServerStatus {
+            running: false,
+            port: 0,
+            clients: 0,
+            local_ip,
+            mdns_advertising: false,
+            tls: false,
+            input_paused: input::is_paused(),
+            permissions_ok: input::check_accessibility_permissions(),
+            listeners: Vec::new(),
+            external_address: None,
+            input_health: input_worker::health(),
+            self_test: self_test::results(),
+        })
+    }
+}
+
+fn listener_statuses() -> Vec<ListenerStatus> {
+    EXTRA_LISTENERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter_map(|server| {
+            let profile = server.profile.as_ref()?;
+            Some(ListenerStatus {
+                name: profile.name.clone(),
+                address: server.addr.to_string(),
+                auth_mode: profile.auth_mode,
+                features: profile.features.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Send a message from the host user to every client on the `messages` topic.
+pub async fn broadcast_message(message: String) -> Result<CommandResponse, String> {
+    broadcast_to_topic(Topic::Messages, message).await
+}
+
+/// Send a message from the host user to the clients subscribed to `topic`.
+pub async fn broadcast_to_topic(topic: Topic, message: String) -> Result<CommandResponse, String> {
+    if running_server().is_none() {
+        return Err("WebSocket server is not running".to_string());
+    }
+    let sent = events::publish(topic, serde_json::json!({ "message": message }));
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Message sent to {} clients", sent),
+    })
+}
+
+/// Send a message from the host user to one client, e.g. a notice that its
+/// session is about to expire.
+pub async fn send_to_client(client_id: String, message: String) -> Result<CommandResponse, String> {
+    if running_server().is_none() {
+        return Err("WebSocket server is not running".to_string());
+    }
+    if !events::publish_to(
+        &client_id,
+        Topic::Messages,
+        serde_json::json!({ "message": message }),
+    ) {
+        return Err(format!("Client {} is not connected", client_id));
+    }
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Message sent to {}", client_id),
+    })
+}
+
+// Clone of the running server that can be held across awaits
+fn running_server() -> Option<Arc<WebSocketServer>> {
+    WEBSOCKET_SERVER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Fingerprint of the certificate every listener serves, when TLS is on.
+pub fn tls_fingerprint() -> Option<String> {
+    running_server().and_then(|server| server.tls_fingerprint.clone())
+}
+
+pub async fn list_clients() -> Result<Vec<ClientInfo>, String> {
+    Ok(running_server()
+        .map(|server| server.list_clients())
+        .unwrap_or_default())
+}
+
+/// Stream a file from the host to one connected remote.
+pub async fn send_file_to_client(
+    client_id: String,
+    path: String,
+) -> Result<CommandResponse, String> {
+    let server = running_server().ok_or_else(|| "WebSocket server is not running".to_string())?;
+    let sender = server
+        .client_sender(&client_id)
+        .ok_or_else(|| format!("Client {} is not connected", client_id))?;
+
+    let name = share::send_file(&sender, Path::new(&path)).await?;
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Sent {} to the remote", name),
+    })
+}
+
+pub fn get_local_ip() -> Option<String> {
+    network::primary_ip().map(|ip| ip.to_string())
+}
+
+// Names the self-signed certificate is valid for
+fn tls_subject_names() -> Vec<String> {
+    let mut names = vec!["localhost".to_string(), discovery::host_name()];
+    names.extend(network::all_addresses().iter().map(|ip| ip.to_string()));
+    names
+}
+
+// Replace the self-signed certificate, used on the next server start
+pub async fn regenerate_tls_certificate() -> Result<CommandResponse, String> {
+    let identity = tls::regenerate(tls_subject_names())?;
+
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!(
+            "New certificate generated (SHA-256 {}). Restart the server to use it.",
+            identity.fingerprint
+        ),
+    })
+}
+
+// Invalidate the pairing code in the QR code; already paired clients stay connected
+pub async fn regenerate_pairing_code() -> Result<CommandResponse, String> {
+    pairing::rotate();
+
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: "Pairing code regenerated".to_string(),
+    })
+}
+
+/// Unpair every remote and clear the API, admin and media server tokens and
+/// the relay room from the keychain. Connected remotes stay until they reconnect.
+pub async fn reset_credentials() -> Result<CommandResponse, String> {
+    pairing::reset();
+    let mut settings = config::current();
+    settings.api_token.clear();
+    settings.admin_token.clear();
+    settings.media_server.token.clear();
+    if matches!(
+        settings.media_server.kind,
+        MediaServerKind::Plex | MediaServerKind::Jellyfin
+    ) {
+        settings.media_server.kind = MediaServerKind::None;
+    }
+    settings.relay = RelaySettings::default();
+    config::update(settings)?;
+    relay::stop();
+
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: "Credentials reset, remotes have to pair again".to_string(),
+    })
+}
+
+/// What a remote needs to set itself up on connecting: the host's name and
+/// OS, the reply language, the keyboard layout for key labels and what the
+/// host can do.
+pub async fn host_info() -> serde_json::Value {
+    let settings = config::current();
+    let keyboard_layout = tokio::task::spawn_blocking(layout::detect).await.ok();
+    let input = input_backend::capabilities();
+
+    let features = &settings.features;
+    let mut capabilities: Vec<&str> = [
+        ("media", features.media),
+        ("keyboard", features.keyboard && input.keyboard),
+        ("text_input", features.text_input && input.text),
+        ("mouse", features.mouse && input.mouse),
+        ("system_controls", features.system_controls),
+        ("open_website", features.open_website),
+        ("cast", features.open_website),
+        ("microphone", features.microphone),
+        ("files", features.files),
+        ("automation", features.automation),
+        ("absolute_pointer", input.absolute_pointer),
+        ("privacy_indicators", privacy::supported()),
+        (
+            "media_server",
+            settings.media_server.kind != MediaServerKind::None,
+        ),
+        ("http_api", !settings.api_token.is_empty()),
+    ]
+    .into_iter()
+    .filter(|(_, available)| *available)
+    .map(|(name, _)| name)
+    .collect();
+    if settings.share_clipboard {
+        capabilities.push("clipboard");
+    }
+
+    serde_json::json!({
+        "host_name": discovery::host_name(),
+        "os": std::env::consts::OS,
+        "locale": settings.locale,
+        "keyboard_layout": keyboard_layout,
+        "input": input,
+        "capabilities": capabilities,
+    })
+}
+
+// Get connection info for QR code
+pub async fn get_connection_info() -> Result<serde_json::Value, String> {
+    connection_info(pairing::current_token()).await
+}
+
+// Connection info, with QR links that pair using `token`
+async fn connection_info(token: String) -> Result<serde_json::Value, String> {
+    // Report the address actually bound, which may differ from the configured one
+    let (bind_ip, websocket_port, tls_fingerprint) = match running_server() {
+        Some(server) => (
+            server.addr.ip(),
+            server.addr.port(),
+            server.tls_fingerprint.clone(),
+        ),
+        None => {
+            let settings = config::current();
+            let bind_ip = settings
+                .bind_address
+                .parse()
+                .unwrap_or(IpAddr::from([0, 0, 0, 0]));
+            (bind_ip, settings.port, None)
+        }
+    };
+    // The web app is served from the WebSocket port
+    let web_app_port = websocket_port;
+    let secure = tls_fingerprint.is_some();
+
+    let payload_for = |host: String| pairing::PairingPayload {
+        host,
+        port: websocket_port,
+        token: token.clone(),
+        tls_fingerprint: tls_fingerprint.clone(),
+    };
+
+    // Every address the server is reachable on, best first, so users on
+    // VPNs or IPv6-only networks can pick one that works for them
+    let addresses = network::candidate_addresses(bind_ip);
+    let candidates: Vec<serde_json::Value> = addresses
+        .iter()
+        .map(|candidate| {
+            let payload = payload_for(network::url_host(&candidate.ip));
+            serde_json::json!({
+                "ip": candidate.ip.to_string(),
+                "interface": candidate.interface,
+                "kind": candidate.kind,
+                "web_app_url": payload.web_app_url(),
+                "websocket_url": payload.websocket_url(),
+            })
+        })
+        .collect();
+
+    let (local_ip, primary_host) = match addresses.first() {
+        Some(candidate) => (candidate.ip.to_string(), network::url_host(&candidate.ip)),
+        None => ("localhost".to_string(), "localhost".to_string()),
+    };
+    let payload = payload_for(primary_host);
+    // Remotes off the LAN reach the host through the relay, checking the
+    // same TLS fingerprint
+    let relay = config::current().relay;
+    let relay_info = relay.enabled().then(|| {
+        serde_json::json!({
+            "address": relay.address,
+            "room": relay.room,
+        })
+    });
+
+    Ok(serde_json::json!({
+        "local_ip": local_ip,
+        "websocket_port": websocket_port,
+        "web_app_port": web_app_port,
+        "web_app_url": payload.web_app_url(),
+        "websocket_url": payload.websocket_url(),
+        "deep_link": payload.deep_link(),
+        "pairing": payload,
+        "pairing_expires_at": pairing::current_token_expires_at(),
+        "candidates": candidates,
+        "tls_enabled": secure,
+        "tls_fingerprint": tls_fingerprint,
+        "relay": relay_info,
+        "host": host_info().await
+    }))
+}
+
+/// Start a guest pairing code that only allows media commands and stops
+/// working after `hours`, disconnecting its guests. Returns connection info
+/// for the guest QR code.
+pub async fn start_guest_access(hours: u64) -> Result<serde_json::Value, String> {
+    if !(1..=MAX_GUEST_HOURS).contains(&hours) {
+        return Err(format!(
+            "Guest access lasts between 1 and {} hours",
+            MAX_GUEST_HOURS
+        ));
+    }
+    if config::current().auth_mode != AuthMode::Token {
+        return Err("Guest access needs token auth".to_string());
+    }
+    let (token, expires_at) = pairing::start_guest(Duration::from_secs(hours * 60 * 60));
+    let generation = GUEST_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(hours * 60 * 60)).await;
+        // Ended or restarted in the meantime
+        if GUEST_GENERATION.load(Ordering::SeqCst) == generation {
+            let _ = end_guest_access().await;
+        }
+    });
+
+    let mut info = connection_info(token).await?;
+    info["pairing_expires_at"] = serde_json::json!(expires_at);
+    info["guest"] = serde_json::json!(true);
+    Ok(info)
+}
+
+/// Stop the guest pairing code and disconnect everyone who used it.
+pub async fn end_guest_access() -> Result<CommandResponse, String> {
+    GUEST_GENERATION.fetch_add(1, Ordering::SeqCst);
+    pairing::end_guest();
+    let kicked = websocket::kick_guests();
+    info!("Guest access ended, {} guests disconnected", kicked);
+
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Guest access ended, {} guests disconnected", kicked),
+    })
+}
+
+// Turn mDNS advertisement of the server on or off and remember the choice
+pub async fn set_mdns_advertisement(enabled: bool) -> Result<CommandResponse, String> {
+    let mut settings = config::current();
+    settings.mdns_advertise = enabled;
+    config::update(settings)?;
+
+    if !enabled {
+        discovery::stop_advertising();
+        return Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "mDNS advertisement disabled".to_string(),
+        });
+    }
+
+    let running_port = running_server().map(|server| server.addr.port());
+    match running_port {
+        Some(port) => {
+            discovery::advertise(port)?;
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message: format!("Advertising {} on port {}", discovery::SERVICE_TYPE, port),
+            })
+        }
+        None => Ok(CommandResponse {
+            status: "info".to_string(),
+            message: "mDNS advertisement will start with the server".to_string(),
+        }),
+    }
+}
+
+// The router can take a few seconds, so this runs beside the server start
+async fn open_port_mapping(port: u16) {
+    match port_mapping::open(port).await {
+        Ok(address) => info!("Reachable from outside the LAN at {}", address),
+        Err(e) => warn!("Port mapping failed: {}", e),
+    }
+}
+
+/// Turn router port mapping on or off and remember the choice.
+pub async fn set_port_mapping(enabled: bool) -> Result<CommandResponse, String> {
+    let mut settings = config::current();
+    settings.port_mapping = enabled;
+    config::update(settings)?;
+
+    if !enabled {
+        port_mapping::close().await;
+        return Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Port mapping removed".to_string(),
+        });
+    }
+
+    match running_server() {
+        Some(server) => {
+            let address = port_mapping::open(server.addr.port()).await?;
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message: format!("Reachable from outside the LAN at {}", address),
+            })
+        }
+        None => Ok(CommandResponse {
+            status: "info".to_string(),
+            message: "Port mapping will start with the server".to_string(),
+        }),
+    }
+}
@@ -0,0 +1,124 @@
+//! Resume tokens, so a phone whose browser was backgrounded and dropped the
+//! socket comes back as the same client. Every connection is handed a token;
+//! reconnecting with `?resume=<token>` within the grace window reclaims the
+//! client id, pairing, event subscriptions and input control.
+//!
+//! Modifier keys are held host-wide and stay down while a session is
+//! suspended. They're released once it expires with nobody else connected.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::input_backend::SystemBackend;
+use crate::protocol::Topic;
+use crate::{control, events, input};
+
+const RESUME_GRACE: Duration = Duration::from_secs(120);
+
+/// What a client had when its socket dropped.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub client_id: String,
+    pub paired: bool,
+    pub topics: Vec<Topic>,
+    pub had_control: bool,
+}
+
+struct Suspended {
+    session: Session,
+    since: Instant,
+}
+
+lazy_static::lazy_static! {
+    // Resume token of every connected client
+    static ref TOKENS: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+    // Dropped sessions by resume token
+    static ref SUSPENDED: Mutex<HashMap<String, Suspended>> = Mutex::new(HashMap::new());
+}
+
+/// Hand a connected client a fresh resume token.
+pub fn issue(client_id: &str) -> String {
+    let token = Uuid::new_v4().simple().to_string();
+    TOKENS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(client_id.to_string(), token.clone());
+    token
+}
+
+/// Keep a disconnected client's state around for the grace window. Call
+/// before the client is removed from events and control.
+pub fn suspend(client_id: &str, paired: bool) {
+    let Some(token) = TOKENS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(client_id)
+    else {
+        return;
+    };
+    let session = Session {
+        client_id: client_id.to_string(),
+        paired,
+        topics: events::topics(client_id),
+        had_control: control::controller().as_deref() == Some(client_id),
+    };
+    SUSPENDED.lock().unwrap_or_else(|e| e.into_inner()).insert(
+        token,
+        Suspended {
+            session,
+            since: Instant::now(),
+        },
+    );
+    expire_after_grace();
+}
+
+/// Claim a suspended session, once.
+pub fn resume(token: &str) -> Option<Session> {
+    let suspended = SUSPENDED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(token)?;
+    if suspended.since.elapsed() > RESUME_GRACE {
+        return None;
+    }
+    info!("Client {} resumed its session", suspended.session.client_id);
+    Some(suspended.session)
+}
+
+/// Put a resumed client's subscriptions and input control back.
+pub fn restore(session: &Session) {
+    let client_id = &session.client_id;
+    if let Err(e) = events::subscribe(client_id, &session.topics) {
+        info!("Not restoring all topics for {}: {}", client_id, e);
+    }
+    if session.had_control {
+        if let Err(e) = control::take(client_id) {
+            info!("Not restoring input control for {}: {}", client_id, e);
+        }
+    }
+}
+
+// Drop sessions nobody came back for, releasing held modifiers if the host
+// is left without remotes
+fn expire_after_grace() {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    runtime.spawn(async {
+        tokio::time::sleep(RESUME_GRACE).await;
+        let expired = {
+            let mut suspended = SUSPENDED.lock().unwrap_or_else(|e| e.into_inner());
+            let before = suspended.len();
+            suspended.retain(|_, session| session.since.elapsed() <= RESUME_GRACE);
+            before - suspended.len()
+        };
+        let nobody_connected = TOKENS.lock().unwrap_or_else(|e| e.into_inner()).is_empty();
+        if expired > 0 && nobody_connected {
+            info!("Session expired with no remotes left, releasing modifier keys");
+            let _ = input::clear_modifier_keys::<SystemBackend>().await;
+        }
+    });
+}
@@ -0,0 +1,110 @@
+//! Sending files from the host to one remote. The remote gets a
+//! `file_offer` message, then binary frames of the 16-byte transfer id
+//! followed by a chunk of the file, the same layout uploads use, and a
+//! final `file_complete` message.
+
+use serde::Serialize;
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::protocol::WebSocketResponse;
+use crate::upload::CHUNK_BYTES;
+
+// Chunks queue up in memory until the socket takes them
+const MAX_SEND_BYTES: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+struct FileOffer {
+    transfer_id: String,
+    name: String,
+    size: u64,
+    chunk_size: usize,
+}
+
+fn notice(
+    sender: &UnboundedSender<Message>,
+    status: &str,
+    message: String,
+    data: serde_json::Value,
+) -> Result<(), String> {
+    let notice = WebSocketResponse {
+        id: None,
+        status: status.to_string(),
+        message,
+        data: Some(data),
+        duration_us: None,
+    };
+    let json = serde_json::to_string(&notice).map_err(|e| e.to_string())?;
+    sender
+        .send(Message::Text(json))
+        .map_err(|_| "Client disconnected".to_string())
+}
+
+/// Stream the file at `path` to a client, returning its name once every
+/// chunk has been queued.
+pub async fn send_file(sender: &UnboundedSender<Message>, path: &Path) -> Result<String, String> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let size = file
+        .metadata()
+        .await
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?
+        .len();
+    if size > MAX_SEND_BYTES {
+        return Err(format!(
+            "Files over {} MB can't be sent to remotes",
+            MAX_SEND_BYTES / 1024 / 1024
+        ));
+    }
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("{:?} is not a file", path))?;
+
+    let id = Uuid::new_v4();
+    let offer = FileOffer {
+        transfer_id: id.to_string(),
+        name: name.clone(),
+        size,
+        chunk_size: CHUNK_BYTES,
+    };
+    notice(
+        sender,
+        "file_offer",
+        format!("Receiving {}", name),
+        serde_json::to_value(&offer).map_err(|e| e.to_string())?,
+    )?;
+    info!("Sending {:?} ({} bytes)", path, size);
+
+    let mut buffer = vec![0u8; CHUNK_BYTES];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .await
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        if read == 0 {
+            break;
+        }
+        let mut frame = Vec::with_capacity(16 + read);
+        frame.extend_from_slice(id.as_bytes());
+        frame.extend_from_slice(&buffer[..read]);
+        sender
+            .send(Message::Binary(frame))
+            .map_err(|_| "Client disconnected".to_string())?;
+        // Let the connection's writer drain between chunks
+        tokio::task::yield_now().await;
+    }
+
+    notice(
+        sender,
+        "file_complete",
+        format!("Received {}", name),
+        serde_json::json!({ "transfer_id": id.to_string() }),
+    )?;
+    Ok(name)
+}
@@ -0,0 +1,131 @@
+//! Editing and text navigation shortcuts with the host's own modifiers, so
+//! a remote can offer copy, undo or word-left buttons without knowing
+//! whether the host wants Cmd or Ctrl.
+
+use enigo::{Direction, InputError, Key};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::input::{text_length, type_text};
+use crate::input_backend::InputBackend;
+use crate::input_worker;
+use crate::protocol::CommandResponse;
+
+const FIND_QUERY_MAX_CHARS: usize = 200;
+// The find bar needs a moment to open and take focus, or the first
+// characters go to the page
+const FIND_BAR_DELAY: Duration = Duration::from_millis(250);
+const FIND_SUBMIT_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditAction {
+    SelectAll,
+    Copy,
+    Cut,
+    Paste,
+    Undo,
+    Redo,
+    WordLeft,
+    WordRight,
+    LineStart,
+    LineEnd,
+}
+
+// Cmd on macOS, Ctrl elsewhere
+fn command_key() -> Key {
+    if cfg!(target_os = "macos") {
+        Key::Meta
+    } else {
+        Key::Control
+    }
+}
+
+// Modifiers to hold, then the key to tap
+fn chord(action: EditAction) -> (Vec<Key>, Key) {
+    let command = command_key();
+    let mac = cfg!(target_os = "macos");
+    match action {
+        EditAction::SelectAll => (vec![command], Key::Unicode('a')),
+        EditAction::Copy => (vec![command], Key::Unicode('c')),
+        EditAction::Cut => (vec![command], Key::Unicode('x')),
+        EditAction::Paste => (vec![command], Key::Unicode('v')),
+        EditAction::Undo => (vec![command], Key::Unicode('z')),
+        // Ctrl+Y is the Windows convention, Shift+Z everywhere else
+        EditAction::Redo if cfg!(target_os = "windows") => (vec![command], Key::Unicode('y')),
+        EditAction::Redo => (vec![command, Key::Shift], Key::Unicode('z')),
+        EditAction::WordLeft if mac => (vec![Key::Alt], Key::LeftArrow),
+        EditAction::WordRight if mac => (vec![Key::Alt], Key::RightArrow),
+        EditAction::WordLeft => (vec![Key::Control], Key::LeftArrow),
+        EditAction::WordRight => (vec![Key::Control], Key::RightArrow),
+        EditAction::LineStart if mac => (vec![Key::Meta], Key::LeftArrow),
+        EditAction::LineEnd if mac => (vec![Key::Meta], Key::RightArrow),
+        EditAction::LineStart => (Vec::new(), Key::Home),
+        EditAction::LineEnd => (Vec::new(), Key::End),
+    }
+}
+
+/// Tap `key` while holding `modifiers`.
+pub(crate) fn press_chord<B: InputBackend>(
+    input: &mut B,
+    modifiers: &[Key],
+    key: Key,
+) -> Result<(), InputError> {
+    let mut result = Ok(());
+    for modifier in modifiers {
+        result = result.and_then(|_| input.key(*modifier, Direction::Press));
+    }
+    result = result.and_then(|_| input.key(key, Direction::Click));
+    // Always let go of the modifiers, even after a failed press
+    for modifier in modifiers.iter().rev() {
+        let _ = input.key(*modifier, Direction::Release);
+    }
+    result
+}
+
+/// Perform an editing shortcut on the host.
+pub async fn perform<B: InputBackend>(action: EditAction) -> Result<CommandResponse, String> {
+    let (modifiers, key) = chord(action);
+    input_worker::run::<B, _, _>(move |input| {
+        press_chord(input, &modifiers, key)
+            .map_err(|e| format!("Failed to send {:?}: {:?}", action, e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Sent {:?}", action),
+        })
+    })
+    .await
+}
+
+/// Open the find bar of the focused app, type `query` and jump to the first
+/// match, as one sequence so nothing else is typed in between.
+pub async fn find_in_page<B: InputBackend>(query: String) -> Result<CommandResponse, String> {
+    if query.is_empty() {
+        return Err("Missing 'query' parameter".to_string());
+    }
+    if text_length(&query) > FIND_QUERY_MAX_CHARS {
+        return Err(format!(
+            "Find query too long (max {} characters)",
+            FIND_QUERY_MAX_CHARS
+        ));
+    }
+
+    input_worker::run::<B, _, _>(move |input| {
+        press_chord(input, &[command_key()], Key::Unicode('f'))
+            .map_err(|e| format!("Failed to open the find bar: {:?}", e))?;
+        std::thread::sleep(FIND_BAR_DELAY);
+        // The find bar keeps the last query selected, so typing replaces it
+        type_text(input, &query)?;
+        std::thread::sleep(FIND_SUBMIT_DELAY);
+        input
+            .key(Key::Return, Direction::Click)
+            .map_err(|e| format!("Failed to submit the find query: {:?}", e))?;
+
+        Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Finding \"{}\"", query),
+        })
+    })
+    .await
+}
@@ -0,0 +1,168 @@
+//! Text to speech on the host, so a remote can have the living-room
+//! computer announce things. Uses `say` on macOS, SAPI through PowerShell
+//! on Windows and `espeak-ng` (or `espeak`) on Linux. The text goes in on
+//! stdin or the environment, never as a command line to be parsed.
+
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use tracing::info;
+
+use crate::protocol::CommandResponse;
+
+const MAX_SPEECH_CHARS: usize = 1000;
+const MAX_VOICE_CHARS: usize = 64;
+const MIN_RATE: u32 = 80;
+const MAX_RATE: u32 = 400;
+
+lazy_static::lazy_static! {
+    // The announcement being spoken, cut off when a new one starts
+    static ref SPEAKING: Mutex<Option<Child>> = Mutex::new(None);
+}
+
+/// Options for `speak`. `rate` is in words per minute.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct SpeechOptions {
+    #[serde(default)]
+    pub rate: Option<u32>,
+    #[serde(default)]
+    pub voice: Option<String>,
+}
+
+#[cfg(target_os = "macos")]
+fn command(options: &SpeechOptions) -> Command {
+    let mut command = Command::new("say");
+    if let Some(rate) = options.rate {
+        command.args(["-r", &rate.to_string()]);
+    }
+    if let Some(voice) = &options.voice {
+        command.args(["-v", voice]);
+    }
+    command
+}
+
+#[cfg(target_os = "linux")]
+fn command(options: &SpeechOptions) -> Command {
+    let program = if crate::input_backend::command_available("espeak-ng") {
+        "espeak-ng"
+    } else {
+        "espeak"
+    };
+    let mut command = Command::new(program);
+    command.arg("--stdin");
+    if let Some(rate) = options.rate {
+        command.args(["-s", &rate.to_string()]);
+    }
+    if let Some(voice) = &options.voice {
+        command.args(["-v", voice]);
+    }
+    command
+}
+
+// SAPI reads the text from stdin, its rate runs from -10 to 10 with 0 at
+// roughly 180 words per minute
+#[cfg(target_os = "windows")]
+fn command(options: &SpeechOptions) -> Command {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    const SCRIPT: &str = "\
+        Add-Type -AssemblyName System.Speech; \
+        $speaker = New-Object System.Speech.Synthesis.SpeechSynthesizer; \
+        if ($env:CC_VOICE) { $speaker.SelectVoice($env:CC_VOICE) }; \
+        $speaker.Rate = [int]$env:CC_RATE; \
+        $speaker.Speak([Console]::In.ReadToEnd())";
+    let rate = options
+        .rate
+        .map(|rate| ((rate as i32 - 180) / 20).clamp(-10, 10))
+        .unwrap_or(0);
+    let mut command = Command::new("powershell");
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .env("CC_RATE", rate.to_string())
+        .env("CC_VOICE", options.voice.as_deref().unwrap_or_default())
+        .creation_flags(CREATE_NO_WINDOW);
+    command
+}
+
+fn clean(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(MAX_SPEECH_CHARS)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn check(options: &SpeechOptions) -> Result<(), String> {
+    if let Some(rate) = options.rate {
+        if !(MIN_RATE..=MAX_RATE).contains(&rate) {
+            return Err(format!(
+                "Speech rate must be between {} and {} words per minute",
+                MIN_RATE, MAX_RATE
+            ));
+        }
+    }
+    if let Some(voice) = &options.voice {
+        let valid = !voice.is_empty()
+            && voice.chars().count() <= MAX_VOICE_CHARS
+            && voice
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '+' | '.'));
+        if !valid {
+            return Err(format!("Invalid voice name: {}", voice));
+        }
+    }
+    Ok(())
+}
+
+/// Stop the announcement in progress, returning whether there was one.
+pub fn stop() -> bool {
+    let Some(mut child) = SPEAKING.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return false;
+    };
+    let running = matches!(child.try_wait(), Ok(None));
+    let _ = child.kill();
+    let _ = child.wait();
+    running
+}
+
+fn start(text: &str, options: &SpeechOptions) -> Result<(), String> {
+    stop();
+    let mut child = command(options)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Text to speech isn't available: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|e| format!("Failed to send text to speak: {}", e))?;
+    }
+    *SPEAKING.lock().unwrap_or_else(|e| e.into_inner()) = Some(child);
+    Ok(())
+}
+
+/// Speak `text` on the host without waiting for it to finish. A new
+/// announcement cuts off the one before it.
+pub async fn speak(
+    client_id: &str,
+    text: &str,
+    options: SpeechOptions,
+) -> Result<CommandResponse, String> {
+    let text = clean(text);
+    if text.is_empty() {
+        return Err("Nothing to speak".to_string());
+    }
+    check(&options)?;
+
+    let chars = text.chars().count();
+    tokio::task::spawn_blocking(move || start(&text, &options))
+        .await
+        .map_err(|e| format!("Speech task panicked: {:?}", e))??;
+    info!("Speaking {} characters from {}", chars, client_id);
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: "Speaking on the host".to_string(),
+    })
+}
@@ -0,0 +1,120 @@
+//! Host telemetry for the remote's dashboard. Each subscribed client gets
+//! its own task that samples CPU, memory, network throughput and battery
+//! and pushes a `stats` event every interval.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use sysinfo::{Networks, System};
+use tokio::task::AbortHandle;
+use tracing::debug;
+
+use crate::battery::{self, BatteryStatus};
+use crate::protocol::{EventMessage, Topic};
+use crate::websocket;
+
+pub const MIN_INTERVAL_MS: u64 = 500;
+pub const MAX_INTERVAL_MS: u64 = 60_000;
+
+lazy_static::lazy_static! {
+    static ref SUBSCRIPTIONS: Mutex<HashMap<String, AbortHandle>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemStats {
+    pub cpu_percent: f32,
+    pub memory_used: u64,
+    pub memory_total: u64,
+    // Bytes per second over all interfaces since the previous sample
+    pub network_rx: u64,
+    pub network_tx: u64,
+    // None on machines without a battery
+    pub battery: Option<BatteryStatus>,
+}
+
+struct Sampler {
+    system: System,
+    networks: Networks,
+    last_sample: Instant,
+}
+
+impl Sampler {
+    fn new() -> Self {
+        let mut system = System::new();
+        system.refresh_cpu_usage();
+        Self {
+            system,
+            networks: Networks::new_with_refreshed_list(),
+            last_sample: Instant::now(),
+        }
+    }
+
+    fn sample(&mut self) -> SystemStats {
+        self.system.refresh_cpu_usage();
+        self.system.refresh_memory();
+        self.networks.refresh(true);
+
+        let elapsed = self.last_sample.elapsed().as_secs_f64().max(0.001);
+        self.last_sample = Instant::now();
+        let (rx, tx) = self.networks.values().fold((0, 0), |(rx, tx), data| {
+            (rx + data.received(), tx + data.transmitted())
+        });
+
+        SystemStats {
+            cpu_percent: self.system.global_cpu_usage(),
+            memory_used: self.system.used_memory(),
+            memory_total: self.system.total_memory(),
+            network_rx: (rx as f64 / elapsed) as u64,
+            network_tx: (tx as f64 / elapsed) as u64,
+            battery: battery::status(),
+        }
+    }
+}
+
+/// Push stats to a client every `interval_ms`, replacing an earlier
+/// subscription. Returns the interval actually used.
+pub fn subscribe(client_id: &str, interval_ms: u64) -> u64 {
+    let interval_ms = interval_ms.clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS);
+    let client = client_id.to_string();
+    let task = tokio::spawn(async move {
+        let mut sampler = Sampler::new();
+        let mut ticker = tokio::time::interval(Duration::from_millis(interval_ms));
+        // The first tick is immediate, CPU usage needs one interval to measure
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let stats = sampler.sample();
+            let update = EventMessage::new(
+                Topic::Stats,
+                serde_json::to_value(&stats).unwrap_or_default(),
+            );
+            if !websocket::push_to_client(&client, &update) {
+                break;
+            }
+        }
+        debug!("Stats subscription of {} ended", client);
+        SUBSCRIPTIONS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&client);
+    });
+
+    let previous = SUBSCRIPTIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(client_id.to_string(), task.abort_handle());
+    if let Some(previous) = previous {
+        previous.abort();
+    }
+    interval_ms
+}
+
+/// Stop pushing stats to a client. False if it wasn't subscribed.
+pub fn unsubscribe(client_id: &str) -> bool {
+    let subscription = SUBSCRIPTIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(client_id);
+    subscription.map(|task| task.abort()).is_some()
+}
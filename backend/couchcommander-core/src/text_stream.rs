@@ -0,0 +1,137 @@
+//! Live typing. Instead of one `text_input` per submitted string, a client in
+//! stream mode sends every edit from the phone keyboard as a numbered delta.
+//! Each client has its own FIFO queue, so its edits apply in `seq` order even
+//! if they arrive out of order, while other clients type independently.
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+use tracing::{debug, warn};
+
+use crate::input_backend::InputBackend;
+use crate::input_worker;
+use crate::protocol::CommandResponse;
+use enigo::{Direction, Key};
+
+/// Queue name for input from the desktop app itself.
+pub const LOCAL_CLIENT: &str = "local";
+
+const MAX_DELTA_CHARS: usize = 1000;
+const MAX_BACKSPACES: usize = 1000;
+// Edits held back waiting for a missing seq before giving up on it
+const MAX_PENDING: usize = 64;
+
+lazy_static::lazy_static! {
+    static ref QUEUES: Mutex<HashMap<String, Arc<tokio::sync::Mutex<StreamState>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// One edit: delete `backspaces` characters, then type `text`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TextDelta {
+    pub seq: u64,
+    #[serde(default)]
+    pub backspaces: usize,
+    #[serde(default)]
+    pub text: String,
+}
+
+#[derive(Debug, Default)]
+pub struct StreamState {
+    next_seq: u64,
+    pending: BTreeMap<u64, TextDelta>,
+}
+
+/// Wait for the client's turn. tokio's mutex is fair, so callers get in
+/// the order they asked.
+pub(crate) async fn queue(client: &str) -> OwnedMutexGuard<StreamState> {
+    let queue = QUEUES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(client.to_string())
+        .or_default()
+        .clone();
+    queue.lock_owned().await
+}
+
+/// Forget a disconnected client's queue and any edits it left pending.
+pub fn remove_client(client: &str) {
+    QUEUES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(client);
+}
+
+fn type_delta<B: InputBackend>(input: &mut B, delta: &TextDelta) -> Result<(), String> {
+    for _ in 0..delta.backspaces {
+        input
+            .key(Key::Backspace, Direction::Click)
+            .map_err(|e| format!("Failed to send backspace: {:?}", e))?;
+    }
+    if !delta.text.is_empty() {
+        crate::input::type_text(input, &delta.text)?;
+    }
+    Ok(())
+}
+
+/// Queue an edit from `client` and type every edit that is now in order.
+pub async fn apply<B: InputBackend>(
+    client: &str,
+    delta: TextDelta,
+) -> Result<CommandResponse, String> {
+    if delta.backspaces > MAX_BACKSPACES || crate::input::text_length(&delta.text) > MAX_DELTA_CHARS
+    {
+        return Err(format!(
+            "Edit too large (max {} characters)",
+            MAX_DELTA_CHARS
+        ));
+    }
+
+    let mut guard = queue(client).await;
+    let state = &mut *guard;
+    if delta.seq < state.next_seq {
+        debug!("Dropping duplicate text edit {} from {}", delta.seq, client);
+        return Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Edit {} already applied", delta.seq),
+        });
+    }
+
+    let seq = delta.seq;
+    state.pending.insert(seq, delta);
+    if state.pending.len() > MAX_PENDING {
+        // The missing edit is never coming, carry on from what we have
+        let first = *state.pending.keys().next().unwrap_or(&seq);
+        warn!(
+            "Text stream from {} skipped edits {}..{}",
+            client, state.next_seq, first
+        );
+        state.next_seq = first;
+    }
+
+    let mut ready = Vec::new();
+    while let Some(delta) = state.pending.remove(&state.next_seq) {
+        ready.push(delta);
+        state.next_seq += 1;
+    }
+    if ready.is_empty() {
+        return Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Edit {} queued, waiting for {}", seq, state.next_seq),
+        });
+    }
+
+    // The queue stays locked until typing finishes, keeping later edits behind
+    let count = ready.len();
+    input_worker::run::<B, _, _>(move |input| {
+        ready.iter().try_for_each(|delta| type_delta(input, delta))
+    })
+    .await?;
+    drop(guard);
+
+    Ok(CommandResponse {
+        status: "success".to_string(),
+        message: format!("Applied {} edit(s)", count),
+    })
+}
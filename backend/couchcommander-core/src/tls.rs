@@ -0,0 +1,127 @@
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio_rustls::TlsAcceptor;
+use tracing::{info, warn};
+
+use crate::config;
+
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+
+/// Self-signed certificate used for `wss://` connections.
+#[derive(Clone)]
+pub struct TlsIdentity {
+    pub acceptor: TlsAcceptor,
+    pub fingerprint: String,
+}
+
+fn tls_dir() -> PathBuf {
+    config::config_dir().join("tls")
+}
+
+/// SHA-256 of the DER certificate as colon separated uppercase hex,
+/// the format browsers show in their certificate viewer.
+pub fn fingerprint(cert: &CertificateDer<'_>) -> String {
+    Sha256::digest(cert.as_ref())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+// Only the user running the host may read the private key
+#[cfg(unix)]
+fn write_private(path: &std::path::Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // The mode only applies when the file is created
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &std::path::Path, contents: &str) -> std::io::Result<()> {
+    std::fs::write(path, contents)
+}
+
+fn generate(subject_alt_names: Vec<String>) -> Result<(), String> {
+    let dir = tls_dir();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create TLS directory {:?}: {}", dir, e))?;
+
+    let certified = rcgen::generate_simple_self_signed(subject_alt_names)
+        .map_err(|e| format!("Failed to generate certificate: {}", e))?;
+
+    std::fs::write(dir.join(CERT_FILE), certified.cert.pem())
+        .map_err(|e| format!("Failed to write certificate: {}", e))?;
+    write_private(&dir.join(KEY_FILE), &certified.key_pair.serialize_pem())
+        .map_err(|e| format!("Failed to write private key: {}", e))?;
+
+    info!("Generated self-signed TLS certificate in {:?}", dir);
+    Ok(())
+}
+
+fn read_pem(file: &str) -> Result<Vec<u8>, String> {
+    let path = tls_dir().join(file);
+    std::fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))
+}
+
+/// Load the persisted certificate, generating one on first use.
+pub fn load_or_generate(subject_alt_names: Vec<String>) -> Result<TlsIdentity, String> {
+    let dir = tls_dir();
+    if !dir.join(CERT_FILE).exists() || !dir.join(KEY_FILE).exists() {
+        generate(subject_alt_names)?;
+    }
+    // Keys from before they were written private
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let private = std::fs::Permissions::from_mode(0o600);
+        if let Err(e) = std::fs::set_permissions(dir.join(KEY_FILE), private) {
+            warn!("Failed to make the TLS key private: {}", e);
+        }
+    }
+
+    let cert_pem = read_pem(CERT_FILE)?;
+    let certs: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut cert_pem.as_slice())
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Invalid certificate file: {}", e))?;
+    let leaf = certs
+        .first()
+        .ok_or_else(|| "Certificate file contains no certificates".to_string())?;
+    let fingerprint = fingerprint(leaf);
+
+    let key_pem = read_pem(KEY_FILE)?;
+    let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut key_pem.as_slice())
+        .map_err(|e| format!("Invalid private key file: {}", e))?
+        .ok_or_else(|| "Private key file contains no key".to_string())?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let server_config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Failed to configure TLS: {}", e))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Failed to load TLS certificate: {}", e))?;
+
+    Ok(TlsIdentity {
+        acceptor: TlsAcceptor::from(Arc::new(server_config)),
+        fingerprint,
+    })
+}
+
+/// Replace the persisted certificate with a freshly generated one.
+pub fn regenerate(subject_alt_names: Vec<String>) -> Result<TlsIdentity, String> {
+    generate(subject_alt_names)?;
+    load_or_generate(Vec::new())
+}
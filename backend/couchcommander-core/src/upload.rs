@@ -0,0 +1,289 @@
+//! File uploads from remotes into a folder on the host. `upload_start`
+//! announces a file and returns an id, then the remote sends binary frames
+//! of the 16-byte id followed by up to `MAX_CHUNK_BYTES` of the file. Data
+//! lands in a hidden `.part` file that's renamed once the last byte is in.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::{self, UploadPermission};
+
+pub const CHUNK_BYTES: usize = 256 * 1024;
+const MAX_CHUNK_BYTES: usize = 1024 * 1024;
+const MAX_UPLOADS_PER_CLIENT: usize = 4;
+const MAX_NAME_CHARS: usize = 200;
+
+lazy_static::lazy_static! {
+    static ref CLIENTS: Mutex<HashMap<String, ClientUploads>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Default)]
+struct ClientUploads {
+    // Connected with a pairing or session token
+    paired: bool,
+    uploads: HashMap<Uuid, Upload>,
+}
+
+struct Upload {
+    name: String,
+    dir: PathBuf,
+    part_path: PathBuf,
+    file: File,
+    size: u64,
+    received: u64,
+}
+
+impl Upload {
+    fn discard(self) {
+        drop(self.file);
+        if let Err(e) = std::fs::remove_file(&self.part_path) {
+            warn!("Failed to remove {:?}: {}", self.part_path, e);
+        }
+    }
+}
+
+/// Returned by `start` so the remote knows where to send its chunks.
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadTicket {
+    pub upload_id: String,
+    pub name: String,
+    pub chunk_size: usize,
+}
+
+/// Sent back after each chunk. `path` is set once the file is complete.
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadProgress {
+    pub upload_id: String,
+    pub received: u64,
+    pub size: u64,
+    pub path: Option<String>,
+}
+
+impl UploadProgress {
+    pub fn is_complete(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+/// Remember how a client connected, uploads may be limited to paired ones.
+pub fn register_client(client_id: &str, paired: bool) {
+    let mut clients = CLIENTS.lock().unwrap_or_else(|e| e.into_inner());
+    clients.entry(client_id.to_string()).or_default().paired = paired;
+}
+
+/// Drop a disconnected client's unfinished uploads.
+pub fn remove_client(client_id: &str) {
+    let removed = CLIENTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(client_id);
+    if let Some(client) = removed {
+        for (id, upload) in client.uploads {
+            info!("Discarding unfinished upload {} of {}", id, upload.name);
+            upload.discard();
+        }
+    }
+}
+
+fn check_permission(paired: bool) -> Result<(), String> {
+    match config::current().uploads.permission {
+        UploadPermission::Off => Err("File uploads are disabled in settings".to_string()),
+        UploadPermission::Paired if !paired => {
+            Err("File uploads are only allowed from paired remotes".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn upload_dir() -> PathBuf {
+    let dir = config::current().uploads.dir;
+    if dir.trim().is_empty() {
+        dirs::download_dir()
+            .or_else(dirs::home_dir)
+            .unwrap_or_else(std::env::temp_dir)
+    } else {
+        PathBuf::from(dir)
+    }
+}
+
+// Keep only the last path component and replace what filesystems reject,
+// and what `cmd` would read as syntax if the file were ever opened through it
+fn sanitize_name(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next()?;
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control()
+                || matches!(c, ':' | '*' | '?' | '"' | '<' | '>' | '|' | '&' | '^' | '%')
+            {
+                '_'
+            } else {
+                c
+            }
+        })
+        .take(MAX_NAME_CHARS)
+        .collect();
+    // No hidden files, and nothing that could be "." or ".."
+    let name = name
+        .trim()
+        .trim_start_matches('.')
+        .trim_end_matches(['.', ' ']);
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+// "clip.mp4" becomes "clip (1).mp4" when the name is taken
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap_or(path)
+}
+
+/// Announce a file of `size` bytes and open its `.part` file.
+pub fn start(client_id: &str, name: &str, size: u64) -> Result<UploadTicket, String> {
+    let name = sanitize_name(name).ok_or_else(|| "Invalid file name".to_string())?;
+    let max_bytes = config::current().uploads.max_mb * 1024 * 1024;
+    if size > max_bytes {
+        return Err(format!(
+            "File is larger than the {} MB upload limit",
+            max_bytes / 1024 / 1024
+        ));
+    }
+
+    let mut clients = CLIENTS.lock().unwrap_or_else(|e| e.into_inner());
+    let client = clients
+        .get_mut(client_id)
+        .ok_or_else(|| "File uploads need a WebSocket connection".to_string())?;
+    check_permission(client.paired)?;
+    if client.uploads.len() >= MAX_UPLOADS_PER_CLIENT {
+        return Err("Too many uploads in progress".to_string());
+    }
+
+    let dir = upload_dir();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create upload folder {:?}: {}", dir, e))?;
+    let id = Uuid::new_v4();
+    let part_path = dir.join(format!(".{}.part", id));
+    let file =
+        File::create(&part_path).map_err(|e| format!("Failed to create {:?}: {}", part_path, e))?;
+
+    info!("Receiving {} ({} bytes) into {:?}", name, size, dir);
+    client.uploads.insert(
+        id,
+        Upload {
+            name: name.clone(),
+            dir,
+            part_path,
+            file,
+            size,
+            received: 0,
+        },
+    );
+    Ok(UploadTicket {
+        upload_id: id.to_string(),
+        name,
+        chunk_size: CHUNK_BYTES,
+    })
+}
+
+pub fn cancel(client_id: &str, upload_id: &str) -> Result<(), String> {
+    let id = Uuid::parse_str(upload_id).map_err(|_| "Invalid upload id".to_string())?;
+    let upload = CLIENTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_mut(client_id)
+        .and_then(|client| client.uploads.remove(&id))
+        .ok_or_else(|| format!("No upload {}", upload_id))?;
+    info!("Upload of {} cancelled", upload.name);
+    upload.discard();
+    Ok(())
+}
+
+/// Write one binary frame. A failed chunk ends the upload.
+pub fn write_chunk(client_id: &str, frame: &[u8]) -> Result<UploadProgress, String> {
+    if frame.len() < 16 {
+        return Err("Upload chunk is missing its upload id".to_string());
+    }
+    let (id, data) = frame.split_at(16);
+    let id = Uuid::from_slice(id).map_err(|_| "Invalid upload id".to_string())?;
+    if data.len() > MAX_CHUNK_BYTES {
+        return Err(format!(
+            "Upload chunks can be at most {} bytes",
+            MAX_CHUNK_BYTES
+        ));
+    }
+
+    let mut clients = CLIENTS.lock().unwrap_or_else(|e| e.into_inner());
+    let client = clients
+        .get_mut(client_id)
+        .ok_or_else(|| format!("No upload {}", id))?;
+    let paired = client.paired;
+    let mut upload = client
+        .uploads
+        .remove(&id)
+        .ok_or_else(|| format!("No upload {}", id))?;
+
+    // Settings may have changed since the upload started
+    let written = check_permission(paired).and_then(|_| {
+        if upload.received + data.len() as u64 > upload.size {
+            return Err("Upload is larger than announced".to_string());
+        }
+        upload
+            .file
+            .write_all(data)
+            .map_err(|e| format!("Failed to write {}: {}", upload.name, e))
+    });
+    if let Err(e) = written {
+        warn!("Upload of {} failed: {}", upload.name, e);
+        upload.discard();
+        return Err(e);
+    }
+    upload.received += data.len() as u64;
+
+    let mut progress = UploadProgress {
+        upload_id: id.to_string(),
+        received: upload.received,
+        size: upload.size,
+        path: None,
+    };
+    if upload.received < upload.size {
+        client.uploads.insert(id, upload);
+        return Ok(progress);
+    }
+    drop(clients);
+
+    let path = finish(upload)?;
+    progress.path = Some(path.to_string_lossy().into_owned());
+    Ok(progress)
+}
+
+// Flush the .part file and give it its real name
+fn finish(upload: Upload) -> Result<PathBuf, String> {
+    if let Err(e) = upload.file.sync_all() {
+        let message = format!("Failed to save {}: {}", upload.name, e);
+        upload.discard();
+        return Err(message);
+    }
+    let path = unique_path(&upload.dir, &upload.name);
+    if let Err(e) = std::fs::rename(&upload.part_path, &path) {
+        let message = format!("Failed to save {}: {}", upload.name, e);
+        upload.discard();
+        return Err(message);
+    }
+    info!("Saved upload to {:?}", path);
+    Ok(path)
+}
@@ -0,0 +1,139 @@
+//! The parts of UPnP shared by DLNA renderers and router port mapping:
+//! SSDP searches, reading description XML and calling SOAP actions.
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+use crate::http_client;
+
+const SSDP_ADDRESS: &str = "239.255.255.250:1900";
+
+/// Text inside the first `<tag>...</tag>`. Descriptions and SOAP replies
+/// leave the elements we need unprefixed.
+pub fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}", tag);
+    let start = xml
+        .match_indices(&open)
+        .map(|(index, _)| index + open.len())
+        .find(|&index| xml[index..].starts_with(['>', ' ']))?;
+    let content_start = start + xml[start..].find('>')? + 1;
+    let length = xml[content_start..].find(&format!("</{}>", tag))?;
+    Some(xml[content_start..content_start + length].trim())
+}
+
+pub fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Resolve a control URL from a description against its location.
+pub fn absolute_url(location: &str, url_base: Option<&str>, path: &str) -> String {
+    if path.starts_with("http://") {
+        return path.to_string();
+    }
+    let base = url_base.unwrap_or(location);
+    let origin_end = base
+        .find("://")
+        .and_then(|scheme| base[scheme + 3..].find('/').map(|slash| scheme + 3 + slash))
+        .unwrap_or(base.len());
+    format!("{}/{}", &base[..origin_end], path.trim_start_matches('/'))
+}
+
+/// Description URLs of the devices that answer an SSDP search for
+/// `search_target` within `timeout`.
+pub async fn search(search_target: &str, timeout: Duration) -> Result<Vec<String>, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| format!("Failed to open an SSDP socket: {}", e))?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: {}\r\n\r\n",
+        SSDP_ADDRESS, search_target
+    );
+    socket
+        .send_to(search.as_bytes(), SSDP_ADDRESS)
+        .await
+        .map_err(|e| format!("Failed to send the SSDP search: {}", e))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut locations = HashSet::new();
+    let mut buffer = [0u8; 2048];
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let Ok(Ok((length, _))) =
+            tokio::time::timeout(remaining, socket.recv_from(&mut buffer)).await
+        else {
+            break;
+        };
+        let reply = String::from_utf8_lossy(&buffer[..length]);
+        let location = reply.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("location")
+                .then(|| value.trim().to_string())
+        });
+        if let Some(location) = location {
+            locations.insert(location);
+        }
+    }
+    Ok(locations.into_iter().collect())
+}
+
+/// The type and absolute control URL of the first service in the
+/// description at `location` whose type contains `kind`, e.g. ":AVTransport:".
+pub fn find_service(location: &str, xml: &str, kind: &str) -> Option<(String, String)> {
+    let service = xml
+        .split("<service>")
+        .skip(1)
+        .find(|service| element(service, "serviceType").is_some_and(|t| t.contains(kind)))?;
+    let service_type = element(service, "serviceType")?;
+    let control_path = element(service, "controlURL")?;
+    let control_url = absolute_url(location, element(xml, "URLBase"), &unescape(control_path));
+    Some((service_type.to_string(), control_url))
+}
+
+/// Call a SOAP action and return the reply body. `arguments` is the XML of
+/// the action's arguments.
+pub async fn action(
+    control_url: &str,
+    service_type: &str,
+    name: &str,
+    arguments: &str,
+) -> Result<String, String> {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:{name} xmlns:u=\"{service}\">{arguments}</u:{name}>\
+         </s:Body></s:Envelope>",
+        name = name,
+        service = service_type,
+        arguments = arguments
+    );
+    let soap_action = format!("\"{}#{}\"", service_type, name);
+    let response = http_client::request(
+        "POST",
+        control_url,
+        &[
+            ("Content-Type", "text/xml; charset=\"utf-8\""),
+            ("SOAPAction", &soap_action),
+        ],
+        Some(&body),
+    )
+    .await?;
+    if response.status != 200 {
+        let reason = element(&response.body, "errorDescription").unwrap_or("no reason given");
+        let code = element(&response.body, "errorCode").unwrap_or("?");
+        return Err(format!("{} failed ({}): {}", name, code, reason));
+    }
+    Ok(response.body)
+}
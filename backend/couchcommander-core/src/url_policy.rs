@@ -0,0 +1,73 @@
+//! Which URLs `open_website` may open. Any paired client can send one, so
+//! web links are checked against the domain allow and deny lists and other
+//! schemes must be explicitly enabled app schemes like `spotify:`.
+
+use crate::config::Settings;
+
+const WEB_SCHEMES: &[&str] = &["http", "https"];
+
+fn scheme(url: &str) -> Option<String> {
+    let (scheme, _) = url.split_once(':')?;
+    let valid = !scheme.is_empty()
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
+
+// Host of an http(s) URL without userinfo or port
+fn host(url: &str) -> Option<String> {
+    let rest = url.split_once("://")?.1;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit('@').next()?;
+    let host = if let Some(ipv6) = host_port.strip_prefix('[') {
+        ipv6.split(']').next()?
+    } else {
+        host_port.split(':').next()?
+    };
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    (!host.is_empty()).then_some(host)
+}
+
+// "youtube.com" covers www.youtube.com and m.youtube.com too
+fn domain_matches(host: &str, domains: &[String]) -> bool {
+    domains.iter().any(|domain| {
+        let domain = domain.trim().trim_start_matches("*.").to_ascii_lowercase();
+        host == domain || host.ends_with(&format!(".{}", domain))
+    })
+}
+
+/// Whether an http(s) URL's host is one of `domains` or below it.
+pub fn host_matches(url: &str, domains: &[String]) -> bool {
+    host(url).is_some_and(|host| domain_matches(&host, domains))
+}
+
+/// Check a URL from a remote against the settings before opening it.
+pub fn check(settings: &Settings, url: &str) -> Result<(), String> {
+    if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("Invalid URL: must not contain spaces or control characters".to_string());
+    }
+    let scheme = scheme(url).ok_or_else(|| "Invalid URL: missing scheme".to_string())?;
+
+    if !WEB_SCHEMES.contains(&scheme.as_str()) {
+        let allowed = settings
+            .url_schemes
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(&scheme));
+        return if allowed {
+            Ok(())
+        } else {
+            Err(format!("URL scheme '{}:' is not allowed", scheme))
+        };
+    }
+
+    let host = host(url).ok_or_else(|| "Invalid URL: missing host".to_string())?;
+    if domain_matches(&host, &settings.url_denylist) {
+        return Err(format!("{} is blocked in settings", host));
+    }
+    if !settings.url_allowlist.is_empty() && !domain_matches(&host, &settings.url_allowlist) {
+        return Err(format!("{} is not in the allowed domains", host));
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! Volume fades, e.g. bringing the music down when someone starts talking.
+//! The volume is stepped to the target through the same native calls as
+//! `volume_set`. One fade runs at a time, and any other volume command ends
+//! it where it is.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::input;
+
+pub const MAX_DURATION_MS: u64 = 60_000;
+// Each step runs a mixer command or an AppleScript, faster isn't smoother
+const STEP: Duration = Duration::from_millis(100);
+
+struct Fade {
+    cancelled: Arc<AtomicBool>,
+    task: JoinHandle<()>,
+}
+
+static FADE: Mutex<Option<Fade>> = Mutex::new(None);
+
+/// Commands that change the volume and so end a fade.
+pub fn is_volume_command(command: &str) -> bool {
+    matches!(
+        command,
+        "volume_up" | "volume_down" | "volume_mute" | "volume_set" | "set_mute" | "volume_fade"
+    )
+}
+
+/// Stop the running fade, waiting for a step in progress so it can't land
+/// after whatever comes next. False if no fade was running.
+pub async fn cancel() -> bool {
+    let fade = FADE.lock().unwrap_or_else(|e| e.into_inner()).take();
+    let Some(fade) = fade else {
+        return false;
+    };
+    fade.cancelled.store(true, Ordering::SeqCst);
+    let _ = fade.task.await;
+    true
+}
+
+/// Move the volume from where it is to `target` percent over `duration_ms`,
+/// replacing a fade already running. Returns the volume it starts from.
+pub async fn start(target: u8, duration_ms: u64) -> Result<u8, String> {
+    cancel().await;
+    let target = target.min(100);
+    let duration_ms = duration_ms.min(MAX_DURATION_MS);
+    let from = input::volume_get().await?;
+
+    let steps = (duration_ms / STEP.as_millis() as u64).max(1);
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let flag = cancelled.clone();
+    let task = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(STEP);
+        let mut last = from;
+        // The first tick is immediate, the first step is one interval in
+        ticker.tick().await;
+        for step in 1..=steps {
+            ticker.tick().await;
+            if flag.load(Ordering::SeqCst) {
+                debug!("Volume fade cancelled at {}%", last);
+                return;
+            }
+            let value = from as f64 + (target as f64 - from as f64) * step as f64 / steps as f64;
+            let value = value.round() as u8;
+            if value == last {
+                continue;
+            }
+            match input::volume_set(value).await {
+                Ok(response) if response.status == "success" => last = value,
+                Ok(response) => {
+                    warn!("Volume fade stopped: {}", response.message);
+                    break;
+                }
+                Err(e) => {
+                    warn!("Volume fade stopped: {}", e);
+                    break;
+                }
+            }
+        }
+        let mut fade = FADE.lock().unwrap_or_else(|e| e.into_inner());
+        if fade
+            .as_ref()
+            .is_some_and(|fade| Arc::ptr_eq(&fade.cancelled, &flag))
+        {
+            *fade = None;
+        }
+    });
+
+    *FADE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Fade { cancelled, task });
+    Ok(from)
+}
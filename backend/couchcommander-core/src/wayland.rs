@@ -0,0 +1,203 @@
+//! Input on Wayland sessions, where compositors don't accept the X11 events
+//! enigo sends. Goes through ydotool, which writes to /dev/uinput via its
+//! `ydotoold` daemon and so works under any compositor.
+
+use enigo::{Axis, Button, Coordinate, Direction, InputError, Key};
+use std::process::Command;
+use tracing::{debug, warn};
+
+use crate::input_backend::InputBackend;
+use crate::layout::{self, LayoutFamily};
+
+pub const YDOTOOL: &str = "ydotool";
+
+// Linux evdev key codes from input-event-codes.h
+const LETTER_CODES: [u16; 26] = [
+    30, 48, 46, 32, 18, 33, 34, 35, 23, 36, 37, 38, 50, 49, 24, 25, 16, 19, 31, 20, 22, 47, 17, 45,
+    21, 44,
+];
+const SEMICOLON_CODE: u16 = 39;
+const DIGIT_CODES: [u16; 10] = [11, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+const FUNCTION_CODES: [u16; 12] = [59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 87, 88];
+
+/// Whether the desktop session is Wayland rather than X11.
+pub fn is_wayland_session() -> bool {
+    std::env::var("XDG_SESSION_TYPE").is_ok_and(|session| session.eq_ignore_ascii_case("wayland"))
+        || std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+// Evdev codes are physical positions, so on AZERTY and QWERTZ some letters
+// live on another key than on the US layout the tables assume
+fn letter_code(ch: char) -> u16 {
+    let us_letter = match (layout::current_family(), ch) {
+        (LayoutFamily::Azerty, 'm') => return SEMICOLON_CODE,
+        (LayoutFamily::Azerty, 'a') => 'q',
+        (LayoutFamily::Azerty, 'q') => 'a',
+        (LayoutFamily::Azerty, 'z') => 'w',
+        (LayoutFamily::Azerty, 'w') => 'z',
+        (LayoutFamily::Qwertz, 'y') => 'z',
+        (LayoutFamily::Qwertz, 'z') => 'y',
+        (_, ch) => ch,
+    };
+    LETTER_CODES[us_letter as usize - 'a' as usize]
+}
+
+fn key_code(key: Key) -> Option<u16> {
+    let code = match key {
+        Key::Space => 57,
+        Key::Return => 28,
+        Key::Escape => 1,
+        Key::Backspace => 14,
+        Key::Tab => 15,
+        Key::UpArrow => 103,
+        Key::DownArrow => 108,
+        Key::LeftArrow => 105,
+        Key::RightArrow => 106,
+        Key::Shift => 42,
+        Key::Control => 29,
+        Key::Alt => 56,
+        Key::Meta => 125,
+        Key::VolumeMute => 113,
+        Key::VolumeDown => 114,
+        Key::VolumeUp => 115,
+        Key::F1 => FUNCTION_CODES[0],
+        Key::F2 => FUNCTION_CODES[1],
+        Key::F3 => FUNCTION_CODES[2],
+        Key::F4 => FUNCTION_CODES[3],
+        Key::F5 => FUNCTION_CODES[4],
+        Key::F6 => FUNCTION_CODES[5],
+        Key::F7 => FUNCTION_CODES[6],
+        Key::F8 => FUNCTION_CODES[7],
+        Key::F9 => FUNCTION_CODES[8],
+        Key::F10 => FUNCTION_CODES[9],
+        Key::F11 => FUNCTION_CODES[10],
+        Key::F12 => FUNCTION_CODES[11],
+        Key::Unicode(ch @ 'a'..='z') => letter_code(ch),
+        Key::Unicode(ch @ '0'..='9') => DIGIT_CODES[ch as usize - '0' as usize],
+        Key::Home => 102,
+        Key::End => 107,
+        // Raw codes from send_raw_key are evdev codes on Wayland
+        Key::Other(code) => return u16::try_from(code).ok(),
+        _ => return None,
+    };
+    Some(code)
+}
+
+// ydotool button ids, combined with 0x40 for down and 0x80 for up
+fn button_code(button: Button) -> Option<u8> {
+    match button {
+        Button::Left => Some(0x00),
+        Button::Right => Some(0x01),
+        Button::Middle => Some(0x02),
+        _ => None,
+    }
+}
+
+/// Sends input through the `ydotool` command line tool.
+pub struct YdotoolBackend;
+
+impl YdotoolBackend {
+    fn run(&self, args: &[String]) -> Result<(), InputError> {
+        debug!("Running ydotool {:?}", args);
+        let output = Command::new(YDOTOOL).args(args).output().map_err(|e| {
+            warn!("Failed to run ydotool: {}", e);
+            InputError::Simulate("failed to run ydotool")
+        })?;
+        if !output.status.success() {
+            warn!(
+                "ydotool {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return Err(InputError::Simulate("ydotool failed, is ydotoold running?"));
+        }
+        Ok(())
+    }
+}
+
+impl InputBackend for YdotoolBackend {
+    fn connect() -> Result<Self, String> {
+        if !crate::input_backend::command_available(YDOTOOL) {
+            return Err(
+                "Wayland session detected but ydotool is not installed. Install ydotool and start ydotoold."
+                    .to_string(),
+            );
+        }
+        Ok(YdotoolBackend)
+    }
+
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), InputError> {
+        let Some(code) = key_code(key) else {
+            // Characters without a key of their own can still be typed
+            return match (key, direction) {
+                (Key::Unicode(ch), Direction::Press | Direction::Click) => {
+                    self.text(&ch.to_string())
+                }
+                (Key::Unicode(_), Direction::Release) => Ok(()),
+                _ => Err(InputError::InvalidInput("key not supported by ydotool")),
+            };
+        };
+
+        let events = match direction {
+            Direction::Press => vec![format!("{}:1", code)],
+            Direction::Release => vec![format!("{}:0", code)],
+            Direction::Click => vec![format!("{}:1", code), format!("{}:0", code)],
+        };
+        let mut args = vec!["key".to_string()];
+        args.extend(events);
+        self.run(&args)
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), InputError> {
+        self.run(&["type".to_string(), "--".to_string(), text.to_string()])
+    }
+
+    fn move_mouse(&mut self, x: i32, y: i32, coordinate: Coordinate) -> Result<(), InputError> {
+        let mut args = vec!["mousemove".to_string()];
+        if coordinate == Coordinate::Abs {
+            args.push("--absolute".to_string());
+        }
+        args.extend([
+            "-x".to_string(),
+            x.to_string(),
+            "-y".to_string(),
+            y.to_string(),
+        ]);
+        self.run(&args)
+    }
+
+    // Wayland keeps the pointer position from other apps and ydotool only
+    // writes to uinput
+    fn location(&mut self) -> Result<(i32, i32), InputError> {
+        Err(InputError::Simulate(
+            "the pointer position can't be read on Wayland",
+        ))
+    }
+
+    fn button(&mut self, button: Button, direction: Direction) -> Result<(), InputError> {
+        let id = button_code(button)
+            .ok_or(InputError::InvalidInput("button not supported by ydotool"))?;
+        let code = match direction {
+            Direction::Press => 0x40 | id,
+            Direction::Release => 0x80 | id,
+            Direction::Click => 0xC0 | id,
+        };
+        self.run(&["click".to_string(), format!("0x{:02X}", code)])
+    }
+
+    fn scroll(&mut self, length: i32, axis: Axis) -> Result<(), InputError> {
+        // Wheel "movement" is in notches; enigo's positive length scrolls down
+        let (x, y) = match axis {
+            Axis::Horizontal => (length, 0),
+            Axis::Vertical => (0, -length),
+        };
+        self.run(&[
+            "mousemove".to_string(),
+            "--wheel".to_string(),
+            "-x".to_string(),
+            x.to_string(),
+            "-y".to_string(),
+            y.to_string(),
+        ])
+    }
+}
@@ -0,0 +1,65 @@
+use axum::body::Body;
+use axum::http::{header, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use rust_embed::RustEmbed;
+use tracing::debug;
+
+// Static export of the Next.js remote (`npm run build` in frontend/).
+// Debug builds read the folder from disk, release builds embed it.
+#[derive(RustEmbed)]
+#[folder = "../../frontend/out/"]
+#[allow_missing = true]
+struct FrontendAssets;
+
+pub fn has_frontend() -> bool {
+    FrontendAssets::get("index.html").is_some()
+}
+
+// Map a request path onto the export layout: `/` is index.html and
+// extensionless routes like `/about` are written as `about.html`.
+fn candidates(path: &str) -> Vec<String> {
+    let path = path.trim_start_matches('/');
+    if path.is_empty() {
+        return vec!["index.html".to_string()];
+    }
+    let path = path.trim_end_matches('/');
+    vec![
+        path.to_string(),
+        format!("{}.html", path),
+        format!("{}/index.html", path),
+    ]
+}
+
+/// Answer a plain HTTP request from the frontend build.
+pub async fn serve_asset(uri: Uri) -> Response {
+    for candidate in candidates(uri.path()) {
+        if let Some(file) = FrontendAssets::get(&candidate) {
+            debug!("Serving {}", candidate);
+            let mime = file.metadata.mimetype().to_string();
+            // Hashed build output never changes, pages should always revalidate
+            let cache = if candidate.starts_with("_next/static/") {
+                "public, max-age=31536000, immutable"
+            } else {
+                "no-cache"
+            };
+            return (
+                [
+                    (header::CONTENT_TYPE, mime),
+                    (header::CACHE_CONTROL, cache.to_string()),
+                ],
+                Body::from(file.data.into_owned()),
+            )
+                .into_response();
+        }
+    }
+
+    match FrontendAssets::get("404.html") {
+        Some(file) => (
+            StatusCode::NOT_FOUND,
+            [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+            Body::from(file.data.into_owned()),
+        )
+            .into_response(),
+        None => (StatusCode::NOT_FOUND, "Not found").into_response(),
+    }
+}
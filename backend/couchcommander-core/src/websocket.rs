@@ -0,0 +1,2685 @@
+use axum::body::Body;
+use axum::http::{header, HeaderValue, Request, Response, StatusCode};
+use futures_util::{SinkExt, StreamExt};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
+use tokio_rustls::TlsAcceptor;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+use tracing::{debug, debug_span, error, info, info_span, warn, Instrument, Span};
+use uuid::Uuid;
+
+use crate::config::{AuthMode, FeatureToggles, ListenerProfile};
+use crate::devices::{self, DeviceRole};
+use crate::input_backend::{self, InputBackend, SystemBackend};
+use crate::metrics;
+use crate::pairing::{self, Authorization};
+use crate::protocol::{CommandResponse, Topic, WebSocketCommand, WebSocketResponse};
+use crate::session::{self, Session};
+use crate::signing::{self, Verifier};
+use crate::tls::TlsIdentity;
+use crate::{
+    aliases, api, battery, color_picker, control, events, files, frontmost, game_keys, gamepad,
+    i18n, idempotency, idle, repeat, schedule, secure_input, shortcuts, stats, upload, volume_fade,
+    web, wol,
+};
+
+pub type ClientConnections =
+    Arc<Mutex<HashMap<String, tokio::sync::mpsc::UnboundedSender<Message>>>>;
+
+// How many consecutive ports to try when the requested one is taken
+const PORT_FALLBACK_ATTEMPTS: u16 = 10;
+
+lazy_static::lazy_static! {
+    static ref CLIENT_INFO: Mutex<HashMap<String, ClientInfo>> = Mutex::new(HashMap::new());
+}
+
+/// A connected remote, as listed to the host user.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientInfo {
+    pub id: String,
+    pub address: String,
+    // Seconds since the epoch
+    pub connected_at: u64,
+    // Name of the extra listener it connected to, none for the main one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listener: Option<String>,
+    #[serde(skip)]
+    sender: tokio::sync::mpsc::UnboundedSender<Message>,
+    // What that listener allows on top of the main feature toggles
+    #[serde(skip)]
+    features: Option<FeatureToggles>,
+    // What the remote calls itself, e.g. "Vihaan's iPhone"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Connected with the admin token, see `ADMIN_COMMANDS`
+    pub admin: bool,
+    // Connected with the guest pairing code, see `guest_allows`
+    pub guest: bool,
+    // The id it sent with `?device=`, see `devices`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    // Session token it's paired with, forgotten when its device is revoked
+    #[serde(skip)]
+    session: Option<String>,
+    // A status page that only reads, connected with `?view=dashboard`
+    pub dashboard: bool,
+}
+
+// Guests control the music and read what's playing, nothing else
+fn guest_allows(command: &str) -> bool {
+    FeatureToggles::is_media(command)
+        || matches!(
+            command,
+            "now_playing"
+                | "get_volume"
+                | "get_capabilities"
+                | "get_keyboard_layout"
+                | "subscribe"
+                | "unsubscribe"
+                | "take_control"
+                | "release_control"
+                | "set_client_name"
+                | "get_remote_layout"
+                | "set_remote_layout"
+                | "start_repeat"
+                | "stop_repeat"
+                | "ping"
+        )
+}
+
+const MAX_CLIENT_NAME_CHARS: usize = 40;
+
+// Managing the host from a remote, for admin clients only
+const ADMIN_COMMANDS: &[&str] = &[
+    "list_clients",
+    "kick_client",
+    "set_input_paused",
+    "regenerate_pairing_code",
+];
+
+fn is_admin(client_id: &str) -> bool {
+    CLIENT_INFO
+        .lock()
+        .unwrap()
+        .get(client_id)
+        .is_some_and(|client| client.admin)
+}
+
+// The device's nickname, the remote's name, or its IP address if it has
+// neither. None for callers that aren't connected remotes, like the scheduler
+fn client_label(client_id: &str) -> Option<String> {
+    let clients = CLIENT_INFO.lock().unwrap();
+    let client = clients.get(client_id)?;
+    let nickname = client.device.as_deref().and_then(devices::nickname);
+    Some(nickname.or_else(|| client.name.clone()).unwrap_or_else(|| {
+        client
+            .address
+            .parse::<SocketAddr>()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|_| client.address.clone())
+    }))
+}
+
+// For relayed remotes this is the relay's address
+fn client_ip(client_id: &str) -> Option<IpAddr> {
+    let clients = CLIENT_INFO.lock().unwrap();
+    let address = &clients.get(client_id)?.address;
+    address.parse::<SocketAddr>().ok().map(|addr| addr.ip())
+}
+
+fn device_of(client_id: &str) -> Option<String> {
+    CLIENT_INFO
+        .lock()
+        .unwrap()
+        .get(client_id)
+        .and_then(|client| client.device.clone())
+}
+
+// A `text_input` with `"sensitive": true`, e.g. a password sent from a
+// password manager. Its text is pasted and never logged or shared
+pub(crate) fn is_sensitive(command: &WebSocketCommand) -> bool {
+    command.command == "text_input"
+        && command
+            .data
+            .as_ref()
+            .and_then(|d| d.get("sensitive"))
+            .and_then(|s| s.as_bool())
+            == Some(true)
+}
+
+fn is_guest(client_id: &str) -> bool {
+    CLIENT_INFO
+        .lock()
+        .unwrap()
+        .get(client_id)
+        .is_some_and(|client| client.guest)
+}
+
+fn is_dashboard(client_id: &str) -> bool {
+    CLIENT_INFO
+        .lock()
+        .unwrap()
+        .get(client_id)
+        .is_some_and(|client| client.dashboard)
+}
+
+/// Close a remote's connection, returning whether it was connected. It can
+/// reconnect with its session token unless pairing is reset too.
+pub fn kick_client(client_id: &str) -> bool {
+    let sender = CLIENT_INFO
+        .lock()
+        .unwrap()
+        .get(client_id)
+        .map(|client| client.sender.clone());
+    match sender {
+        Some(sender) => {
+            info!("Disconnecting client {}", client_id);
+            sender.send(Message::Close(None)).is_ok()
+        }
+        None => false,
+    }
+}
+
+/// Close the connections of every guest, returning how many there were.
+pub fn kick_guests() -> usize {
+    let guests: Vec<String> = CLIENT_INFO
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|client| client.guest)
+        .map(|client| client.id.clone())
+        .collect();
+    guests.iter().filter(|id| kick_client(id)).count()
+}
+
+/// Close the connections of a device and forget their session tokens,
+/// returning how many there were.
+pub fn kick_device(device_id: &str) -> usize {
+    let clients: Vec<(String, Option<String>)> = CLIENT_INFO
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|client| client.device.as_deref() == Some(device_id))
+        .map(|client| (client.id.clone(), client.session.clone()))
+        .collect();
+    for session in clients.iter().filter_map(|(_, session)| session.as_deref()) {
+        pairing::revoke_session(session);
+    }
+    clients.iter().filter(|(id, _)| kick_client(id)).count()
+}
+
+// Whether the listener a client came in on allows `command`. Clients
+// outside the server, like the scheduler, aren't limited by one
+fn listener_allows(client_id: &str, command: &str) -> bool {
+    CLIENT_INFO
+        .lock()
+        .unwrap()
+        .get(client_id)
+        .and_then(|client| client.features.as_ref())
+        .is_none_or(|features| features.allows(command))
+}
+
+// Tell `clients` subscribers who is connected now
+fn publish_clients(event: &str, client_id: &str) {
+    let mut clients: Vec<ClientInfo> = CLIENT_INFO.lock().unwrap().values().cloned().collect();
+    clients.sort_by_key(|client| client.connected_at);
+    events::publish(
+        Topic::Clients,
+        serde_json::json!({ "event": event, "client_id": client_id, "clients": clients }),
+    );
+}
+
+/// Queue a message for one client outside the command/reply flow, e.g. a
+/// subscription update. False once the client is gone.
+pub fn push_to_client<T: Serialize>(client_id: &str, message: &T) -> bool {
+    let Some(sender) = CLIENT_INFO
+        .lock()
+        .unwrap()
+        .get(client_id)
+        .map(|client| client.sender.clone())
+    else {
+        return false;
+    };
+    match serde_json::to_string(message) {
+        Ok(json) => sender.send(Message::Text(json)).is_ok(),
+        Err(e) => {
+            error!("Failed to serialize message for {}: {}", client_id, e);
+            true
+        }
+    }
+}
+
+pub struct WebSocketServer {
+    pub addr: SocketAddr,
+    pub clients: ClientConnections,
+    pub tls_fingerprint: Option<String>,
+    // Set on extra listeners from the settings
+    pub profile: Option<Arc<ListenerProfile>>,
+    tls: Option<TlsAcceptor>,
+    shutdown: Notify,
+}
+
+// An IPv6 wildcard also accepts IPv4 clients. Linux does this by default,
+// Windows needs IPV6_V6ONLY cleared explicitly.
+fn bind_once(addr: SocketAddr) -> std::io::Result<std::net::TcpListener> {
+    if !(addr.is_ipv6() && addr.ip().is_unspecified()) {
+        return std::net::TcpListener::bind(addr);
+    }
+
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(false)?;
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(128)?;
+    Ok(socket.into())
+}
+
+/// Bind the server socket, moving on to the next port when the requested one
+/// is already in use. The returned listener reports the port actually bound.
+pub fn bind_listener(ip: IpAddr, port: u16) -> std::io::Result<std::net::TcpListener> {
+    let mut last_error = None;
+
+    for offset in 0..PORT_FALLBACK_ATTEMPTS {
+        let Some(candidate) = port.checked_add(offset) else {
+            break;
+        };
+        match bind_once(SocketAddr::new(ip, candidate)) {
+            Ok(listener) => {
+                if offset > 0 {
+                    warn!("Port {} is in use, bound to {} instead", port, candidate);
+                }
+                listener.set_nonblocking(true)?;
+                return Ok(listener);
+            }
+            Err(e) if e.kind() == ErrorKind::AddrInUse => {
+                debug!("Port {} is in use", candidate);
+                last_error = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(last_error
+        .unwrap_or_else(|| std::io::Error::new(ErrorKind::AddrInUse, "No free port available")))
+}
+
+impl WebSocketServer {
+    pub fn new(addr: SocketAddr, tls: Option<TlsIdentity>) -> Self {
+        let clients = Arc::new(Mutex::new(HashMap::new()));
+        let (tls, tls_fingerprint) = match tls {
+            Some(identity) => (Some(identity.acceptor), Some(identity.fingerprint)),
+            None => (None, None),
+        };
+
+        Self {
+            addr,
+            clients,
+            tls_fingerprint,
+            profile: None,
+            tls,
+            shutdown: Notify::new(),
+        }
+    }
+
+    /// An extra listener that shares the main server's clients, so they
+    /// are listed and closed together.
+    pub fn with_profile(
+        addr: SocketAddr,
+        tls: Option<TlsIdentity>,
+        clients: ClientConnections,
+        profile: ListenerProfile,
+    ) -> Self {
+        Self {
+            clients,
+            profile: Some(Arc::new(profile)),
+            ..Self::new(addr, tls)
+        }
+    }
+
+    pub fn is_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    pub async fn start(
+        &self,
+        listener: std::net::TcpListener,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let listener = TcpListener::from_std(listener)?;
+        if !web::has_frontend() {
+            warn!("Frontend build not found, run `npm run build` in frontend/");
+        }
+        info!(
+            "WebSocket server listening on: {} (tls: {})",
+            self.addr,
+            self.is_tls()
+        );
+
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let Ok((stream, addr)) = accepted else {
+                        break;
+                    };
+                    let clients = Arc::clone(&self.clients);
+                    let tls = self.tls.clone();
+                    let profile = self.profile.clone();
+                    tokio::spawn(
+                        accept_connection(stream, addr, clients, tls, profile)
+                            .instrument(info_span!("client", %addr)),
+                    );
+                }
+                _ = self.shutdown.notified() => {
+                    info!("WebSocket server on {} shutting down", self.addr);
+                    break;
+                }
+            }
+        }
+
+        // Ask connected clients to close so their sockets are released too
+        let clients = self.clients.lock().unwrap();
+        for (_, tx) in clients.iter() {
+            let _ = tx.send(Message::Close(None));
+        }
+
+        Ok(())
+    }
+
+    /// Stop accepting connections and release the listening port.
+    pub fn shutdown(&self) {
+        self.shutdown.notify_one();
+    }
+
+    pub fn get_client_count(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    pub fn list_clients(&self) -> Vec<ClientInfo> {
+        let clients = self.clients.lock().unwrap();
+        let info = CLIENT_INFO.lock().unwrap();
+        let mut list: Vec<ClientInfo> = clients
+            .keys()
+            .filter_map(|id| info.get(id).cloned())
+            .collect();
+        list.sort_by_key(|client| client.connected_at);
+        list
+    }
+
+    pub fn client_sender(
+        &self,
+        client_id: &str,
+    ) -> Option<tokio::sync::mpsc::UnboundedSender<Message>> {
+        self.clients.lock().unwrap().get(client_id).cloned()
+    }
+
+    /// Serve a remote that reached us through the relay like one accepted
+    /// here. `addr` is the relay's, the remote's own address isn't known.
+    pub fn serve_relayed(&self, stream: TcpStream, addr: SocketAddr) {
+        let clients = Arc::clone(&self.clients);
+        let tls = self.tls.clone();
+        let profile = self.profile.clone();
+        tokio::spawn(
+            accept_connection(stream, addr, clients, tls, profile)
+                .instrument(info_span!("relay client", %addr)),
+        );
+    }
+}
+
+// Run the optional TLS handshake, then serve HTTP on the connection
+async fn accept_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    clients: ClientConnections,
+    tls: Option<TlsAcceptor>,
+    profile: Option<Arc<ListenerProfile>>,
+) {
+    match tls {
+        Some(acceptor) => match acceptor.accept(stream).await {
+            Ok(tls_stream) => serve_http(tls_stream, addr, clients, profile).await,
+            Err(e) => warn!("TLS handshake with {} failed: {}", addr, e),
+        },
+        None => serve_http(stream, addr, clients, profile).await,
+    }
+}
+
+// The web app and the WebSocket share one port: upgrade requests become
+// remote-control sessions, everything else is answered from the frontend build.
+// The HTTP API is only served on the main listener.
+async fn serve_http<S>(
+    stream: S,
+    addr: SocketAddr,
+    clients: ClientConnections,
+    profile: Option<Arc<ListenerProfile>>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |req: Request<Incoming>| {
+        let clients = Arc::clone(&clients);
+        let profile = profile.clone();
+        async move {
+            let response = if is_websocket_upgrade(&req) {
+                upgrade_to_websocket(req, addr, clients, profile)
+            } else if api::is_api_request(&req) && profile.is_none() {
+                api::handle(req).await
+            } else {
+                web::serve_asset(req.uri().clone()).await
+            };
+            Ok::<_, Infallible>(response)
+        }
+    });
+
+    if let Err(e) = http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), service)
+        .with_upgrades()
+        .await
+    {
+        debug!("HTTP connection from {} closed: {}", addr, e);
+    }
+}
+
+fn is_websocket_upgrade(req: &Request<Incoming>) -> bool {
+    let has_token = |name: header::HeaderName, token: &str| {
+        req.headers()
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|value| value.trim().eq_ignore_ascii_case(token))
+    };
+    has_token(header::CONNECTION, "upgrade") && has_token(header::UPGRADE, "websocket")
+}
+
+fn upgrade_to_websocket(
+    mut req: Request<Incoming>,
+    addr: SocketAddr,
+    clients: ClientConnections,
+    profile: Option<Arc<ListenerProfile>>,
+) -> Response<Body> {
+    let auth_mode = profile
+        .as_ref()
+        .map_or_else(|| crate::config::current().auth_mode, |p| p.auth_mode);
+    let query = req.uri().query();
+    let sign = pairing::sign_from_query(query);
+    // A paired remote that signs sends its session token's hash, not the token
+    let presented = pairing::token_from_query(query)
+        .map(str::to_string)
+        .or_else(|| sign.and_then(pairing::session_for_hash));
+    let unauthorized = |reason: String| {
+        warn!("Rejected WebSocket connection from {}: {}", addr, reason);
+        Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .body(Body::from(reason))
+            .unwrap_or_default()
+    };
+    // Checked first, so a revoked device doesn't use up the pairing code
+    let device = pairing::device_from_query(query).map(str::to_string);
+    if device.as_deref().is_some_and(devices::is_revoked) {
+        return unauthorized("This device was revoked on the host".to_string());
+    }
+    let (paired, guest, session_token) = match pairing::authorize(presented.as_deref(), auth_mode) {
+        Ok(Authorization::Paired { session_token }) => (true, false, Some(session_token)),
+        Ok(Authorization::Session) => (true, false, None),
+        Ok(Authorization::Guest) => (false, true, None),
+        Ok(Authorization::Open) => (false, false, None),
+        Err(reason) => return unauthorized(reason),
+    };
+    // The presented token is only a secret in token auth mode
+    let secret = presented.as_deref().filter(|_| paired || guest);
+    let verifier = match secret.filter(|_| sign.is_some()) {
+        Some(secret) => Some(Verifier::new(secret)),
+        None => {
+            let settings = crate::config::current();
+            if settings.require_signing && !settings.tls_enabled && auth_mode == AuthMode::Token {
+                return unauthorized("This host only accepts signed messages".to_string());
+            }
+            None
+        }
+    };
+    // Lets a remote that pinned our certificate check it reached this host:
+    // a relaying attacker serves its own certificate and, when the remote
+    // only sent its token's hash, can't forge the proof
+    let fingerprint = pairing::verify_fingerprint_from_query(query)
+        .zip(secret)
+        .and_then(|(nonce, secret)| {
+            let fingerprint = crate::server::tls_fingerprint()?;
+            let proof = signing::fingerprint_proof(secret, nonce, &fingerprint);
+            Some(serde_json::json!({ "value": fingerprint, "proof": proof }))
+        });
+    // Admins manage the host, so only the main listener takes the admin token
+    let admin_token = crate::config::current().admin_token;
+    let admin = !guest
+        && profile.is_none()
+        && !admin_token.is_empty()
+        && pairing::admin_from_query(query)
+            .is_some_and(|token| signing::same(token.as_bytes(), admin_token.as_bytes()));
+    // Reclaim the session of a client that dropped within the grace window
+    let resumed = pairing::resume_from_query(query).and_then(session::resume);
+    let session = session_token
+        .clone()
+        .or_else(|| presented.clone().filter(|_| paired));
+    // A paired remote is the device its session token is bound to, whatever
+    // `?device=` it sends now
+    let device = match &session {
+        Some(session) => pairing::bind_device(session, device.as_deref()),
+        None => device,
+    };
+    if device.as_deref().is_some_and(devices::is_revoked) {
+        return unauthorized("This device was revoked on the host".to_string());
+    }
+    let admission = Admission {
+        paired,
+        session_token,
+        session,
+        resumed,
+        verifier,
+        fingerprint,
+        admin,
+        guest,
+        device,
+        dashboard: pairing::dashboard_from_query(query),
+    };
+
+    let Some(key) = req.headers().get(header::SEC_WEBSOCKET_KEY) else {
+        return Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Body::from("Missing Sec-WebSocket-Key"))
+            .unwrap_or_default();
+    };
+    let accept = derive_accept_key(key.as_bytes());
+
+    // hyper hands over the raw connection once the 101 response is written
+    let on_upgrade = hyper::upgrade::on(&mut req);
+    tokio::spawn(
+        async move {
+            match on_upgrade.await {
+                Ok(upgraded) => {
+                    let ws_stream = WebSocketStream::from_raw_socket(
+                        TokioIo::new(upgraded),
+                        Role::Server,
+                        None,
+                    )
+                    .await;
+                    handle_connection(ws_stream, addr, clients, profile, admission).await;
+                }
+                Err(e) => warn!("WebSocket upgrade failed: {}", e),
+            }
+        }
+        .instrument(Span::current()),
+    );
+
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+    let headers = response.headers_mut();
+    headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+    if let Ok(accept) = HeaderValue::from_str(&accept) {
+        headers.insert(header::SEC_WEBSOCKET_ACCEPT, accept);
+    }
+    response
+}
+
+// How a client got through the handshake
+struct Admission {
+    paired: bool,
+    // Issued when the client paired with the pairing token just now
+    session_token: Option<String>,
+    // The session token it's paired with, new or presented
+    session: Option<String>,
+    resumed: Option<Session>,
+    // Set when the client asked to sign its messages
+    verifier: Option<Verifier>,
+    // Certificate fingerprint and its proof, when the client asked for them
+    fingerprint: Option<serde_json::Value>,
+    admin: bool,
+    guest: bool,
+    device: Option<String>,
+    dashboard: bool,
+}
+
+async fn handle_connection<S>(
+    ws_stream: WebSocketStream<S>,
+    addr: SocketAddr,
+    clients: ClientConnections,
+    profile: Option<Arc<ListenerProfile>>,
+    admission: Admission,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    info!("New WebSocket connection: {}", addr);
+    let Admission {
+        paired,
+        session_token,
+        session,
+        resumed,
+        mut verifier,
+        fingerprint,
+        admin,
+        guest,
+        device,
+        dashboard,
+    } = admission;
+
+    let client_id = resumed
+        .as_ref()
+        .map(|session| session.client_id.clone())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    // A resumed session keeps the permissions it was paired with
+    let paired = paired || resumed.as_ref().is_some_and(|session| session.paired);
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+    // Add client to connections
+    {
+        let mut clients_guard = clients.lock().unwrap();
+        clients_guard.insert(client_id.clone(), tx.clone());
+    }
+    CLIENT_INFO.lock().unwrap().insert(
+        client_id.clone(),
+        ClientInfo {
+            id: client_id.clone(),
+            address: addr.to_string(),
+            connected_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since_epoch| since_epoch.as_secs())
+                .unwrap_or_default(),
+            listener: profile.as_ref().map(|p| p.name.clone()),
+            sender: tx.clone(),
+            features: profile.as_ref().map(|p| p.features.clone()),
+            name: None,
+            admin,
+            guest,
+            device: device.clone(),
+            session,
+            dashboard,
+        },
+    );
+    if let Some(device) = &device {
+        devices::connected(device);
+    }
+    metrics::client_connected();
+    upload::register_client(&client_id, paired);
+    events::register_client(&client_id);
+    if let Some(session) = &resumed {
+        session::restore(session);
+    }
+    publish_clients("connected", &client_id);
+
+    // Reconnect with `?resume=<token>` to come back as this client
+    let welcome = WebSocketResponse {
+        id: None,
+        status: "session".to_string(),
+        message: if resumed.is_some() {
+            "Session resumed".to_string()
+        } else {
+            "Session started".to_string()
+        },
+        data: Some(serde_json::json!({
+            "client_id": client_id,
+            "resume_token": session::issue(&client_id),
+            "resumed": resumed.is_some(),
+            "admin": admin,
+            "guest": guest,
+            "signing": verifier.as_ref().map(|v| serde_json::json!({ "nonce": v.nonce() })),
+            "fingerprint": fingerprint,
+            "host": crate::server::host_info().await,
+            "self_test": crate::self_test::results(),
+        })),
+        duration_us: None,
+    };
+    if let Ok(json) = serde_json::to_string(&welcome) {
+        let _ = tx.send(Message::Text(json));
+    }
+
+    // Freshly paired clients keep this token to reconnect once the QR code rotates
+    if let Some(session_token) = session_token {
+        let paired = WebSocketResponse {
+            id: None,
+            status: "paired".to_string(),
+            message: "Paired with host".to_string(),
+            data: Some(serde_json::json!({ "session_token": session_token })),
+            duration_us: None,
+        };
+        if let Ok(json) = serde_json::to_string(&paired) {
+            let _ = tx.send(Message::Text(json));
+        }
+    }
+
+    let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+
+    // Handle outgoing messages
+    let client_id_clone = client_id.clone();
+    let clients_clone = Arc::clone(&clients);
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if ws_sender.send(msg).await.is_err() {
+                // Remove client on send error
+                let mut clients_guard = clients_clone.lock().unwrap();
+                clients_guard.remove(&client_id_clone);
+                break;
+            }
+        }
+    });
+
+    // Handle incoming messages
+    while let Some(msg) = ws_receiver.next().await {
+        match msg {
+            Ok(Message::Text(text)) => {
+                // Signed messages carry the command as their payload
+                let opened = match verifier.as_mut() {
+                    Some(verifier) => verifier.open(&text),
+                    None => Ok(text),
+                };
+                // Wrap command handling in a catch-all error handler
+                let response = match opened {
+                    Ok(text) => match serde_json::from_str::<WebSocketCommand>(&text) {
+                        Ok(command) => run_once(&client_id, command).await,
+                        Err(e) => {
+                            warn!("Failed to parse command: {}", e);
+                            WebSocketResponse {
+                                id: id_of_invalid(&text),
+                                status: "error".to_string(),
+                                message: format!("Invalid command format: {}", e),
+                                data: None,
+                                duration_us: None,
+                            }
+                        }
+                    },
+                    Err(e) => {
+                        warn!("Rejected message from {}: {}", client_id, e);
+                        WebSocketResponse {
+                            id: None,
+                            status: "error".to_string(),
+                            message: e,
+                            data: None,
+                            duration_us: None,
+                        }
+                    }
+                };
+
+                let response_json = serde_json::to_string(&response).unwrap_or_else(|e| {
+                    error!("Failed to serialize response: {}", e);
+                    serde_json::to_string(&WebSocketResponse {
+                        id: None,
+                        status: "error".to_string(),
+                        message: "Failed to serialize response".to_string(),
+                        data: None,
+                        duration_us: None,
+                    })
+                    .unwrap_or_else(|_| {
+                        r#"{"status":"error","message":"Critical serialization error"}"#.to_string()
+                    })
+                });
+
+                // Send response back through the client's sender
+                if let Some(sender) = {
+                    let clients_guard = clients.lock().unwrap();
+                    clients_guard.get(&client_id).cloned()
+                } {
+                    if let Err(e) = sender.send(Message::Text(response_json)) {
+                        error!("Failed to send response to client {}: {}", client_id, e);
+                    }
+                }
+            }
+            // Binary frames only carry file upload chunks
+            Ok(Message::Binary(frame)) => {
+                let response = match upload::write_chunk(&client_id, &frame) {
+                    Ok(progress) => WebSocketResponse {
+                        id: None,
+                        status: if progress.is_complete() {
+                            "upload_complete".to_string()
+                        } else {
+                            "upload_progress".to_string()
+                        },
+                        message: format!(
+                            "Received {} of {} bytes",
+                            progress.received, progress.size
+                        ),
+                        data: serde_json::to_value(&progress).ok(),
+                        duration_us: None,
+                    },
+                    Err(e) => WebSocketResponse {
+                        id: None,
+                        status: "error".to_string(),
+                        message: e,
+                        data: None,
+                        duration_us: None,
+                    },
+                };
+                if let Ok(json) = serde_json::to_string(&response) {
+                    let _ = tx.send(Message::Text(json));
+                }
+            }
+            Ok(Message::Close(_)) => {
+                info!("Client {} disconnected", addr);
+                break;
+            }
+            Err(e) => {
+                warn!("WebSocket error: {}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    // Remove client from connections
+    {
+        let mut clients_guard = clients.lock().unwrap();
+        clients_guard.remove(&client_id);
+    }
+    CLIENT_INFO.lock().unwrap().remove(&client_id);
+    crate::text_stream::remove_client(&client_id);
+    upload::remove_client(&client_id);
+    stats::unsubscribe(&client_id);
+    repeat::stop(&client_id);
+    session::suspend(&client_id, paired);
+    let _ = gamepad::release_all::<SystemBackend>(&client_id).await;
+    game_keys::release_all::<SystemBackend>(&client_id);
+    events::remove_client(&client_id);
+    control::release(&client_id);
+    if let Some(device) = &device {
+        devices::disconnected(device);
+    }
+    publish_clients("disconnected", &client_id);
+    metrics::client_disconnected();
+
+    info!("Client {} connection closed", addr);
+}
+
+// Still answer a malformed command under its id when it has one
+fn id_of_invalid(text: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(text)
+        .ok()?
+        .get("id")?
+        .as_str()
+        .map(str::to_string)
+}
+
+// Run a command from a remote, answering a retried id from the cache
+// instead of running it again
+async fn run_once(client_id: &str, command: WebSocketCommand) -> WebSocketResponse {
+    let id = command.id.clone();
+    if let Some(id) = &id {
+        match idempotency::begin(client_id, id) {
+            idempotency::Begin::New => {}
+            idempotency::Begin::Running => {
+                return WebSocketResponse {
+                    id: Some(id.clone()),
+                    status: "error".to_string(),
+                    message: "Command is still running".to_string(),
+                    data: None,
+                    duration_us: None,
+                }
+            }
+            idempotency::Begin::Done(response) => {
+                debug!("Answering retried command {} from the cache", id);
+                return response;
+            }
+        }
+    }
+
+    let span = debug_span!("command", name = %command.command, id = ?command.id);
+    let command_name = command.command.clone();
+    let started = Instant::now();
+    let response = dispatch::<SystemBackend>(client_id, command)
+        .instrument(span)
+        .await;
+    let elapsed = started.elapsed();
+    metrics::record_command(&command_name, elapsed, response.status != "error");
+    if let Some(device) = device_of(client_id) {
+        devices::record_command(&device);
+    }
+    let client = client_label(client_id).unwrap_or_else(|| client_id.to_string());
+    metrics::publish_activity(
+        client_id,
+        &client,
+        &command_name,
+        &response.status,
+        &response.message,
+        elapsed,
+    );
+    if let Some(id) = &id {
+        idempotency::finish(client_id, id, &response);
+    }
+    response
+}
+
+/// Error code in the reply data of a command that ran out of time.
+pub const TIMEOUT_CODE: &str = "TIMEOUT";
+
+// How long a command may take before the remote is told it timed out.
+// Pointer input is stale after a moment, opening apps and pages can be slow
+fn command_timeout(command: &str) -> Duration {
+    let millis = match command {
+        "mouse_move"
+        | "mouse_click"
+        | "scroll"
+        | "send_key"
+        | "toggle_modifier_key"
+        | "clear_modifier_keys"
+        | "text_stream"
+        | "gamepad_button"
+        | "gamepad_release"
+        | "key_down"
+        | "key_up"
+        | "release_keys"
+        | "edit_action"
+        | "send_raw_key"
+        | "list_clients"
+        | "kick_client"
+        | "set_input_paused"
+        | "regenerate_pairing_code"
+        | "set_client_name"
+        | "get_remote_layout"
+        | "set_remote_layout"
+        | "start_repeat"
+        | "stop_repeat"
+        | "ping" => 2_000,
+        "play_pause" | "media_previous" | "media_next" | "media_stop" | "media_seek"
+        | "volume_up" | "volume_down" | "volume_mute" | "set_mute" | "volume_set"
+        | "brightness_set" | "brightness_up" | "brightness_down" | "get_volume"
+        | "get_brightness" | "toggle_mic_mute" | "get_mic_mute" | "volume_fade" => 5_000,
+        "open_website"
+        | "open_file"
+        | "applescript"
+        | "text_input"
+        | "find_in_page"
+        | "notify_host"
+        | "list_directory"
+        | "get_keyboard_layout"
+        | "now_playing"
+        | "set_audio_output"
+        | "list_audio_outputs"
+        | "media_browse"
+        | "media_on_deck"
+        | "list_cast_devices"
+        | "cast_media"
+        | "cast_stop" => 30_000,
+        _ => 10_000,
+    };
+    Duration::from_millis(millis)
+}
+/// Run a command with its time budget, after expanding aliases from the
+/// settings, and word the reply for the user. Every command from a remote or
+/// the HTTP API goes through here.
+pub async fn dispatch<B: InputBackend>(
+    client_id: &str,
+    command: WebSocketCommand,
+) -> WebSocketResponse {
+    let mut response = run_aliased::<B>(client_id, command).await;
+    response.message = i18n::localize(&response.message, crate::config::current().locale);
+    response
+}
+
+async fn run_aliased<B: InputBackend>(
+    client_id: &str,
+    command: WebSocketCommand,
+) -> WebSocketResponse {
+    let (id, name) = (command.id.clone(), command.command.clone());
+    let mut steps = aliases::expand(command);
+    if steps.len() == 1 {
+        return run_with_budget::<B>(client_id, steps.remove(0)).await;
+    }
+
+    // A macro stops at its first failing command and replies with it
+    let started = Instant::now();
+    let count = steps.len();
+    let mut last = None;
+    for step in steps {
+        let response = run_with_budget::<B>(client_id, step).await;
+        let failed = response.status == "error";
+        last = Some(response);
+        if failed {
+            break;
+        }
+    }
+    let Some(mut response) = last else {
+        return WebSocketResponse {
+            id,
+            status: "error".to_string(),
+            message: format!("Alias '{}' has no commands", name),
+            data: None,
+            duration_us: took(started),
+        };
+    };
+    if response.status != "error" {
+        response.message = format!("Ran {} ({} commands)", name, count);
+    }
+    response.duration_us = took(started);
+    response
+}
+
+async fn run_with_budget<B: InputBackend>(
+    client_id: &str,
+    command: WebSocketCommand,
+) -> WebSocketResponse {
+    let id = command.id.clone();
+    let budget = command_timeout(&command.command);
+    let started = Instant::now();
+    match tokio::time::timeout(budget, handle_command::<B>(client_id, command)).await {
+        Ok(response) => response,
+        Err(_) => WebSocketResponse {
+            id,
+            status: "error".to_string(),
+            message: "Command timed out".to_string(),
+            data: Some(serde_json::json!({
+                "code": TIMEOUT_CODE,
+                "timeout_ms": budget.as_millis() as u64,
+            })),
+            duration_us: took(started),
+        },
+    }
+}
+
+// Microseconds since `start`, reported with every reply
+fn took(start: Instant) -> Option<u64> {
+    Some(start.elapsed().as_micros() as u64)
+}
+
+fn epoch_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+/// Run a protocol command against an input backend and build the reply.
+pub async fn handle_command<B: InputBackend>(
+    client_id: &str,
+    command: WebSocketCommand,
+) -> WebSocketResponse {
+    use crate::input::{
+        applescript, audio_outputs, brightness_down, brightness_get, brightness_set, brightness_up,
+        clear_modifier_keys, get_modifier_key_states, media_next, media_previous, media_seek,
+        media_stop, mouse_click, mouse_move, now_playing, open_search, open_website, play_pause,
+        scroll, send_key, sensitive_text_input, set_audio_output, test_enigo_creation,
+        test_space_key, text_input, toggle_modifier_key, volume_down, volume_get, volume_mute,
+        volume_set, volume_set_muted, volume_up,
+    };
+    let received = Instant::now();
+    let received_us = epoch_micros();
+
+    // Reading state is harmless, everything else injects input
+    let read_only = matches!(
+        command.command.as_str(),
+        "get_modifier_key_states"
+            | "get_capabilities"
+            | "get_brightness"
+            | "get_volume"
+            | "get_mic_mute"
+            | "get_privacy_indicators"
+            | "list_audio_outputs"
+            | "now_playing"
+            | "media_browse"
+            | "media_on_deck"
+            | "list_cast_devices"
+            | "get_keyboard_layout"
+            | "list_directory"
+            | "subscribe_system_stats"
+            | "unsubscribe_system_stats"
+            | "get_battery_status"
+            | "get_idle_time"
+            | "get_frontmost_app"
+            | "get_mouse_position"
+            | "pick_color"
+            | "list_displays"
+            | "list_display_modes"
+            | "get_keyboard_backlight"
+            | "get_power_profiles"
+            | "list_bluetooth_devices"
+            | "get_network_info"
+            | "get_host_status"
+            | "get_activity"
+            | "list_schedules"
+            | "list_linked_hosts"
+            | "subscribe"
+            | "unsubscribe"
+            | "get_remote_layout"
+            | "ping"
+    );
+    // Changes only this remote's own session, so it's let through whenever
+    // reads are, except from dashboards, which stay strictly read-only
+    let own_session = matches!(
+        command.command.as_str(),
+        "take_control"
+            | "release_control"
+            | "set_client_name"
+            | "set_remote_layout"
+            | "stop_repeat"
+    );
+    let admin_command = ADMIN_COMMANDS.contains(&command.command.as_str());
+    if admin_command && !is_admin(client_id) {
+        return WebSocketResponse {
+            id: command.id,
+            status: "error".to_string(),
+            message: format!("Command '{}' needs an admin remote", command.command),
+            data: None,
+            duration_us: took(received),
+        };
+    }
+
+    if is_guest(client_id) && !guest_allows(&command.command) {
+        return WebSocketResponse {
+            id: command.id,
+            status: "error".to_string(),
+            message: "Guests can only control media".to_string(),
+            data: None,
+            duration_us: took(received),
+        };
+    }
+
+    if is_dashboard(client_id) && !read_only {
+        return WebSocketResponse {
+            id: command.id,
+            status: "error".to_string(),
+            message: "Dashboards can only read the host's state".to_string(),
+            data: None,
+            duration_us: took(received),
+        };
+    }
+
+    let device_role = device_of(client_id).map(|device| devices::role(&device));
+    if device_role == Some(DeviceRole::Media) && !guest_allows(&command.command) {
+        return WebSocketResponse {
+            id: command.id,
+            status: "error".to_string(),
+            message: "This device can only control media".to_string(),
+            data: None,
+            duration_us: took(received),
+        };
+    }
+
+    // Parental controls set for the device on the host
+    let restricted = device_of(client_id).map(|device| {
+        devices::restrictions(&device).check(&command.command, read_only || own_session)
+    });
+    if let Some(Err(reason)) = restricted {
+        return WebSocketResponse {
+            id: command.id,
+            status: "error".to_string(),
+            message: reason,
+            data: None,
+            duration_us: took(received),
+        };
+    }
+
+    // Do-not-type zone, see `secure_input`
+    if secure_input::types_text(&command.command)
+        && !is_admin(client_id)
+        && crate::config::current().protect_password_fields
+        && secure_input::password_field_focused()
+    {
+        return WebSocketResponse {
+            id: command.id,
+            status: "error".to_string(),
+            message: "A password field has focus on the host, typing from remotes is blocked"
+                .to_string(),
+            data: None,
+            duration_us: took(received),
+        };
+    }
+
+    // Admins can still resume input while it's paused
+    if crate::input::is_paused() && !read_only && !own_session && !admin_command {
+        return WebSocketResponse {
+            id: command.id,
+            status: "error".to_string(),
+            message: "Input is paused on the host".to_string(),
+            data: None,
+            duration_us: took(received),
+        };
+    }
+
+    if !crate::config::current().command_allowed(&command.command) {
+        return WebSocketResponse {
+            id: command.id,
+            status: "error".to_string(),
+            message: format!("Command '{}' is disabled in settings", command.command),
+            data: None,
+            duration_us: took(received),
+        };
+    }
+
+    if !listener_allows(client_id, &command.command) {
+        return WebSocketResponse {
+            id: command.id,
+            status: "error".to_string(),
+            message: format!(
+                "Command '{}' isn't allowed on this connection",
+                command.command
+            ),
+            data: None,
+            duration_us: took(received),
+        };
+    }
+
+    if control::is_input(&command.command) {
+        if let Err(message) = control::claim(client_id) {
+            return WebSocketResponse {
+                id: command.id,
+                status: "error".to_string(),
+                message,
+                data: None,
+                duration_us: took(received),
+            };
+        }
+    }
+
+    // Remotes polling state don't keep the host awake
+    if !read_only && !own_session {
+        idle::record_activity();
+    }
+    if control::is_input(&command.command) {
+        if let Some(label) = client_label(client_id) {
+            control::record_input(client_id, &label);
+        }
+    }
+
+    // A volume command from any remote ends a fade where it is
+    if volume_fade::is_volume_command(&command.command) {
+        volume_fade::cancel().await;
+    }
+
+    // Data returned alongside the response by the read-only commands
+    let mut reading = None;
+    let result = match command.command.as_str() {
+        "play_pause" => play_pause::<B>().await.map_err(|e| e.to_string()),
+        "media_previous" => media_previous::<B>().await.map_err(|e| e.to_string()),
+        "media_next" => media_next::<B>().await.map_err(|e| e.to_string()),
+        "media_stop" => media_stop::<B>().await.map_err(|e| e.to_string()),
+        "volume_up" => volume_up::<B>().await.map_err(|e| e.to_string()),
+        "volume_down" => volume_down::<B>().await.map_err(|e| e.to_string()),
+        "volume_mute" => volume_mute::<B>().await.map_err(|e| e.to_string()),
+        "test_enigo_creation" => test_enigo_creation::<B>().await.map_err(|e| e.to_string()),
+        "test_space_key" => test_space_key::<B>().await.map_err(|e| e.to_string()),
+        "text_input" => {
+            if let Some(data) = &command.data {
+                if let Some(text) = data.get("text").and_then(|t| t.as_str()) {
+                    // Additional safety checks
+                    if text.is_empty() {
+                        Ok(CommandResponse {
+                            status: "success".to_string(),
+                            message: "Empty text input ignored".to_string(),
+                        })
+                    } else if crate::input::text_length(text) > 1000 {
+                        Ok(CommandResponse {
+                            status: "error".to_string(),
+                            message: "Text too long (max 1000 characters)".to_string(),
+                        })
+                    } else if is_sensitive(&command) {
+                        // No error text either, a backend may echo what it got
+                        sensitive_text_input::<B>(client_id, text.to_string())
+                            .await
+                            .map_err(|_| "Sensitive text input failed".to_string())
+                    } else {
+                        match text_input::<B>(client_id, text.to_string()).await {
+                            Ok(response) => Ok(response),
+                            Err(e) => {
+                                error!("Text input error: {}", e);
+                                Ok(CommandResponse {
+                                    status: "error".to_string(),
+                                    message: format!("Text input failed: {}", e),
+                                })
+                            }
+                        }
+                    }
+                } else {
+                    Err("Missing or invalid 'text' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for text_input command".to_string())
+            }
+        }
+        "text_stream" => {
+            if let Some(data) = &command.data {
+                match serde_json::from_value(data.clone()) {
+                    Ok(delta) => crate::text_stream::apply::<B>(client_id, delta).await,
+                    Err(e) => Err(format!("Invalid text_stream edit: {}", e)),
+                }
+            } else {
+                Err("Missing data for text_stream command".to_string())
+            }
+        }
+        "mouse_move" => {
+            if let Some(data) = &command.data {
+                let delta_x = data.get("deltaX").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+                let delta_y = data.get("deltaY").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+                mouse_move::<B>(delta_x, delta_y)
+                    .await
+                    .map_err(|e| e.to_string())
+            } else {
+                Err("Missing data for mouse_move command".to_string())
+            }
+        }
+        "mouse_click" => {
+            if let Some(data) = &command.data {
+                if let Some(button) = data.get("button").and_then(|b| b.as_str()) {
+                    mouse_click::<B>(button.to_string())
+                        .await
+                        .map_err(|e| e.to_string())
+                } else {
+                    Err("Missing 'button' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for mouse_click command".to_string())
+            }
+        }
+        "scroll" => {
+            if let Some(data) = &command.data {
+                let delta_x = data.get("deltaX").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+                let delta_y = data.get("deltaY").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+                scroll::<B>(delta_x, delta_y).await.map_err(|e| e.to_string())
+            } else {
+                Err("Missing data for scroll command".to_string())
+            }
+        }
+        "open_website" => {
+            if let Some(data) = &command.data {
+                if let Some(url) = data.get("url").and_then(|u| u.as_str()) {
+                    let kiosk = data.get("kiosk").and_then(|k| k.as_bool());
+                    open_website(url.to_string(), kiosk)
+                        .await
+                        .map_err(|e| e.to_string())
+                } else {
+                    Err("Missing 'url' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for open_website command".to_string())
+            }
+        }
+        "list_cast_devices" => match crate::cast::list_devices().await {
+            Ok(devices) => {
+                let count = devices.len();
+                reading = Some(serde_json::json!({ "devices": devices }));
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: format!("Found {} cast devices", count),
+                })
+            }
+            Err(e) => Err(e),
+        },
+        // `{"url": ..., "device": id or name}`
+        "cast_media" => {
+            if let Some(data) = &command.data {
+                let url = data.get("url").and_then(|u| u.as_str());
+                let device = data.get("device").and_then(|d| d.as_str());
+                match (url, device) {
+                    (Some(url), Some(device)) => crate::cast::cast_media(url, device).await,
+                    (None, _) => Err("Missing 'url' parameter".to_string()),
+                    (_, None) => Err("Missing 'device' parameter".to_string()),
+                }
+            } else {
+                Err("Missing data for cast_media command".to_string())
+            }
+        }
+        // The device last cast to unless `{"device": ...}` is given
+        "cast_stop" => {
+            let device = command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("device"))
+                .and_then(|d| d.as_str());
+            crate::cast::stop(device).await
+        }
+        "toggle_modifier_key" => {
+            if let Some(data) = &command.data {
+                if let Some(key_name) = data.get("key_name").and_then(|k| k.as_str()) {
+                    toggle_modifier_key::<B>(key_name.to_string())
+                        .await
+                        .map_err(|e| e.to_string())
+                } else {
+                    Err("Missing 'key_name' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for toggle_modifier_key command".to_string())
+            }
+        }
+        "clear_modifier_keys" => clear_modifier_keys::<B>().await.map_err(|e| e.to_string()),
+        "get_capabilities" => Ok(CommandResponse {
+            status: "success".to_string(),
+            message: "Input capabilities retrieved".to_string(),
+        }),
+        "get_keyboard_layout" => {
+            match tokio::task::spawn_blocking(crate::layout::detect).await {
+                Ok(layout) => {
+                    reading = serde_json::to_value(&layout).ok();
+                    Ok(CommandResponse {
+                        status: "success".to_string(),
+                        message: format!("Keyboard layout is {}", layout.id),
+                    })
+                }
+                Err(e) => Err(format!("Layout detection panicked: {:?}", e)),
+            }
+        }
+        "get_modifier_key_states" => match get_modifier_key_states().await {
+            Ok(_states) => Ok(CommandResponse {
+                status: "success".to_string(),
+                message: "Modifier key states retrieved".to_string(),
+            }),
+            Err(e) => Err(e.to_string()),
+        },
+        "volume_set" => {
+            if let Some(data) = &command.data {
+                if let Some(value) = data.get("value").and_then(|v| v.as_u64()) {
+                    volume_set(value as u8).await.map_err(|e| e.to_string())
+                } else {
+                    Err("Missing or invalid 'value' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for volume_set command".to_string())
+            }
+        },
+        // `{"target": 20, "duration_ms": 3000}`, returns before the fade ends
+        "volume_fade" => {
+            let data = command.data.as_ref();
+            let target = data.and_then(|d| d.get("target")).and_then(|t| t.as_u64());
+            let duration_ms = data
+                .and_then(|d| d.get("duration_ms"))
+                .and_then(|d| d.as_u64());
+            match (target, duration_ms) {
+                (Some(target), Some(duration_ms)) if target <= 100 => {
+                    match volume_fade::start(target as u8, duration_ms).await {
+                        Ok(from) => {
+                            let duration_ms = duration_ms.min(volume_fade::MAX_DURATION_MS);
+                            reading = Some(serde_json::json!({
+                                "from": from,
+                                "target": target,
+                                "duration_ms": duration_ms,
+                            }));
+                            Ok(CommandResponse {
+                                status: "success".to_string(),
+                                message: format!(
+                                    "Fading volume from {}% to {}% over {} ms",
+                                    from, target, duration_ms
+                                ),
+                            })
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                (Some(_), Some(_)) => Err("'target' must be between 0 and 100".to_string()),
+                _ => Err("Missing 'target' or 'duration_ms' parameter".to_string()),
+            }
+        }
+        "applescript" => {
+            if let Some(data) = &command.data {
+                match serde_json::from_value(data.clone()) {
+                    Ok(script) => applescript(script).await,
+                    Err(e) => Err(format!("Unknown or invalid script: {}", e)),
+                }
+            } else {
+                Err("Missing data for applescript command".to_string())
+            }
+        }
+        "media_seek" => {
+            if let Some(data) = &command.data {
+                if let Some(offset) = data.get("offset").and_then(|v| v.as_f64()) {
+                    media_seek::<B>(offset).await
+                } else {
+                    Err("Missing or invalid 'offset' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for media_seek command".to_string())
+            }
+        }
+        "now_playing" => match now_playing().await {
+            Ok(playing) => {
+                reading = Some(playing);
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: "Now playing retrieved".to_string(),
+                })
+            }
+            Err(e) => Err(e),
+        },
+        // Libraries without a parent, otherwise what's inside `{"parent": id}`
+        "media_browse" => {
+            let parent = command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("parent"))
+                .and_then(|p| p.as_str());
+            match crate::media_server::browse(parent).await {
+                Ok(items) => {
+                    let count = items.len();
+                    reading = Some(serde_json::json!({ "items": items }));
+                    Ok(CommandResponse {
+                        status: "success".to_string(),
+                        message: format!("{} items", count),
+                    })
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "media_on_deck" => match crate::media_server::on_deck().await {
+            Ok(items) => {
+                let count = items.len();
+                reading = Some(serde_json::json!({ "items": items }));
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: format!("{} items on deck", count),
+                })
+            }
+            Err(e) => Err(e),
+        },
+        "get_volume" => match volume_get().await {
+            Ok(value) => {
+                reading = Some(serde_json::json!({ "value": value }));
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: format!("Volume is {}%", value),
+                })
+            }
+            Err(e) => Err(e),
+        },
+        "set_mute" => {
+            if let Some(data) = &command.data {
+                if let Some(muted) = data.get("muted").and_then(|m| m.as_bool()) {
+                    volume_set_muted(muted).await
+                } else {
+                    Err("Missing or invalid 'muted' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for set_mute command".to_string())
+            }
+        }
+        // Flips the microphone, or sets it with `{"muted": true}`
+        "toggle_mic_mute" => {
+            let muted = command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("muted"))
+                .and_then(|m| m.as_bool());
+            match crate::microphone::set_muted(muted).await {
+                Ok(muted) => {
+                    reading = Some(serde_json::json!({ "muted": muted }));
+                    Ok(CommandResponse {
+                        status: "success".to_string(),
+                        message: if muted {
+                            "Microphone muted"
+                        } else {
+                            "Microphone unmuted"
+                        }
+                        .to_string(),
+                    })
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "get_mic_mute" => match crate::microphone::is_muted().await {
+            Ok(muted) => {
+                reading = Some(serde_json::json!({ "muted": muted }));
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: if muted {
+                        "Microphone is muted"
+                    } else {
+                        "Microphone is on"
+                    }
+                    .to_string(),
+                })
+            }
+            Err(e) => Err(e),
+        },
+        "get_privacy_indicators" => match crate::privacy::current().await {
+            Ok(indicators) => {
+                reading = serde_json::to_value(indicators).ok();
+                let state = |in_use: bool| if in_use { "in use" } else { "off" };
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: format!(
+                        "Camera {}, microphone {}",
+                        state(indicators.camera),
+                        state(indicators.microphone)
+                    ),
+                })
+            }
+            Err(e) => Err(e),
+        },
+        "list_audio_outputs" => match audio_outputs().await {
+            Ok(outputs) => {
+                reading = Some(serde_json::json!({ "outputs": outputs }));
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: "Audio outputs retrieved".to_string(),
+                })
+            }
+            Err(e) => Err(e),
+        },
+        "set_audio_output" => {
+            if let Some(data) = &command.data {
+                if let Some(name) = data.get("name").and_then(|n| n.as_str()) {
+                    set_audio_output(name.to_string()).await
+                } else {
+                    Err("Missing 'name' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for set_audio_output command".to_string())
+            }
+        }
+        "brightness_up" => brightness_up::<B>().await.map_err(|e| e.to_string()),
+        "brightness_down" => brightness_down::<B>().await.map_err(|e| e.to_string()),
+        "get_brightness" => match brightness_get().await {
+            Ok(value) => {
+                reading = Some(serde_json::json!({ "value": value }));
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: format!("Brightness is {}%", value),
+                })
+            }
+            Err(e) => Err(e),
+        },
+        "brightness_set" => {
+            if let Some(data) = &command.data {
+                if let Some(value) = data.get("value").and_then(|v| v.as_u64()) {
+                    brightness_set(value as u8).await.map_err(|e| e.to_string())
+                } else {
+                    Err("Missing or invalid 'value' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for brightness_set command".to_string())
+            }
+        },
+        "open_search" => {
+            let data = command.data.as_ref();
+            let query = data
+                .and_then(|d| d.get("query"))
+                .and_then(|q| q.as_str())
+                .map(|q| q.to_string());
+            // Typing a query usually means "open the first hit"
+            let submit = data
+                .and_then(|d| d.get("submit"))
+                .and_then(|s| s.as_bool())
+                .unwrap_or(query.is_some());
+            open_search::<B>(query, submit).await
+        }
+        "find_in_page" => {
+            let query = command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("query"))
+                .and_then(|q| q.as_str())
+                .unwrap_or_default();
+            shortcuts::find_in_page::<B>(query.to_string()).await
+        }
+        "list_directory" => {
+            let path = command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("path"))
+                .and_then(|p| p.as_str());
+            match files::list_directory(path) {
+                Ok(listing) => {
+                    let count = listing.entries.len();
+                    reading = serde_json::to_value(&listing).ok();
+                    Ok(CommandResponse {
+                        status: "success".to_string(),
+                        message: format!("Listed {} entries", count),
+                    })
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "open_file" => {
+            if let Some(data) = &command.data {
+                if let Some(path) = data.get("path").and_then(|p| p.as_str()) {
+                    files::open_file(path).map(|name| CommandResponse {
+                        status: "success".to_string(),
+                        message: format!("Opened {}", name),
+                    })
+                } else {
+                    Err("Missing 'path' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for open_file command".to_string())
+            }
+        }
+        "wake_device" => {
+            if let Some(data) = &command.data {
+                let broadcast = data
+                    .get("broadcast")
+                    .and_then(|b| b.as_str())
+                    .map(|b| b.parse::<std::net::Ipv4Addr>());
+                let port = data
+                    .get("port")
+                    .and_then(|p| p.as_u64())
+                    .and_then(|p| u16::try_from(p).ok());
+                match (data.get("mac_address").and_then(|m| m.as_str()), broadcast) {
+                    (_, Some(Err(_))) => Err("Invalid 'broadcast' address".to_string()),
+                    (Some(mac), broadcast) => {
+                        wol::wake(mac, broadcast.and_then(|b| b.ok()), port).map(|_| {
+                            CommandResponse {
+                                status: "success".to_string(),
+                                message: format!("Wake-on-LAN packet sent to {}", mac),
+                            }
+                        })
+                    }
+                    (None, _) => Err("Missing 'mac_address' parameter".to_string()),
+                }
+            } else {
+                Err("Missing data for wake_device command".to_string())
+            }
+        }
+        "list_linked_hosts" => {
+            let hosts = crate::forwarding::list();
+            let message = format!("{} linked hosts", hosts.len());
+            reading = Some(serde_json::json!({ "hosts": hosts }));
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message,
+            })
+        }
+        // `{"host": name, "command": name, "data": {...}}`
+        "forward_to" => {
+            let host = command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("host"))
+                .and_then(|h| h.as_str());
+            let inner = command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("command"))
+                .and_then(|c| c.as_str());
+            match (host, inner) {
+                (Some(host), Some(inner)) => {
+                    // The other host only sees its own token, so this
+                    // client's limits are checked here
+                    let restricted = device_of(client_id)
+                        .map(|device| devices::restrictions(&device).check(inner, false));
+                    if let Some(Err(reason)) = restricted {
+                        Err(reason)
+                    } else if !listener_allows(client_id, inner) {
+                        Err(format!(
+                            "Command '{}' isn't allowed on this connection",
+                            inner
+                        ))
+                    } else {
+                        let forwarded = WebSocketCommand {
+                            id: command.id.clone(),
+                            command: inner.to_string(),
+                            data: command.data.as_ref().and_then(|d| d.get("data")).cloned(),
+                        };
+                        match crate::forwarding::forward(host, forwarded).await {
+                            Ok(reply) if reply.status == "error" => Err(reply.message),
+                            Ok(reply) => {
+                                reading = reply.data;
+                                Ok(CommandResponse {
+                                    status: "success".to_string(),
+                                    message: reply.message,
+                                })
+                            }
+                            Err(e) => Err(e),
+                        }
+                    }
+                }
+                _ => Err("Missing 'host' or 'command' parameter".to_string()),
+            }
+        }
+        "schedule_command" => {
+            if let Some(data) = &command.data {
+                let commands = data
+                    .get("commands")
+                    .cloned()
+                    .map(serde_json::from_value::<Vec<schedule::ScheduledCommand>>);
+                let delay = data.get("delay_secs").and_then(|d| d.as_u64());
+                let label = data
+                    .get("label")
+                    .and_then(|l| l.as_str())
+                    .map(|l| l.to_string());
+                // Scheduled commands run as the scheduler, so check them
+                // against this client's listener and device limits now. The
+                // device's limits are checked again when they run
+                let restrictions =
+                    device_of(client_id).map(|device| devices::restrictions(&device));
+                let blocked = |commands: &[schedule::ScheduledCommand]| {
+                    commands.iter().any(|scheduled| {
+                        let command = WebSocketCommand {
+                            id: None,
+                            command: scheduled.command.clone(),
+                            data: None,
+                        };
+                        aliases::expand(command).iter().any(|step| {
+                            !listener_allows(client_id, &step.command)
+                                || restrictions
+                                    .as_ref()
+                                    .is_some_and(|r| r.check(&step.command, false).is_err())
+                        })
+                    })
+                };
+                match (commands, delay) {
+                    (Some(Ok(commands)), Some(_)) if blocked(&commands) => {
+                        Err("These commands aren't allowed on this connection".to_string())
+                    }
+                    (Some(Ok(commands)), Some(delay)) => {
+                        match schedule::add(commands, delay, label, device_of(client_id)) {
+                            Ok(scheduled) => {
+                                reading = serde_json::to_value(&scheduled).ok();
+                                Ok(CommandResponse {
+                                    status: "success".to_string(),
+                                    message: format!("Scheduled in {} s", delay),
+                                })
+                            }
+                            Err(e) => Err(e),
+                        }
+                    }
+                    (Some(Err(e)), _) => Err(format!("Invalid 'commands' parameter: {}", e)),
+                    _ => Err("Missing 'commands' or 'delay_secs' parameter".to_string()),
+                }
+            } else {
+                Err("Missing data for schedule_command command".to_string())
+            }
+        }
+        "list_schedules" => {
+            let schedules = schedule::list();
+            let message = format!("{} schedules pending", schedules.len());
+            reading = Some(serde_json::json!({ "schedules": schedules }));
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message,
+            })
+        }
+        "cancel_schedule" => {
+            if let Some(data) = &command.data {
+                if let Some(id) = data.get("id").and_then(|i| i.as_str()) {
+                    schedule::cancel(id).map(|_| CommandResponse {
+                        status: "success".to_string(),
+                        message: "Schedule cancelled".to_string(),
+                    })
+                } else {
+                    Err("Missing 'id' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for cancel_schedule command".to_string())
+            }
+        }
+        "list_clients" => {
+            let clients = crate::server::list_clients().await.unwrap_or_default();
+            let message = format!("{} remotes connected", clients.len());
+            reading = Some(serde_json::json!({ "clients": clients }));
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message,
+            })
+        }
+        "kick_client" => match command
+            .data
+            .as_ref()
+            .and_then(|d| d.get("client_id"))
+            .and_then(|i| i.as_str())
+        {
+            Some(id) if id == client_id => Err("Admins can't kick themselves".to_string()),
+            Some(id) if kick_client(id) => Ok(CommandResponse {
+                status: "success".to_string(),
+                message: format!("Disconnected {}", id),
+            }),
+            Some(id) => Err(format!("No remote with id {}", id)),
+            None => Err("Missing 'client_id' parameter".to_string()),
+        },
+        "set_input_paused" => match command
+            .data
+            .as_ref()
+            .and_then(|d| d.get("paused"))
+            .and_then(|p| p.as_bool())
+        {
+            Some(paused) => {
+                crate::input::set_paused(paused);
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: if paused {
+                        "Remote input paused".to_string()
+                    } else {
+                        "Remote input resumed".to_string()
+                    },
+                })
+            }
+            None => Err("Missing 'paused' parameter".to_string()),
+        },
+        "regenerate_pairing_code" => crate::server::regenerate_pairing_code().await,
+        "set_client_name" => match command
+            .data
+            .as_ref()
+            .and_then(|d| d.get("name"))
+            .and_then(|n| n.as_str())
+        {
+            Some(name) => {
+                let name: String = name
+                    .chars()
+                    .filter(|c| !c.is_control())
+                    .take(MAX_CLIENT_NAME_CHARS)
+                    .collect();
+                let name = name.trim().to_string();
+                if let Some(client) = CLIENT_INFO.lock().unwrap().get_mut(client_id) {
+                    client.name = Some(name.clone()).filter(|name| !name.is_empty());
+                }
+                publish_clients("renamed", client_id);
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: format!("Name set to {}", name),
+                })
+            }
+            None => Err("Missing 'name' parameter".to_string()),
+        },
+        // Layouts follow the device id, not the browser
+        "get_remote_layout" => match device_of(client_id) {
+            Some(device) => {
+                let layout = devices::layout(&device);
+                let message = if layout.is_some() {
+                    "Layout loaded"
+                } else {
+                    "No layout stored"
+                };
+                reading = Some(serde_json::json!({ "layout": layout }));
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: message.to_string(),
+                })
+            }
+            None => Err("Layouts are kept per device, connect with ?device=<id>".to_string()),
+        },
+        // `{"layout": {...}}`, any JSON the remote wants back later
+        "set_remote_layout" => match (
+            device_of(client_id),
+            command.data.as_ref().and_then(|d| d.get("layout")),
+        ) {
+            (Some(device), Some(layout)) => match devices::set_layout(&device, layout.clone()) {
+                Ok(_) => Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: "Layout saved".to_string(),
+                }),
+                Err(e) => Err(e),
+            },
+            (None, _) => Err("Layouts are kept per device, connect with ?device=<id>".to_string()),
+            (_, None) => Err("Missing 'layout' parameter".to_string()),
+        },
+        "get_idle_time" => {
+            let idle = idle::idle_time();
+            let message = format!("Last remote input {} s ago", idle.remote_secs);
+            reading = serde_json::to_value(&idle).ok();
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message,
+            })
+        }
+        "get_frontmost_app" => match frontmost::current().await {
+            Ok(app) => {
+                let message = format!("{} is in front", app.name);
+                reading = serde_json::to_value(&app).ok();
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message,
+                })
+            }
+            Err(e) => Err(e),
+        },
+        "get_host_status" => {
+            let metrics = metrics::snapshot();
+            let host = crate::discovery::host_name();
+            reading = Some(serde_json::json!({
+                "host": host,
+                "uptime_secs": metrics.uptime_secs,
+                "clients": metrics.connected_clients,
+                "total_commands": metrics.total_commands,
+                "commands_per_sec": metrics.commands_per_sec,
+                "input_paused": crate::input::is_paused(),
+            }));
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message: format!("{} is up", host),
+            })
+        }
+        "get_activity" => {
+            let activity = metrics::recent_activity();
+            let message = format!("{} recent commands", activity.len());
+            reading = serde_json::to_value(&activity).ok();
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message,
+            })
+        }
+        "get_mouse_position" => match crate::input::mouse_position::<B>().await {
+            Ok(position) => {
+                let message = format!("Mouse at ({}, {})", position.x, position.y);
+                reading = serde_json::to_value(&position).ok();
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message,
+                })
+            }
+            Err(e) => Err(e),
+        },
+        "pick_color" => {
+            let coordinate = |name| {
+                command
+                    .data
+                    .as_ref()
+                    .and_then(|d| d.get(name))
+                    .and_then(|v| v.as_i64())
+                    .and_then(|v| i32::try_from(v).ok())
+            };
+            // Under the pointer unless given
+            let point = match (coordinate("x"), coordinate("y")) {
+                (Some(x), Some(y)) => Ok((x, y)),
+                (None, None) => crate::input::mouse_position::<B>()
+                    .await
+                    .map(|position| (position.x, position.y)),
+                _ => Err("Give both 'x' and 'y' or neither".to_string()),
+            };
+            match point {
+                Ok((x, y)) => match color_picker::pick(x, y).await {
+                    Ok(color) => {
+                        let message = format!("{} at ({}, {})", color.hex, x, y);
+                        reading = serde_json::to_value(&color).ok();
+                        Ok(CommandResponse {
+                            status: "success".to_string(),
+                            message,
+                        })
+                    }
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            }
+        }
+        "get_battery_status" => match battery::status() {
+            Some(status) => {
+                let message = format!(
+                    "Battery at {}%{}",
+                    status.percent,
+                    if status.charging { ", charging" } else { "" }
+                );
+                reading = serde_json::to_value(&status).ok();
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message,
+                })
+            }
+            None => Ok(CommandResponse {
+                status: "info".to_string(),
+                message: "The host has no battery".to_string(),
+            }),
+        },
+        "notify_host" => {
+            if let Some(data) = &command.data {
+                let title = data.get("title").and_then(|v| v.as_str()).unwrap_or_default();
+                let body = data.get("body").and_then(|v| v.as_str()).unwrap_or_default();
+                let client = client_id.to_string();
+                let (title, body) = (title.to_string(), body.to_string());
+                match tokio::task::spawn_blocking(move || {
+                    crate::notifications::post(&client, &title, &body)
+                })
+                .await
+                {
+                    Ok(Ok(())) => Ok(CommandResponse {
+                        status: "success".to_string(),
+                        message: "Notification shown on the host".to_string(),
+                    }),
+                    Ok(Err(e)) => Err(e),
+                    Err(e) => Err(format!("Notification task panicked: {:?}", e)),
+                }
+            } else {
+                Err("Missing data for notify_host command".to_string())
+            }
+        }
+        "speak" => {
+            if let Some(data) = &command.data {
+                let text = data.get("text").and_then(|v| v.as_str()).unwrap_or_default();
+                match serde_json::from_value::<crate::speech::SpeechOptions>(data.clone()) {
+                    Ok(options) => crate::speech::speak(client_id, text, options).await,
+                    Err(e) => Err(format!("Invalid speech options: {}", e)),
+                }
+            } else {
+                Err("Missing data for speak command".to_string())
+            }
+        }
+        "announce" => match command
+            .data
+            .as_ref()
+            .and_then(|d| d.get("text"))
+            .and_then(|v| v.as_str())
+        {
+            Some(text) => crate::accessibility::announce(text).await,
+            None => Err("Missing 'text' parameter".to_string()),
+        },
+        "stop_speaking" => Ok(CommandResponse {
+            status: "success".to_string(),
+            message: if crate::speech::stop() {
+                "Stopped speaking".to_string()
+            } else {
+                "Nothing was being spoken".to_string()
+            },
+        }),
+        // Clients subtract these from their own clock to split the round trip
+        "ping" => {
+            let client_time = command.data.as_ref().and_then(|d| d.get("client_time")).cloned();
+            reading = Some(serde_json::json!({
+                "client_time": client_time,
+                "received_us": received_us,
+                "replied_us": epoch_micros(),
+            }));
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message: "pong".to_string(),
+            })
+        }
+        "send_raw_key" => match command
+            .data
+            .as_ref()
+            .and_then(|d| d.get("code"))
+            .and_then(|v| v.as_u64())
+            .and_then(|code| u32::try_from(code).ok())
+        {
+            Some(code) => crate::input::send_raw_key::<B>(code).await,
+            None => Err("Missing or invalid 'code' parameter".to_string()),
+        },
+        "list_displays" => match crate::display::list().await {
+            Ok(screens) => {
+                let message = format!("{} displays", screens.len());
+                reading = serde_json::to_value(&screens).ok();
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message,
+                })
+            }
+            Err(e) => Err(e),
+        },
+        // `{"kind": "text" | "image" | "qr" | "url", ..., "display": name}`
+        "display_content" => {
+            let data = command.data.clone().unwrap_or_default();
+            let display = data
+                .get("display")
+                .and_then(|d| d.as_str())
+                .map(str::to_string);
+            match serde_json::from_value::<crate::display_content::Content>(data) {
+                Ok(content) => {
+                    crate::display_content::show(content, display)
+                        .await
+                        .map(|message| CommandResponse {
+                            status: "success".to_string(),
+                            message,
+                        })
+                }
+                Err(e) => Err(format!("Invalid content: {}", e)),
+            }
+        }
+        "hide_content" => Ok(CommandResponse {
+            status: "success".to_string(),
+            message: if crate::display_content::hide() {
+                "Content hidden".to_string()
+            } else {
+                "Nothing was shown".to_string()
+            },
+        }),
+        "list_display_modes" => match crate::display::list_modes().await {
+            Ok(displays) => {
+                let message = format!("Modes of {} displays", displays.len());
+                reading = serde_json::to_value(&displays).ok();
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message,
+                })
+            }
+            Err(e) => Err(e),
+        },
+        // `{"mode": "mirror"}` for the layout, or `{"width": 3840, "height":
+        // 2160, "refresh_rate": 24, "display": name}` for the resolution
+        "set_display_mode" => {
+            let data = command.data.as_ref();
+            let mode = data
+                .and_then(|d| d.get("mode"))
+                .cloned()
+                .map(serde_json::from_value::<crate::display::DisplayMode>);
+            let size = |key: &str| {
+                data.and_then(|d| d.get(key))
+                    .and_then(|v| v.as_u64())
+                    .and_then(|v| u32::try_from(v).ok())
+            };
+            match (mode, size("width"), size("height")) {
+                (Some(Ok(mode)), _, _) => crate::display::set_mode(mode).await,
+                (Some(Err(e)), _, _) => Err(format!("Invalid 'mode' parameter: {}", e)),
+                (None, Some(width), Some(height)) => {
+                    let display = data
+                        .and_then(|d| d.get("display"))
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string);
+                    let refresh_rate = data
+                        .and_then(|d| d.get("refresh_rate"))
+                        .and_then(|v| v.as_f64());
+                    crate::display::set_resolution(display, width, height, refresh_rate).await
+                }
+                (None, _, _) => {
+                    Err("Missing 'mode' or 'width' and 'height' parameters".to_string())
+                }
+            }
+        }
+        "get_keyboard_backlight" => match crate::hardware::keyboard_backlight().await {
+            Ok(level) => {
+                reading = Some(serde_json::json!({ "level": level }));
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: format!("Keyboard backlight at {}%", level),
+                })
+            }
+            Err(e) => Err(e),
+        },
+        // `{"level": 0-100}`, or `{"step": "up" | "down"}` where only the
+        // keys work (macOS)
+        "set_keyboard_backlight" => {
+            let data = command.data.as_ref();
+            let level = data
+                .and_then(|d| d.get("level"))
+                .and_then(|v| v.as_u64())
+                .map(|v| v.min(100) as u8);
+            let step = data
+                .and_then(|d| d.get("step"))
+                .cloned()
+                .map(serde_json::from_value::<crate::hardware::Step>);
+            match (level, step) {
+                (Some(level), _) => match crate::hardware::set_keyboard_backlight(level).await {
+                    Ok(level) => {
+                        reading = Some(serde_json::json!({ "level": level }));
+                        Ok(CommandResponse {
+                            status: "success".to_string(),
+                            message: format!("Keyboard backlight set to {}%", level),
+                        })
+                    }
+                    Err(e) => Err(e),
+                },
+                (None, Some(Ok(step))) => crate::hardware::step_keyboard_backlight::<B>(step)
+                    .await
+                    .map(|_| CommandResponse {
+                        status: "success".to_string(),
+                        message: format!("Keyboard backlight {:?}", step),
+                    }),
+                (None, Some(Err(e))) => Err(format!("Invalid 'step' parameter: {}", e)),
+                (None, None) => Err("Missing 'level' or 'step' parameter".to_string()),
+            }
+        }
+        "get_power_profiles" => match crate::hardware::power_profiles().await {
+            Ok(profiles) => {
+                let message = profiles
+                    .iter()
+                    .find(|profile| profile.active)
+                    .map(|profile| format!("Power profile is {}", profile.name))
+                    .unwrap_or_else(|| "No power profile is active".to_string());
+                reading = serde_json::to_value(&profiles).ok();
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message,
+                })
+            }
+            Err(e) => Err(e),
+        },
+        "set_power_profile" => match command
+            .data
+            .as_ref()
+            .and_then(|d| d.get("profile"))
+            .and_then(|v| v.as_str())
+        {
+            Some(profile) => crate::hardware::set_power_profile(profile.to_string())
+                .await
+                .map(|message| CommandResponse {
+                    status: "success".to_string(),
+                    message,
+                }),
+            None => Err("Missing 'profile' parameter".to_string()),
+        },
+        "get_network_info" => {
+            let info = crate::network::info(client_ip(client_id)).await;
+            let message = match &info.wifi {
+                Some(wifi) => match wifi.signal_percent {
+                    Some(signal) => format!("On Wi-Fi, signal {}%", signal),
+                    None => "On Wi-Fi".to_string(),
+                },
+                None => "Not on Wi-Fi".to_string(),
+            };
+            reading = serde_json::to_value(&info).ok();
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message,
+            })
+        }
+        "list_bluetooth_devices" => match crate::bluetooth::list().await {
+            Ok(devices) => {
+                let message = format!("{} paired Bluetooth devices", devices.len());
+                reading = serde_json::to_value(&devices).ok();
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message,
+                })
+            }
+            Err(e) => Err(e),
+        },
+        "connect_bluetooth_device" => match command
+            .data
+            .as_ref()
+            .and_then(|d| d.get("address"))
+            .and_then(|v| v.as_str())
+        {
+            Some(address) => match crate::bluetooth::connect(address.to_string()).await {
+                Ok(device) => {
+                    let message = format!("Connected {}", device.name);
+                    reading = serde_json::to_value(&device).ok();
+                    Ok(CommandResponse {
+                        status: "success".to_string(),
+                        message,
+                    })
+                }
+                Err(e) => Err(e),
+            },
+            None => Err("Missing 'address' parameter".to_string()),
+        },
+        "display_sleep" => crate::display::sleep().await,
+        "display_wake" => crate::display::wake::<B>().await,
+        "toggle_accessibility" => {
+            let data = command.data.as_ref();
+            let feature = data
+                .and_then(|d| d.get("feature"))
+                .cloned()
+                .map(serde_json::from_value::<crate::accessibility::Feature>);
+            let enabled = data
+                .and_then(|d| d.get("enabled"))
+                .and_then(|v| v.as_bool());
+            match feature {
+                Some(Ok(feature)) => crate::accessibility::set(feature, enabled).await,
+                Some(Err(e)) => Err(format!("Invalid 'feature' parameter: {}", e)),
+                None => Err("Missing 'feature' parameter".to_string()),
+            }
+        }
+        "zoom" => {
+            let action = command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("action"))
+                .cloned()
+                .map(serde_json::from_value::<crate::zoom::ZoomAction>);
+            match action {
+                Some(Ok(action)) => crate::zoom::zoom::<B>(action).await,
+                Some(Err(e)) => Err(format!("Invalid 'action' parameter: {}", e)),
+                None => Err("Missing 'action' parameter".to_string()),
+            }
+        }
+        "switch_desktop" | "move_window_to_desktop" => {
+            if let Some(data) = &command.data {
+                match crate::desktops::DesktopTarget::from_data(data) {
+                    Ok(target) if command.command == "switch_desktop" => {
+                        crate::desktops::switch_desktop::<B>(target).await
+                    }
+                    Ok(target) => crate::desktops::move_window_to_desktop::<B>(target).await,
+                    Err(e) => Err(e),
+                }
+            } else {
+                Err(format!("Missing data for {} command", command.command))
+            }
+        }
+        "edit_action" => {
+            let action = command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("action"))
+                .cloned()
+                .map(serde_json::from_value::<shortcuts::EditAction>);
+            match action {
+                Some(Ok(action)) => shortcuts::perform::<B>(action).await,
+                Some(Err(e)) => Err(format!("Invalid 'action' parameter: {}", e)),
+                None => Err("Missing 'action' parameter".to_string()),
+            }
+        }
+        "key_down" | "key_up" => {
+            match command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("key"))
+                .and_then(|v| v.as_str())
+            {
+                Some(key) => {
+                    let result = if command.command == "key_down" {
+                        game_keys::key_down::<B>(client_id, key)
+                    } else {
+                        game_keys::key_up::<B>(client_id, key)
+                    };
+                    result.map(|_| CommandResponse {
+                        status: "success".to_string(),
+                        message: format!("Key {} queued", key),
+                    })
+                }
+                None => Err("Missing 'key' parameter".to_string()),
+            }
+        }
+        "release_keys" => Ok(CommandResponse {
+            status: "success".to_string(),
+            message: format!("Released {} keys", game_keys::release_all::<B>(client_id)),
+        }),
+        "gamepad_button" => {
+            if let Some(data) = &command.data {
+                let button = data
+                    .get("button")
+                    .cloned()
+                    .map(serde_json::from_value::<gamepad::Button>);
+                let pressed = data.get("pressed").and_then(|v| v.as_bool()).unwrap_or(true);
+                match button {
+                    Some(Ok(button)) => gamepad::button::<B>(client_id, button, pressed).await,
+                    Some(Err(e)) => Err(format!("Invalid 'button' parameter: {}", e)),
+                    None => Err("Missing 'button' parameter".to_string()),
+                }
+            } else {
+                Err("Missing data for gamepad_button command".to_string())
+            }
+        }
+        "gamepad_release" => gamepad::release_all::<B>(client_id).await,
+        "take_control" => control::take(client_id).map(|_| CommandResponse {
+            status: "success".to_string(),
+            message: "You control input".to_string(),
+        }),
+        "release_control" => {
+            if control::release(client_id) {
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: "Released input control".to_string(),
+                })
+            } else {
+                Err("You don't control input".to_string())
+            }
+        }
+        "subscribe" | "unsubscribe" => {
+            let topics = command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("topics"))
+                .cloned()
+                .map(serde_json::from_value::<Vec<Topic>>);
+            match topics {
+                Some(Ok(topics)) => {
+                    // Other remotes' commands aren't a guest's business
+                    let subscribed = if command.command == "subscribe" {
+                        if topics.contains(&Topic::Activity) && is_guest(client_id) {
+                            Err("Guests can't follow the activity log".to_string())
+                        } else {
+                            events::subscribe(client_id, &topics)
+                        }
+                    } else {
+                        Ok(events::unsubscribe(client_id, &topics))
+                    };
+                    match subscribed {
+                        Ok(subscribed) => {
+                            reading = Some(serde_json::json!({ "topics": subscribed }));
+                            Ok(CommandResponse {
+                                status: "success".to_string(),
+                                message: format!("Subscribed to {} topics", subscribed.len()),
+                            })
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                Some(Err(e)) => Err(format!("Invalid 'topics' parameter: {}", e)),
+                None => Err("Missing 'topics' parameter".to_string()),
+            }
+        }
+        "subscribe_system_stats" => {
+            let interval_ms = command
+                .data
+                .as_ref()
+                .and_then(|d| d.get("interval_ms"))
+                .and_then(|i| i.as_u64())
+                .unwrap_or_else(|| crate::config::current().stats_interval_ms);
+            let interval_ms = stats::subscribe(client_id, interval_ms);
+            reading = Some(serde_json::json!({ "interval_ms": interval_ms }));
+            Ok(CommandResponse {
+                status: "success".to_string(),
+                message: format!("Sending system stats every {} ms", interval_ms),
+            })
+        }
+        "unsubscribe_system_stats" => {
+            if stats::unsubscribe(client_id) {
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: "Stopped sending system stats".to_string(),
+                })
+            } else {
+                Ok(CommandResponse {
+                    status: "info".to_string(),
+                    message: "Not subscribed to system stats".to_string(),
+                })
+            }
+        }
+        // `{"command": "volume_up", "data": {...}, "interval_ms": 150}`, runs
+        // until `stop_repeat`
+        "start_repeat" => match command
+            .data
+            .as_ref()
+            .and_then(|d| d.get("command"))
+            .and_then(|c| c.as_str())
+        {
+            Some(name) => {
+                let data = command.data.as_ref();
+                let repeated = WebSocketCommand {
+                    id: None,
+                    command: name.to_string(),
+                    data: data.and_then(|d| d.get("data")).cloned(),
+                };
+                let interval_ms = data
+                    .and_then(|d| d.get("interval_ms"))
+                    .and_then(|i| i.as_u64())
+                    .unwrap_or(repeat::DEFAULT_INTERVAL_MS);
+                match repeat::start(client_id, repeated, interval_ms) {
+                    Ok(interval_ms) => {
+                        reading = Some(serde_json::json!({ "interval_ms": interval_ms }));
+                        Ok(CommandResponse {
+                            status: "success".to_string(),
+                            message: format!("Repeating {} every {} ms", name, interval_ms),
+                        })
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            None => Err("Missing 'command' parameter".to_string()),
+        },
+        "stop_repeat" => {
+            if repeat::stop(client_id) {
+                Ok(CommandResponse {
+                    status: "success".to_string(),
+                    message: "Stopped repeating".to_string(),
+                })
+            } else {
+                Ok(CommandResponse {
+                    status: "info".to_string(),
+                    message: "Nothing is repeating".to_string(),
+                })
+            }
+        }
+        "upload_start" => {
+            if let Some(data) = &command.data {
+                let name = data.get("name").and_then(|n| n.as_str());
+                let size = data.get("size").and_then(|s| s.as_u64());
+                if let (Some(name), Some(size)) = (name, size) {
+                    match upload::start(client_id, name, size) {
+                        Ok(ticket) => {
+                            reading = serde_json::to_value(&ticket).ok();
+                            Ok(CommandResponse {
+                                status: "success".to_string(),
+                                message: format!("Ready to receive {}", ticket.name),
+                            })
+                        }
+                        Err(e) => Err(e),
+                    }
+                } else {
+                    Err("Missing 'name' or 'size' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for upload_start command".to_string())
+            }
+        }
+        "upload_cancel" => {
+            if let Some(data) = &command.data {
+                if let Some(upload_id) = data.get("upload_id").and_then(|u| u.as_str()) {
+                    upload::cancel(client_id, upload_id).map(|_| CommandResponse {
+                        status: "success".to_string(),
+                        message: "Upload cancelled".to_string(),
+                    })
+                } else {
+                    Err("Missing 'upload_id' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for upload_cancel command".to_string())
+            }
+        }
+        "send_key" => {
+            if let Some(data) = &command.data {
+                if let Some(key) = data.get("key").and_then(|k| k.as_str()) {
+                    send_key::<B>(key.to_string()).await.map_err(|e| e.to_string())
+                } else {
+                    Err("Missing 'key' parameter".to_string())
+                }
+            } else {
+                Err("Missing data for send_key command".to_string())
+            }
+        },
+        _ => Err(format!("Unknown command: {}", command.command)),
+    };
+
+    match result {
+        Ok(response) => {
+            // Special handling for get_modifier_key_states to include data
+            let data = if command.command == "get_modifier_key_states" {
+                get_modifier_key_states().await.ok()
+            } else if command.command == "get_capabilities" {
+                let hardware = crate::hardware::capabilities().await;
+                serde_json::to_value(input_backend::capabilities())
+                    .ok()
+                    .map(|mut capabilities| {
+                        capabilities["hardware"] = serde_json::json!(hardware);
+                        capabilities
+                    })
+            } else {
+                reading
+            };
+
+            WebSocketResponse {
+                id: command.id,
+                status: response.status,
+                message: response.message,
+                data,
+                duration_us: took(received),
+            }
+        }
+        Err(error) => WebSocketResponse {
+            id: command.id,
+            status: "error".to_string(),
+            message: error,
+            data: None,
+            duration_us: took(received),
+        },
+    }
+}
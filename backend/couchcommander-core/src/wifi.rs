@@ -0,0 +1,150 @@
+//! The Wi-Fi network the host is on and how good its link is, to tell a
+//! weak signal apart from other causes of a laggy remote.
+//!
+//! macOS asks `system_profiler`, which leaves the network name out unless
+//! the app has the Location permission. Windows reads `netsh wlan` and Linux
+//! NetworkManager's `nmcli`.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WifiInfo {
+    pub ssid: Option<String>,
+    pub signal_percent: Option<u8>,
+    pub signal_dbm: Option<i32>,
+    // Transmit rate, as the OS reports it
+    pub link_speed_mbps: Option<f64>,
+}
+
+// The usual mapping, -100 dBm is nothing and -50 dBm is full strength
+#[cfg(target_os = "macos")]
+fn dbm_to_percent(dbm: i32) -> u8 {
+    ((dbm + 100) * 2).clamp(0, 100) as u8
+}
+
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn percent_to_dbm(percent: u8) -> i32 {
+    i32::from(percent.min(100)) / 2 - 100
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn current() -> Result<Option<WifiInfo>, String> {
+    Err("Wi-Fi can't be read on this platform".to_string())
+}
+
+// `spairport_current_network_information` of the interface that's connected:
+// `{"_name": "Home", "spairport_signal_noise": "-52 dBm / -94 dBm",
+// "spairport_network_rate": 866}`
+#[cfg(target_os = "macos")]
+fn current() -> Result<Option<WifiInfo>, String> {
+    let output = std::process::Command::new("system_profiler")
+        .args(["SPAirPortDataType", "-json"])
+        .output()
+        .map_err(|e| format!("Failed to run system_profiler: {}", e))?;
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Unexpected system_profiler output: {}", e))?;
+    let network = report["SPAirPortDataType"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|airport| airport["spairport_airport_interfaces"].as_array())
+        .flatten()
+        .find_map(|interface| interface.get("spairport_current_network_information"));
+    let Some(network) = network else {
+        return Ok(None);
+    };
+    let signal_dbm = network["spairport_signal_noise"]
+        .as_str()
+        .and_then(|signal| signal.split_whitespace().next())
+        .and_then(|dbm| dbm.parse().ok());
+    Ok(Some(WifiInfo {
+        // "<redacted>" without the Location permission
+        ssid: network["_name"]
+            .as_str()
+            .filter(|name| !name.starts_with('<'))
+            .map(str::to_string),
+        signal_percent: signal_dbm.map(dbm_to_percent),
+        signal_dbm,
+        link_speed_mbps: network["spairport_network_rate"].as_f64(),
+    }))
+}
+
+// `netsh wlan show interfaces` lines like `    Signal : 90%`
+#[cfg(target_os = "windows")]
+fn current() -> Result<Option<WifiInfo>, String> {
+    let output = std::process::Command::new("netsh")
+        .args(["wlan", "show", "interfaces"])
+        .output()
+        .map_err(|e| format!("Failed to run netsh: {}", e))?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let field = |label: &str| {
+        text.lines().find_map(|line| {
+            let (key, value) = line.split_once(" : ")?;
+            (key.trim() == label).then(|| value.trim().to_string())
+        })
+    };
+    // Only listed while connected
+    let Some(ssid) = field("SSID") else {
+        return Ok(None);
+    };
+    let signal_percent =
+        field("Signal").and_then(|signal| signal.trim_end_matches('%').parse().ok());
+    Ok(Some(WifiInfo {
+        ssid: Some(ssid).filter(|ssid| !ssid.is_empty()),
+        signal_percent,
+        signal_dbm: signal_percent.map(percent_to_dbm),
+        link_speed_mbps: field("Transmit rate (Mbps)").and_then(|rate| rate.parse().ok()),
+    }))
+}
+
+// `nmcli -t` separates fields with `:` and escapes the ones inside values,
+// e.g. `yes:Cafe\: Guest:72:540 Mbit/s`
+#[cfg(target_os = "linux")]
+fn current() -> Result<Option<WifiInfo>, String> {
+    let output = std::process::Command::new("nmcli")
+        .args(["-t", "-f", "ACTIVE,SSID,SIGNAL,RATE", "device", "wifi"])
+        .output()
+        .map_err(|e| format!("Failed to run nmcli: {}", e))?;
+    if !output.status.success() {
+        return Err("NetworkManager isn't running".to_string());
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let fields = |line: &str| {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => field.extend(chars.next()),
+                ':' => fields.push(std::mem::take(&mut field)),
+                c => field.push(c),
+            }
+        }
+        fields.push(field);
+        fields
+    };
+    let Some(active) = text
+        .lines()
+        .map(fields)
+        .find(|fields| fields.first().is_some_and(|active| active == "yes"))
+    else {
+        return Ok(None);
+    };
+    let signal_percent = active.get(2).and_then(|signal| signal.parse().ok());
+    Ok(Some(WifiInfo {
+        ssid: active.get(1).filter(|ssid| !ssid.is_empty()).cloned(),
+        signal_percent,
+        signal_dbm: signal_percent.map(percent_to_dbm),
+        link_speed_mbps: active
+            .get(3)
+            .and_then(|rate| rate.split_whitespace().next())
+            .and_then(|rate| rate.parse().ok()),
+    }))
+}
+
+/// The Wi-Fi link, `None` while the host isn't on Wi-Fi (e.g. wired).
+pub async fn status() -> Result<Option<WifiInfo>, String> {
+    tokio::task::spawn_blocking(current)
+        .await
+        .map_err(|e| format!("Wi-Fi task panicked: {:?}", e))?
+}
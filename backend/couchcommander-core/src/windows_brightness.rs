@@ -0,0 +1,204 @@
+//! Brightness on Windows. Built-in laptop panels are driven through WMI
+//! (`WmiMonitorBrightnessMethods`), external monitors over DDC/CI through the
+//! Monitor Configuration API in dxva2. Either one working counts as success,
+//! since most setups only have one kind of display.
+
+use std::os::windows::process::CommandExt;
+use std::process::Command;
+use tracing::{debug, warn};
+
+// Keeps powershell from flashing a console window
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+type Bool = i32;
+type Handle = isize;
+type Hdc = isize;
+type Hmonitor = isize;
+type Lparam = isize;
+type MonitorEnumProc = unsafe extern "system" fn(Hmonitor, Hdc, *mut Rect, Lparam) -> Bool;
+
+#[repr(C)]
+struct Rect {
+    left: i32,
+    top: i32,
+    right: i32,
+    bottom: i32,
+}
+
+// Declared with pack(1) in physicalmonitorenumerationapi.h
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct PhysicalMonitor {
+    handle: Handle,
+    description: [u16; 128],
+}
+
+#[link(name = "user32")]
+extern "system" {
+    fn EnumDisplayMonitors(
+        hdc: Hdc,
+        clip: *const Rect,
+        callback: MonitorEnumProc,
+        data: Lparam,
+    ) -> Bool;
+}
+
+#[link(name = "dxva2")]
+extern "system" {
+    fn GetNumberOfPhysicalMonitorsFromHMONITOR(monitor: Hmonitor, count: *mut u32) -> Bool;
+    fn GetPhysicalMonitorsFromHMONITOR(
+        monitor: Hmonitor,
+        count: u32,
+        monitors: *mut PhysicalMonitor,
+    ) -> Bool;
+    fn DestroyPhysicalMonitors(count: u32, monitors: *const PhysicalMonitor) -> Bool;
+    fn GetMonitorBrightness(
+        monitor: Handle,
+        minimum: *mut u32,
+        current: *mut u32,
+        maximum: *mut u32,
+    ) -> Bool;
+    fn SetMonitorBrightness(monitor: Handle, brightness: u32) -> Bool;
+}
+
+fn powershell(script: &str) -> Result<String, String> {
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map_err(|e| format!("Failed to run powershell: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn wmi_get() -> Result<u8, String> {
+    let output = powershell(
+        "(Get-CimInstance -Namespace root/WMI -ClassName WmiMonitorBrightness -ErrorAction Stop \
+         | Select-Object -First 1).CurrentBrightness",
+    )?;
+    output
+        .parse()
+        .map_err(|_| "No WMI brightness reported".to_string())
+}
+
+fn wmi_set(value: u8) -> Result<(), String> {
+    powershell(&format!(
+        "Get-CimInstance -Namespace root/WMI -ClassName WmiMonitorBrightnessMethods -ErrorAction Stop \
+         | Invoke-CimMethod -MethodName WmiSetBrightness -Arguments @{{Timeout=0; Brightness={}}} \
+         -ErrorAction Stop | Out-Null",
+        value
+    ))
+    .map(|_| ())
+}
+
+unsafe extern "system" fn collect_monitor(
+    monitor: Hmonitor,
+    _hdc: Hdc,
+    _rect: *mut Rect,
+    data: Lparam,
+) -> Bool {
+    let monitors = &mut *(data as *mut Vec<Hmonitor>);
+    monitors.push(monitor);
+    1
+}
+
+// Physical monitor handles for every display, freed once `f` returns
+fn with_physical_monitors<T>(f: impl FnOnce(&[PhysicalMonitor]) -> T) -> T {
+    let mut hmonitors: Vec<Hmonitor> = Vec::new();
+    unsafe {
+        EnumDisplayMonitors(
+            0,
+            std::ptr::null(),
+            collect_monitor,
+            &mut hmonitors as *mut Vec<Hmonitor> as Lparam,
+        );
+    }
+
+    let mut physical = Vec::new();
+    for hmonitor in hmonitors {
+        let mut count = 0;
+        if unsafe { GetNumberOfPhysicalMonitorsFromHMONITOR(hmonitor, &mut count) } == 0
+            || count == 0
+        {
+            continue;
+        }
+        let mut monitors = vec![
+            PhysicalMonitor {
+                handle: 0,
+                description: [0; 128],
+            };
+            count as usize
+        ];
+        if unsafe { GetPhysicalMonitorsFromHMONITOR(hmonitor, count, monitors.as_mut_ptr()) } != 0 {
+            physical.extend(monitors);
+        }
+    }
+
+    let result = f(&physical);
+    if !physical.is_empty() {
+        unsafe { DestroyPhysicalMonitors(physical.len() as u32, physical.as_ptr()) };
+    }
+    result
+}
+
+// DDC brightness as (min, current, max) in the monitor's own units
+fn ddc_range(monitor: &PhysicalMonitor) -> Option<(u32, u32, u32)> {
+    let (mut min, mut current, mut max) = (0, 0, 0);
+    let ok = unsafe { GetMonitorBrightness(monitor.handle, &mut min, &mut current, &mut max) };
+    (ok != 0 && max > min).then_some((min, current, max))
+}
+
+fn ddc_get() -> Option<u8> {
+    with_physical_monitors(|monitors| {
+        monitors.iter().find_map(|monitor| {
+            let (min, current, max) = ddc_range(monitor)?;
+            Some((current.saturating_sub(min) * 100 / (max - min)) as u8)
+        })
+    })
+}
+
+// Returns how many monitors took the new level
+fn ddc_set(value: u8) -> usize {
+    with_physical_monitors(|monitors| {
+        monitors
+            .iter()
+            .filter(|monitor| {
+                let Some((min, _, max)) = ddc_range(monitor) else {
+                    return false;
+                };
+                let level = min + (max - min) * value as u32 / 100;
+                unsafe { SetMonitorBrightness(monitor.handle, level) != 0 }
+            })
+            .count()
+    })
+}
+
+/// Current brightness in percent, from the built-in panel if there is one.
+pub fn get() -> Result<u8, String> {
+    match wmi_get() {
+        Ok(value) => Ok(value),
+        Err(e) => {
+            debug!("WMI brightness unavailable: {}", e);
+            ddc_get().ok_or_else(|| "No display reports its brightness".to_string())
+        }
+    }
+}
+
+/// Set every display that supports it to `value` percent.
+pub fn set(value: u8) -> Result<(), String> {
+    let value = value.min(100);
+    let wmi = wmi_set(value);
+    if let Err(e) = &wmi {
+        debug!("WMI brightness unavailable: {}", e);
+    }
+    let external = ddc_set(value);
+    debug!("Set brightness over DDC/CI on {} monitor(s)", external);
+
+    if wmi.is_err() && external == 0 {
+        warn!("No display accepted the brightness change");
+        return Err("No display supports brightness control over WMI or DDC/CI".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! Just enough COM to call Windows interfaces through their vtables
+//! directly, shared by the Core Audio and UI Automation code.
+
+use std::ffi::c_void;
+use std::ptr;
+
+pub type Hresult = i32;
+
+#[repr(C)]
+#[derive(PartialEq, Eq)]
+pub struct Guid {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+const COINIT_MULTITHREADED: u32 = 0;
+
+// Vtable slots, after IUnknown's QueryInterface, AddRef and Release
+const RELEASE: usize = 2;
+
+type ReleaseFn = unsafe extern "system" fn(*mut c_void) -> u32;
+
+#[link(name = "ole32")]
+extern "system" {
+    fn CoInitializeEx(reserved: *mut c_void, coinit: u32) -> Hresult;
+    fn CoUninitialize();
+    fn CoCreateInstance(
+        clsid: *const Guid,
+        outer: *mut c_void,
+        context: u32,
+        iid: *const Guid,
+        object: *mut *mut c_void,
+    ) -> Hresult;
+}
+
+// An interface pointer, released when dropped
+pub struct Interface(pub *mut c_void);
+
+impl Interface {
+    // Function `slot` of the vtable, as `F`
+    pub unsafe fn method<F: Copy>(&self, slot: usize) -> F {
+        let vtable = *(self.0 as *const *const usize);
+        std::mem::transmute_copy(&*vtable.add(slot))
+    }
+
+    pub fn create(clsid: &Guid, iid: &Guid, context: u32, action: &str) -> Result<Self, String> {
+        let mut object = ptr::null_mut();
+        check(
+            unsafe { CoCreateInstance(clsid, ptr::null_mut(), context, iid, &mut object) },
+            action,
+        )?;
+        Ok(Interface(object))
+    }
+}
+
+impl Drop for Interface {
+    fn drop(&mut self) {
+        unsafe {
+            let release: ReleaseFn = self.method(RELEASE);
+            release(self.0);
+        }
+    }
+}
+
+pub fn check(result: Hresult, action: &str) -> Result<(), String> {
+    if result < 0 {
+        return Err(format!("{} failed (0x{:08X})", action, result as u32));
+    }
+    Ok(())
+}
+
+// COM stays initialized only while `f` runs
+pub fn with_com<T>(f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    let initialized = unsafe { CoInitializeEx(ptr::null_mut(), COINIT_MULTITHREADED) } >= 0;
+    let result = f();
+    if initialized {
+        unsafe { CoUninitialize() };
+    }
+    result
+}
@@ -0,0 +1,112 @@
+//! Microphone mute on Windows, through the endpoint volume of the default
+//! communications capture device in Core Audio. The COM interfaces are
+//! called through their vtables directly.
+
+use std::ffi::c_void;
+use std::ptr;
+
+use crate::windows_com::{check, with_com, Guid, Hresult, Interface};
+
+type Bool = i32;
+
+const CLSID_MM_DEVICE_ENUMERATOR: Guid = Guid {
+    data1: 0xBCDE_0395,
+    data2: 0xE52F,
+    data3: 0x467C,
+    data4: [0x8E, 0x3D, 0xC4, 0x57, 0x92, 0x91, 0x69, 0x2E],
+};
+const IID_IMM_DEVICE_ENUMERATOR: Guid = Guid {
+    data1: 0xA956_64D2,
+    data2: 0x9614,
+    data3: 0x4F35,
+    data4: [0xA7, 0x46, 0xDE, 0x8D, 0xB6, 0x36, 0x17, 0xE6],
+};
+const IID_IAUDIO_ENDPOINT_VOLUME: Guid = Guid {
+    data1: 0x5CDF_2C82,
+    data2: 0x841E,
+    data3: 0x4546,
+    data4: [0x97, 0x22, 0x0C, 0xF7, 0x40, 0x78, 0x22, 0x9A],
+};
+
+const CLSCTX_ALL: u32 = 0x17;
+// EDataFlow::eCapture and ERole::eCommunications, the microphone calls use
+const CAPTURE: u32 = 1;
+const COMMUNICATIONS: u32 = 2;
+
+// Vtable slots, after IUnknown's QueryInterface, AddRef and Release
+const ENUMERATOR_GET_DEFAULT_AUDIO_ENDPOINT: usize = 4;
+const DEVICE_ACTIVATE: usize = 3;
+const ENDPOINT_SET_MUTE: usize = 14;
+const ENDPOINT_GET_MUTE: usize = 15;
+
+type GetDefaultAudioEndpointFn =
+    unsafe extern "system" fn(*mut c_void, u32, u32, *mut *mut c_void) -> Hresult;
+type ActivateFn = unsafe extern "system" fn(
+    *mut c_void,
+    *const Guid,
+    u32,
+    *mut c_void,
+    *mut *mut c_void,
+) -> Hresult;
+type SetMuteFn = unsafe extern "system" fn(*mut c_void, Bool, *const Guid) -> Hresult;
+type GetMuteFn = unsafe extern "system" fn(*mut c_void, *mut Bool) -> Hresult;
+
+fn endpoint() -> Result<Interface, String> {
+    let enumerator = Interface::create(
+        &CLSID_MM_DEVICE_ENUMERATOR,
+        &IID_IMM_DEVICE_ENUMERATOR,
+        CLSCTX_ALL,
+        "Opening Core Audio",
+    )?;
+    unsafe {
+        let get_default: GetDefaultAudioEndpointFn =
+            enumerator.method(ENUMERATOR_GET_DEFAULT_AUDIO_ENDPOINT);
+        let mut object = ptr::null_mut();
+        check(
+            get_default(enumerator.0, CAPTURE, COMMUNICATIONS, &mut object),
+            "Finding the microphone",
+        )?;
+        let device = Interface(object);
+
+        let activate: ActivateFn = device.method(DEVICE_ACTIVATE);
+        let mut object = ptr::null_mut();
+        check(
+            activate(
+                device.0,
+                &IID_IAUDIO_ENDPOINT_VOLUME,
+                CLSCTX_ALL,
+                ptr::null_mut(),
+                &mut object,
+            ),
+            "Opening the microphone",
+        )?;
+        Ok(Interface(object))
+    }
+}
+
+// COM stays initialized only while `f` runs
+fn with_endpoint<T>(f: impl FnOnce(&Interface) -> Result<T, String>) -> Result<T, String> {
+    with_com(|| endpoint().and_then(|endpoint| f(&endpoint)))
+}
+
+pub fn is_muted() -> Result<bool, String> {
+    with_endpoint(|endpoint| unsafe {
+        let get_mute: GetMuteFn = endpoint.method(ENDPOINT_GET_MUTE);
+        let mut muted: Bool = 0;
+        check(
+            get_mute(endpoint.0, &mut muted),
+            "Reading the microphone mute",
+        )?;
+        Ok(muted != 0)
+    })
+}
+
+pub fn set_muted(muted: bool) -> Result<(), String> {
+    with_endpoint(|endpoint| unsafe {
+        let set_mute: SetMuteFn = endpoint.method(ENDPOINT_SET_MUTE);
+        check(
+            set_mute(endpoint.0, muted as Bool, ptr::null()),
+            "Muting the microphone",
+        )
+    })
+}
@@ -0,0 +1,215 @@
+//! UI Automation on Windows: whether a password field has keyboard focus,
+//! which covers classic controls as well as browsers and UWP apps, and
+//! notifications for the screen reader to read out.
+
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+use crate::windows_com::{check, with_com, Guid, Hresult, Interface};
+
+type Bool = i32;
+
+const CLSID_CUI_AUTOMATION: Guid = Guid {
+    data1: 0xFF48_DBA4,
+    data2: 0x60EF,
+    data3: 0x4201,
+    data4: [0xAA, 0x87, 0x54, 0x10, 0x3E, 0xEF, 0x59, 0x4E],
+};
+const IID_IUI_AUTOMATION: Guid = Guid {
+    data1: 0x30CB_E57D,
+    data2: 0xD9D0,
+    data3: 0x452A,
+    data4: [0xAB, 0x13, 0x7A, 0xC5, 0xAC, 0x48, 0x25, 0xEE],
+};
+
+const CLSCTX_INPROC_SERVER: u32 = 0x1;
+
+// Vtable slots, after IUnknown's QueryInterface, AddRef and Release
+const AUTOMATION_GET_FOCUSED_ELEMENT: usize = 8;
+const ELEMENT_GET_CURRENT_IS_PASSWORD: usize = 35;
+
+const IID_IUNKNOWN: Guid = Guid {
+    data1: 0x0000_0000,
+    data2: 0x0000,
+    data3: 0x0000,
+    data4: [0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+const IID_IRAW_ELEMENT_PROVIDER_SIMPLE: Guid = Guid {
+    data1: 0xD6DD_68D1,
+    data2: 0x86FD,
+    data3: 0x4332,
+    data4: [0x86, 0x66, 0x9A, 0xBE, 0xDE, 0xA2, 0xD2, 0x4C],
+};
+
+const S_OK: Hresult = 0;
+const E_NOINTERFACE: Hresult = 0x8000_4002_u32 as i32;
+const PROVIDER_OPTIONS_SERVER_SIDE: i32 = 0x2;
+const NOTIFICATION_KIND_OTHER: i32 = 4;
+const NOTIFICATION_PROCESSING_IMPORTANT_MOST_RECENT: i32 = 1;
+
+#[link(name = "user32")]
+extern "system" {
+    fn GetForegroundWindow() -> isize;
+}
+#[link(name = "oleaut32")]
+extern "system" {
+    fn SysAllocString(text: *const u16) -> *mut u16;
+    fn SysFreeString(text: *mut u16);
+}
+#[link(name = "uiautomationcore")]
+extern "system" {
+    fn UiaHostProviderFromHwnd(window: isize, provider: *mut *mut c_void) -> Hresult;
+    fn UiaRaiseNotificationEvent(
+        provider: *mut c_void,
+        kind: i32,
+        processing: i32,
+        display: *mut u16,
+        activity: *mut u16,
+    ) -> Hresult;
+}
+
+type GetFocusedElementFn = unsafe extern "system" fn(*mut c_void, *mut *mut c_void) -> Hresult;
+type GetIsPasswordFn = unsafe extern "system" fn(*mut c_void, *mut Bool) -> Hresult;
+
+pub fn password_field_focused() -> Result<bool, String> {
+    with_com(|| {
+        let automation = Interface::create(
+            &CLSID_CUI_AUTOMATION,
+            &IID_IUI_AUTOMATION,
+            CLSCTX_INPROC_SERVER,
+            "Opening UI Automation",
+        )?;
+        unsafe {
+            let get_focused: GetFocusedElementFn =
+                automation.method(AUTOMATION_GET_FOCUSED_ELEMENT);
+            let mut object = ptr::null_mut();
+            check(
+                get_focused(automation.0, &mut object),
+                "Finding the focused element",
+            )?;
+            // Nothing has focus, e.g. on the lock screen
+            if object.is_null() {
+                return Ok(false);
+            }
+            let element = Interface(object);
+
+            let is_password: GetIsPasswordFn = element.method(ELEMENT_GET_CURRENT_IS_PASSWORD);
+            let mut password: Bool = 0;
+            check(
+                is_password(element.0, &mut password),
+                "Reading the focused element",
+            )?;
+            Ok(password != 0)
+        }
+    })
+}
+
+// Notifications have to come from an element, so this is the smallest
+// IRawElementProviderSimple there is: a static one without properties,
+// hosted by the window in front so the screen reader pays attention to it
+#[repr(C)]
+struct ProviderVtable {
+    query_interface:
+        unsafe extern "system" fn(*mut c_void, *const Guid, *mut *mut c_void) -> Hresult,
+    add_ref: unsafe extern "system" fn(*mut c_void) -> u32,
+    release: unsafe extern "system" fn(*mut c_void) -> u32,
+    provider_options: unsafe extern "system" fn(*mut c_void, *mut i32) -> Hresult,
+    pattern_provider: unsafe extern "system" fn(*mut c_void, i32, *mut *mut c_void) -> Hresult,
+    // The VARIANT is left VT_EMPTY, its first field
+    property_value: unsafe extern "system" fn(*mut c_void, i32, *mut u16) -> Hresult,
+    host_provider: unsafe extern "system" fn(*mut c_void, *mut *mut c_void) -> Hresult,
+}
+
+#[repr(C)]
+struct Provider {
+    vtable: &'static ProviderVtable,
+}
+
+static PROVIDER: Provider = Provider {
+    vtable: &ProviderVtable {
+        query_interface,
+        add_ref,
+        release,
+        provider_options,
+        pattern_provider,
+        property_value,
+        host_provider,
+    },
+};
+
+static HOST_WINDOW: AtomicIsize = AtomicIsize::new(0);
+
+unsafe extern "system" fn query_interface(
+    this: *mut c_void,
+    iid: *const Guid,
+    object: *mut *mut c_void,
+) -> Hresult {
+    if *iid == IID_IUNKNOWN || *iid == IID_IRAW_ELEMENT_PROVIDER_SIMPLE {
+        *object = this;
+        return S_OK;
+    }
+    *object = ptr::null_mut();
+    E_NOINTERFACE
+}
+
+// Static, so there's nothing to count
+unsafe extern "system" fn add_ref(_this: *mut c_void) -> u32 {
+    1
+}
+
+unsafe extern "system" fn release(_this: *mut c_void) -> u32 {
+    1
+}
+
+unsafe extern "system" fn provider_options(_this: *mut c_void, options: *mut i32) -> Hresult {
+    *options = PROVIDER_OPTIONS_SERVER_SIDE;
+    S_OK
+}
+
+unsafe extern "system" fn pattern_provider(
+    _this: *mut c_void,
+    _pattern: i32,
+    provider: *mut *mut c_void,
+) -> Hresult {
+    *provider = ptr::null_mut();
+    S_OK
+}
+
+unsafe extern "system" fn property_value(
+    _this: *mut c_void,
+    _property: i32,
+    value: *mut u16,
+) -> Hresult {
+    *value = 0;
+    S_OK
+}
+
+unsafe extern "system" fn host_provider(_this: *mut c_void, host: *mut *mut c_void) -> Hresult {
+    UiaHostProviderFromHwnd(HOST_WINDOW.load(Ordering::Relaxed), host)
+}
+
+/// Have the screen reader (Narrator, NVDA, JAWS) read `text` out.
+pub fn announce(text: &str) -> Result<(), String> {
+    with_com(|| unsafe {
+        let window = GetForegroundWindow();
+        if window == 0 {
+            return Err("No window is in front to announce from".to_string());
+        }
+        HOST_WINDOW.store(window, Ordering::Relaxed);
+
+        let wide = |text: &str| text.encode_utf16().chain([0]).collect::<Vec<u16>>();
+        let display = SysAllocString(wide(text).as_ptr());
+        let activity = SysAllocString(wide("CouchCommander").as_ptr());
+        let result = UiaRaiseNotificationEvent(
+            &PROVIDER as *const Provider as *mut c_void,
+            NOTIFICATION_KIND_OTHER,
+            NOTIFICATION_PROCESSING_IMPORTANT_MOST_RECENT,
+            display,
+            activity,
+        );
+        SysFreeString(display);
+        SysFreeString(activity);
+        check(result, "Raising the announcement")
+    })
+}
@@ -0,0 +1,54 @@
+//! Wake-on-LAN, so an always-on host can wake other machines on the LAN.
+
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use tracing::info;
+
+pub const DEFAULT_PORT: u16 = 9;
+
+// Accepts "aa:bb:cc:dd:ee:ff", "AA-BB-CC-DD-EE-FF" and "aabbccddeeff"
+fn parse_mac(mac: &str) -> Result<[u8; 6], String> {
+    let hex: String = mac
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect();
+    let invalid = || format!("Invalid MAC address: {}", mac);
+    if hex.len() != 12 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; 6];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+// Six 0xFF bytes, then the MAC sixteen times
+fn magic_packet(mac: [u8; 6]) -> Vec<u8> {
+    let mut packet = vec![0xFF; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac);
+    }
+    packet
+}
+
+/// Broadcast a magic packet for `mac`. `broadcast` defaults to the limited
+/// broadcast address, a subnet's own (e.g. 192.168.1.255) works better on
+/// hosts with several interfaces.
+pub fn wake(mac: &str, broadcast: Option<Ipv4Addr>, port: Option<u16>) -> Result<(), String> {
+    let packet = magic_packet(parse_mac(mac)?);
+    let target = SocketAddr::from((
+        broadcast.unwrap_or(Ipv4Addr::BROADCAST),
+        port.unwrap_or(DEFAULT_PORT),
+    ));
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .map_err(|e| format!("Failed to open UDP socket: {}", e))?;
+    socket
+        .set_broadcast(true)
+        .map_err(|e| format!("Failed to enable broadcast: {}", e))?;
+    socket
+        .send_to(&packet, target)
+        .map_err(|e| format!("Failed to send magic packet to {}: {}", target, e))?;
+    info!("Sent Wake-on-LAN packet for {} to {}", mac, target);
+    Ok(())
+}
@@ -0,0 +1,182 @@
+//! Screen zoom for low-vision users watching the TV: the Accessibility Zoom
+//! on macOS, Magnifier on Windows and GNOME's magnifier on Linux.
+//!
+//! macOS goes through the Zoom keyboard shortcuts, which have to be turned on
+//! in the Accessibility settings, and always follows the pointer. Windows
+//! uses the Magnifier shortcuts and Linux sets the GNOME settings directly.
+
+use serde::{Deserialize, Serialize};
+
+use crate::input_backend::InputBackend;
+use crate::protocol::CommandResponse;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoomAction {
+    Toggle,
+    In,
+    Out,
+    Off,
+    // Zoom in with the view centered on the pointer
+    FollowCursor,
+}
+
+fn success(message: String) -> CommandResponse {
+    CommandResponse {
+        status: "success".to_string(),
+        message,
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod gnome {
+    use crate::accessibility::gsettings;
+
+    const APPLICATIONS: &str = "org.gnome.desktop.a11y.applications";
+    const MAGNIFIER: &str = "org.gnome.desktop.a11y.magnifier";
+    // Each step in or out, GNOME allows up to 32x
+    const STEP: f64 = 1.25;
+    const MAX_FACTOR: f64 = 32.0;
+
+    fn enabled() -> Result<bool, String> {
+        Ok(gsettings(&["get", APPLICATIONS, "screen-magnifier-enabled"])? == "true")
+    }
+
+    fn set_enabled(enabled: bool) -> Result<(), String> {
+        let value = if enabled { "true" } else { "false" };
+        gsettings(&["set", APPLICATIONS, "screen-magnifier-enabled", value]).map(|_| ())
+    }
+
+    fn factor() -> Result<f64, String> {
+        gsettings(&["get", MAGNIFIER, "mag-factor"])?
+            .parse()
+            .map_err(|e| format!("Unexpected magnification: {}", e))
+    }
+
+    fn set_factor(factor: f64) -> Result<(), String> {
+        gsettings(&["set", MAGNIFIER, "mag-factor", &format!("{:.2}", factor)]).map(|_| ())
+    }
+
+    pub fn toggle() -> Result<String, String> {
+        let enabled = !enabled()?;
+        set_enabled(enabled)?;
+        Ok(if enabled { "Zoom on" } else { "Zoom off" }.to_string())
+    }
+
+    // Turning the magnifier on is the first step in
+    pub fn zoom_in() -> Result<String, String> {
+        let mut factor = factor()?;
+        if enabled()? {
+            factor = (factor * STEP).min(MAX_FACTOR);
+            set_factor(factor)?;
+        } else {
+            set_enabled(true)?;
+        }
+        Ok(format!("Zoomed in to {:.1}x", factor))
+    }
+
+    // Going below 1x turns the magnifier off and keeps the last factor for
+    // the next time it's turned on
+    pub fn zoom_out() -> Result<String, String> {
+        if !enabled()? {
+            return Ok("Zoom is already off".to_string());
+        }
+        let factor = factor()? / STEP;
+        if factor < 1.0 {
+            set_enabled(false)?;
+            return Ok("Zoom off".to_string());
+        }
+        set_factor(factor)?;
+        Ok(format!("Zoomed out to {:.1}x", factor))
+    }
+
+    pub fn off() -> Result<String, String> {
+        set_enabled(false)?;
+        Ok("Zoom off".to_string())
+    }
+
+    pub fn follow_cursor() -> Result<String, String> {
+        gsettings(&["set", MAGNIFIER, "mouse-tracking", "centered"])?;
+        set_enabled(true)?;
+        Ok("Zoom follows the pointer".to_string())
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub async fn zoom<B: InputBackend>(action: ZoomAction) -> Result<CommandResponse, String> {
+    tokio::task::spawn_blocking(move || {
+        let message = match action {
+            ZoomAction::Toggle => gnome::toggle()?,
+            ZoomAction::In => gnome::zoom_in()?,
+            ZoomAction::Out => gnome::zoom_out()?,
+            ZoomAction::Off => gnome::off()?,
+            ZoomAction::FollowCursor => gnome::follow_cursor()?,
+        };
+        Ok(success(message))
+    })
+    .await
+    .map_err(|e| format!("Zoom task panicked: {:?}", e))?
+}
+
+// Option+Cmd+8 toggles, Option+Cmd+= and Option+Cmd+- zoom in and out
+#[cfg(target_os = "macos")]
+pub async fn zoom<B: InputBackend>(action: ZoomAction) -> Result<CommandResponse, String> {
+    use enigo::Key;
+
+    let key = match action {
+        ZoomAction::Toggle => '8',
+        ZoomAction::In | ZoomAction::FollowCursor => '=',
+        ZoomAction::Out => '-',
+        ZoomAction::Off => return Err("macOS can only toggle zoom or zoom in and out".to_string()),
+    };
+    crate::input_worker::run::<B, _, _>(move |input| {
+        crate::shortcuts::press_chord(input, &[Key::Option, Key::Meta], Key::Unicode(key))
+            .map_err(|e| format!("Failed to zoom: {:?}", e))?;
+        Ok(success(format!("Zoom {:?}", action)))
+    })
+    .await
+}
+
+// Win+Plus starts Magnifier and zooms in, Win+Minus zooms out and Win+Esc
+// closes it. Its full screen view follows the pointer
+#[cfg(target_os = "windows")]
+pub async fn zoom<B: InputBackend>(action: ZoomAction) -> Result<CommandResponse, String> {
+    use enigo::Key;
+    use std::time::Duration;
+
+    const VK_OEM_PLUS: u32 = 0xBB;
+    const VK_OEM_MINUS: u32 = 0xBD;
+    // Magnifier ignores its view shortcuts until it's up
+    const MAGNIFIER_START_DELAY: Duration = Duration::from_millis(500);
+
+    let magnifier_running = || crate::accessibility::process_running("Magnify.exe");
+
+    crate::input_worker::run::<B, _, _>(move |input| {
+        let mut chord = |modifiers: &[Key], key: Key| {
+            crate::shortcuts::press_chord(input, modifiers, key)
+                .map_err(|e| format!("Failed to zoom: {:?}", e))
+        };
+        match action {
+            ZoomAction::Toggle if magnifier_running() => chord(&[Key::Meta], Key::Escape)?,
+            ZoomAction::Toggle | ZoomAction::In => chord(&[Key::Meta], Key::Other(VK_OEM_PLUS))?,
+            ZoomAction::Out => chord(&[Key::Meta], Key::Other(VK_OEM_MINUS))?,
+            ZoomAction::Off => chord(&[Key::Meta], Key::Escape)?,
+            ZoomAction::FollowCursor => {
+                let started = !magnifier_running();
+                chord(&[Key::Meta], Key::Other(VK_OEM_PLUS))?;
+                if started {
+                    std::thread::sleep(MAGNIFIER_START_DELAY);
+                }
+                // Ctrl+Alt+F, the full screen view
+                chord(&[Key::Control, Key::Alt], Key::Unicode('f'))?;
+            }
+        }
+        Ok(success(format!("Zoom {:?}", action)))
+    })
+    .await
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+pub async fn zoom<B: InputBackend>(_action: ZoomAction) -> Result<CommandResponse, String> {
+    Err("Zoom isn't supported on this platform".to_string())
+}
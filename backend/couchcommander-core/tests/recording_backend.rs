@@ -0,0 +1,84 @@
+//! Commands run through `handle_command` against `RecordingBackend`, checking
+//! the input each one sends.
+
+use couchcommander_core::input_backend::{InputEvent, RecordingBackend};
+use couchcommander_core::protocol::{WebSocketCommand, WebSocketResponse};
+use couchcommander_core::websocket::handle_command;
+use enigo::{Axis, Button, Coordinate, Direction, Key};
+use serde_json::json;
+
+// Every connection records to one log, so tests take turns
+static RECORDER: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+const CLIENT: &str = "recording-test";
+
+async fn run(command: &str, data: serde_json::Value) -> (WebSocketResponse, Vec<InputEvent>) {
+    let _turn = RECORDER.lock().await;
+    RecordingBackend::take_events();
+    let command = WebSocketCommand {
+        id: None,
+        command: command.to_string(),
+        data: Some(data),
+    };
+    let response = handle_command::<RecordingBackend>(CLIENT, command).await;
+    (response, RecordingBackend::take_events())
+}
+
+#[tokio::test]
+async fn send_key_presses_the_named_key() {
+    let (response, events) = run("send_key", json!({ "key": "Enter" })).await;
+    assert_eq!(response.status, "success", "{}", response.message);
+    assert_eq!(events, [InputEvent::Key(Key::Return, Direction::Press)]);
+}
+
+#[tokio::test]
+async fn send_key_types_single_characters() {
+    let (response, events) = run("send_key", json!({ "key": "a" })).await;
+    assert_eq!(response.status, "success", "{}", response.message);
+    assert_eq!(events, [InputEvent::Text("a".to_string())]);
+}
+
+#[tokio::test]
+async fn send_key_rejects_unknown_keys() {
+    let (response, events) = run("send_key", json!({ "key": "not-a-key" })).await;
+    assert_eq!(response.status, "error");
+    assert!(events.is_empty());
+}
+
+#[tokio::test]
+async fn text_input_types_the_text() {
+    let (response, events) = run("text_input", json!({ "text": "hello" })).await;
+    assert_eq!(response.status, "success", "{}", response.message);
+    assert_eq!(events, [InputEvent::Text("hello".to_string())]);
+}
+
+#[tokio::test]
+async fn mouse_move_moves_relative() {
+    let (response, events) = run("mouse_move", json!({ "deltaX": 12, "deltaY": -5 })).await;
+    assert_eq!(response.status, "success", "{}", response.message);
+    assert_eq!(events, [InputEvent::MoveMouse(12, -5, Coordinate::Rel)]);
+}
+
+#[tokio::test]
+async fn mouse_click_presses_the_button() {
+    let (response, events) = run("mouse_click", json!({ "button": "right" })).await;
+    assert_eq!(response.status, "success", "{}", response.message);
+    assert_eq!(
+        events,
+        [InputEvent::Button(Button::Right, Direction::Press)]
+    );
+}
+
+#[tokio::test]
+async fn scroll_skips_an_axis_without_movement() {
+    let (response, events) = run("scroll", json!({ "deltaX": 0, "deltaY": 3 })).await;
+    assert_eq!(response.status, "success", "{}", response.message);
+    assert_eq!(events, [InputEvent::Scroll(3, Axis::Vertical)]);
+}
+
+#[tokio::test]
+async fn unknown_commands_send_nothing() {
+    let (response, events) = run("no_such_command", json!({})).await;
+    assert_eq!(response.status, "error");
+    assert!(events.is_empty());
+}
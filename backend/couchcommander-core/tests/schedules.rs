@@ -0,0 +1,69 @@
+//! What `schedule_command` refuses to schedule.
+
+use couchcommander_core::input_backend::RecordingBackend;
+use couchcommander_core::protocol::{WebSocketCommand, WebSocketResponse};
+use couchcommander_core::websocket::handle_command;
+use serde_json::json;
+
+const CLIENT: &str = "schedule-test";
+
+async fn run(command: &str, data: serde_json::Value) -> WebSocketResponse {
+    let command = WebSocketCommand {
+        id: None,
+        command: command.to_string(),
+        data: Some(data),
+    };
+    handle_command::<RecordingBackend>(CLIENT, command).await
+}
+
+async fn pending() -> Vec<serde_json::Value> {
+    let response = run("list_schedules", json!({})).await;
+    assert_eq!(response.status, "success", "{}", response.message);
+    response
+        .data
+        .and_then(|data| data.get("schedules").cloned())
+        .and_then(|schedules| schedules.as_array().cloned())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn sensitive_text_is_not_scheduled() {
+    let response = run(
+        "schedule_command",
+        json!({
+            "delay_secs": 60,
+            "commands": [{
+                "command": "text_input",
+                "data": { "text": "hunter2", "sensitive": true },
+            }],
+        }),
+    )
+    .await;
+    assert_eq!(response.status, "error");
+    assert!(!response.message.contains("hunter2"));
+    assert!(pending()
+        .await
+        .iter()
+        .all(|schedule| !schedule.to_string().contains("hunter2")));
+}
+
+#[tokio::test]
+async fn schedules_cannot_schedule_more() {
+    let response = run(
+        "schedule_command",
+        json!({
+            "delay_secs": 60,
+            "commands": [{
+                "command": "schedule_command",
+                "data": { "delay_secs": 1, "commands": [] },
+            }],
+        }),
+    )
+    .await;
+    assert_eq!(response.status, "error");
+    assert!(
+        response.message.contains("other schedules"),
+        "{}",
+        response.message
+    );
+}
@@ -0,0 +1,105 @@
+use couchcommander_core::display::Screen;
+use couchcommander_core::display_content::{self, Content, Shown};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tauri::{
+    AppHandle, Manager, Monitor, Runtime, Url, WebviewUrl, WebviewWindowBuilder, WindowEvent,
+};
+use tracing::warn;
+
+// Each content gets a new window, `content-1`, `content-2`, ... as a closing
+// window keeps its label until the event loop gets to it
+const LABEL_PREFIX: &str = "content-";
+
+static NEXT_WINDOW: AtomicUsize = AtomicUsize::new(1);
+
+/// Show what remotes put up with `display_content` in a borderless full screen
+/// window on the display they chose, and close it when they take it down.
+pub fn init<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    let mut shown = display_content::watch();
+    tauri::async_runtime::spawn(async move {
+        while shown.changed().await.is_ok() {
+            let content = shown.borrow_and_update().clone();
+            close(&app);
+            if let Some(content) = content {
+                if let Err(e) = show(&app, &content) {
+                    warn!("Failed to show content on the host: {}", e);
+                }
+            }
+        }
+    });
+}
+
+// Whether the middle of `screen` is on `monitor`. Screens are listed in
+// physical pixels, except on macOS where they're in points
+fn contains(monitor: &Monitor, screen: &Screen, scale: f64) -> bool {
+    let x = f64::from(screen.x) + f64::from(screen.width) / 2.0;
+    let y = f64::from(screen.y) + f64::from(screen.height) / 2.0;
+    let left = f64::from(monitor.position().x) / scale;
+    let top = f64::from(monitor.position().y) / scale;
+    let width = f64::from(monitor.size().width) / scale;
+    let height = f64::from(monitor.size().height) / scale;
+    (left..left + width).contains(&x) && (top..top + height).contains(&y)
+}
+
+fn monitor<R: Runtime>(
+    app: &AppHandle<R>,
+    screen: Option<&Screen>,
+) -> tauri::Result<Option<Monitor>> {
+    if let Some(screen) = screen {
+        let monitors = app.available_monitors()?;
+        let found = monitors
+            .iter()
+            .find(|monitor| contains(monitor, screen, 1.0))
+            .or_else(|| {
+                monitors
+                    .iter()
+                    .find(|monitor| contains(monitor, screen, monitor.scale_factor()))
+            });
+        if let Some(monitor) = found {
+            return Ok(Some(monitor.clone()));
+        }
+    }
+    app.primary_monitor()
+}
+
+fn show<R: Runtime>(app: &AppHandle<R>, shown: &Shown) -> tauri::Result<()> {
+    // Text and images are drawn by the desktop UI, which asks for them with
+    // get_displayed_content
+    let url = match &shown.content {
+        Content::Url { url } => {
+            WebviewUrl::External(url.parse::<Url>().map_err(tauri::Error::InvalidUrl)?)
+        }
+        _ => WebviewUrl::App("index.html#content".into()),
+    };
+    let number = NEXT_WINDOW.fetch_add(1, Ordering::Relaxed);
+    let label = format!("{}{}", LABEL_PREFIX, number);
+    let window = WebviewWindowBuilder::new(app, label, url)
+        .title("CouchCommander")
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .visible(false)
+        .build()?;
+    if let Some(monitor) = monitor(app, shown.screen.as_ref())? {
+        window.set_position(*monitor.position())?;
+    }
+    window.set_fullscreen(true)?;
+    window.show()?;
+    window.set_focus()?;
+    // Closed on the host, e.g. with Alt+F4 or Esc in the desktop UI
+    window.on_window_event(|event| {
+        if let WindowEvent::CloseRequested { .. } = event {
+            display_content::hide();
+        }
+    });
+    Ok(())
+}
+
+fn close<R: Runtime>(app: &AppHandle<R>) {
+    for (label, window) in app.webview_windows() {
+        if label.starts_with(LABEL_PREFIX) {
+            let _ = window.destroy();
+        }
+    }
+}
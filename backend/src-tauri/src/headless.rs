@@ -0,0 +1,123 @@
+use couchcommander_core::{battery, config, input, logging, notifications, qr, relay, server};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const QR_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+fn port_arg() -> Option<u16> {
+    let args: Vec<String> = std::env::args().collect();
+    args.windows(2)
+        .find(|pair| pair[0] == "--port")
+        .and_then(|pair| pair[1].parse().ok())
+}
+
+fn relay_arg() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.windows(2)
+        .find(|pair| pair[0] == "--relay")
+        .map(|pair| pair[1].clone())
+}
+
+fn print_qr_code(url: &str) {
+    match qr::terminal(url) {
+        Ok(code) => println!("{}", code),
+        Err(e) => warn!("Failed to render QR code: {}", e),
+    }
+}
+
+/// Run the server without creating a window, for HTPCs and SSH sessions.
+/// Blocks until Ctrl+C.
+pub fn run() {
+    logging::init(Some(&config::config_dir().join("logs")));
+    info!("CouchCommander starting in headless mode");
+    config::load();
+    config::watch_for_changes();
+    battery::watch_low_battery();
+    notifications::watch();
+
+    if !input::check_accessibility_permissions() {
+        warn!("Accessibility permissions are missing, input commands will fail");
+    }
+
+    let rt = match tokio::runtime::Runtime::new() {
+        Ok(rt) => rt,
+        Err(e) => {
+            error!("Failed to create runtime: {}", e);
+            return;
+        }
+    };
+
+    rt.block_on(async {
+        match server::start_websocket_server(port_arg()).await {
+            Ok(response) => info!("{}", response.message),
+            Err(e) => {
+                error!("Failed to start server: {}", e);
+                return;
+            }
+        }
+
+        // The pairing code rotates on use and on expiry, show each new one
+        let mut shown_url = String::new();
+        let mut refresh = tokio::time::interval(QR_REFRESH_INTERVAL);
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        loop {
+            tokio::select! {
+                result = &mut ctrl_c => {
+                    if let Err(e) = result {
+                        error!("Failed to wait for Ctrl+C: {}", e);
+                    }
+                    break;
+                }
+                _ = refresh.tick() => match server::get_connection_info().await {
+                    Ok(info) => {
+                        let url = info["web_app_url"].as_str().unwrap_or_default();
+                        if url != shown_url {
+                            println!();
+                            print_qr_code(url);
+                            println!("Scan the code or open {} on your phone", url);
+                            println!("Press Ctrl+C to stop");
+                            shown_url = url.to_string();
+                        }
+                    }
+                    Err(e) => warn!("Failed to build connection info: {}", e),
+                },
+            }
+        }
+
+        info!("Shutting down");
+        if let Err(e) = server::stop_websocket_server().await {
+            warn!("Failed to stop server: {}", e);
+        }
+    });
+}
+
+/// Run only a relay for hosts and remotes to meet on (`--relay <address>`),
+/// e.g. on a small VPS. Blocks until Ctrl+C.
+pub fn run_relay() {
+    logging::init(None);
+    let address = match relay_arg().map(|arg| arg.parse()) {
+        Some(Ok(address)) => address,
+        _ => {
+            error!("Usage: --relay <ip:port>, e.g. --relay 0.0.0.0:7000");
+            return;
+        }
+    };
+    let rt = match tokio::runtime::Runtime::new() {
+        Ok(rt) => rt,
+        Err(e) => {
+            error!("Failed to create runtime: {}", e);
+            return;
+        }
+    };
+    rt.block_on(async {
+        tokio::select! {
+            result = relay::serve(address) => {
+                if let Err(e) = result {
+                    error!("{}", e);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => info!("Shutting down"),
+        }
+    });
+}
@@ -0,0 +1,42 @@
+use couchcommander_core::config::HotkeySettings;
+use couchcommander_core::{input, server};
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+use tracing::{info, warn};
+
+/// Replace the registered global shortcuts with the configured ones. These
+/// work even while a remote is moving the mouse, so the host can take over.
+pub fn register<R: Runtime>(app: &AppHandle<R>, hotkeys: &HotkeySettings) -> Result<(), String> {
+    let shortcuts = app.global_shortcut();
+    shortcuts
+        .unregister_all()
+        .map_err(|e| format!("Failed to clear global shortcuts: {}", e))?;
+
+    if !hotkeys.toggle_server.is_empty() {
+        shortcuts
+            .on_shortcut(hotkeys.toggle_server.as_str(), |_app, _shortcut, event| {
+                if event.state() != ShortcutState::Pressed {
+                    return;
+                }
+                tauri::async_runtime::spawn(async {
+                    match server::toggle_websocket_server().await {
+                        Ok(response) => info!("{}", response.message),
+                        Err(e) => warn!("Failed to toggle server from hotkey: {}", e),
+                    }
+                });
+            })
+            .map_err(|e| format!("Invalid hotkey '{}': {}", hotkeys.toggle_server, e))?;
+    }
+
+    if !hotkeys.toggle_pause.is_empty() {
+        shortcuts
+            .on_shortcut(hotkeys.toggle_pause.as_str(), |_app, _shortcut, event| {
+                if event.state() == ShortcutState::Pressed {
+                    input::toggle_paused();
+                }
+            })
+            .map_err(|e| format!("Invalid hotkey '{}': {}", hotkeys.toggle_pause, e))?;
+    }
+
+    Ok(())
+}
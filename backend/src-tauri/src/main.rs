@@ -2,5 +2,11 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
-    backend_lib::run()
+    if std::env::args().any(|arg| arg == "--relay") {
+        backend_lib::run_relay()
+    } else if std::env::args().any(|arg| arg == "--headless") {
+        backend_lib::run_headless()
+    } else {
+        backend_lib::run()
+    }
 }
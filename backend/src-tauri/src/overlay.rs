@@ -0,0 +1,71 @@
+use couchcommander_core::config;
+use couchcommander_core::control::{self, ActiveRemote};
+use tauri::{
+    AppHandle, Emitter, Manager, PhysicalPosition, Runtime, WebviewUrl, WebviewWindow,
+    WebviewWindowBuilder,
+};
+use tracing::warn;
+
+const LABEL: &str = "overlay";
+const WIDTH: f64 = 320.0;
+const HEIGHT: f64 = 64.0;
+// Gap to the top right corner of the screen, in logical pixels
+const MARGIN: f64 = 16.0;
+
+/// Show a small always-on-top overlay naming the remote that's sending input,
+/// with a button to pause remote input, and hide it once input stops.
+pub fn init<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    let mut active = control::watch_active();
+    tauri::async_runtime::spawn(async move {
+        while active.changed().await.is_ok() {
+            let remote = active.borrow_and_update().clone();
+            match remote {
+                Some(remote) if config::current().control_overlay => show(&app, &remote),
+                _ => hide(&app),
+            }
+        }
+    });
+}
+
+fn window<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<WebviewWindow<R>> {
+    if let Some(window) = app.get_webview_window(LABEL) {
+        return Ok(window);
+    }
+    let window =
+        WebviewWindowBuilder::new(app, LABEL, WebviewUrl::App("index.html#overlay".into()))
+            .title("CouchCommander")
+            .inner_size(WIDTH, HEIGHT)
+            .resizable(false)
+            .decorations(false)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .focused(false)
+            .visible(false)
+            .build()?;
+    if let Some(monitor) = window.current_monitor()? {
+        let scale = monitor.scale_factor();
+        let x =
+            monitor.position().x + monitor.size().width as i32 - ((WIDTH + MARGIN) * scale) as i32;
+        let y = monitor.position().y + (MARGIN * scale) as i32;
+        window.set_position(PhysicalPosition::new(x, y))?;
+    }
+    Ok(window)
+}
+
+fn show<R: Runtime>(app: &AppHandle<R>, remote: &ActiveRemote) {
+    let result = window(app).and_then(|window| {
+        // A window that's still loading asks with get_active_remote instead
+        app.emit_to(LABEL, "input-controller", remote)?;
+        window.show()
+    });
+    if let Err(e) = result {
+        warn!("Failed to show the control overlay: {}", e);
+    }
+}
+
+fn hide<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window(LABEL) {
+        let _ = window.hide();
+    }
+}
@@ -0,0 +1,120 @@
+use couchcommander_core::{input, server};
+use std::time::Duration;
+use tauri::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::{info, warn};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+// Menu item ids
+const TOGGLE_SERVER: &str = "toggle_server";
+const TOGGLE_PAUSE: &str = "toggle_pause";
+const SHOW_WINDOW: &str = "show_window";
+const QUIT: &str = "quit";
+
+/// Add the tray icon and keep its menu in sync with the server state, so the
+/// server can be run with the main window closed.
+pub fn init<R: Runtime>(app: &tauri::App<R>) -> tauri::Result<()> {
+    let status = MenuItem::with_id(app, "status", "Server stopped", false, None::<&str>)?;
+    let toggle_server = MenuItem::with_id(app, TOGGLE_SERVER, "Start server", true, None::<&str>)?;
+    let toggle_pause = CheckMenuItem::with_id(
+        app,
+        TOGGLE_PAUSE,
+        "Pause remote input",
+        true,
+        false,
+        None::<&str>,
+    )?;
+    let show_window = MenuItem::with_id(app, SHOW_WINDOW, "Show QR code", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, QUIT, "Quit CouchCommander", true, None::<&str>)?;
+
+    let menu = Menu::with_items(
+        app,
+        &[
+            &status,
+            &PredefinedMenuItem::separator(app)?,
+            &toggle_server,
+            &toggle_pause,
+            &show_window,
+            &PredefinedMenuItem::separator(app)?,
+            &quit,
+        ],
+    )?;
+
+    let mut tray = TrayIconBuilder::with_id("main")
+        .tooltip("CouchCommander")
+        .menu(&menu)
+        .on_menu_event(on_menu_event);
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+
+    // Poll rather than push so changes made from the window or a remote show up too
+    tauri::async_runtime::spawn(async move {
+        let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            refresh.tick().await;
+            let Ok(server_status) = server::get_server_status().await else {
+                continue;
+            };
+
+            let (status_text, toggle_text) = if server_status.running {
+                let clients = match server_status.clients {
+                    1 => "1 client".to_string(),
+                    n => format!("{} clients", n),
+                };
+                (
+                    format!("Running on port {}, {}", server_status.port, clients),
+                    "Stop server",
+                )
+            } else {
+                ("Server stopped".to_string(), "Start server")
+            };
+
+            let updates = [
+                status.set_text(status_text),
+                toggle_server.set_text(toggle_text),
+                toggle_pause.set_checked(server_status.input_paused),
+            ];
+            for result in updates {
+                if let Err(e) = result {
+                    warn!("Failed to update tray menu: {}", e);
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Bring the main window, which shows the QR code, to the front.
+pub fn show_main_window<R: Runtime>(app: &AppHandle<R>) {
+    let Some(window) = app.get_webview_window("main") else {
+        warn!("Main window not found");
+        return;
+    };
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+}
+
+fn on_menu_event<R: Runtime>(app: &AppHandle<R>, event: MenuEvent) {
+    match event.id().as_ref() {
+        TOGGLE_SERVER => {
+            tauri::async_runtime::spawn(async {
+                match server::toggle_websocket_server().await {
+                    Ok(response) => info!("{}", response.message),
+                    Err(e) => warn!("Failed to toggle server from tray: {}", e),
+                }
+            });
+        }
+        TOGGLE_PAUSE => {
+            input::toggle_paused();
+        }
+        SHOW_WINDOW => show_main_window(app),
+        QUIT => app.exit(0),
+        _ => {}
+    }
+}
@@ -1,18 +1,37 @@
-import { useState, useEffect } from 'react';
+import { useState, useEffect, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { Button } from '@/components/ui/button';
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card';
 import { Badge } from '@/components/ui/badge';
 import { Separator } from '@/components/ui/separator';
-import { Gamepad2, Wifi, WifiOff, Server, Smartphone, Network, TestTube2 } from 'lucide-react';
+import { Gamepad2, Wifi, WifiOff, Server, Smartphone, Network, TestTube2, ListChecks } from 'lucide-react';
 import { ThemeToggle } from '@/components/theme-toggle';
+import { ActivityFeed } from '@/components/activity-feed';
 import './App.css';
 
+interface InputHealth {
+	backend: string;
+	healthy: boolean;
+	restarts: number;
+	last_error: string | null;
+}
+
 interface ServerStatus {
 	running: boolean;
 	port: number;
 	clients: number;
 	local_ip: string | null;
+	input_paused: boolean;
+	permissions_ok: boolean;
+	input_health: InputHealth;
+}
+
+interface CandidateAddress {
+	ip: string;
+	interface: string;
+	kind: 'lan' | 'vpn' | 'public' | 'ipv6' | 'virtual';
+	web_app_url: string;
+	websocket_url: string;
 }
 
 interface ConnectionInfo {
@@ -21,16 +40,38 @@ interface ConnectionInfo {
 	web_app_port: number;
 	web_app_url: string;
 	websocket_url: string;
+	deep_link: string;
+	pairing_expires_at: number | null;
+	candidates: CandidateAddress[];
+}
+
+interface DiagnosticCheck {
+	id: string;
+	label: string;
+	status: 'pass' | 'warn' | 'fail';
+	detail: string;
+}
+
+interface DiagnosticReport {
+	ok: boolean;
+	checks: DiagnosticCheck[];
 }
 
+const CHECK_ICONS: Record<DiagnosticCheck['status'], string> = {
+	pass: '✅',
+	warn: '⚠️',
+	fail: '❌',
+};
+
 function App() {
 	const [serverStatus, setServerStatus] = useState<ServerStatus | null>(null);
 	const [connectionInfo, setConnectionInfo] = useState<ConnectionInfo | null>(null);
 	const [qrCodeData, setQrCodeData] = useState<string>('');
 	const [isStarting, setIsStarting] = useState(false);
 	const [isStopping, setIsStopping] = useState(false);
-	const [nextJsRunning, setNextJsRunning] = useState(false);
-	const [isStartingNextJs, setIsStartingNextJs] = useState(false);
+	const [diagnostics, setDiagnostics] = useState<DiagnosticReport | null>(null);
+	const [isDiagnosing, setIsDiagnosing] = useState(false);
+	const qrUrl = useRef('');
 
 	// Update server status
 	const updateServerStatus = async () => {
@@ -42,93 +83,94 @@ function App() {
 		}
 	};
 
-	// Check Next.js server status
-	const checkNextJsStatus = async () => {
-		try {
-			const running = (await invoke('check_nextjs_server')) as boolean;
-			setNextJsRunning(running);
-		} catch (error) {
-			console.error('Failed to check Next.js server:', error);
-			setNextJsRunning(false);
-		}
-	};
-
-	// Start Next.js server
-	const startNextJsServer = async () => {
-		setIsStartingNextJs(true);
-		try {
-			await invoke('start_nextjs_server');
-			setTimeout(() => {
-				checkNextJsStatus();
-				setIsStartingNextJs(false);
-			}, 3000);
-		} catch (error) {
-			console.error('Failed to start Next.js server:', error);
-			setIsStartingNextJs(false);
-		}
-	};
-
 	// Get connection info and generate QR code
 	const updateConnectionInfo = async () => {
 		try {
 			const info = (await invoke('get_connection_info')) as ConnectionInfo;
 			setConnectionInfo(info);
 
-			// Generate QR code for the web app URL
+			// Only re-render the QR code when the pairing URL changed
+			if (info.web_app_url === qrUrl.current) return;
 			const qrData = (await invoke('generate_qr_code', {
 				url: info.web_app_url,
 			})) as string;
+			qrUrl.current = info.web_app_url;
 			setQrCodeData(qrData);
 		} catch (error) {
 			console.error('Failed to get connection info:', error);
 		}
 	};
 
-	// Start server (now automatically starts frontend too)
+	// Invalidate the current QR code, e.g. after it was shown to the wrong person
+	const regeneratePairingCode = async () => {
+		try {
+			await invoke('regenerate_pairing_code');
+			await updateConnectionInfo();
+		} catch (error) {
+			console.error('Failed to regenerate pairing code:', error);
+		}
+	};
+
+	// Start server (the web app is served by the backend alongside it)
 	const startServer = async () => {
 		setIsStarting(true);
-		setIsStartingNextJs(true);
 		try {
-			// Start WebSocket server (which now also starts Next.js automatically)
-			await invoke('start_websocket_server', { port: 8080 });
+			await invoke('start_websocket_server');
 			await updateServerStatus();
 			await updateConnectionInfo();
-
-			// Check Next.js status after a delay to allow it to start
-			setTimeout(async () => {
-				await checkNextJsStatus();
-				setIsStartingNextJs(false);
-			}, 3000);
 		} catch (error) {
 			console.error('Failed to start server:', error);
-			setIsStartingNextJs(false);
 		} finally {
 			setIsStarting(false);
 		}
 	};
 
-	// Stop server (now automatically stops frontend too)
+	// Stop server (also stops the web app)
 	const stopServer = async () => {
 		setIsStopping(true);
-		setIsStartingNextJs(true);
 		try {
 			await invoke('stop_websocket_server');
 			await updateServerStatus();
 			setQrCodeData('');
-
-			// Check Next.js status after a delay to confirm it stopped
-			setTimeout(async () => {
-				await checkNextJsStatus();
-				setIsStartingNextJs(false);
-			}, 2000);
+			qrUrl.current = '';
 		} catch (error) {
 			console.error('Failed to stop server:', error);
-			setIsStartingNextJs(false);
 		} finally {
 			setIsStopping(false);
 		}
 	};
 
+	// macOS drops injected input until the app is allowed under Accessibility
+	const requestAccessibility = async () => {
+		try {
+			await invoke('request_accessibility_permissions');
+		} catch (error) {
+			console.error('Failed to request accessibility permissions:', error);
+		}
+	};
+
+	// Panic switch, also available from the tray menu
+	const toggleInputPaused = async () => {
+		try {
+			await invoke('set_input_paused', { paused: !serverStatus?.input_paused });
+			await updateServerStatus();
+		} catch (error) {
+			console.error('Failed to toggle input pause:', error);
+		}
+	};
+
+	// Setup checklist, run on launch and on demand
+	const runDiagnostics = async () => {
+		setIsDiagnosing(true);
+		try {
+			setDiagnostics((await invoke('run_diagnostics')) as DiagnosticReport);
+		} catch (error) {
+			console.error('Failed to run diagnostics:', error);
+		} finally {
+			setIsDiagnosing(false);
+		}
+	};
+
 	// Test media control
 	const testMediaControl = async (command: string) => {
 		try {
@@ -145,14 +187,18 @@ function App() {
 		});
 	};
 
+	// The web app is served on the same port as the WebSocket
+	const webAppRunning = !!serverStatus?.running;
+
 	useEffect(() => {
 		updateServerStatus();
 		updateConnectionInfo();
-		checkNextJsStatus();
+		runDiagnostics();
 
+		// Connection info is polled too, the pairing code rotates on use and expiry
 		const interval = setInterval(() => {
 			updateServerStatus();
-			checkNextJsStatus();
+			updateConnectionInfo();
 		}, 3000);
 
 		return () => clearInterval(interval);
@@ -197,6 +243,21 @@ function App() {
 							<CardDescription>Manage the WebSocket server and frontend for device connections</CardDescription>
 						</CardHeader>
 						<CardContent className="space-y-4">
+							{serverStatus && !serverStatus.permissions_ok && (
+								<div className="text-xs p-2 rounded bg-destructive/10 text-destructive flex items-center justify-between gap-2">
+									<span>⚠️ Accessibility access is required before remotes can control this computer</span>
+									<Button size="sm" variant="outline" onClick={requestAccessibility}>
+										Grant Access
+									</Button>
+								</div>
+							)}
+							{serverStatus?.permissions_ok && !serverStatus.input_health.healthy && (
+								<div className="text-xs p-2 rounded bg-destructive/10 text-destructive">
+									⚠️ Input through {serverStatus.input_health.backend} is failing
+									{serverStatus.input_health.last_error && <>: {serverStatus.input_health.last_error}</>}
+								</div>
+							)}
+
 							<div className="server-controls">
 								<Button
 									className={serverStatus?.running ? 'btn-success' : 'btn-primary'}
@@ -214,6 +275,9 @@ function App() {
 										<>▶️ Start Servers</>
 									)}
 								</Button>
+								<Button variant="outline" size="lg" onClick={toggleInputPaused}>
+									{serverStatus?.input_paused ? <>▶️ Resume Remote Input</> : <>⏸️ Pause Remote Input</>}
+								</Button>
 							</div>
 
 							{!serverStatus?.running && (
@@ -258,19 +322,14 @@ function App() {
 							<CardDescription>Connect your phone to control your computer</CardDescription>
 						</CardHeader>
 						<CardContent className="space-y-4">
-							{/* Next.js Server Status */}
+							{/* Web App Server Status */}
 							<div className="nextjs-status">
 								<div className="info-row">
 									<span className="label">Web App Server:</span>
 									<div className="nextjs-controls">
-										<Badge variant={!isStartingNextJs ? 'secondary' : 'destructive'} className="value">
-											{isStartingNextJs ? '⏳ Starting...' : nextJsRunning ? '✅ Running' : '❌ Stopped'}
+										<Badge variant="secondary" className="value">
+											{webAppRunning ? `✅ Running on port ${serverStatus?.port}` : '❌ Stopped'}
 										</Badge>
-										{!nextJsRunning && !serverStatus?.running && !isStartingNextJs && (
-											<Button size="sm" onClick={startNextJsServer} disabled={isStartingNextJs}>
-												▶️ Start Manually
-											</Button>
-										)}
 									</div>
 								</div>
 								{serverStatus?.running && (
@@ -280,11 +339,21 @@ function App() {
 
 							<Separator />
 
-							{serverStatus?.running && nextJsRunning && qrCodeData ? (
+							{webAppRunning && qrCodeData ? (
 								<div className="qr-section">
 									<div className="qr-code-container">
 										<img src={qrCodeData} alt="QR Code" className="qr-code" />
 									</div>
+									<div className="flex items-center gap-2">
+										{connectionInfo?.pairing_expires_at && (
+											<span className="text-xs text-muted-foreground">
+												Code valid until {new Date(connectionInfo.pairing_expires_at * 1000).toLocaleTimeString()}
+											</span>
+										)}
+										<Button size="sm" variant="outline" onClick={regeneratePairingCode}>
+											🔄 New code
+										</Button>
+									</div>
 									<div className="qr-instructions">
 										<p>
 											<strong>📲 Connect your phone:</strong>
@@ -310,6 +379,26 @@ function App() {
 													📋 Copy
 												</Button>
 											</div>
+											{connectionInfo && connectionInfo.candidates.length > 1 && (
+												<div className="mt-2 space-y-1">
+													<p className="text-xs text-muted-foreground">Other addresses (VPN, IPv6):</p>
+													{connectionInfo.candidates.slice(1).map((candidate) => (
+														<div key={candidate.ip} className="flex items-center gap-2">
+															<Badge variant="outline" className="text-xs">
+																{candidate.interface || candidate.kind}
+															</Badge>
+															<Button
+																variant="ghost"
+																size="sm"
+																className="connection-url"
+																onClick={() => copyToClipboard(candidate.web_app_url)}
+															>
+																{candidate.ip}
+															</Button>
+														</div>
+													))}
+												</div>
+											)}
 										</div>
 									</div>
 								</div>
@@ -319,8 +408,8 @@ function App() {
 									<p className="text-muted-foreground text-center">
 										{!serverStatus?.running
 											? 'Start the WebSocket server first'
-											: !nextJsRunning
-											? 'Start the web app server to generate QR code'
+											: !webAppRunning
+											? 'Web app server is not running'
 											: 'Generating QR code...'}
 									</p>
 								</div>
@@ -369,6 +458,33 @@ function App() {
 						</CardContent>
 					</Card>
 
+					{/* Setup Checklist Panel */}
+					<Card className="panel">
+						<CardHeader>
+							<CardTitle className="flex items-center gap-2">
+								<ListChecks className="h-5 w-5" />
+								Setup Checklist
+							</CardTitle>
+							<CardDescription>Permissions, network and system control checks</CardDescription>
+						</CardHeader>
+						<CardContent className="space-y-3">
+							{diagnostics?.checks.map((check) => (
+								<div key={check.id} className="info-row items-start">
+									<span className="label">
+										{CHECK_ICONS[check.status]} {check.label}
+									</span>
+									<span className="value text-xs text-muted-foreground text-right">{check.detail}</span>
+								</div>
+							))}
+							<Button variant="outline" size="sm" onClick={runDiagnostics} disabled={isDiagnosing}>
+								{isDiagnosing ? <>⏳ Checking...</> : <>🔄 Run Checks Again</>}
+							</Button>
+						</CardContent>
+					</Card>
+
+					{/* Activity Panel */}
+					<ActivityFeed />
+
 					{/* Network Info Panel */}
 					<Card className="panel">
 						<CardHeader>
@@ -392,6 +508,17 @@ function App() {
 												<span className="label">Web App:</span>
 												<code className="value small text-xs bg-muted px-2 py-1 rounded">{connectionInfo.web_app_url}</code>
 											</div>
+											<div className="info-row">
+												<span className="label">App Link:</span>
+												<Button
+													variant="ghost"
+													size="sm"
+													className="value small text-xs"
+													onClick={() => copyToClipboard(connectionInfo.deep_link)}
+												>
+													📋 Copy couchcommander:// link
+												</Button>
+											</div>
 										</div>
 									</div>
 
@@ -414,8 +541,8 @@ function App() {
 												<Badge variant="outline" className="text-xs">
 													{connectionInfo.web_app_port}
 												</Badge>
-												<Badge variant={nextJsRunning ? 'default' : 'destructive'} className="text-xs">
-													{nextJsRunning ? 'Running' : 'Stopped'}
+												<Badge variant={webAppRunning ? 'default' : 'destructive'} className="text-xs">
+													{webAppRunning ? 'Running' : 'Stopped'}
 												</Badge>
 											</li>
 										</ul>
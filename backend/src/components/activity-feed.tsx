@@ -0,0 +1,74 @@
+import { useEffect, useState } from "react";
+import { listen } from "@tauri-apps/api/event";
+import { Activity } from "lucide-react";
+import { Badge } from "@/components/ui/badge";
+import {
+  Card,
+  CardContent,
+  CardDescription,
+  CardHeader,
+  CardTitle,
+} from "@/components/ui/card";
+
+interface CommandEvent {
+  client_id: string;
+  client: string;
+  command: string;
+  status: string;
+  message: string;
+  duration_ms: number;
+  timestamp: number;
+}
+
+const MAX_EVENTS = 50;
+
+// Live list of the commands remotes send, newest first
+export function ActivityFeed() {
+  const [events, setEvents] = useState<CommandEvent[]>([]);
+
+  useEffect(() => {
+    const unlisten = listen<CommandEvent>("command-activity", (event) => {
+      setEvents((events) => [event.payload, ...events].slice(0, MAX_EVENTS));
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  return (
+    <Card className="panel">
+      <CardHeader>
+        <CardTitle className="flex items-center gap-2">
+          <Activity className="h-5 w-5" />
+          Activity
+        </CardTitle>
+        <CardDescription>Commands from connected remotes</CardDescription>
+      </CardHeader>
+      <CardContent className="space-y-2">
+        {events.length === 0 && (
+          <p className="text-sm text-muted-foreground">No commands yet</p>
+        )}
+        {events.map((event, index) => (
+          <div
+            key={`${event.timestamp}-${index}`}
+            className="info-row text-sm"
+            title={event.message}
+          >
+            <span className="label truncate">
+              {event.client} · <code>{event.command}</code>
+            </span>
+            <span className="value flex items-center gap-2 text-xs text-muted-foreground">
+              {event.duration_ms.toFixed(1)} ms
+              <Badge
+                variant={event.status === "error" ? "destructive" : "outline"}
+                className="text-xs"
+              >
+                {event.status}
+              </Badge>
+            </span>
+          </div>
+        ))}
+      </CardContent>
+    </Card>
+  );
+}
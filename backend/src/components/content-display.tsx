@@ -0,0 +1,45 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+type Content =
+  | { kind: "text"; text: string }
+  | { kind: "image"; src: string }
+  | { kind: "url"; url: string };
+
+interface Shown {
+  content: Content;
+}
+
+// Full screen window showing what a remote sent with display_content
+export function ContentDisplay() {
+  const [content, setContent] = useState<Content | null>(null);
+
+  useEffect(() => {
+    // Each new content opens a new window, so this only has to ask once
+    invoke<Shown | null>("get_displayed_content").then((shown) =>
+      setContent(shown?.content ?? null),
+    );
+    const close = (event: KeyboardEvent) => {
+      if (event.key === "Escape") invoke("hide_displayed_content");
+    };
+    window.addEventListener("keydown", close);
+    return () => window.removeEventListener("keydown", close);
+  }, []);
+
+  return (
+    <div className="flex h-screen w-screen items-center justify-center bg-black p-12 text-white">
+      {content?.kind === "text" && (
+        <p className="whitespace-pre-wrap text-center text-6xl font-semibold leading-tight">
+          {content.text}
+        </p>
+      )}
+      {content?.kind === "image" && (
+        <img
+          src={content.src}
+          alt=""
+          className="max-h-full max-w-full object-contain"
+        />
+      )}
+    </div>
+  );
+}
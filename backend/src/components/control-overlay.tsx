@@ -0,0 +1,56 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { Pause, Smartphone } from "lucide-react";
+import { Button } from "@/components/ui/button";
+
+interface ActiveRemote {
+  client_id: string;
+  name: string;
+}
+
+// Shown in the always-on-top overlay window while a remote sends input
+export function ControlOverlay() {
+  const [remote, setRemote] = useState<ActiveRemote | null>(null);
+  const [paused, setPaused] = useState(false);
+
+  useEffect(() => {
+    // The first event can go out before this window has loaded
+    invoke<ActiveRemote | null>("get_active_remote").then(setRemote);
+    const unlisten = listen<ActiveRemote>("input-controller", (event) => {
+      setRemote(event.payload);
+      setPaused(false);
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  const pause = async () => {
+    try {
+      await invoke("set_input_paused", { paused: true });
+      setPaused(true);
+    } catch (error) {
+      console.error("Failed to pause remote input:", error);
+    }
+  };
+
+  return (
+    <div className="flex h-screen items-center justify-between gap-3 bg-background px-4 text-sm">
+      <div className="flex min-w-0 items-center gap-2">
+        <Smartphone className="h-4 w-4 shrink-0" />
+        <span className="truncate">
+          {paused
+            ? "Remote input paused"
+            : `Controlled by ${remote?.name ?? "a remote"}`}
+        </span>
+      </div>
+      {!paused && (
+        <Button variant="outline" size="sm" onClick={pause}>
+          <Pause className="h-4 w-4" />
+          Pause
+        </Button>
+      )}
+    </div>
+  );
+}
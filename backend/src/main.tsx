@@ -1,12 +1,25 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
 import App from "./App";
+import { ContentDisplay } from "./components/content-display";
+import { ControlOverlay } from "./components/control-overlay";
 import { ThemeProvider } from "./components/theme-provider";
 
+// The control overlay window loads the same page with #overlay, and the
+// window showing content from remotes with #content
+const isOverlay = window.location.hash === "#overlay";
+const isContent = window.location.hash === "#content";
+
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
     <ThemeProvider defaultTheme="system" storageKey="couchcommander-ui-theme">
-      <App />
+      {isOverlay ? (
+        <ControlOverlay />
+      ) : isContent ? (
+        <ContentDisplay />
+      ) : (
+        <App />
+      )}
     </ThemeProvider>
   </React.StrictMode>,
 );
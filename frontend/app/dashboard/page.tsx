@@ -0,0 +1,237 @@
+'use client';
+
+import { useState, useEffect, Suspense } from 'react';
+import { Card, CardContent, CardHeader, CardTitle } from '@/components/ui/card';
+import { Badge } from '@/components/ui/badge';
+import { Activity, Monitor, Music, Wifi, WifiOff } from 'lucide-react';
+import { useSearchParams } from 'next/navigation';
+import { deviceId } from '@/lib/device';
+
+// Matches the host's `metrics::ACTIVITY_HISTORY`
+const ACTIVITY_HISTORY = 50;
+const STATUS_REFRESH_MS = 30_000;
+const RECONNECT_MS = 3_000;
+
+interface HostStatus {
+  host: string;
+  uptime_secs: number;
+  clients: number;
+  total_commands: number;
+  commands_per_sec: number;
+  input_paused: boolean;
+}
+
+interface NowPlaying {
+  player: string;
+  status: string;
+  title?: string;
+  artist?: string;
+  album?: string;
+}
+
+interface Battery {
+  percent: number;
+  charging: boolean;
+}
+
+interface CommandEvent {
+  client_id: string;
+  client: string;
+  command: string;
+  status: string;
+  message: string;
+  duration_ms: number;
+  timestamp: number;
+}
+
+function formatUptime(seconds: number): string {
+  const days = Math.floor(seconds / 86400);
+  const hours = Math.floor((seconds % 86400) / 3600);
+  const minutes = Math.floor((seconds % 3600) / 60);
+  if (days > 0) return `${days}d ${hours}h`;
+  if (hours > 0) return `${hours}h ${minutes}m`;
+  return `${minutes}m`;
+}
+
+function DashboardContent() {
+  const searchParams = useSearchParams();
+  // Served by the host itself unless pointed elsewhere
+  const ip = searchParams.get('ip');
+  const port = searchParams.get('port') ?? '8080';
+  const secure = searchParams.get('tls') === '1';
+  const token = searchParams.get('token');
+
+  const [isConnected, setIsConnected] = useState(false);
+  const [status, setStatus] = useState<HostStatus | null>(null);
+  const [nowPlaying, setNowPlaying] = useState<NowPlaying | null>(null);
+  const [battery, setBattery] = useState<Battery | null>(null);
+  const [activity, setActivity] = useState<CommandEvent[]>([]);
+
+  useEffect(() => {
+    const address = ip ? (ip.includes(':') ? ip : `${ip}:${port}`) : window.location.host;
+    let ws: WebSocket | null = null;
+    let refresh: ReturnType<typeof setInterval> | undefined;
+    let reconnect: ReturnType<typeof setTimeout> | undefined;
+    let stopped = false;
+
+    const send = (command: string, id?: string, data?: unknown) => {
+      if (ws?.readyState === WebSocket.OPEN) ws.send(JSON.stringify({ command, id, data }));
+    };
+
+    const connect = () => {
+      // Reuse the session of a remote paired from this browser
+      const credential = localStorage.getItem(`couchcommander.session.${address}`) ?? token;
+      const params = new URLSearchParams({ device: deviceId(), view: 'dashboard' });
+      if (credential) params.set('token', credential);
+      ws = new WebSocket(`${secure || window.location.protocol === 'https:' ? 'wss' : 'ws'}://${address}/?${params}`);
+
+      ws.onopen = () => {
+        setIsConnected(true);
+        send('subscribe', undefined, { topics: ['now_playing', 'clients', 'battery', 'activity'] });
+        send('get_host_status', 'host_status');
+        send('get_activity', 'activity');
+        send('now_playing', 'now_playing');
+        send('get_battery_status', 'battery');
+        refresh = setInterval(() => send('get_host_status', 'host_status'), STATUS_REFRESH_MS);
+      };
+
+      ws.onclose = () => {
+        setIsConnected(false);
+        clearInterval(refresh);
+        if (!stopped) reconnect = setTimeout(connect, RECONNECT_MS);
+      };
+
+      ws.onmessage = (event) => {
+        try {
+          const message = JSON.parse(event.data);
+          if (message.type === 'event') {
+            if (message.topic === 'now_playing') setNowPlaying(message.data);
+            else if (message.topic === 'battery') setBattery(message.data.battery);
+            else if (message.topic === 'clients') setStatus((s) => (s ? { ...s, clients: message.data.clients } : s));
+            else if (message.topic === 'activity')
+              setActivity((events) => [message.data, ...events].slice(0, ACTIVITY_HISTORY));
+          } else if (message.status === 'success' && message.data) {
+            if (message.id === 'host_status') setStatus(message.data);
+            else if (message.id === 'activity') setActivity([...message.data].reverse());
+            else if (message.id === 'now_playing') setNowPlaying(message.data);
+            else if (message.id === 'battery') setBattery(message.data);
+          }
+        } catch (err) {
+          console.error('Failed to parse message:', err);
+        }
+      };
+    };
+
+    connect();
+    return () => {
+      stopped = true;
+      clearInterval(refresh);
+      clearTimeout(reconnect);
+      ws?.close();
+    };
+  }, [ip, port, secure, token]);
+
+  return (
+    <div className='min-h-screen bg-gradient-to-br from-background via-muted/20 to-background'>
+      <div className='container mx-auto px-4 py-6 max-w-3xl space-y-4'>
+        <div className='flex items-center justify-between'>
+          <h1 className='text-3xl font-bold text-foreground'>{status?.host ?? 'CouchCommander'}</h1>
+          <div className='flex items-center gap-2'>
+            {isConnected ? <Wifi className='h-4 w-4 text-green-500' /> : <WifiOff className='h-4 w-4 text-destructive' />}
+            <Badge variant={isConnected ? 'default' : 'destructive'}>{isConnected ? 'Live' : 'Offline'}</Badge>
+          </div>
+        </div>
+
+        <Card>
+          <CardHeader>
+            <CardTitle className='flex items-center gap-2'>
+              <Monitor className='h-5 w-5' /> Host
+            </CardTitle>
+          </CardHeader>
+          <CardContent className='grid grid-cols-2 sm:grid-cols-4 gap-4 text-sm'>
+            <div>
+              <div className='text-muted-foreground'>Uptime</div>
+              <div className='text-lg font-semibold'>{status ? formatUptime(status.uptime_secs) : '–'}</div>
+            </div>
+            <div>
+              <div className='text-muted-foreground'>Remotes</div>
+              <div className='text-lg font-semibold'>{status?.clients ?? '–'}</div>
+            </div>
+            <div>
+              <div className='text-muted-foreground'>Commands</div>
+              <div className='text-lg font-semibold'>{status?.total_commands ?? '–'}</div>
+            </div>
+            <div>
+              <div className='text-muted-foreground'>Battery</div>
+              <div className='text-lg font-semibold'>
+                {battery ? `${battery.percent}%${battery.charging ? ' ⚡' : ''}` : '–'}
+              </div>
+            </div>
+            {status?.input_paused && (
+              <Badge variant='secondary' className='col-span-full w-fit'>
+                Input paused
+              </Badge>
+            )}
+          </CardContent>
+        </Card>
+
+        <Card>
+          <CardHeader>
+            <CardTitle className='flex items-center gap-2'>
+              <Music className='h-5 w-5' /> Now Playing
+            </CardTitle>
+          </CardHeader>
+          <CardContent>
+            {nowPlaying?.title ? (
+              <div>
+                <div className='text-lg font-semibold'>{nowPlaying.title}</div>
+                <div className='text-muted-foreground'>{[nowPlaying.artist, nowPlaying.album].filter(Boolean).join(' · ')}</div>
+                <div className='text-xs text-muted-foreground mt-1'>
+                  {nowPlaying.player} · {nowPlaying.status}
+                </div>
+              </div>
+            ) : (
+              <div className='text-muted-foreground'>Nothing playing</div>
+            )}
+          </CardContent>
+        </Card>
+
+        <Card>
+          <CardHeader>
+            <CardTitle className='flex items-center gap-2'>
+              <Activity className='h-5 w-5' /> Activity
+            </CardTitle>
+          </CardHeader>
+          <CardContent>
+            {activity.length === 0 ? (
+              <div className='text-muted-foreground'>No commands yet</div>
+            ) : (
+              <ul className='space-y-2 text-sm'>
+                {activity.map((event, i) => (
+                  <li key={`${event.timestamp}-${i}`} className='flex items-center justify-between gap-4'>
+                    <div className='min-w-0'>
+                      <code className='font-mono text-primary'>{event.command}</code>
+                      <span className='text-muted-foreground'> from {event.client}</span>
+                    </div>
+                    <div className='flex items-center gap-2 shrink-0'>
+                      <span className='text-xs text-muted-foreground'>{new Date(event.timestamp).toLocaleTimeString()}</span>
+                      <Badge variant={event.status === 'success' ? 'outline' : 'destructive'}>{event.status}</Badge>
+                    </div>
+                  </li>
+                ))}
+              </ul>
+            )}
+          </CardContent>
+        </Card>
+      </div>
+    </div>
+  );
+}
+
+export default function Dashboard() {
+  return (
+    <Suspense fallback={<div>Loading...</div>}>
+      <DashboardContent />
+    </Suspense>
+  );
+}
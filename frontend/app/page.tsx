@@ -12,15 +12,29 @@ import { Badge } from '@/components/ui/badge';
 import { Gamepad2, Wifi, WifiOff } from 'lucide-react';
 import { toast } from 'sonner';
 import { useSearchParams } from 'next/navigation';
+import { deviceId } from '@/lib/device';
+
+// Tab to open for the layout the host suggests when the app in front changes
+const CONTEXT_TABS: Record<string, string> = {
+  video_player: 'media',
+  music_player: 'media',
+  presentation: 'keyboard',
+  browser: 'websites',
+};
 
 function HomeContent() {
   const searchParams = useSearchParams();
   const ip = searchParams.get('ip');
+  const port = searchParams.get('port') ?? '8080';
+  const secure = searchParams.get('tls') === '1';
+  const token = searchParams.get('token');
 
   const [isConnected, setIsConnected] = useState(false);
   const [serverIP, setServerIP] = useState<string>();
   const [activeTab, setActiveTab] = useState('media');
   const [websocket, setWebsocket] = useState<WebSocket | null>(null);
+  // Bumped when a stored session token was rejected, to retry with the QR token
+  const [authRetry, setAuthRetry] = useState(0);
   const [modifierKeyStates, setModifierKeyStates] = useState<{
     cmd?: boolean;
     shift?: boolean;
@@ -37,17 +51,37 @@ function HomeContent() {
           websocket.close();
         }
 
-        const ws = new WebSocket(`ws://${ip}:8080`);
+        // Accept "host:port" or "[v6]:port" from manual entry, otherwise use the port from the QR link
+        const hasPort = ip.startsWith('[') ? ip.includes(']:') : ip.includes(':');
+        const address = hasPort ? ip : `${ip}:${port}`;
+        // Prefer the session token from an earlier pairing, the QR token may be single-use
+        const sessionKey = `couchcommander.session.${address}`;
+        const session = localStorage.getItem(sessionKey);
+        const credential = session ?? token;
+        const params = new URLSearchParams({ device: deviceId() });
+        if (credential) params.set('token', credential);
+        const query = `/?${params}`;
+        const ws = new WebSocket(`${secure ? 'wss' : 'ws'}://${address}${query}`);
+        let opened = false;
 
         ws.onopen = () => {
+          opened = true;
           setIsConnected(true);
           setServerIP(ip);
           setWebsocket(ws);
+          // Follow the host to the tab that fits the app in front
+          ws.send(JSON.stringify({ command: 'subscribe', data: { topics: ['context'] } }));
           console.log('Connected to server');
           toast.success('Connected to server');
         };
 
         ws.onclose = () => {
+          if (!opened && session) {
+            // The host forgot this session (e.g. it restarted), pair again
+            localStorage.removeItem(sessionKey);
+            setAuthRetry((n) => n + 1);
+            return;
+          }
           setIsConnected(false);
           setServerIP(undefined);
           setWebsocket(null);
@@ -56,6 +90,8 @@ function HomeContent() {
         };
 
         ws.onerror = (event) => {
+          // A rejected session is retried from onclose
+          if (!opened && session) return;
           console.error('WebSocket error:', event);
           toast.error('WebSocket error');
           setIsConnected(false);
@@ -68,7 +104,12 @@ function HomeContent() {
             const message = JSON.parse(event.data);
             console.log('Received message:', message);
 
-            if (message.status === 'error') {
+            if (message.type === 'event' && message.topic === 'context') {
+              const tab = CONTEXT_TABS[message.data.layout];
+              if (tab) setActiveTab(tab);
+            } else if (message.status === 'paired') {
+              localStorage.setItem(sessionKey, message.data.session_token);
+            } else if (message.status === 'error') {
               toast.error(message.message || 'An error occurred');
             } else if (message.status === 'success' && message.data) {
               // Check if this is a modifier key states response by looking at the data structure
@@ -97,7 +138,7 @@ function HomeContent() {
         // throw error;
       }
     },
-    [websocket]
+    [websocket, port, secure, token]
   );
 
   // useeffect that triggers the handle connect if search param ip has a value
@@ -107,7 +148,7 @@ function HomeContent() {
         handleConnect(ip);
       }
     }
-  }, [ip, handleConnect, isConnected]);
+  }, [ip, handleConnect, isConnected, authRetry]);
 
   const handleDisconnect = useCallback(() => {
     if (websocket) {
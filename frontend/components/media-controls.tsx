@@ -5,7 +5,8 @@ import { Card, CardContent, CardHeader, CardTitle } from '@/components/ui/card';
 import { Button } from '@/components/ui/button';
 import { Slider } from '@/components/ui/slider';
 import { Label } from '@/components/ui/label';
-import { Play, SkipBack, SkipForward, VolumeX, Volume1, Volume2, Sun, SunDim, Music, Maximize } from 'lucide-react';
+import { holdToRepeat } from '@/lib/hold-repeat';
+import { Play, SkipBack, SkipForward, VolumeX, Volume1, Volume2, Sun, SunDim, Music, Maximize, Monitor, MonitorOff } from 'lucide-react';
 
 interface MediaControlsProps {
   onCommand: (command: string, data?: Record<string, unknown>, id?: string) => void;
@@ -98,13 +99,13 @@ export function MediaControls({ onCommand, isConnected }: MediaControlsProps) {
           </div>
 
           <div className='grid grid-cols-3 gap-2'>
-            <Button onClick={() => handleMediaCommand('volume_down')} disabled={!isConnected} variant='outline' size='sm'>
+            <Button {...holdToRepeat(onCommand, 'volume_down')} disabled={!isConnected} variant='outline' size='sm'>
               <Volume1 className='h-4 w-4' />
             </Button>
             <Button onClick={() => handleMediaCommand('volume_mute')} disabled={!isConnected} variant='outline' size='sm'>
               <VolumeX className='h-4 w-4' />
             </Button>
-            <Button onClick={() => handleMediaCommand('volume_up')} disabled={!isConnected} variant='outline' size='sm'>
+            <Button {...holdToRepeat(onCommand, 'volume_up')} disabled={!isConnected} variant='outline' size='sm'>
               <Volume2 className='h-4 w-4' />
             </Button>
           </div>
@@ -142,6 +143,18 @@ export function MediaControls({ onCommand, isConnected }: MediaControlsProps) {
               Increase
             </Button>
           </div>
+
+          {/* Blanks the TV, music keeps playing */}
+          <div className='grid grid-cols-2 gap-2'>
+            <Button onClick={() => handleMediaCommand('display_sleep')} disabled={!isConnected} variant='outline' className='flex items-center gap-2'>
+              <MonitorOff className='h-4 w-4' />
+              Screen Off
+            </Button>
+            <Button onClick={() => handleMediaCommand('display_wake')} disabled={!isConnected} variant='outline' className='flex items-center gap-2'>
+              <Monitor className='h-4 w-4' />
+              Screen On
+            </Button>
+          </div>
         </CardContent>
       </Card>
     </div>
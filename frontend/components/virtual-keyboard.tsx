@@ -6,6 +6,7 @@ import { Button } from '@/components/ui/button';
 import { Input } from '@/components/ui/input';
 import { Label } from '@/components/ui/label';
 import { Badge } from '@/components/ui/badge';
+import { holdToRepeat } from '@/lib/hold-repeat';
 import { Tabs, TabsList, TabsTrigger, TabsContent } from '@/components/ui/tabs';
 import { Keyboard, Send, LockIcon as CapsLock, ArrowUp, ArrowDown, ArrowLeft, ArrowRight, Command } from 'lucide-react';
 interface VirtualKeyboardProps {
@@ -368,17 +369,17 @@ export function VirtualKeyboard({ onKeyPress, onTextInput, isConnected, sendComm
           <div className='flex justify-center'>
             <div className='grid grid-cols-3 gap-1 w-32'>
               <div></div>
-              <Button onClick={() => handleSpecialKey('up')} disabled={!isConnected} variant='outline' size='sm' className='min-h-10'>
+              <Button {...holdToRepeat(sendCommand, 'send_key', { key: 'up' })} disabled={!isConnected} variant='outline' size='sm' className='min-h-10'>
                 <ArrowUp className='h-4 w-4' />
               </Button>
               <div></div>
-              <Button onClick={() => handleSpecialKey('left')} disabled={!isConnected} variant='outline' size='sm' className='min-h-10'>
+              <Button {...holdToRepeat(sendCommand, 'send_key', { key: 'left' })} disabled={!isConnected} variant='outline' size='sm' className='min-h-10'>
                 <ArrowLeft className='h-4 w-4' />
               </Button>
-              <Button onClick={() => handleSpecialKey('down')} disabled={!isConnected} variant='outline' size='sm' className='min-h-10'>
+              <Button {...holdToRepeat(sendCommand, 'send_key', { key: 'down' })} disabled={!isConnected} variant='outline' size='sm' className='min-h-10'>
                 <ArrowDown className='h-4 w-4' />
               </Button>
-              <Button onClick={() => handleSpecialKey('right')} disabled={!isConnected} variant='outline' size='sm' className='min-h-10'>
+              <Button {...holdToRepeat(sendCommand, 'send_key', { key: 'right' })} disabled={!isConnected} variant='outline' size='sm' className='min-h-10'>
                 <ArrowRight className='h-4 w-4' />
               </Button>
             </div>
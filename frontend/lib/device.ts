@@ -0,0 +1,12 @@
+// Stable id the host remembers this browser by in its device list
+export function deviceId(): string {
+  const key = 'couchcommander.device';
+  let id = localStorage.getItem(key);
+  if (!id) {
+    // randomUUID needs a secure context, the remote is often served over plain http
+    const bytes = crypto.getRandomValues(new Uint8Array(16));
+    id = Array.from(bytes, (b) => b.toString(16).padStart(2, '0')).join('');
+    localStorage.setItem(key, id);
+  }
+  return id;
+}
@@ -0,0 +1,22 @@
+import type { MouseEvent, PointerEvent } from 'react';
+
+type Send = (command: string, data?: Record<string, unknown>) => void;
+
+// Button props that repeat `command` on the host for as long as the button is
+// held, instead of sending a message per repeat over a jittery network
+export function holdToRepeat(send: Send, command: string, data?: Record<string, unknown>) {
+  const stop = () => send('stop_repeat');
+  return {
+    onPointerDown: (event: PointerEvent<HTMLButtonElement>) => {
+      // Keep getting the release even if the finger slides off the button
+      event.currentTarget.setPointerCapture(event.pointerId);
+      send('start_repeat', { command, data });
+    },
+    onPointerUp: stop,
+    onPointerCancel: stop,
+    // A click from the keyboard has no pointer press, run the command once
+    onClick: (event: MouseEvent<HTMLButtonElement>) => {
+      if (event.detail === 0) send(command, data);
+    },
+  };
+}
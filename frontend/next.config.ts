@@ -8,6 +8,11 @@ const nextConfig: NextConfig = {
     ignoreBuildErrors: true,
   },
   distDir: "build",
+  // Static export to out/, embedded and served by the desktop backend
+  output: "export",
+  images: {
+    unoptimized: true,
+  },
 };
 
 export default nextConfig;